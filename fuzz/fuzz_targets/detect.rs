@@ -0,0 +1,32 @@
+#![no_main]
+
+use gette::RequestBuilder;
+use libfuzzer_sys::fuzz_target;
+
+/// Detection alone, with no getter to be asked about the source, so that
+/// arbitrary sources never reach the filesystem.
+fn resolve(src: &str) -> Result<gette::Detection, gette::Error> {
+    RequestBuilder::builder()
+        .clear_getters()
+        .src(src.to_string())
+        .dest("dest".to_string())
+        .resolve()
+}
+
+fuzz_target!(|src: &str| {
+    let Ok(detection) = resolve(src) else {
+        return;
+    };
+
+    // the forced getter must survive detection
+    if let Some((proto, _)) = src.split_once('+') {
+        if !proto.is_empty() && proto.chars().all(|c| c.is_ascii_alphanumeric()) {
+            assert_eq!(detection.getter, proto);
+        }
+    }
+
+    // resolving the resolved source again must be a no-op
+    let again = resolve(&format!("{}+{}", detection.getter, detection.url)).unwrap();
+    assert_eq!(again.getter, detection.getter);
+    assert_eq!(again.url, detection.url);
+});
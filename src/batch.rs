@@ -0,0 +1,407 @@
+//! Fetching many sources at once with the same settings.
+
+use std::{path::Path, sync::Arc, time::Instant};
+
+use futures::{stream::FuturesUnordered, StreamExt};
+use tokio::sync::Semaphore;
+
+use crate::{
+    throttle::HostLimits, timing, BatchProgress, DestPath, Error, GetOptions, GetResult, NoDest,
+    NoSrc, RequestBuilder,
+};
+
+/// Fetches a batch of sources concurrently, every one of them with the
+/// settings, getters and clients of the [`RequestBuilder`] it's made from
+/// with [`RequestBuilder::queue`].
+///
+/// ```no_run
+/// # async fn run() {
+/// let results = gette::RequestBuilder::builder()
+///     .queue("s3+https://s3.amazonaws.com/bucket/app.tar.gz", "app")
+///     .queue("s3+https://s3.amazonaws.com/bucket/data.zip", "data")
+///     .parallelism(4)
+///     .get_all()
+///     .await;
+/// # }
+/// ```
+pub struct MultiRequestBuilder {
+    pub(crate) builder: RequestBuilder<NoSrc, NoDest>,
+    /// Sources along with their dests and the options only they're got with.
+    pub(crate) queue: Vec<(String, DestPath, Option<GetOptions>)>,
+    parallelism: usize,
+    /// Sources fetched from the same host at once to start with and at
+    /// most, the parallelism for both without them.
+    host_concurrency: Option<(usize, usize)>,
+    fail_fast: bool,
+}
+
+impl MultiRequestBuilder {
+    pub(crate) fn new(builder: RequestBuilder<NoSrc, NoDest>) -> Self {
+        Self {
+            builder,
+            queue: Vec::new(),
+            parallelism: 8,
+            host_concurrency: None,
+            fail_fast: false,
+        }
+    }
+
+    /// Queue up another source to be fetched to dest.
+    pub fn queue(mut self, src: impl Into<String>, dest: impl AsRef<Path>) -> Self {
+        let dest = DestPath::Path(dest.as_ref().to_path_buf());
+        self.queue.push((src.into(), dest, None));
+        self
+    }
+
+    /// Queue up a source to be fetched to the dest the template derives
+    /// from it, see [`RequestBuilder::dest_template`]. Fails on variables
+    /// the template doesn't know.
+    pub fn queue_template(mut self, src: impl Into<String>, template: &str) -> Result<Self, Error> {
+        let dest = DestPath::Template(template.parse()?);
+        self.queue.push((src.into(), dest, None));
+        Ok(self)
+    }
+
+    /// Queue up a source with options of its own, which win over those of
+    /// the builder like [`RequestBuilder::options`] does.
+    pub fn queue_with(
+        mut self,
+        src: impl Into<String>,
+        dest: impl AsRef<Path>,
+        options: GetOptions,
+    ) -> Self {
+        let dest = DestPath::Path(dest.as_ref().to_path_buf());
+        self.queue.push((src.into(), dest, Some(options)));
+        self
+    }
+
+    /// Fetch at most this many sources at a time, 8 by default.
+    pub fn parallelism(mut self, n: usize) -> Self {
+        self.parallelism = n.max(1);
+        self
+    }
+
+    /// Fetch at most initial sources at a time from the same host to start
+    /// with, and at most max. Hosts that throttle a get, see
+    /// [`Error::is_throttled`], have their limit halved, which is then
+    /// raised by one after as many gets in a row as the limit went through
+    /// without being throttled, back up to max. Both are the parallelism
+    /// by default. Sources that aren't fetched over the network are only
+    /// limited by the parallelism.
+    pub fn host_concurrency(mut self, initial: usize, max: usize) -> Self {
+        self.host_concurrency = Some((initial, max));
+        self
+    }
+
+    /// Cancel whatever hasn't finished yet as soon as a source fails, which
+    /// then fails with [`Error::Cancelled`]. Off by default, so that every
+    /// source gets its chance.
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Fetches every source queued, returning what came of each in the order
+    /// they were queued in. Progress is reported to the reporter of the
+    /// builder, see [`ProgressReporter::batch`](crate::ProgressReporter::batch),
+    /// the last report adding up the timings of the whole batch.
+    pub async fn get_all(&self) -> Vec<Result<GetResult, Error>> {
+        self.get_all_with(&self.builder, |_| Ok(None)).await
+    }
+
+    /// Fetches every source queued like [`MultiRequestBuilder::get_all`],
+    /// but with builder, and with the options pin gives each of them on top
+    /// of their own. Sources pin fails on fail with its error instead.
+    pub(crate) async fn get_all_with<F>(
+        &self,
+        builder: &RequestBuilder<NoSrc, NoDest>,
+        pin: F,
+    ) -> Vec<Result<GetResult, Error>>
+    where
+        F: Fn(usize) -> Result<Option<GetOptions>, Error> + Sync,
+    {
+        let reporter = builder.decompress_opts.progress.as_ref();
+        let start = Instant::now();
+        let mut progress = BatchProgress {
+            total: self.queue.len(),
+            ..Default::default()
+        };
+
+        let mut results: Vec<Option<Result<GetResult, Error>>> =
+            self.queue.iter().map(|_| None).collect();
+        let (initial, max) = self
+            .host_concurrency
+            .unwrap_or((self.parallelism, self.parallelism));
+        let hosts = HostLimits::new(initial, max);
+        let slots = Semaphore::new(self.parallelism);
+        // mapped from indices rather than from references into the queue,
+        // which would keep the future from being Send
+        let mut gets: FuturesUnordered<_> = (0..self.queue.len())
+            .map(|i| {
+                let (pin, hosts, slots) = (&pin, &hosts, &slots);
+                async move {
+                    let (src, dest, options) = &self.queue[i];
+                    // sources waiting on their host leave their slot to others
+                    let permit = match builder.host_of(src) {
+                        Some(host) => Some(Arc::new(hosts.acquire(&host).await)),
+                        None => None,
+                    };
+                    let _slot = slots.acquire().await;
+                    let res = match pin(i) {
+                        Ok(None) if options.is_none() && permit.is_none() => {
+                            builder.get_src(src, dest).await
+                        }
+                        Ok(pinned) => {
+                            let mut builder = builder.clone();
+                            for options in options.iter().chain(&pinned) {
+                                builder = builder.options(options.clone());
+                            }
+                            builder.throttle = permit.clone();
+                            builder.get_src(src, dest).await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    match (&res, &permit) {
+                        (Ok(_), Some(permit)) => permit.succeeded(),
+                        (Err(e), Some(permit)) if e.is_throttled() => permit.throttled(),
+                        _ => {}
+                    }
+                    (i, res)
+                }
+            })
+            .collect();
+        while let Some((i, res)) = gets.next().await {
+            progress.done += 1;
+            match &res {
+                Ok(got) => {
+                    progress.bytes += got.bytes;
+                    progress.timings.add(&got.timings);
+                }
+                Err(_) => progress.failed += 1,
+            }
+            progress.elapsed = start.elapsed();
+            progress.timings.average_rate = timing::rate(progress.bytes, progress.elapsed);
+            if let Some(reporter) = reporter {
+                reporter.batch(&progress);
+            }
+
+            let failed = res.is_err();
+            results[i] = Some(res);
+            if failed && self.fail_fast {
+                break;
+            }
+        }
+        // whatever's still in flight is cancelled here, cleaning up after itself
+        drop(gets);
+
+        results
+            .into_iter()
+            .map(|res| res.unwrap_or(Err(Error::Cancelled)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::{GetOptions, Getter, ProgressReporter, Url};
+
+    /// A getter that takes its time, failing the sources asking it to.
+    #[derive(Default)]
+    struct SlowGetter {
+        running: Arc<AtomicUsize>,
+        most: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Getter for SlowGetter {
+        async fn get(&self, dest: &Path, url: &Url, _: &GetOptions) -> Result<(), Error> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+
+            if url.path().contains("fail") {
+                return Err(Error::SourceNotFound { source: None });
+            }
+            fs::write(dest, url.as_str())?;
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        batch: Mutex<Vec<BatchProgress>>,
+    }
+
+    impl ProgressReporter for Recorder {
+        fn batch(&self, progress: &BatchProgress) {
+            self.batch.lock().unwrap().push(progress.clone());
+        }
+    }
+
+    fn batch(
+        getter: SlowGetter,
+        recorder: Arc<Recorder>,
+        dir: &Path,
+        names: &[&str],
+    ) -> MultiRequestBuilder {
+        let builder = RequestBuilder::builder()
+            .add_getter("slow", Box::new(getter))
+            .progress(recorder);
+        let mut batch = MultiRequestBuilder::new(builder);
+        for name in names {
+            batch = batch.queue(format!("slow://host/{}", name), dir.join(name));
+        }
+        batch
+    }
+
+    #[tokio::test]
+    async fn test_get_all() {
+        let tmp = tempfile::tempdir().unwrap();
+        let getter = SlowGetter::default();
+        let most = getter.most.clone();
+        let recorder = Arc::new(Recorder::default());
+        let names = ["a", "b", "c", "d", "e", "f"];
+
+        let results = batch(getter, recorder.clone(), tmp.path(), &names)
+            .parallelism(2)
+            .get_all()
+            .await;
+        assert_eq!(most.load(Ordering::SeqCst), 2);
+        assert_eq!(results.len(), names.len());
+        for (res, name) in results.into_iter().zip(names) {
+            let res = res.unwrap();
+            assert_eq!(res.final_path, tmp.path().join(name));
+            assert_eq!(res.source_url.path(), format!("/{}", name));
+            assert!(res.timings.transfer >= Duration::from_millis(20));
+        }
+
+        let progress = recorder.batch.lock().unwrap();
+        assert_eq!(progress.len(), names.len());
+        let last = progress.last().unwrap();
+        assert_eq!((last.done, last.failed, last.total), (6, 0, 6));
+        assert_eq!(last.bytes, 6 * "slow://host/a".len() as u64);
+        // two at a time take about half as long as the gets add up to
+        assert!(last.timings.transfer >= Duration::from_millis(120));
+        assert!(last.elapsed >= Duration::from_millis(60));
+        assert!(last.elapsed < last.timings.total, "{:?}", last);
+        assert!(last.timings.average_rate.is_some());
+    }
+
+    /// A server throttling the requests made above its limit at once.
+    #[derive(Default)]
+    struct Throttling {
+        limit: usize,
+        running: AtomicUsize,
+        /// Requests in flight as each attempt was made, it included.
+        attempts: Mutex<Vec<usize>>,
+    }
+
+    #[async_trait]
+    impl Getter for Arc<Throttling> {
+        async fn get(&self, dest: &Path, url: &Url, _: &GetOptions) -> Result<(), Error> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.attempts.lock().unwrap().push(running);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+
+            if running > self.limit {
+                return Err(Error::Throttled {
+                    source: "slow down".into(),
+                });
+            }
+            fs::write(dest, url.as_str())?;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_all_host_concurrency() {
+        let tmp = tempfile::tempdir().unwrap();
+        let server = Arc::new(Throttling {
+            limit: 4,
+            ..Default::default()
+        });
+        let builder = RequestBuilder::builder()
+            .add_getter("throttling", Box::new(server.clone()))
+            .add_getter("slow", Box::new(SlowGetter::default()))
+            .retry(crate::RetryPolicy {
+                max_attempts: 50,
+                base_delay: Duration::from_millis(5),
+                max_delay: Duration::from_millis(20),
+                jitter: true,
+            });
+        let mut batch = MultiRequestBuilder::new(builder)
+            .parallelism(16)
+            .host_concurrency(16, 16);
+        for i in 0..64 {
+            let dest = tmp.path().join(i.to_string());
+            batch = batch.queue(format!("throttling://host/{}", i), dest);
+        }
+        // other hosts keep going at full speed
+        let others = tmp.path().join("others");
+        for i in 0..8 {
+            batch = batch.queue(format!("slow://other/{}", i), others.join(i.to_string()));
+        }
+
+        let results = batch.get_all().await;
+        assert!(results.iter().all(Result::is_ok));
+
+        // the first burst is throttled, until the batch settles around the
+        // limit of the server
+        let attempts = server.attempts.lock().unwrap();
+        let burst = attempts[..16].iter().max().unwrap();
+        assert!(*burst > 8, "{:?}", attempts);
+        let settled = &attempts[attempts.len() * 2 / 3..];
+        assert!(
+            settled.iter().all(|running| *running <= 6),
+            "{:?}",
+            attempts
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_all_fail_fast() {
+        let names = ["a", "fail", "c", "d"];
+        for fail_fast in [false, true] {
+            let tmp = tempfile::tempdir().unwrap();
+            let recorder = Arc::new(Recorder::default());
+            let results = batch(SlowGetter::default(), recorder.clone(), tmp.path(), &names)
+                .parallelism(1)
+                .fail_fast(fail_fast)
+                .get_all()
+                .await;
+
+            assert_eq!(results.len(), names.len());
+            assert!(results[0].is_ok());
+            assert!(matches!(
+                results[1].as_ref().map_err(Error::inner),
+                Err(Error::SourceNotFound { .. })
+            ));
+            for res in &results[2..] {
+                match fail_fast {
+                    true => assert!(matches!(res, Err(Error::Cancelled))),
+                    false => assert!(res.is_ok()),
+                }
+            }
+            assert_eq!(tmp.path().join("c").exists(), !fail_fast);
+
+            let progress = recorder.batch.lock().unwrap();
+            let last = progress.last().unwrap();
+            assert_eq!(last.failed, 1);
+            assert_eq!(last.done, if fail_fast { 2 } else { 4 });
+        }
+    }
+}
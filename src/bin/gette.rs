@@ -0,0 +1,286 @@
+//! Command line front end to the library, fetching sources to dests the way
+//! [`gette::RequestBuilder`] does.
+
+use std::{path::PathBuf, process::ExitCode, time::Duration};
+
+use gette::{
+    getters::FileMode, ArchiveOption, Error, ErrorKind, ExtractProgress, ExtractStats, GetOptions,
+    GetResult, ProgressReporter, RequestBuilder,
+};
+
+const USAGE: &str = "\
+usage: gette [OPTIONS] SRC DEST
+       gette [OPTIONS] --parallel N SRC=DEST...
+
+options:
+  --checksum SUM    checksum the source must have, as in ?checksum=
+  --mode MODE       how local sources end up at dest: symlink, copy or hardlink
+  --archive FORMAT  archive format to extract the source with, or false
+  --ref REF         revision of a repository to check out
+  --timeout SECS    give up on a get taking longer than this
+  --parallel N      fetch the SRC=DEST pairs given, N at a time
+  --progress        report extraction and batch progress on stderr
+  --resolve-only    print what the source resolves to and exit
+  -h, --help        print this and exit
+
+exit codes:
+  0 done, 1 failed, 2 invalid usage or request, 3 source not found,
+  4 checksum or signature mismatch, 5 transient failure, worth retrying,
+  6 access denied";
+
+/// What the command line asks for.
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    pairs: Vec<(String, PathBuf)>,
+    options: GetOptions,
+    git_ref: Option<String>,
+    timeout: Option<Duration>,
+    parallel: Option<usize>,
+    progress: bool,
+    resolve_only: bool,
+}
+
+impl Args {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut positional = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value =
+                |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+            match arg.as_str() {
+                "--checksum" => parsed.options.checksum = Some(value(&arg)?.parse()?),
+                "--mode" => parsed.options.file_mode = Some(value(&arg)?.parse::<FileMode>()?),
+                "--archive" => {
+                    parsed.options.archive = Some(value(&arg)?.parse::<ArchiveOption>()?)
+                }
+                "--ref" => parsed.git_ref = Some(value(&arg)?),
+                "--timeout" => {
+                    let secs = value(&arg)?
+                        .parse()
+                        .map_err(|_| "timeout must be a number of seconds")?;
+                    parsed.timeout = Some(Duration::from_secs(secs));
+                }
+                "--parallel" => {
+                    let n = value(&arg)?
+                        .parse()
+                        .map_err(|_| "parallel must be a number")?;
+                    parsed.parallel = Some(n);
+                }
+                "--progress" => parsed.progress = true,
+                "--resolve-only" => parsed.resolve_only = true,
+                "-h" | "--help" => return Err(String::new()),
+                flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+                _ => positional.push(arg),
+            }
+        }
+
+        parsed.pairs = match (parsed.parallel, &positional[..]) {
+            (Some(_), []) => return Err("--parallel needs SRC=DEST pairs".to_string()),
+            // a dest rarely has an = in it, a query string often does
+            (Some(_), pairs) => pairs
+                .iter()
+                .map(|pair| match pair.rsplit_once('=') {
+                    Some((src, dest)) => Ok((src.to_string(), PathBuf::from(dest))),
+                    None => Err(format!("{} isn't a SRC=DEST pair", pair)),
+                })
+                .collect::<Result<_, _>>()?,
+            (None, [src]) if parsed.resolve_only => vec![(src.clone(), PathBuf::new())],
+            (None, [src, dest]) => vec![(src.clone(), PathBuf::from(dest))],
+            (None, _) => return Err("expected SRC and DEST".to_string()),
+        };
+        if let Some(git_ref) = &parsed.git_ref {
+            for (src, _) in &mut parsed.pairs {
+                let sep = if src.contains('?') { '&' } else { '?' };
+                *src = format!("{}{}ref={}", src, sep, git_ref);
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Reports progress on stderr.
+struct Stderr;
+
+impl ProgressReporter for Stderr {
+    fn extracting(&self, progress: &ExtractProgress) {
+        match progress.total_entries {
+            Some(total) => eprint!("\rextracting {}/{} entries", progress.entries, total),
+            None => eprint!("\rextracting {} entries", progress.entries),
+        }
+    }
+
+    fn extracted(&self, stats: &ExtractStats) {
+        eprintln!(
+            "\rextracted {} entries, {} bytes, from {}",
+            stats.entries, stats.bytes, stats.format
+        );
+    }
+
+    fn batch(&self, progress: &gette::BatchProgress) {
+        eprintln!(
+            "{}/{} done, {} failed",
+            progress.done, progress.total, progress.failed
+        );
+    }
+}
+
+/// Exit code telling scripts what sort of error ended the get.
+fn exit_code(err: &Error) -> u8 {
+    match err.kind() {
+        ErrorKind::Config => 2,
+        ErrorKind::NotFound => 3,
+        ErrorKind::Integrity => 4,
+        ErrorKind::Transient | ErrorKind::Timeout => 5,
+        ErrorKind::Auth => 6,
+        _ => 1,
+    }
+}
+
+fn report(res: &GetResult) {
+    match res.skipped {
+        Some(_) => println!("{} is up to date", res.final_path.display()),
+        None => println!(
+            "{} -> {} ({} bytes)",
+            res.source_url,
+            res.final_path.display(),
+            res.bytes
+        ),
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) if message.is_empty() => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("gette: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut builder = RequestBuilder::builder().options(args.options);
+    if let Some(timeout) = args.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if args.progress {
+        builder = builder.progress(std::sync::Arc::new(Stderr));
+    }
+    let client = builder.build();
+
+    if args.resolve_only {
+        let mut code = ExitCode::SUCCESS;
+        for (src, _) in &args.pairs {
+            match client.resolve(src) {
+                Ok(detection) => {
+                    println!("getter:  {}", detection.getter);
+                    println!("url:     {}", detection.url);
+                    if let Some(subpath) = &detection.subpath {
+                        println!("subpath: {}", subpath);
+                    }
+                    if let Some(archive) = &detection.archive {
+                        println!("archive: {}", archive);
+                    }
+                }
+                Err(err) => {
+                    eprintln!("gette: {}", err);
+                    code = ExitCode::from(exit_code(&err));
+                }
+            }
+        }
+        return code;
+    }
+
+    let results = match args.parallel {
+        Some(n) => {
+            // there's at least one pair, parsing made sure of it
+            let ((src, dest), rest) = args.pairs.split_first().unwrap();
+            let batch = rest
+                .iter()
+                .fold(client.queue(src.clone(), dest), |batch, (src, dest)| {
+                    batch.queue(src.clone(), dest)
+                });
+            batch.parallelism(n).get_all().await
+        }
+        None => {
+            let (src, dest) = &args.pairs[0];
+            vec![client.get(src, dest).await]
+        }
+    };
+
+    // the first failure decides the exit code
+    let mut code = None;
+    for res in &results {
+        match res {
+            Ok(res) => report(res),
+            Err(err) => {
+                eprintln!("gette: {}", err);
+                code.get_or_insert(exit_code(err));
+            }
+        }
+    }
+    code.map_or(ExitCode::SUCCESS, ExitCode::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn it_should_parse_args() {
+        let args = parse(&["--mode", "copy", "--ref", "v1", "./src?x=1", "./dest"]).unwrap();
+        assert_eq!(args.options.file_mode, Some(FileMode::Copy));
+        assert_eq!(
+            args.pairs,
+            [("./src?x=1&ref=v1".to_string(), PathBuf::from("./dest"))]
+        );
+
+        let args = parse(&[
+            "--parallel",
+            "2",
+            "a.txt?archive=false=out/a",
+            "b.txt=out/b",
+        ])
+        .unwrap();
+        assert_eq!(args.parallel, Some(2));
+        assert_eq!(args.pairs[0].0, "a.txt?archive=false");
+        assert_eq!(args.pairs[1].1, PathBuf::from("out/b"));
+
+        let args = parse(&["--resolve-only", "./src"]).unwrap();
+        assert!(args.resolve_only);
+
+        for args in [
+            &["./src"][..],
+            &["--checksum", "md5:12", "a", "b"],
+            &["--mode", "move", "a", "b"],
+            &["--timeout"],
+            &["--parallel", "2", "a.txt"],
+            &["--force", "a", "b"],
+        ] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn it_should_tell_errors_apart_in_exit_codes() {
+        assert_eq!(exit_code(&Error::SourceNotFound { source: None }), 3);
+        let mismatch = Error::ChecksumMismatch {
+            algo: "md5",
+            expected: "a".to_string(),
+            actual: "b".to_string(),
+        };
+        assert_eq!(exit_code(&mismatch), 4);
+        let reset = std::io::Error::from(std::io::ErrorKind::ConnectionReset);
+        assert_eq!(exit_code(&Error::Io(reset)), 5);
+        assert_eq!(exit_code(&Error::GetterNotFound("s4".into(), vec![])), 2);
+    }
+}
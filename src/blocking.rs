@@ -0,0 +1,125 @@
+//! Gets for code that isn't async, each running on a small runtime of its
+//! own. None of these can be called from within a Tokio runtime, where they
+//! fail with [`Error::BlockingInRuntime`] instead; await the async API there.
+
+use std::path::Path;
+
+use tokio::runtime::{self, Runtime};
+
+use crate::{Detection, Error, GetResult, NoDest, NoSrc, Options, RequestBuilder};
+
+/// Fetches src to dest with the default settings, like [`crate::get`].
+///
+/// ```no_run
+/// fn main() -> Result<(), gette::Error> {
+///     gette::blocking::get("s3+https://s3.amazonaws.com/bucket/tool.tar.gz", "./tool")?;
+///     Ok(())
+/// }
+/// ```
+pub fn get(src: &str, dest: impl AsRef<Path>) -> Result<GetResult, Error> {
+    get_with(src, dest, Options::default())
+}
+
+/// Fetches src to dest with the settings given, like [`crate::get_with`].
+pub fn get_with(src: &str, dest: impl AsRef<Path>, options: Options) -> Result<GetResult, Error> {
+    runtime()?.block_on(crate::get_with(src, dest, options))
+}
+
+/// A [`crate::Client`] that blocks, made with
+/// [`RequestBuilder::build_blocking`]. Its runtime is kept for every get
+/// made with it.
+///
+/// ```no_run
+/// let client = gette::RequestBuilder::builder()
+///     .file_mode(gette::getters::FileMode::Copy)
+///     .build_blocking()
+///     .unwrap();
+/// client.get("./release/tool.tar.gz", "./tool").unwrap();
+/// ```
+pub struct Client {
+    inner: crate::Client,
+    runtime: Runtime,
+}
+
+impl Client {
+    /// Fetches src to dest, see [`crate::Client::get`].
+    pub fn get(&self, src: &str, dest: impl AsRef<Path>) -> Result<GetResult, Error> {
+        outside_runtime()?;
+        self.runtime.block_on(self.inner.get(src, dest))
+    }
+
+    /// Runs detection on src without fetching anything, see
+    /// [`crate::Client::resolve`].
+    pub fn resolve(&self, src: &str) -> Result<Detection, Error> {
+        self.inner.resolve(src)
+    }
+}
+
+impl RequestBuilder<NoSrc, NoDest> {
+    /// Finishes the builder into a [`Client`] that blocks, like
+    /// [`RequestBuilder::build`] does into one that's async.
+    pub fn build_blocking(self) -> Result<Client, Error> {
+        Ok(Client {
+            inner: self.build(),
+            runtime: runtime()?,
+        })
+    }
+}
+
+fn runtime() -> Result<Runtime, Error> {
+    outside_runtime()?;
+    Ok(runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?)
+}
+
+/// Fails when there's a runtime running already, which would panic on
+/// block_on, as well as on dropping a runtime of our own.
+fn outside_runtime() -> Result<(), Error> {
+    match runtime::Handle::try_current() {
+        Ok(_) => Err(Error::BlockingInRuntime),
+        Err(_) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::getters::FileMode;
+
+    #[test]
+    fn test_blocking_get() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("source.txt");
+        fs::write(&source, "test").unwrap();
+        let src = source.to_str().unwrap();
+
+        let res = get(src, tmp.path().join("linked")).unwrap();
+        assert!(res.final_path.is_symlink());
+
+        let client = RequestBuilder::builder()
+            .file_mode(FileMode::Copy)
+            .build_blocking()
+            .unwrap();
+        for name in ["a", "b"] {
+            let res = client.get(src, tmp.path().join(name)).unwrap();
+            assert!(!res.final_path.is_symlink());
+            assert_eq!(fs::read_to_string(&res.final_path).unwrap(), "test");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocking_in_runtime() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("source.txt");
+        fs::write(&source, "test").unwrap();
+
+        let err = get(source.to_str().unwrap(), tmp.path().join("dest"))
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::BlockingInRuntime), "{:?}", err);
+        assert!(!tmp.path().join("dest").exists());
+    }
+}
@@ -0,0 +1,264 @@
+//! Downloads kept between gets, so that the same source isn't fetched over
+//! and over, e.g. by every job on a CI runner.
+
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{checksum, staging::TempDest, Checksum, Error};
+
+/// What a download is kept under, a hash of the url it was fetched from
+/// and the checksum it was verified against. Getter parameters like `?ref=`
+/// are part of the url, so every revision of a source is kept apart.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    /// The key of a download from url that matched checksum.
+    pub fn new(url: &Url, checksum: &Checksum) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(url.as_str());
+        hasher.update("\n");
+        hasher.update(checksum.to_string());
+        Self(checksum::hex(&hasher.finalize()))
+    }
+
+    /// The hash in hex, safe to use as a file name.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Where downloads are kept between gets, see
+/// [`RequestBuilder::cache`](crate::RequestBuilder::cache). Only downloads
+/// that were verified against a checksum are put in it, and whatever is
+/// taken from it is verified against the checksum again before it's used,
+/// so a store needn't guard against its entries going bad.
+pub trait CacheStore: Send + Sync {
+    /// Puts the download kept under key at `to`, where nothing is yet,
+    /// returning whether there was one.
+    fn get(&self, key: &CacheKey, to: &Path) -> Result<bool, Error>;
+    /// Keeps the file at path under key, leaving the file as it is. Gets
+    /// racing each other may put the same key at once.
+    fn put(&self, key: &CacheKey, path: &Path) -> Result<(), Error>;
+    /// Drops what's kept under key, once it no longer matches its checksum.
+    fn remove(&self, key: &CacheKey) -> Result<(), Error>;
+}
+
+/// Default bound on the size of a [`DirCache`], 1 GiB.
+pub const DEFAULT_MAX_SIZE: u64 = 1 << 30;
+
+/// A cache in a local directory, a file per key, that evicts the least
+/// recently used downloads once it's over its size. Entries are written
+/// next to where they end up and renamed into place, so that processes
+/// sharing the directory only ever see complete ones.
+#[derive(Debug, Clone)]
+pub struct DirCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl DirCache {
+    /// A cache in dir, created on the first put, bound to
+    /// [`DEFAULT_MAX_SIZE`].
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_size: DEFAULT_MAX_SIZE,
+        }
+    }
+
+    /// Evict downloads once the cache holds more than this many bytes.
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = bytes;
+        self
+    }
+
+    fn path(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(key.as_str())
+    }
+
+    /// Removes the least recently used entries until the cache fits in its
+    /// size. Entries evicted by someone else in the meantime are skipped.
+    fn evict(&self) -> Result<(), Error> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            // entries still being written are left to whoever writes them
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            match entry.metadata() {
+                Ok(meta) if meta.is_file() => {
+                    entries.push((meta.modified()?, meta.len(), entry.path()))
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let mut size: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort();
+        for (_, len, path) in entries {
+            if size <= self.max_size {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            size -= len;
+        }
+        Ok(())
+    }
+}
+
+impl CacheStore for DirCache {
+    /// Hardlinks the entry to `to`, copying it when `to` is on another
+    /// filesystem, and marks it as used.
+    fn get(&self, key: &CacheKey, to: &Path) -> Result<bool, Error> {
+        let path = self.path(key);
+        match fs::hard_link(&path, to) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(e) if e.kind() == ErrorKind::CrossesDevices => match fs::copy(&path, to) {
+                Ok(_) => {}
+                // evicted in between
+                Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+                Err(e) => return Err(e.into()),
+            },
+            Err(e) => return Err(e.into()),
+        }
+        // how recently an entry was used is told by when it was last modified,
+        // which is when a download fetched right now would have been
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+            .or_else(|e| match e.kind() {
+                ErrorKind::NotFound => Ok(()),
+                _ => Err(e),
+            })?;
+        Ok(true)
+    }
+
+    fn put(&self, key: &CacheKey, path: &Path) -> Result<(), Error> {
+        fs::create_dir_all(&self.dir)?;
+        let entry = self.path(key);
+        // what's kept under a key is the same whoever put it
+        if entry.exists() {
+            return Ok(());
+        }
+        let tmp = TempDest::new(&entry);
+        match fs::hard_link(path, tmp.path()) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+                fs::copy(path, tmp.path())?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+        tmp.promote(&entry)?;
+        self.evict()
+    }
+
+    fn remove(&self, key: &CacheKey) -> Result<(), Error> {
+        match fs::remove_file(self.path(key)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn key(path: &str) -> CacheKey {
+        let url = Url::parse(&format!("https://host/{}", path)).unwrap();
+        CacheKey::new(
+            &url,
+            &Checksum::parse("md5:098f6bcd4621d373cade4e832627b4f6").unwrap(),
+        )
+    }
+
+    #[test]
+    fn it_should_key_by_url_and_checksum() {
+        assert_eq!(key("a.txt"), key("a.txt"));
+        assert_ne!(key("a.txt"), key("a.txt?ref=v2"));
+
+        let url = Url::parse("https://host/a.txt").unwrap();
+        let other = Checksum::parse("md5:00000000000000000000000000000000").unwrap();
+        assert_ne!(key("a.txt"), CacheKey::new(&url, &other));
+        assert_eq!(key("a.txt").as_str().len(), 64);
+    }
+
+    #[test]
+    fn it_should_keep_downloads() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache = DirCache::new(tmp.path().join("cache"));
+        let to = tmp.path().join("to.txt");
+        assert!(!cache.get(&key("a.txt"), &to).unwrap());
+
+        let download = tmp.path().join("download.txt");
+        fs::write(&download, "test").unwrap();
+        cache.put(&key("a.txt"), &download).unwrap();
+        // racing puts of the same key both land
+        cache.put(&key("a.txt"), &download).unwrap();
+        assert_eq!(fs::read_to_string(&download).unwrap(), "test");
+
+        assert!(cache.get(&key("a.txt"), &to).unwrap());
+        assert_eq!(fs::read_to_string(&to).unwrap(), "test");
+        assert!(!cache.get(&key("b.txt"), &tmp.path().join("b.txt")).unwrap());
+
+        cache.remove(&key("a.txt")).unwrap();
+        cache.remove(&key("a.txt")).unwrap();
+        assert!(!cache
+            .get(&key("a.txt"), &tmp.path().join("again.txt"))
+            .unwrap());
+        // nothing is left behind but entries
+        assert_eq!(fs::read_dir(tmp.path().join("cache")).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn it_should_evict_least_recently_used() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cache = DirCache::new(tmp.path().join("cache")).max_size(8);
+        let download = tmp.path().join("download.txt");
+        fs::write(&download, "test").unwrap();
+
+        let put = |name| {
+            cache.put(&key(name), &download).unwrap();
+            // mtimes may be coarse
+            std::thread::sleep(Duration::from_millis(20));
+        };
+        put("a.txt");
+        put("b.txt");
+        // a is used again, which makes b the least recently used
+        assert!(cache.get(&key("a.txt"), &tmp.path().join("a.txt")).unwrap());
+        std::thread::sleep(Duration::from_millis(20));
+        put("c.txt");
+
+        let kept = |name| cache.path(&key(name)).exists();
+        assert!(kept("a.txt"));
+        assert!(!kept("b.txt"));
+        assert!(kept("c.txt"));
+    }
+}
@@ -0,0 +1,937 @@
+use std::{
+    ffi::OsString,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    str::FromStr,
+    task::{ready, Context, Poll},
+};
+
+use sha2::digest::DynDigest;
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use url::Url;
+
+use crate::{
+    deadline::{self, Checked},
+    Error, GetResult,
+};
+
+/// Digest algorithms a checksum can be given in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ChecksumAlgo {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+    #[cfg(feature = "blake3")]
+    Blake3,
+    /// CRC-32C, as object stores keep it. It only catches corruption, not
+    /// tampering.
+    Crc32c,
+}
+
+impl ChecksumAlgo {
+    /// Name the algorithm is prefixed with in checksums, e.g. `sha256`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChecksumAlgo::Md5 => "md5",
+            ChecksumAlgo::Sha1 => "sha1",
+            ChecksumAlgo::Sha256 => "sha256",
+            ChecksumAlgo::Sha512 => "sha512",
+            #[cfg(feature = "blake3")]
+            ChecksumAlgo::Blake3 => "b3",
+            ChecksumAlgo::Crc32c => "crc32c",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "md5" => Some(ChecksumAlgo::Md5),
+            "sha1" => Some(ChecksumAlgo::Sha1),
+            "sha256" => Some(ChecksumAlgo::Sha256),
+            "sha512" => Some(ChecksumAlgo::Sha512),
+            #[cfg(feature = "blake3")]
+            "b3" | "blake3" => Some(ChecksumAlgo::Blake3),
+            "crc32c" => Some(ChecksumAlgo::Crc32c),
+            _ => None,
+        }
+    }
+
+    /// Tells the algorithm of a bare hex digest by its length. BLAKE3 and
+    /// CRC-32C have to be asked for by name, BLAKE3 digests are as long as
+    /// SHA-256 ones.
+    fn from_hex_len(len: usize) -> Option<Self> {
+        match len {
+            32 => Some(ChecksumAlgo::Md5),
+            40 => Some(ChecksumAlgo::Sha1),
+            64 => Some(ChecksumAlgo::Sha256),
+            128 => Some(ChecksumAlgo::Sha512),
+            _ => None,
+        }
+    }
+
+    /// Hashes everything a reader yields.
+    pub async fn of_reader<R: AsyncRead + Unpin>(&self, mut reader: R) -> Result<Checksum, Error> {
+        let mut hasher = self.hasher();
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            match reader.read(&mut buf).await? {
+                0 => break,
+                n => hasher.update(&buf[..n]),
+            }
+        }
+
+        Ok(self.checksum(hasher))
+    }
+
+    /// Hashes a file on a blocking thread, since it may be large. Within a
+    /// get, it stops once the phase it's part of times out.
+    pub async fn of_file(&self, path: impl AsRef<Path>) -> Result<Checksum, Error> {
+        let algo = *self;
+        let path = path.as_ref().to_path_buf();
+        deadline::blocking(move || {
+            let mut hasher = algo.hasher();
+            io::copy(&mut Checked(fs::File::open(path)?), &mut hasher)?;
+            Ok(algo.checksum(hasher))
+        })
+        .await
+    }
+
+    pub(crate) fn checksum(self, hasher: Hasher) -> Checksum {
+        Checksum {
+            algo: self,
+            digest: hasher.finish().into_vec(),
+        }
+    }
+
+    pub(crate) fn hasher(&self) -> Hasher {
+        match self {
+            ChecksumAlgo::Md5 => Hasher::Digest(Box::<md5::Md5>::default()),
+            ChecksumAlgo::Sha1 => Hasher::Digest(Box::<sha1::Sha1>::default()),
+            ChecksumAlgo::Sha256 => Hasher::Digest(Box::<sha2::Sha256>::default()),
+            ChecksumAlgo::Sha512 => Hasher::Digest(Box::<sha2::Sha512>::default()),
+            #[cfg(feature = "blake3")]
+            ChecksumAlgo::Blake3 => Hasher::Blake3(Box::default()),
+            ChecksumAlgo::Crc32c => Hasher::Crc32c(!0),
+        }
+    }
+}
+
+/// The digest a source is expected to have, given with `?checksum=` as
+/// `<algo>:<hex>` like go-getter, or as bare hex whose length tells the
+/// algorithm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    algo: ChecksumAlgo,
+    digest: Vec<u8>,
+}
+
+impl Checksum {
+    /// Parses a checksum the way `?checksum=` is, e.g. `sha256:<hex>`.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        s.parse().map_err(Error::InvalidChecksum)
+    }
+
+    /// Sha256 checksum of a file, see [`ChecksumAlgo::of_file`] for the
+    /// others.
+    pub async fn of_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        ChecksumAlgo::Sha256.of_file(path).await
+    }
+
+    /// Makes a checksum out of a raw digest, e.g. one an object store keeps.
+    pub fn from_digest(algo: ChecksumAlgo, digest: &[u8]) -> Result<Self, Error> {
+        let len = algo.hasher().finish().len();
+        if digest.len() != len {
+            return Err(Error::InvalidChecksum(format!(
+                "{} digests are {} bytes long",
+                algo.name(),
+                len
+            )));
+        }
+
+        Ok(Self {
+            algo,
+            digest: digest.to_vec(),
+        })
+    }
+
+    pub fn algo(&self) -> ChecksumAlgo {
+        self.algo
+    }
+
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+
+    /// Compares a checksum computed in the same algorithm with this one.
+    pub(crate) fn verify(&self, actual: &Checksum) -> Result<(), Error> {
+        self.compare(&actual.digest)
+    }
+
+    /// Hashes a file and compares it with this checksum, failing with
+    /// [`Error::ChecksumMismatch`] when they differ.
+    pub async fn verify_file(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        self.compare(&self.algo.of_file(path).await?.digest)
+    }
+
+    /// Whether a file already has this checksum.
+    pub(crate) async fn matches_file(&self, path: &Path) -> Result<bool, Error> {
+        Ok(self.algo.of_file(path).await? == *self)
+    }
+
+    /// Compares the [`tree`] checksum of what was fetched with this one.
+    pub(crate) fn verify_tree(&self, actual: &Checksum) -> Result<(), Error> {
+        if self == actual {
+            return Ok(());
+        }
+
+        Err(Error::ChecksumMismatch {
+            algo: "tree",
+            expected: self.to_string(),
+            actual: actual.to_string(),
+        })
+    }
+
+    fn compare(&self, actual: &[u8]) -> Result<(), Error> {
+        if *actual == *self.digest {
+            return Ok(());
+        }
+
+        Err(Error::ChecksumMismatch {
+            algo: self.algo.name(),
+            expected: hex(&self.digest),
+            actual: hex(actual),
+        })
+    }
+}
+
+/// Passes on what a reader yields, checking it against a checksum once
+/// it's all been read. A mismatch fails the read that reaches the end, with
+/// an [`io::ErrorKind::InvalidData`] error wrapping
+/// [`Error::ChecksumMismatch`], so that whatever reads to the end never takes
+/// a corrupted source for a good one.
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use gette::{checksum::VerifyingReader, Checksum};
+/// use tokio::io::AsyncReadExt;
+///
+/// let checksum = Checksum::parse("md5:098f6bcd4621d373cade4e832627b4f6").unwrap();
+/// let mut reader = VerifyingReader::new(&b"tset"[..], checksum);
+/// let err = reader.read_to_end(&mut Vec::new()).await.unwrap_err();
+/// let err = err.into_inner().unwrap().downcast::<gette::Error>().unwrap();
+/// assert!(matches!(*err, gette::Error::ChecksumMismatch { .. }));
+/// # })
+/// ```
+pub struct VerifyingReader<R> {
+    inner: R,
+    expected: Checksum,
+    /// Gone once the end is reached and checked.
+    hasher: Option<Hasher>,
+}
+
+impl<R> VerifyingReader<R> {
+    pub fn new(inner: R, expected: Checksum) -> Self {
+        Self {
+            hasher: Some(expected.algo.hasher()),
+            inner,
+            expected,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for VerifyingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        // a read with no room for anything reads nothing without being the end
+        let (filled, room) = (buf.filled().len(), buf.remaining() > 0);
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        let read = &buf.filled()[filled..];
+        match this.hasher.as_mut() {
+            Some(hasher) if !read.is_empty() => hasher.update(read),
+            Some(_) if room => {
+                let hasher = this
+                    .hasher
+                    .take()
+                    .expect("the hasher is there until the end");
+                let actual = this.expected.algo.checksum(hasher);
+                this.expected
+                    .verify(&actual)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            }
+            _ => {}
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Hashes a directory into a sha256 checksum that depends only on what's in
+/// it, so that it can be pinned with `?tree_checksum=`. The algorithm is kept
+/// stable across releases:
+///
+/// 1. Every path under the root is listed relative to it, with components
+///    joined by `/`, and sorted by its bytes. The root itself isn't listed,
+///    unless it's a file, which is then listed under an empty path.
+/// 2. Each path makes a record of its kind, the path and a value, each of
+///    them followed by a NUL byte:
+///    - `file`, with `<mode> <sha256 hex>` of its contents as the value. The
+///      mode is `755` when its owner may execute it and `644` otherwise, so
+///      that the umask it was written with doesn't matter.
+///    - `link`, with the target of the symlink as the value. Symlinks are
+///      never followed, except at the root.
+///    - `dir`, with an empty value, so that empty directories count too.
+/// 3. The checksum is the sha256 of the records one after another.
+pub async fn tree(root: impl AsRef<Path>) -> Result<Checksum, Error> {
+    let root = root.as_ref().to_path_buf();
+    deadline::blocking(move || {
+        let mut records = Vec::new();
+        if root.is_dir() {
+            tree_records(&root, "", &mut records)?;
+        } else {
+            records.push(tree_record(&root, String::new(), fs::metadata(&root)?)?);
+        }
+        records.sort();
+
+        let mut hasher = ChecksumAlgo::Sha256.hasher();
+        for (_, record) in records {
+            hasher.update(&record);
+        }
+        Ok(ChecksumAlgo::Sha256.checksum(hasher))
+    })
+    .await
+}
+
+fn tree_records(
+    dir: &Path,
+    prefix: &str,
+    records: &mut Vec<(String, Vec<u8>)>,
+) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        let meta = entry.metadata()?;
+        if meta.is_dir() {
+            tree_records(&entry.path(), &format!("{}/", path), records)?;
+        }
+        records.push(tree_record(&entry.path(), path, meta)?);
+    }
+
+    Ok(())
+}
+
+fn tree_record(path: &Path, name: String, meta: fs::Metadata) -> Result<(String, Vec<u8>), Error> {
+    let (kind, value) = if meta.is_symlink() {
+        ("link", fs::read_link(path)?.to_string_lossy().into_owned())
+    } else if meta.is_dir() {
+        ("dir", String::new())
+    } else {
+        #[cfg(unix)]
+        let executable = std::os::unix::fs::PermissionsExt::mode(&meta.permissions()) & 0o100 != 0;
+        #[cfg(not(unix))]
+        let executable = false;
+
+        let mut hasher = ChecksumAlgo::Sha256.hasher();
+        io::copy(&mut Checked(fs::File::open(path)?), &mut hasher)?;
+        let mode = if executable { "755" } else { "644" };
+        ("file", format!("{} {}", mode, hex(&hasher.finish())))
+    };
+
+    let mut record = Vec::new();
+    for field in [kind, &name, &value] {
+        record.extend_from_slice(field.as_bytes());
+        record.push(0);
+    }
+    Ok((name, record))
+}
+
+/// Where the checksum a get verified came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ChecksumSource {
+    /// Given in the source with `?checksum=<algo>:<hex>`.
+    Url,
+    /// Looked up in the sums file at this url, given with `?checksum=file:`.
+    SumsFile(String),
+    /// Kept by the store the source was fetched from, like the checksums S3
+    /// computes on upload.
+    Remote,
+}
+
+/// Path the [`Record`] of what was last fetched into dest is kept at.
+pub(crate) fn sidecar_path(dest: &Path) -> Option<PathBuf> {
+    let mut name = OsString::from(".");
+    name.push(dest.file_name()?);
+    name.push(".gette-checksum");
+    Some(dest.with_file_name(name))
+}
+
+/// What gette recorded about the source last fetched into dest, one line
+/// each, so that older records holding only the first of them still read.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Record {
+    /// Checksum of the archive extracted into dest, since dest holds what
+    /// was extracted rather than the archive itself.
+    pub checksum: Option<Checksum>,
+    /// Url the source was fetched from.
+    pub source: Option<Url>,
+    /// Fingerprint the getter gave the source when it was fetched, see
+    /// [`Getter::fingerprint`](crate::Getter::fingerprint).
+    pub fingerprint: Option<String>,
+}
+
+impl Record {
+    /// What's recorded of the get that put result at dest, its source only
+    /// when it's asked for.
+    pub(crate) fn of(result: &GetResult, source: bool, fingerprint: Option<String>) -> Self {
+        // dest holds what was extracted, not the archive the checksum is of
+        let checksum = result.checksum.clone().filter(|_| result.archive.is_some());
+        Self {
+            checksum,
+            source: source.then(|| result.source_url.clone()),
+            fingerprint,
+        }
+    }
+
+    /// Reads a record, leaving out whatever part of it doesn't parse.
+    pub fn parse(s: &str) -> Self {
+        let mut lines = s.lines().map(str::trim);
+        Self {
+            checksum: lines.next().and_then(|l| l.parse().ok()),
+            source: lines.next().and_then(|l| Url::parse(l).ok()),
+            fingerprint: lines.next().filter(|l| !l.is_empty()).map(String::from),
+        }
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(checksum) = &self.checksum {
+            write!(f, "{}", checksum)?;
+        }
+        // every part has a line of its own, empty when it's missing
+        if self.source.is_some() || self.fingerprint.is_some() {
+            let source = self.source.as_ref().map(Url::as_str);
+            write!(f, "\n{}", source.unwrap_or_default())?;
+        }
+        if let Some(fingerprint) = &self.fingerprint {
+            write!(f, "\n{}", fingerprint)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Checksum {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (algo, value) = match s.split_once(':') {
+            Some((name, value)) => (
+                ChecksumAlgo::from_name(name)
+                    .ok_or_else(|| format!("unsupported checksum type {}", name))?,
+                value,
+            ),
+            None => (
+                ChecksumAlgo::from_hex_len(s.len()).ok_or_else(|| {
+                    format!("can't tell the checksum type of {} hex characters", s.len())
+                })?,
+                s,
+            ),
+        };
+
+        let digest = unhex(value).ok_or_else(|| "checksum must be in hex".to_string())?;
+        let len = algo.hasher().finish().len();
+        if digest.len() != len {
+            return Err(format!(
+                "{} checksum must be {} hex characters",
+                algo.name(),
+                len * 2
+            ));
+        }
+
+        Ok(Self { algo, digest })
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.algo.name(), hex(&self.digest))
+    }
+}
+
+/// Serialized as it's displayed, `<algo>:<hex>`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Checksum {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserialized from what `?checksum=` takes, a bare hex digest included.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Checksum {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Picks the checksum of the file called `name` out of the contents of a sums
+/// file, in either the format of `sha256sum` (`<hex>  <file>`) or of BSD
+/// (`SHA256 (<file>) = <hex>`). Lines in neither format are skipped, so that
+/// e.g. a clearsigned sums file can be read as is. Entries in subdirectories
+/// are matched by their file name when no entry matches exactly.
+pub(crate) fn from_sums(file: &str, contents: &str, name: &str) -> Result<Checksum, Error> {
+    let entries: Vec<(&str, Checksum)> = contents.lines().filter_map(sums_entry).collect();
+
+    let mut found: Vec<&(&str, Checksum)> = entries.iter().filter(|(n, _)| *n == name).collect();
+    if found.is_empty() {
+        found = entries
+            .iter()
+            .filter(|(n, _)| n.rsplit('/').next() == Some(name))
+            .collect();
+    }
+
+    let names =
+        |entries: &[&(&str, Checksum)]| entries.iter().map(|(n, _)| n.to_string()).collect();
+    match found.split_first() {
+        Some(((_, checksum), rest)) if rest.iter().all(|(_, c)| c == checksum) => {
+            Ok(checksum.clone())
+        }
+        Some(_) => Err(Error::ChecksumAmbiguous {
+            file: file.to_string(),
+            name: name.to_string(),
+            entries: names(&found),
+        }),
+        None => Err(Error::ChecksumNotFound {
+            file: file.to_string(),
+            name: name.to_string(),
+            entries: names(&entries.iter().collect::<Vec<_>>()),
+        }),
+    }
+}
+
+fn sums_entry(line: &str) -> Option<(&str, Checksum)> {
+    let line = line.trim();
+
+    let (name, checksum) = match line.split_once(" (") {
+        Some((algo, rest)) if !algo.contains(char::is_whitespace) => {
+            let (name, digest) = rest.rsplit_once(") = ")?;
+            (name, format!("{}:{}", algo, digest).parse().ok()?)
+        }
+        _ => {
+            let (digest, name) = line.split_once(char::is_whitespace)?;
+            // a star marks files hashed in binary mode, which makes no difference
+            let name = name.trim_start();
+            (name.strip_prefix('*').unwrap_or(name), digest.parse().ok()?)
+        }
+    };
+
+    Some((name.strip_prefix("./").unwrap_or(name), checksum))
+}
+
+/// Incrementally hashes bytes as they're downloaded.
+pub(crate) enum Hasher {
+    Digest(Box<dyn DynDigest + Send>),
+    #[cfg(feature = "blake3")]
+    Blake3(Box<blake3::Hasher>),
+    Crc32c(u32),
+}
+
+impl Hasher {
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Digest(digest) => digest.update(data),
+            #[cfg(feature = "blake3")]
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            Hasher::Crc32c(crc) => {
+                for b in data {
+                    *crc = CRC32C[((*crc ^ *b as u32) & 0xff) as usize] ^ (*crc >> 8);
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> Box<[u8]> {
+        match self {
+            Hasher::Digest(digest) => digest.finalize(),
+            #[cfg(feature = "blake3")]
+            Hasher::Blake3(hasher) => Box::new(*hasher.finalize().as_bytes()),
+            Hasher::Crc32c(crc) => Box::new((!crc).to_be_bytes()),
+        }
+    }
+}
+
+/// Lookup table of the reflected Castagnoli polynomial, for a byte at a time.
+const CRC32C: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+impl io::Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // digests of "test"
+    const MD5: &str = "098f6bcd4621d373cade4e832627b4f6";
+    const SHA256: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn it_should_parse_checksums() {
+        let tests = vec![
+            (format!("md5:{}", MD5), ChecksumAlgo::Md5, MD5),
+            (format!("sha256:{}", SHA256), ChecksumAlgo::Sha256, SHA256),
+            (
+                format!("SHA256:{}", SHA256.to_uppercase()),
+                ChecksumAlgo::Sha256,
+                SHA256,
+            ),
+            (MD5.to_string(), ChecksumAlgo::Md5, MD5),
+            (SHA256.to_string(), ChecksumAlgo::Sha256, SHA256),
+        ];
+
+        for (input, algo, digest) in tests {
+            let checksum: Checksum = input.parse().unwrap();
+            assert_eq!(checksum.algo(), algo, "{}", input);
+            assert_eq!(checksum.to_string(), format!("{}:{}", algo.name(), digest));
+        }
+
+        for input in [
+            "crc64:0011",
+            "md5:0011",
+            "sha256:zz",
+            "0123456789",
+            &format!("md5:{}", SHA256),
+        ] {
+            assert!(input.parse::<Checksum>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn it_should_pick_checksums_out_of_sums_files() {
+        let other = SHA256.replace('f', "e");
+        let sums = format!(
+            "-----BEGIN PGP SIGNED MESSAGE-----\n\
+             Hash: SHA256\n\
+             \n\
+             {sha}  tool-linux.tar.gz\n\
+             {other} *tool-darwin.tar.gz\n\
+             {other}  ./docs/README\n\
+             SHA256 (tool-windows.zip) = {sha}\n\
+             MD5 (nested/tool.deb) = {md5}\n\
+             {other}  a/dup.txt\n\
+             {sha}  b/dup.txt\n\
+             {sha}  a/same.txt\n\
+             {sha}  b/same.txt\n\
+             -----BEGIN PGP SIGNATURE-----\n",
+            sha = SHA256,
+            md5 = MD5,
+            other = other,
+        );
+
+        let tests = vec![
+            ("tool-linux.tar.gz", format!("sha256:{}", SHA256)),
+            ("tool-darwin.tar.gz", format!("sha256:{}", other)),
+            ("README", format!("sha256:{}", other)),
+            ("tool-windows.zip", format!("sha256:{}", SHA256)),
+            ("tool.deb", format!("md5:{}", MD5)),
+            ("same.txt", format!("sha256:{}", SHA256)),
+        ];
+        for (name, checksum) in tests {
+            let res = from_sums("SHA256SUMS", &sums, name);
+            assert_eq!(res.unwrap().to_string(), checksum, "{}", name);
+        }
+
+        let err = from_sums("SHA256SUMS", &sums, "dup.txt").unwrap_err();
+        assert!(
+            matches!(err, Error::ChecksumAmbiguous { ref entries, .. } if entries == &["a/dup.txt", "b/dup.txt"]),
+            "{:?}",
+            err
+        );
+
+        let err = from_sums("SHA256SUMS", &sums, "tool-linux.tar.xz").unwrap_err();
+        assert!(
+            matches!(err, Error::ChecksumNotFound { ref entries, .. } if entries.len() == 9 && entries[0] == "tool-linux.tar.gz"),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn it_should_hash_with_every_algorithm() {
+        // digests of "abc", fed in two chunks to go through the incremental path
+        let mut tests = vec![
+            ("md5", "900150983cd24fb0d6963f7d28e17f72"),
+            ("sha1", "a9993e364706816aba3e25717850c26c9cd0d89d"),
+            (
+                "sha256",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                "sha512",
+                "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+                 2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            ),
+        ];
+        if cfg!(feature = "blake3") {
+            tests.push((
+                "b3",
+                "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            ));
+        }
+        tests.push(("crc32c", "364b3fb7"));
+
+        for (name, digest) in tests {
+            let checksum: Checksum = format!("{}:{}", name, digest).parse().unwrap();
+            assert_eq!(checksum.algo().name(), name);
+            let mut hasher = checksum.algo().hasher();
+            hasher.update(b"a");
+            hasher.update(b"bc");
+            checksum.verify(&checksum.algo().checksum(hasher)).unwrap();
+
+            // bare digests are told apart by length, except for BLAKE3 and CRC-32C
+            if name != "b3" && name != "crc32c" {
+                assert_eq!(digest.parse::<Checksum>().unwrap(), checksum);
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_read_records() {
+        let checksum: Checksum = format!("md5:{}", MD5).parse().unwrap();
+        let source = Url::parse("https://example.com/test.tar.gz").unwrap();
+        let tests = vec![
+            Record {
+                checksum: Some(checksum.clone()),
+                ..Default::default()
+            },
+            Record {
+                source: Some(source.clone()),
+                ..Default::default()
+            },
+            Record {
+                checksum: Some(checksum.clone()),
+                source: Some(source.clone()),
+                ..Default::default()
+            },
+            Record {
+                fingerprint: Some("\"etag\"".to_string()),
+                ..Default::default()
+            },
+            Record {
+                checksum: None,
+                source: Some(source.clone()),
+                fingerprint: Some("\"etag\"".to_string()),
+            },
+        ];
+        for record in tests {
+            assert_eq!(Record::parse(&record.to_string()), record);
+        }
+
+        // records from before the source was kept
+        assert_eq!(
+            Record::parse(&format!("md5:{}\n", MD5)).checksum,
+            Some(checksum)
+        );
+        assert_eq!(Record::parse("garbage"), Record::default());
+    }
+
+    #[tokio::test]
+    async fn it_should_verify_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("test.txt");
+        fs::write(&file, "test").unwrap();
+
+        for digest in [MD5, SHA256] {
+            let checksum: Checksum = digest.parse().unwrap();
+            checksum.verify_file(&file).await.unwrap();
+        }
+
+        let checksum: Checksum = MD5.replace('0', "1").parse().unwrap();
+        let err = checksum.verify_file(&file).await.unwrap_err();
+        assert!(
+            matches!(err, Error::ChecksumMismatch { algo: "md5", ref actual, .. } if actual == MD5),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_hash_readers_and_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("test.txt");
+        fs::write(&file, "test").unwrap();
+
+        let expected = Checksum::parse(&format!("sha256:{}", SHA256)).unwrap();
+        assert_eq!(Checksum::of_file(&file).await.unwrap(), expected);
+        assert_eq!(
+            ChecksumAlgo::Sha256.of_reader(&b"test"[..]).await.unwrap(),
+            expected
+        );
+        assert_eq!(
+            ChecksumAlgo::Md5.of_file(&file).await.unwrap().to_string(),
+            format!("md5:{}", MD5)
+        );
+        assert_eq!(hex(expected.digest()), SHA256);
+        assert_eq!(
+            Checksum::from_digest(ChecksumAlgo::Sha256, expected.digest()).unwrap(),
+            expected
+        );
+        assert_eq!(
+            ChecksumAlgo::Crc32c
+                .of_reader(&b"123456789"[..])
+                .await
+                .unwrap(),
+            Checksum::parse("crc32c:e3069283").unwrap()
+        );
+
+        let err = Checksum::parse("sha256:1234").unwrap_err();
+        assert!(matches!(err, Error::InvalidChecksum(_)), "{:?}", err);
+        let err = Checksum::from_digest(ChecksumAlgo::Crc32c, &[0; 8]).unwrap_err();
+        assert!(matches!(err, Error::InvalidChecksum(_)), "{:?}", err);
+        let err = Checksum::of_file(tmp.path().join("missing"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Io(_)), "{:?}", err);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn it_should_stop_hashing_once_timed_out() {
+        use std::time::{Duration, Instant};
+
+        use crate::Phase;
+
+        // a file that never ends is only ever left by timing out
+        let soon = Some(Instant::now() + Duration::from_millis(50));
+        let hashed = ChecksumAlgo::Sha256.of_file("/dev/zero");
+        let err = deadline::within(soon, Phase::Verification, hashed)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::Timeout {
+                    phase: Phase::Verification
+                }
+            ),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_hash_trees() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("tree");
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::create_dir_all(root.join("empty")).unwrap();
+        fs::write(root.join("bin/tool"), "test").unwrap();
+        fs::write(root.join("README"), "readme").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("bin/tool", root.join("tool")).unwrap();
+
+        // the records the algorithm is documented to hash
+        let mut expected = vec![
+            format!("file\0README\0644 {}\0", hex(&sha256(b"readme"))),
+            "dir\0bin\0\0".to_string(),
+            format!("file\0bin/tool\0644 {}\0", hex(&sha256(b"test"))),
+            "dir\0empty\0\0".to_string(),
+        ];
+        if cfg!(unix) {
+            expected.push("link\0tool\0bin/tool\0".to_string());
+        }
+        let expected = Checksum {
+            algo: ChecksumAlgo::Sha256,
+            digest: sha256(expected.concat().as_bytes()).into_vec(),
+        };
+
+        let first = tree(&root).await.unwrap();
+        assert_eq!(first, expected);
+        expected.verify_tree(&first).unwrap();
+
+        // the same tree written again elsewhere hashes the same
+        let copy = tmp.path().join("copy");
+        fs::create_dir_all(copy.join("empty")).unwrap();
+        fs::create_dir_all(copy.join("bin")).unwrap();
+        fs::write(copy.join("README"), "readme").unwrap();
+        fs::write(copy.join("bin/tool"), "test").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("bin/tool", copy.join("tool")).unwrap();
+        assert_eq!(tree(&copy).await.unwrap(), first);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let tool = root.join("bin/tool");
+            fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+            assert_ne!(tree(&root).await.unwrap(), first);
+            fs::set_permissions(&tool, fs::Permissions::from_mode(0o600)).unwrap();
+            assert_eq!(tree(&root).await.unwrap(), first);
+        }
+
+        fs::remove_dir(root.join("empty")).unwrap();
+        let changed = tree(&root).await.unwrap();
+        assert_ne!(changed, first);
+        let err = first.verify_tree(&changed).unwrap_err();
+        assert!(
+            matches!(err, Error::ChecksumMismatch { algo: "tree", .. }),
+            "{:?}",
+            err
+        );
+    }
+
+    fn sha256(data: &[u8]) -> Box<[u8]> {
+        let mut hasher = ChecksumAlgo::Sha256.hasher();
+        hasher.update(data);
+        hasher.finish()
+    }
+}
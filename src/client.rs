@@ -0,0 +1,227 @@
+use std::{path::Path, time::Duration};
+
+use tokio::io::AsyncRead;
+
+use crate::{
+    getters::FileMode, options, Checksum, DestPath, Detection, Error, GetResult,
+    MultiRequestBuilder, NoDest, NoSrc, RequestBuilder,
+};
+
+/// Fetches src to dest with the default detectors, getters and settings,
+/// for when there's nothing to configure.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), gette::Error> {
+/// gette::get("s3+https://s3.amazonaws.com/bucket/tool.tar.gz", "./tool").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn get(src: &str, dest: impl AsRef<Path>) -> Result<GetResult, Error> {
+    get_with(src, dest, Options::default()).await
+}
+
+/// Fetches src to dest like [`get`], with the settings most often changed.
+/// Anything else takes a [`RequestBuilder`].
+///
+/// ```no_run
+/// # async fn run() -> Result<(), gette::Error> {
+/// let options = gette::Options {
+///     file_mode: gette::getters::FileMode::Copy,
+///     timeout: Some(std::time::Duration::from_secs(60)),
+///     ..Default::default()
+/// };
+/// gette::get_with("./release/tool.tar.gz", "./tool", options).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn get_with(
+    src: &str,
+    dest: impl AsRef<Path>,
+    options: Options,
+) -> Result<GetResult, Error> {
+    let src = match &options.checksum {
+        Some(checksum) => options::with_param(src, "checksum", checksum),
+        None => src.to_string(),
+    };
+    let mut builder = RequestBuilder::builder().file_mode(options.file_mode);
+    if let Some(timeout) = options.timeout {
+        builder = builder.timeout(timeout);
+    }
+    builder.src(src).dest(dest).get().await
+}
+
+/// Settings of [`get_with`].
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// What the download has to match, as with `?checksum=`.
+    pub checksum: Option<Checksum>,
+    /// How local sources end up at dest, see [`RequestBuilder::file_mode`].
+    pub file_mode: FileMode,
+    /// How long the get may take in all, see [`RequestBuilder::timeout`].
+    pub timeout: Option<Duration>,
+}
+
+/// A [`RequestBuilder`] configured once, with [`RequestBuilder::build`], to
+/// fetch any number of sources with. Clones share everything, the clients
+/// of the getters included.
+///
+/// ```rust
+/// # tokio_test::block_on(async {
+/// # let tmp = tempfile::tempdir().unwrap();
+/// # std::fs::write(tmp.path().join("a.txt"), "a").unwrap();
+/// # std::fs::write(tmp.path().join("b.txt"), "b").unwrap();
+/// let client = gette::RequestBuilder::builder()
+///     .file_mode(gette::getters::FileMode::Copy)
+///     .build();
+/// for name in ["a.txt", "b.txt"] {
+///     let src = tmp.path().join(name);
+///     client
+///         .get(src.to_str().unwrap(), tmp.path().join("out").join(name))
+///         .await
+///         .unwrap();
+/// }
+/// # assert_eq!(std::fs::read_to_string(tmp.path().join("out/b.txt")).unwrap(), "b");
+/// # })
+/// ```
+#[derive(Clone)]
+pub struct Client {
+    builder: RequestBuilder<NoSrc, NoDest>,
+}
+
+impl Client {
+    pub(crate) fn new(builder: RequestBuilder<NoSrc, NoDest>) -> Self {
+        Self { builder }
+    }
+
+    /// Fetches src to dest, like [`RequestBuilder::get`] does for the source
+    /// and dest it's given.
+    pub async fn get(&self, src: &str, dest: impl AsRef<Path>) -> Result<GetResult, Error> {
+        let dest = DestPath::Path(dest.as_ref().to_path_buf());
+        self.builder.get_src(src, &dest).await
+    }
+
+    /// Fetches src into memory, like [`RequestBuilder::get_bytes`] does for
+    /// the source it's given.
+    pub async fn get_bytes(&self, src: &str) -> Result<Vec<u8>, Error> {
+        self.builder.bytes_of(src).await
+    }
+
+    /// Fetches src into memory as text, like [`RequestBuilder::get_string`]
+    /// does for the source it's given.
+    pub async fn get_string(&self, src: &str) -> Result<String, Error> {
+        self.builder.string_of(src).await
+    }
+
+    /// Opens src as a stream, like [`RequestBuilder::get_reader`] does for
+    /// the source it's given.
+    pub async fn get_reader(&self, src: &str) -> Result<impl AsyncRead + Unpin + Send, Error> {
+        self.builder.reader_of(src).await
+    }
+
+    /// Runs detection on src without fetching anything, like
+    /// [`RequestBuilder::resolve`].
+    pub fn resolve(&self, src: &str) -> Result<Detection, Error> {
+        self.builder.resolve_src(src)
+    }
+
+    /// Queue up a source to be fetched to dest along with others, see
+    /// [`MultiRequestBuilder`].
+    pub fn queue(&self, src: impl Into<String>, dest: impl AsRef<Path>) -> MultiRequestBuilder {
+        MultiRequestBuilder::new(self.builder.clone()).queue(src, dest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::{GetOptions, Getter, Url};
+
+    /// A getter that counts what it fetched.
+    #[derive(Default)]
+    struct Counting(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Getter for Counting {
+        async fn get(&self, dest: &Path, url: &Url, _: &GetOptions) -> Result<(), Error> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            fs::write(dest, url.as_str())?;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client() {
+        let tmp = tempfile::tempdir().unwrap();
+        let getter = Counting::default();
+        let count = getter.0.clone();
+        let client = RequestBuilder::builder()
+            .add_getter("count", Box::new(getter))
+            .build();
+
+        let other = client.clone();
+        for (client, name) in [(&client, "a.txt"), (&other, "b.txt"), (&client, "c.txt")] {
+            let src = format!("count://host/{}", name);
+            let res = client.get(&src, tmp.path().join(name)).await.unwrap();
+            assert_eq!(res.getter, "count");
+            assert_eq!(fs::read_to_string(tmp.path().join(name)).unwrap(), src);
+        }
+        // every clone fetches with the same getter
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+
+        let detection = client.resolve("count://host/d.tar.gz").unwrap();
+        assert_eq!(detection.archive.as_deref(), Some("tar.gz"));
+        let results = client
+            .queue("count://host/d.txt", tmp.path().join("d.txt"))
+            .get_all()
+            .await;
+        assert!(results[0].is_ok());
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_get_with() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("source.txt");
+        fs::write(&source, "test").unwrap();
+        let src = source.to_str().unwrap();
+
+        let res = get(src, tmp.path().join("linked")).await.unwrap();
+        assert!(res.final_path.is_symlink());
+
+        let options = Options {
+            checksum: Some("md5:098f6bcd4621d373cade4e832627b4f6".parse().unwrap()),
+            file_mode: FileMode::Copy,
+            timeout: Some(Duration::from_secs(60)),
+        };
+        let res = get_with(src, tmp.path().join("copied"), options.clone())
+            .await
+            .unwrap();
+        assert!(!res.final_path.is_symlink());
+        assert_eq!(res.checksum, options.checksum);
+
+        let options = Options {
+            checksum: Some("md5:00000000000000000000000000000000".parse().unwrap()),
+            ..options
+        };
+        let err = get_with(src, tmp.path().join("mismatched"), options)
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            matches!(err.inner(), Error::ChecksumMismatch { .. }),
+            "{:?}",
+            err
+        );
+        assert!(!tmp.path().join("mismatched").exists());
+    }
+}
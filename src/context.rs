@@ -0,0 +1,102 @@
+//! What a failed get was doing when it failed, see [`Error::Context`].
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use url::Url;
+
+use crate::Error;
+
+/// The phases a get goes through, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Phase {
+    /// Resolving the source to a getter and a url, looking up its checksum.
+    Detection,
+    /// Waiting on other gets to the same dest.
+    Locking,
+    /// Fetching the source with its getter.
+    Transfer,
+    /// Checking what was fetched against its checksum and signature.
+    Verification,
+    /// Extracting what was fetched, if it's an archive.
+    Extraction,
+    /// Running the post-processors on what was fetched, see
+    /// [`PostProcessor`](crate::PostProcessor).
+    PostProcessing,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Detection => "detection",
+            Phase::Locking => "locking",
+            Phase::Transfer => "transfer",
+            Phase::Verification => "verification",
+            Phase::Extraction => "extraction",
+            Phase::PostProcessing => "post-processing",
+        })
+    }
+}
+
+/// Which get an error came from and how far along it was.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ErrorContext {
+    /// The source as it was given.
+    pub src: String,
+    /// The url the source resolved to, once detection got that far.
+    pub url: Option<Url>,
+    /// Where the source was being fetched to.
+    pub dest: PathBuf,
+    /// What the get was doing.
+    pub phase: Phase,
+}
+
+impl ErrorContext {
+    pub(crate) fn new(src: &str, dest: &Path) -> Self {
+        Self {
+            src: src.to_string(),
+            url: None,
+            dest: dest.to_path_buf(),
+            phase: Phase::Detection,
+        }
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "getting {}", self.src)?;
+        if let Some(url) = self.url.as_ref().filter(|url| url.as_str() != self.src) {
+            write!(f, " ({})", url)?;
+        }
+        // gets into memory have no dest
+        if !self.dest.as_os_str().is_empty() {
+            write!(f, " to {}", self.dest.display())?;
+        }
+        write!(f, " failed during {}", self.phase)
+    }
+}
+
+/// Wraps the error of a result in what the get was doing.
+pub(crate) trait Context<T> {
+    fn context(self, context: &ErrorContext, phase: Phase) -> Result<T, Error>;
+}
+
+impl<T> Context<T> for Result<T, Error> {
+    fn context(self, context: &ErrorContext, phase: Phase) -> Result<T, Error> {
+        self.map_err(|e| match e {
+            // the innermost context knows best
+            Error::Context { .. } => e,
+            e => Error::Context {
+                context: Box::new(ErrorContext {
+                    phase,
+                    ..context.clone()
+                }),
+                source: Box::new(e),
+            },
+        })
+    }
+}
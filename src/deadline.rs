@@ -0,0 +1,294 @@
+//! Deadlines on a get as a whole and on the phases it goes through.
+
+use std::{
+    cell::RefCell,
+    future::Future,
+    io::{self, Read},
+    iter,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{Error, Phase};
+
+tokio::task_local! {
+    /// What tells the blocking work of the phase [`within`] runs that it
+    /// was given up on.
+    static CANCEL: Cancel;
+}
+
+thread_local! {
+    /// The [`Cancel`] of the blocking work running on this thread, if any.
+    static WORKER: RefCell<Option<Cancel>> = const { RefCell::new(None) };
+}
+
+/// How long a get and its phases may take, as set on the builder.
+#[derive(Default, Debug, Clone, Copy)]
+pub(crate) struct Timeouts {
+    pub total: Option<Duration>,
+    pub transfer: Option<Duration>,
+    pub extract: Option<Duration>,
+}
+
+impl Timeouts {
+    /// Deadlines of a get starting now.
+    pub(crate) fn start(self) -> Deadlines {
+        Deadlines {
+            end: self.total.map(|total| Instant::now() + total),
+            timeouts: self,
+        }
+    }
+}
+
+/// Deadlines of a single get.
+pub(crate) struct Deadlines {
+    end: Option<Instant>,
+    timeouts: Timeouts,
+}
+
+impl Deadlines {
+    /// When the get as a whole has to be done by.
+    pub(crate) fn end(&self) -> Option<Instant> {
+        self.end
+    }
+
+    /// When a transfer starting now has to be done by.
+    pub(crate) fn transfer(&self) -> Option<Instant> {
+        self.phase(self.timeouts.transfer)
+    }
+
+    /// When an extraction starting now has to be done by.
+    pub(crate) fn extract(&self) -> Option<Instant> {
+        self.phase(self.timeouts.extract)
+    }
+
+    fn phase(&self, timeout: Option<Duration>) -> Option<Instant> {
+        let phase = timeout.map(|timeout| Instant::now() + timeout);
+        match (self.end, phase) {
+            (Some(end), Some(phase)) => Some(end.min(phase)),
+            (end, phase) => end.or(phase),
+        }
+    }
+}
+
+/// Runs a phase of a get, failing it with [`Error::Timeout`] if it isn't
+/// done by the deadline. What the phase leaves behind is cleaned up as it's
+/// dropped, once the blocking work it was running, see [`blocking`], has
+/// stopped.
+pub(crate) async fn within<T>(
+    deadline: Option<Instant>,
+    phase: Phase,
+    fut: impl Future<Output = Result<T, Error>>,
+) -> Result<T, Error> {
+    let cancel = Cancel::new(phase);
+    // a phase that's dropped stops its blocking work as well
+    let _dropped = Dropped(cancel.clone());
+    let fut = CANCEL.scope(cancel.clone(), fut);
+    let Some(deadline) = deadline else {
+        return fut.await;
+    };
+    tokio::pin!(fut);
+    match tokio::time::timeout_at(deadline.into(), fut.as_mut()).await {
+        Ok(res) => res,
+        Err(_) => {
+            cancel.cancel();
+            if cancel.0.running.load(Ordering::SeqCst) > 0 {
+                let _ = fut.await;
+            }
+            Err(Error::Timeout { phase })
+        }
+    }
+}
+
+/// Runs blocking work on a thread of its own, so that it doesn't hold up
+/// the runtime and the phase it's part of can time out. The work is to go
+/// by [`checkpoint`] between the entries or chunks it goes through.
+pub(crate) async fn blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, Error> + Send + 'static,
+) -> Result<T, Error> {
+    let running = Running::new(CANCEL.try_with(Cancel::clone).ok());
+    tokio::task::spawn_blocking(move || {
+        let running = running;
+        WORKER.with(|w| *w.borrow_mut() = running.0.clone());
+        work()
+    })
+    .await
+    .map_err(|e| Error::Unknown(Box::new(e)))?
+}
+
+/// Fails with [`Error::Timeout`] once the phase the blocking work running
+/// on this thread is part of was given up on.
+pub(crate) fn checkpoint() -> Result<(), Error> {
+    WORKER.with(|w| w.borrow().as_ref().map_or(Ok(()), Cancel::check))
+}
+
+/// Reader that goes by [`checkpoint`] before every read, for blocking work
+/// reading through files too large to hold it up for.
+pub(crate) struct Checked<R>(pub(crate) R);
+
+impl<R: Read> Read for Checked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        checkpoint().map_err(|e| io::Error::new(io::ErrorKind::TimedOut, e))?;
+        self.0.read(buf)
+    }
+}
+
+/// What [`checkpoint`] goes by on this thread, for work handed on to other
+/// threads to check.
+pub(crate) fn current() -> Option<Cancel> {
+    WORKER.with(|w| w.borrow().clone())
+}
+
+/// Tells blocking work that the phase it's part of, or any phase that one
+/// is part of, was given up on.
+#[derive(Clone)]
+pub(crate) struct Cancel(Arc<CancelState>);
+
+struct CancelState {
+    phase: Phase,
+    cancelled: AtomicBool,
+    /// Blocking work still running in the phase.
+    running: AtomicUsize,
+    parent: Option<Cancel>,
+}
+
+impl Cancel {
+    fn new(phase: Phase) -> Self {
+        Self(Arc::new(CancelState {
+            phase,
+            cancelled: AtomicBool::new(false),
+            running: AtomicUsize::new(0),
+            parent: CANCEL.try_with(Cancel::clone).ok(),
+        }))
+    }
+
+    fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// The phase and the phases it's part of, innermost first.
+    fn chain(&self) -> impl Iterator<Item = &Cancel> {
+        iter::successors(Some(self), |c| c.0.parent.as_ref())
+    }
+
+    /// Fails with [`Error::Timeout`] once the phase was given up on.
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        match self.chain().find(|c| c.0.cancelled.load(Ordering::SeqCst)) {
+            Some(c) => Err(Error::Timeout { phase: c.0.phase }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Cancels the phase as it's dropped.
+struct Dropped(Cancel);
+
+impl Drop for Dropped {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Counts blocking work as running in its phases for as long as it's alive,
+/// and clears the [`Cancel`] of the thread it ends on.
+struct Running(Option<Cancel>);
+
+impl Running {
+    fn new(cancel: Option<Cancel>) -> Self {
+        for c in cancel.iter().flat_map(Cancel::chain) {
+            c.0.running.fetch_add(1, Ordering::SeqCst);
+        }
+        Self(cancel)
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        for c in self.0.iter().flat_map(Cancel::chain) {
+            c.0.running.fetch_sub(1, Ordering::SeqCst);
+        }
+        WORKER.with(|w| w.borrow_mut().take());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadlines() {
+        let minute = Duration::from_secs(60);
+        let deadlines = Timeouts::default().start();
+        assert_eq!(deadlines.end(), None);
+        assert_eq!(deadlines.transfer(), None);
+
+        let deadlines = Timeouts {
+            total: Some(minute),
+            transfer: Some(minute * 2),
+            extract: Some(minute / 2),
+        }
+        .start();
+        let end = deadlines.end().unwrap();
+        // a phase never outlasts the get
+        assert_eq!(deadlines.transfer(), Some(end));
+        assert!(deadlines.extract().unwrap() < end);
+
+        let deadlines = Timeouts {
+            extract: Some(minute),
+            ..Default::default()
+        }
+        .start();
+        assert_eq!(deadlines.end(), None);
+        assert!(deadlines.extract().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_within() {
+        let soon = Some(Instant::now() + Duration::from_millis(10));
+        let res = within(soon, Phase::Transfer, async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        })
+        .await;
+        assert!(matches!(
+            res,
+            Err(Error::Timeout {
+                phase: Phase::Transfer
+            })
+        ));
+
+        let res = within(soon, Phase::Transfer, async { Ok(1) }).await;
+        assert_eq!(res.unwrap(), 1);
+        let res = within(None, Phase::Transfer, async { Ok(1) }).await;
+        assert_eq!(res.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_within_stops_blocking_work() {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let soon = Some(Instant::now() + Duration::from_millis(10));
+        let work = blocking({
+            let stopped = stopped.clone();
+            move || loop {
+                if let Err(e) = checkpoint() {
+                    std::thread::sleep(Duration::from_millis(10));
+                    stopped.store(true, Ordering::SeqCst);
+                    return Err::<(), _>(e);
+                }
+            }
+        });
+        let res = within(soon, Phase::Extraction, work).await;
+        assert!(matches!(
+            res,
+            Err(Error::Timeout {
+                phase: Phase::Extraction
+            })
+        ));
+        // and it's waited for
+        assert!(stopped.load(Ordering::SeqCst));
+        assert!(checkpoint().is_ok());
+    }
+}
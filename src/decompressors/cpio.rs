@@ -0,0 +1,343 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use super::{Budget, Selector};
+use crate::{DecompressOpts, Error, LinkPolicy};
+
+const TYPE_MASK: u32 = 0o170_000;
+const DIR: u32 = 0o040_000;
+const FILE: u32 = 0o100_000;
+const SYMLINK: u32 = 0o120_000;
+
+const TRAILER: &str = "TRAILER!!!";
+
+/// Header of an entry in the "new" ascii format (with or without checksums),
+/// the one rpm payloads use.
+struct Header {
+    ino: u32,
+    mode: u32,
+    nlink: u32,
+    mtime: u32,
+    size: u32,
+    dev: (u32, u32),
+    name: String,
+}
+
+impl Header {
+    fn read<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let mut raw = [0u8; 110];
+        reader.read_exact(&mut raw).map_err(truncated)?;
+        if &raw[..6] != b"070701" && &raw[..6] != b"070702" {
+            return Err(Error::InvalidArchive(
+                "only the new ascii cpio format is supported".to_string(),
+            ));
+        }
+
+        let field = |i: usize| -> Result<u32, Error> {
+            let hex = std::str::from_utf8(&raw[6 + i * 8..14 + i * 8]).unwrap_or_default();
+            u32::from_str_radix(hex, 16)
+                .map_err(|_| Error::InvalidArchive("malformed cpio header".to_string()))
+        };
+
+        let namesize = field(11)? as usize;
+        if namesize == 0 || namesize > 4096 {
+            return Err(Error::InvalidArchive("malformed cpio header".to_string()));
+        }
+        let mut name = vec![0; namesize];
+        reader.read_exact(&mut name).map_err(truncated)?;
+        name.pop();
+        skip(reader, padding(110 + namesize as u64))?;
+
+        Ok(Self {
+            ino: field(0)?,
+            mode: field(1)?,
+            nlink: field(4)?,
+            mtime: field(5)?,
+            size: field(6)?,
+            dev: (field(7)?, field(8)?),
+            name: String::from_utf8_lossy(&name).into_owned(),
+        })
+    }
+}
+
+/// Walks a cpio stream and extracts it into dest. Device nodes, fifos and
+/// sockets are skipped.
+pub(super) fn unpack<R: Read>(
+    mut reader: R,
+    dest: &Path,
+    opts: &DecompressOpts,
+) -> Result<(), Error> {
+    if opts.subpath.is_none() {
+        fs::create_dir_all(dest).map_err(Error::DestinationNotCreated)?;
+    }
+
+    let mut budget = Budget::new(opts);
+    let mut selector = Selector::new(opts.subpath.as_deref());
+    // hardlinked files only carry their data on the last of their entries
+    let mut links: HashMap<(u32, u32, u32), Vec<PathBuf>> = HashMap::new();
+
+    loop {
+        let header = Header::read(&mut reader)?;
+        if header.name == TRAILER {
+            break;
+        }
+        budget.entry()?;
+
+        let size = u64::from(header.size);
+        let mut data = (&mut reader).take(size);
+        extract(
+            &header,
+            &mut data,
+            dest,
+            opts,
+            &mut budget,
+            &mut selector,
+            &mut links,
+        )?;
+        // whatever wasn't extracted still has to be read through
+        io::copy(&mut data, &mut io::sink())?;
+        skip(&mut reader, padding(size))?;
+    }
+
+    // hardlinks to an empty file never get any data
+    for path in links.into_values().flatten() {
+        super::prepare_entry(&path)?;
+        fs::File::create(&path)?;
+    }
+
+    selector.finish()
+}
+
+fn extract<R: Read>(
+    header: &Header,
+    data: &mut R,
+    dest: &Path,
+    opts: &DecompressOpts,
+    budget: &mut Budget,
+    selector: &mut Selector,
+    links: &mut HashMap<(u32, u32, u32), Vec<PathBuf>>,
+) -> Result<(), Error> {
+    let name = Path::new(&header.name);
+    let kind = header.mode & TYPE_MASK;
+    let path = match super::strip_components(name, opts.strip_components) {
+        Some(stripped) => match selector.select(&stripped, kind == DIR) {
+            Some(target) => Some(super::target_path(dest, &stripped, target)?),
+            None => None,
+        },
+        None => None,
+    };
+
+    match kind {
+        DIR => {
+            if let Some(path) = path {
+                fs::create_dir_all(path)?;
+            }
+        }
+        FILE if header.nlink > 1 => {
+            let key = (header.dev.0, header.dev.1, header.ino);
+            let paths = links.entry(key).or_default();
+            paths.extend(path);
+            if header.size == 0 {
+                return Ok(());
+            }
+
+            let paths = links.remove(&key).unwrap_or_default();
+            if let Some((first, rest)) = paths.split_first() {
+                write(header, data, first, opts, budget)?;
+                for path in rest {
+                    super::prepare_entry(path)?;
+                    fs::hard_link(first, path)?;
+                }
+            }
+        }
+        FILE => {
+            if let Some(path) = path {
+                write(header, data, &path, opts, budget)?;
+            }
+        }
+        SYMLINK => {
+            let Some(path) = path else {
+                return Ok(());
+            };
+            if opts.links == LinkPolicy::Reject {
+                return Err(Error::ArchiveLinkRejected {
+                    entry: header.name.clone(),
+                });
+            }
+
+            let mut target = String::new();
+            data.take(4096)
+                .read_to_string(&mut target)
+                .map_err(|_| super::unsafe_path(name))?;
+            if opts.links == LinkPolicy::AllowRelativeWithinDest
+                && !super::link_within(dest, &path, Path::new(&target))
+            {
+                return Err(super::unsafe_path(name));
+            }
+
+            super::prepare_entry(&path)?;
+            #[cfg(target_family = "unix")]
+            std::os::unix::fs::symlink(&target, &path)?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn write<R: Read>(
+    header: &Header,
+    data: &mut R,
+    path: &Path,
+    opts: &DecompressOpts,
+    budget: &mut Budget,
+) -> Result<(), Error> {
+    super::prepare_entry(path)?;
+    let mut out = fs::File::create(path)?;
+    budget.copy(data, &mut out)?;
+
+    if opts.preserve_mtime {
+        out.set_modified(UNIX_EPOCH + Duration::from_secs(header.mtime.into()))?;
+    }
+    super::set_mode(path, header.mode & 0o777, opts.umask)
+}
+
+/// Entries and names are padded to a multiple of 4 bytes.
+fn padding(len: u64) -> u64 {
+    (4 - len % 4) % 4
+}
+
+fn skip<R: Read>(reader: &mut R, n: u64) -> Result<(), Error> {
+    io::copy(&mut reader.take(n), &mut io::sink())?;
+    Ok(())
+}
+
+fn truncated(e: io::Error) -> Error {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => {
+            Error::InvalidArchive("cpio archive is truncated".to_string())
+        }
+        _ => Error::Io(e),
+    }
+}
+
+/// Builds a cpio archive in the new ascii format out of `(name, mode, ino,
+/// nlink, data)` entries.
+#[cfg(test)]
+pub(super) fn newc(entries: &[(&str, u32, u32, u32, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let trailer = [(TRAILER, 0, 0, 1, &b""[..])];
+
+    for (name, mode, ino, nlink, data) in entries.iter().chain(&trailer) {
+        let fields = [
+            *ino,
+            *mode,
+            0,
+            0,
+            *nlink,
+            1_500_000_000,
+            data.len() as u32,
+            8,
+            1,
+            0,
+            0,
+            name.len() as u32 + 1,
+            0,
+        ];
+        out.extend(b"070701");
+        for field in fields {
+            out.extend(format!("{:08x}", field).into_bytes());
+        }
+        out.extend(name.as_bytes());
+        out.push(0);
+        out.resize(out.len() + padding(110 + name.len() as u64 + 1) as usize, 0);
+        out.extend(*data);
+        out.resize(out.len() + padding(data.len() as u64) as usize, 0);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn it_should_extract_links() {
+        let archive = newc(&[
+            ("./usr/bin/", DIR | 0o755, 1, 2, b""),
+            ("./usr/bin/tool", FILE | 0o755, 2, 2, b""),
+            ("./usr/bin/tool-1", FILE | 0o755, 2, 2, b"#!/bin/sh"),
+            ("./usr/bin/t", SYMLINK | 0o777, 3, 1, b"tool"),
+            ("./usr/lib/empty", FILE | 0o644, 4, 2, b""),
+            ("./usr/lib/empty-1", FILE | 0o644, 4, 2, b""),
+        ]);
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dest = tmp.path().join("dest");
+        unpack(&archive[..], &dest, &DecompressOpts::default()).unwrap();
+
+        for name in ["tool", "tool-1", "t"] {
+            assert_eq!(
+                fs::read_to_string(dest.join("usr/bin").join(name)).unwrap(),
+                "#!/bin/sh"
+            );
+        }
+        assert!(fs::symlink_metadata(dest.join("usr/bin/t"))
+            .unwrap()
+            .is_symlink());
+        assert_eq!(fs::read(dest.join("usr/lib/empty-1")).unwrap(), b"");
+
+        // a link whose data carrier isn't selected still gets the data
+        let dest = tmp.path().join("tool");
+        let opts = DecompressOpts {
+            subpath: Some("usr/bin/tool".to_string()),
+            ..Default::default()
+        };
+        unpack(&archive[..], &dest, &opts).unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "#!/bin/sh");
+    }
+
+    #[test]
+    fn it_should_reject_entries_escaping_dest() {
+        let tests = [
+            ("../evil", FILE | 0o644, &b"evil"[..]),
+            ("./link", SYMLINK | 0o777, &b"../../outside"[..]),
+        ];
+
+        for (name, mode, data) in tests {
+            let tmp = tempfile::tempdir().unwrap();
+            let archive = newc(&[(name, mode, 1, 1, data)]);
+            let res = unpack(
+                &archive[..],
+                &tmp.path().join("dest"),
+                &DecompressOpts::default(),
+            );
+            assert!(
+                matches!(res, Err(Error::UnsafeArchivePath { .. })),
+                "{}: {:?}",
+                name,
+                res
+            );
+            assert!(!tmp.path().join("evil").exists());
+        }
+    }
+
+    #[test]
+    fn it_should_fail_on_truncated_archives() {
+        let archive = newc(&[("./a.txt", FILE | 0o644, 1, 1, b"aaaa")]);
+        let tmp = tempfile::tempdir().unwrap();
+        let res = unpack(
+            &archive[..120],
+            &tmp.path().join("dest"),
+            &DecompressOpts::default(),
+        );
+        assert!(matches!(res, Err(Error::InvalidArchive(_))), "{:?}", res);
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use async_trait::async_trait;
+
+use crate::{DecompressOpts, Error, StreamFn};
+
+/// Extracts the files debian packages install. The maintainer scripts and
+/// metadata are extracted instead when the subpath is `control`, or starts
+/// with `control/` to pick some of them.
+pub struct Deb;
+
+#[async_trait]
+impl crate::Decompressor for Deb {
+    fn extensions(&self) -> &[&str] {
+        &["deb", "udeb"]
+    }
+
+    async fn decompress(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        opts: &DecompressOpts,
+    ) -> Result<(), Error> {
+        super::unblocked(archive, dest, opts, |archive, dest, opts| {
+            unpack(fs::File::open(archive)?, dest, opts)
+        })
+        .await
+    }
+
+    fn streaming(&self) -> Option<StreamFn> {
+        Some(|r, dest, opts| unpack(r, dest, opts))
+    }
+}
+
+/// Walks the ar container of a package up to the tarball that was asked for,
+/// and extracts that.
+fn unpack<R: Read>(mut reader: R, dest: &Path, opts: &DecompressOpts) -> Result<(), Error> {
+    let subpath = opts.subpath.as_deref().map(|s| s.trim_start_matches('/'));
+    let (member, subpath) = match subpath.and_then(|s| s.strip_prefix("control")) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => (
+            "control.tar",
+            Some(rest.trim_start_matches('/')).filter(|s| !s.is_empty()),
+        ),
+        _ => ("data.tar", subpath),
+    };
+
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic).map_err(invalid)?;
+    if &magic != b"!<arch>\n" {
+        return Err(Error::InvalidArchive("not a debian package".to_string()));
+    }
+
+    loop {
+        let mut header = [0u8; 60];
+        match reader.read_exact(&mut header) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            res => res?,
+        }
+
+        let name = String::from_utf8_lossy(&header[..16]);
+        // gnu ar ends names with a slash
+        let name = name.trim_end().trim_end_matches('/');
+        let size: u64 = std::str::from_utf8(&header[48..58])
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .ok_or_else(|| Error::InvalidArchive("malformed ar header".to_string()))?;
+
+        let mut body = (&mut reader).take(size);
+        if let Some(ext) = name.strip_prefix(member) {
+            if ext.is_empty() || ext.starts_with('.') {
+                let opts = DecompressOpts {
+                    subpath: subpath.map(|s| s.to_string()),
+                    ..opts.clone()
+                };
+                let tarball = super::decoder(ext.trim_start_matches('.'), body)?;
+                return super::tar::unpack(tarball, dest, &opts);
+            }
+        }
+
+        // members are aligned to 2 bytes
+        io::copy(&mut body, &mut io::sink())?;
+        io::copy(&mut (&mut reader).take(size % 2), &mut io::sink())?;
+    }
+
+    Err(Error::InvalidArchive(format!(
+        "debian package has no {} member",
+        member
+    )))
+}
+
+fn invalid(e: io::Error) -> Error {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => Error::InvalidArchive("not a debian package".to_string()),
+        _ => Error::Io(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::Decompressor;
+
+    fn tarball(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o755);
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+
+        let mut enc = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        enc.write_all(&builder.into_inner().unwrap()).unwrap();
+        enc.finish().unwrap()
+    }
+
+    fn deb(members: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut out = b"!<arch>\n".to_vec();
+        for (name, data) in members {
+            out.extend(
+                format!(
+                    "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+                    name,
+                    0,
+                    0,
+                    0,
+                    100644,
+                    data.len()
+                )
+                .into_bytes(),
+            );
+            out.extend(data);
+            if data.len() % 2 == 1 {
+                out.push(b'\n');
+            }
+        }
+        out
+    }
+
+    fn package() -> Vec<u8> {
+        deb(&[
+            ("debian-binary", b"2.0\n".to_vec()),
+            (
+                "control.tar.gz",
+                tarball(&[("./control", "Package: tool"), ("./postinst", "#!/bin/sh")]),
+            ),
+            (
+                "data.tar.gz",
+                tarball(&[
+                    ("./usr/bin/tool", "#!/bin/sh"),
+                    ("./usr/share/doc/tool/README", "readme"),
+                ]),
+            ),
+        ])
+    }
+
+    #[tokio::test]
+    async fn it_should_extract_the_data_tarball() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("tool.deb");
+        fs::write(&archive, package()).unwrap();
+
+        let dest = tmp.path().join("dest");
+        Deb.decompress(&archive, &dest, &DecompressOpts::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(dest.join("usr/bin/tool")).unwrap(),
+            "#!/bin/sh"
+        );
+        assert!(!dest.join("control").exists());
+    }
+
+    #[tokio::test]
+    async fn it_should_extract_control_files_with_the_control_subpath() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("tool.deb");
+        fs::write(&archive, package()).unwrap();
+
+        let extract = |subpath: &str| {
+            let dest = tmp.path().join(subpath.replace('/', "_"));
+            let opts = DecompressOpts {
+                subpath: Some(subpath.to_string()),
+                ..Default::default()
+            };
+            let archive = archive.clone();
+            async move { Deb.decompress(&archive, &dest, &opts).await.map(|_| dest) }
+        };
+
+        let dest = extract("control").await.unwrap();
+        assert_eq!(
+            fs::read_to_string(dest.join("control")).unwrap(),
+            "Package: tool"
+        );
+        assert!(dest.join("postinst").is_file());
+
+        let dest = extract("control/postinst").await.unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "#!/bin/sh");
+
+        let dest = extract("usr/bin/tool").await.unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "#!/bin/sh");
+    }
+
+    #[tokio::test]
+    async fn it_should_reject_malformed_packages() {
+        let tmp = tempfile::tempdir().unwrap();
+        let tests = vec![
+            ("not-deb", b"definitely not a deb".to_vec()),
+            ("no-data", deb(&[("debian-binary", b"2.0\n".to_vec())])),
+        ];
+
+        for (name, bytes) in tests {
+            let archive = tmp.path().join(name);
+            fs::write(&archive, bytes).unwrap();
+            let res = Deb
+                .decompress(
+                    &archive,
+                    &tmp.path().join("dest"),
+                    &DecompressOpts::default(),
+                )
+                .await;
+            assert!(
+                matches!(res, Err(Error::InvalidArchive(_))),
+                "{}: {:?}",
+                name,
+                res
+            );
+        }
+    }
+}
@@ -0,0 +1,968 @@
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    time::Instant,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::mpsc,
+};
+
+mod cpio;
+mod deb;
+mod rpm;
+mod select;
+#[cfg(feature = "7z")]
+mod sevenz;
+mod single;
+mod tar;
+mod zip;
+
+pub use self::deb::Deb;
+pub use self::rpm::Rpm;
+#[cfg(feature = "7z")]
+pub use self::sevenz::SevenZip;
+#[cfg(feature = "bzip2")]
+pub use self::single::Bz2;
+pub use self::single::Gz;
+#[cfg(feature = "xz")]
+pub use self::single::Xz;
+#[cfg(feature = "zstd")]
+pub use self::single::Zst;
+#[cfg(feature = "bzip2")]
+pub use self::tar::TarBz2;
+#[cfg(feature = "xz")]
+pub use self::tar::TarXz;
+#[cfg(feature = "zstd")]
+pub use self::tar::TarZst;
+pub use self::tar::{Tar, TarGz};
+pub use self::zip::Zip;
+
+pub(crate) use self::select::{glob_match, Selector, Target};
+
+use crate::{
+    deadline, staging, timing::Stopwatch, Checksum, ChecksumAlgo, DecompressLimits, DecompressOpts,
+    Error, ExtractProgress, OnExists, Phase, ProgressReporter, StreamFn,
+};
+
+/// Moves a finished extraction over to dest, settling what's already there
+/// according to the `on_exists` policy. Entries are merged into an existing
+/// directory without following symlinks found inside of it.
+/// An extraction staged on another filesystem than dest is copied over.
+pub(crate) fn promote(staged: &Path, dest: &Path, on_exists: OnExists) -> Result<(), Error> {
+    let Ok(meta) = fs::symlink_metadata(dest) else {
+        staging::rename(staged, dest)?;
+        return Ok(());
+    };
+
+    if staged.is_dir() && dest.is_dir() {
+        let empty = fs::read_dir(dest)?.next().is_none();
+        match on_exists {
+            _ if empty => {}
+            OnExists::Error => return Err(Error::DestinationNotEmpty(dest.display().to_string())),
+            OnExists::Merge => {
+                if let Some(conflict) = conflict(staged, dest)? {
+                    return Err(Error::DestinationConflict(conflict.display().to_string()));
+                }
+            }
+            OnExists::Overwrite => {}
+            OnExists::Clean => clean(dest)?,
+        }
+
+        return merge(staged, dest);
+    }
+
+    match on_exists {
+        OnExists::Error | OnExists::Merge => {
+            return Err(Error::DestinationConflict(dest.display().to_string()))
+        }
+        OnExists::Overwrite | OnExists::Clean if meta.is_dir() => fs::remove_dir_all(dest)?,
+        OnExists::Overwrite | OnExists::Clean => fs::remove_file(dest)?,
+    }
+    staging::rename(staged, dest)?;
+
+    Ok(())
+}
+
+fn merge(staged: &Path, dest: &Path) -> Result<(), Error> {
+    for entry in fs::read_dir(staged)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+
+        match fs::symlink_metadata(&target) {
+            Ok(m) if m.is_dir() && entry.file_type()?.is_dir() => {
+                merge(&entry.path(), &target)?;
+                continue;
+            }
+            Ok(m) if m.is_dir() => fs::remove_dir_all(&target)?,
+            Ok(_) => fs::remove_file(&target)?,
+            Err(_) => {}
+        }
+        staging::rename(&entry.path(), &target)?;
+    }
+
+    Ok(())
+}
+
+/// Finds the first staged entry that would replace something in dest, only
+/// directories present on both sides can be merged.
+fn conflict(staged: &Path, dest: &Path) -> Result<Option<PathBuf>, Error> {
+    for entry in fs::read_dir(staged)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+
+        match fs::symlink_metadata(&target) {
+            Ok(m) if m.is_dir() && entry.file_type()?.is_dir() => {
+                if let Some(conflict) = conflict(&entry.path(), &target)? {
+                    return Ok(Some(conflict));
+                }
+            }
+            Ok(_) => return Ok(Some(target)),
+            Err(_) => {}
+        }
+    }
+
+    Ok(None)
+}
+
+fn clean(dest: &Path) -> Result<(), Error> {
+    for entry in fs::read_dir(dest)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(entry.path())?;
+        } else {
+            fs::remove_file(entry.path())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Refuses to clean out directories whose loss would be a disaster: the
+/// filesystem root, the home directory, and the working directory or any of
+/// its parents.
+pub(crate) fn check_clean(dest: &Path) -> Result<(), Error> {
+    let Ok(dest) = dest.canonicalize() else {
+        return Ok(());
+    };
+
+    let home = crate::paths::home_dir().and_then(|h| h.canonicalize().ok());
+    let cwd = std::env::current_dir().and_then(|d| d.canonicalize()).ok();
+
+    if dest.parent().is_none()
+        || home.is_some_and(|h| h == dest)
+        || cwd.is_some_and(|c| c.starts_with(&dest))
+    {
+        return Err(Error::DangerousClean(dest.display().to_string()));
+    }
+
+    Ok(())
+}
+
+/// Runs an extraction of the archive at archive into dest that blocks as it
+/// goes off the runtime, see [`deadline::blocking`].
+pub(crate) async fn unblocked(
+    archive: &Path,
+    dest: &Path,
+    opts: &DecompressOpts,
+    extract: fn(&Path, &Path, &DecompressOpts) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let (archive, dest, opts) = (archive.to_path_buf(), dest.to_path_buf(), opts.clone());
+    deadline::blocking(move || extract(&archive, &dest, &opts)).await
+}
+
+/// Keeps count of what an extraction has written so far against its limits,
+/// and reports it as it goes.
+pub(crate) struct Budget {
+    limits: DecompressLimits,
+    progress: Option<Arc<dyn ProgressReporter>>,
+    deadline: Option<Instant>,
+    current: ExtractProgress,
+}
+
+impl Budget {
+    pub(crate) fn new(opts: &DecompressOpts) -> Self {
+        Self {
+            limits: opts.limits,
+            progress: opts.progress.clone(),
+            deadline: opts.deadline,
+            current: ExtractProgress::default(),
+        }
+    }
+
+    /// Records how many entries the archive has, for formats that know.
+    pub(crate) fn total(&mut self, entries: u64) {
+        self.current.total_entries = Some(entries);
+    }
+
+    /// Accounts for the next entry of the archive.
+    pub(crate) fn entry(&mut self) -> Result<(), Error> {
+        self.check_deadline()?;
+        self.current.entries += 1;
+        if self.current.entries > self.limits.max_entries {
+            return Err(exceeded("entry count", self.limits.max_entries));
+        }
+        self.report();
+
+        Ok(())
+    }
+
+    /// Accounts for an entry whose size is known upfront.
+    pub(crate) fn reserve(&mut self, size: u64) -> Result<(), Error> {
+        let allowed = self.allowed();
+        if size > allowed {
+            return Err(self.overrun(allowed));
+        }
+        self.current.bytes += size;
+        self.report();
+
+        Ok(())
+    }
+
+    /// Copies an entry to its destination, giving up as soon as it grows past
+    /// the limits instead of trusting the size the archive claims.
+    pub(crate) fn copy<R: Read, W: Write>(
+        &mut self,
+        reader: R,
+        mut writer: W,
+    ) -> Result<(), Error> {
+        let allowed = self.allowed();
+        let mut reader = Deadline {
+            inner: reader.take(allowed.saturating_add(1)),
+            deadline: self.deadline,
+        };
+        let written = match io::copy(&mut reader, &mut writer) {
+            Ok(written) => written,
+            Err(e) => {
+                self.check_deadline()?;
+                return Err(e.into());
+            }
+        };
+        if written > allowed {
+            return Err(self.overrun(allowed));
+        }
+        self.current.bytes += written;
+        self.report();
+
+        Ok(())
+    }
+
+    fn check_deadline(&self) -> Result<(), Error> {
+        deadline::checkpoint()?;
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(Error::Timeout {
+                phase: Phase::Extraction,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn report(&self) {
+        if let Some(progress) = &self.progress {
+            progress.extracting(&self.current);
+        }
+    }
+
+    fn allowed(&self) -> u64 {
+        let remaining = self
+            .limits
+            .max_total_bytes
+            .saturating_sub(self.current.bytes);
+        remaining.min(self.limits.max_entry_bytes)
+    }
+
+    fn overrun(&self, allowed: u64) -> Error {
+        if allowed == self.limits.max_entry_bytes {
+            exceeded("entry size", self.limits.max_entry_bytes)
+        } else {
+            exceeded("total size", self.limits.max_total_bytes)
+        }
+    }
+}
+
+/// Reader that stops once the deadline of the extraction has passed, or it
+/// was given up on, so that a single large entry can't hold it up.
+struct Deadline<R> {
+    inner: R,
+    deadline: Option<Instant>,
+}
+
+impl<R: Read> Read for Deadline<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let passed = self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline);
+        if passed || deadline::checkpoint().is_err() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.inner.read(buf)
+    }
+}
+
+fn exceeded(limit: &'static str, max: u64) -> Error {
+    Error::DecompressLimitExceeded { limit, max }
+}
+
+/// Resolves an archive entry name to the path it is extracted to, refusing
+/// absolute names and names that climb out of dest.
+pub(crate) fn entry_path(dest: &Path, name: &Path) -> Result<PathBuf, Error> {
+    let rel = normalize(Path::new(""), name).ok_or_else(|| unsafe_path(name))?;
+    // deep trees, like those of node_modules, only fit on Windows in the
+    // extended form
+    let path = crate::dest::extended(&dest.join(rel));
+
+    // an earlier symlink entry could redirect this one outside of dest
+    if let Some(parent) = path.parent() {
+        if !resolves_within(dest, parent)? {
+            return Err(unsafe_path(name));
+        }
+    }
+
+    Ok(path)
+}
+
+/// Resolves where a selected entry is extracted to. An entry extracted as
+/// dest itself goes inside of it instead when dest is a directory.
+pub(crate) fn target_path(dest: &Path, name: &Path, target: Target) -> Result<PathBuf, Error> {
+    let rel = match target {
+        Target::Dest if dest.is_dir() => PathBuf::from(name.file_name().unwrap_or_default()),
+        Target::Dest => return Ok(dest.to_path_buf()),
+        Target::Under(rel) => rel,
+    };
+
+    fs::create_dir_all(dest).map_err(Error::DestinationNotCreated)?;
+    entry_path(dest, &rel)
+}
+
+/// Drops the first `n` components of an entry name, the way GNU tar's
+/// `--strip-components` does. Entries with too few components are skipped.
+pub(crate) fn strip_components(name: &Path, n: usize) -> Option<PathBuf> {
+    let stripped: PathBuf = name
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .skip(n)
+        .collect();
+
+    if stripped.as_os_str().is_empty() {
+        return None;
+    }
+
+    Some(stripped)
+}
+
+/// Checks that a symlink entry extracted to `path` points inside dest,
+/// following links extracted earlier so that a chain of them can't be used to
+/// climb out.
+pub(crate) fn link_within(dest: &Path, path: &Path, target: &Path) -> bool {
+    // path may be in the extended form while dest isn't
+    let (dest, path) = (crate::dest::plain(dest), crate::dest::plain(path));
+    let Some(parent) = path.parent().and_then(|p| p.strip_prefix(&dest).ok()) else {
+        return false;
+    };
+
+    resolve(&dest, parent, target, 0).is_some()
+}
+
+pub(crate) fn unsafe_path(name: &Path) -> Error {
+    Error::UnsafeArchivePath {
+        entry: name.display().to_string(),
+    }
+}
+
+/// Lexically applies `path` on top of `base`, both relative to dest, returning
+/// `None` when the result would be absolute or outside of dest.
+fn normalize(base: &Path, path: &Path) -> Option<PathBuf> {
+    let mut out = base.to_path_buf();
+    for c in path.components() {
+        match c {
+            Component::Normal(p) => out.push(p),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    Some(out)
+}
+
+/// Same as `normalize`, but every symlink met along the way inside dest is
+/// replaced by its own target.
+fn resolve(dest: &Path, base: &Path, path: &Path, depth: usize) -> Option<PathBuf> {
+    // same limit linux puts on following nested links
+    if depth > 40 {
+        return None;
+    }
+
+    let mut out = base.to_path_buf();
+    for c in path.components() {
+        match c {
+            Component::Normal(p) => {
+                out.push(p);
+                if let Ok(link) = fs::read_link(crate::dest::extended(&dest.join(&out))) {
+                    out.pop();
+                    out = resolve(dest, &out, &link, depth + 1)?;
+                }
+            }
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    Some(out)
+}
+
+fn resolves_within(dest: &Path, path: &Path) -> Result<bool, Error> {
+    let dest = dest.canonicalize()?;
+
+    let mut existing = path;
+    loop {
+        match existing.canonicalize() {
+            Ok(canonical) => return Ok(canonical.starts_with(&dest)),
+            Err(_) => match existing.parent() {
+                Some(parent) => existing = parent,
+                None => return Ok(false),
+            },
+        }
+    }
+}
+
+/// Makes room for an entry at path: creates its parent directories and
+/// removes a symlink left there by an earlier entry so it isn't followed.
+pub(crate) fn prepare_entry(path: &Path) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+/// A download for [`stream`] to feed an extractor.
+pub(crate) struct Download<'a> {
+    pub(crate) body: Pin<Box<dyn AsyncRead + Send>>,
+    /// When the download has to be done by.
+    pub(crate) deadline: Option<Instant>,
+    /// Bytes read from the body at a time.
+    pub(crate) buffer_size: usize,
+    /// What the download is hashed with, if it is.
+    pub(crate) algo: Option<ChecksumAlgo>,
+    pub(crate) expected: Option<&'a Checksum>,
+    /// Told how much has been downloaded as it goes, for the transfer rate.
+    pub(crate) stopwatch: &'a Stopwatch,
+}
+
+/// Chunks of a download sent to the extractor that it hasn't read yet, so
+/// that a slow extraction holds up the download rather than piling it up
+/// in memory.
+const CHUNKS_IN_FLIGHT: usize = 16;
+
+/// Feeds a download into an extractor running on the blocking pool, chunk by
+/// chunk as it arrives, hashing it along the way when there's a checksum to
+/// verify or record. Returns how much was downloaded, along with its checksum
+/// if it was hashed. The download gives up once its deadline passes.
+///
+/// No more than [`CHUNKS_IN_FLIGHT`] chunks of the buffer size, and the one
+/// each side is on, are held at once, however large the download.
+pub(crate) async fn stream(
+    extract: StreamFn,
+    download: Download<'_>,
+    dest: PathBuf,
+    opts: DecompressOpts,
+) -> Result<(u64, Option<Checksum>), Error> {
+    let Download {
+        mut body,
+        deadline,
+        buffer_size,
+        algo,
+        expected,
+        stopwatch,
+    } = download;
+    let (tx, rx) = mpsc::channel(CHUNKS_IN_FLIGHT);
+    let extraction =
+        tokio::task::spawn_blocking(move || extract(&mut ChannelReader::new(rx), &dest, &opts));
+
+    let mut hasher = algo.map(|algo| algo.hasher());
+    let mut extracting = true;
+    let mut bytes = 0;
+    let mut buf = vec![0; buffer_size];
+    let download = loop {
+        let read = deadline::within(deadline, Phase::Transfer, async {
+            Ok(body.read(&mut buf).await?)
+        });
+        match read.await {
+            Ok(0) => break Ok(()),
+            Ok(n) => {
+                bytes += n as u64;
+                stopwatch.transferred(bytes);
+                if let Some(hasher) = &mut hasher {
+                    hasher.update(&buf[..n]);
+                }
+                // the extractor gave up early, its result says why. The rest
+                // of the download is still needed for the checksum though.
+                if extracting && tx.send(buf[..n].to_vec()).await.is_err() {
+                    extracting = false;
+                    if hasher.is_none() {
+                        break Ok(());
+                    }
+                }
+            }
+            Err(e) => break Err(e),
+        }
+    };
+    drop(tx);
+
+    let extracted = extraction.await.map_err(|e| Error::Unknown(Box::new(e)))?;
+    download?;
+    let actual = algo.zip(hasher).map(|(algo, hasher)| algo.checksum(hasher));
+    // a corrupted download is the likelier reason for the extraction to fail
+    if let (Some(expected), Some(actual)) = (expected, &actual) {
+        expected.verify(actual)?;
+    }
+    extracted.map(|()| (bytes, actual))
+}
+
+/// Blocking reader over the chunks of a download sent by [`stream`].
+struct ChannelReader {
+    rx: mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl ChannelReader {
+    fn new(rx: mpsc::Receiver<Vec<u8>>) -> Self {
+        Self {
+            rx,
+            chunk: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.rx.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Gives an extracted entry its mode from the archive, clamped by the umask.
+/// Other platforms have no mode bits, so there's nothing to do there.
+pub(crate) fn set_mode(path: &Path, mode: u32, umask: Option<u32>) -> Result<(), Error> {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = mode & !umask.unwrap_or(0);
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(target_family = "unix"))]
+    let _ = (path, mode, umask);
+
+    Ok(())
+}
+
+/// The file an extraction produced when it's the only thing it produced,
+/// either as `out` itself or as the single entry of the `out` directory.
+pub(crate) fn single_file(out: &Path) -> Result<Option<PathBuf>, Error> {
+    let meta = fs::symlink_metadata(out)?;
+    if meta.is_file() {
+        return Ok(Some(out.to_path_buf()));
+    }
+    if !meta.is_dir() {
+        return Ok(None);
+    }
+
+    let mut entries = fs::read_dir(out)?;
+    let (Some(entry), None) = (entries.next().transpose()?, entries.next()) else {
+        return Ok(None);
+    };
+    Ok(entry.file_type()?.is_file().then(|| entry.path()))
+}
+
+/// Wraps a reader in the decoder for a compression, named either by its file
+/// extension or the way packages name it (`gzip`, `zstd`, ...).
+pub(crate) fn decoder<'a, R: Read + 'a>(
+    compression: &str,
+    reader: R,
+) -> Result<Box<dyn Read + 'a>, Error> {
+    Ok(match compression {
+        "" | "none" => Box::new(reader),
+        "gz" | "gzip" => Box::new(flate2::read::GzDecoder::new(reader)),
+        #[cfg(feature = "bzip2")]
+        "bz2" | "bzip2" => Box::new(bzip2::read::BzDecoder::new(reader)),
+        #[cfg(feature = "xz")]
+        "xz" => Box::new(xz2::read::XzDecoder::new(reader)),
+        #[cfg(feature = "xz")]
+        "lzma" => {
+            let stream = xz2::stream::Stream::new_lzma_decoder(u64::MAX)
+                .map_err(|e| Error::Unknown(Box::new(e)))?;
+            Box::new(xz2::read::XzDecoder::new_stream(reader, stream))
+        }
+        #[cfg(feature = "zstd")]
+        "zst" | "zstd" => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        other => return Err(Error::DecompressorNotFound(other.to_string())),
+    })
+}
+
+/// Guesses the archive format of a downloaded file from its leading bytes,
+/// returning `None` when it doesn't look like any archive. Compressed streams
+/// are peeked into to tell a tarball apart from a single compressed file.
+pub(crate) fn sniff(path: &Path) -> Result<Option<&'static str>, Error> {
+    let mut head = Vec::new();
+    fs::File::open(path)?.take(512).read_to_end(&mut head)?;
+
+    let format = if head.starts_with(&[0x50, 0x4b, 0x03, 0x04])
+        || head.starts_with(&[0x50, 0x4b, 0x05, 0x06])
+    {
+        "zip"
+    } else if head.starts_with(&[b'7', b'z', 0xbc, 0xaf, 0x27, 0x1c]) {
+        #[cfg(not(feature = "7z"))]
+        return Ok(None);
+        #[cfg(feature = "7z")]
+        "7z"
+    } else if head.starts_with(b"!<arch>\ndebian-binary") {
+        "deb"
+    } else if head.starts_with(&[0xed, 0xab, 0xee, 0xdb]) {
+        "rpm"
+    } else if is_tar(&head) {
+        "tar"
+    } else if head.starts_with(&[0x1f, 0x8b]) {
+        let f = fs::File::open(path)?;
+        compressed(flate2::read::GzDecoder::new(f), "tar.gz", "gz")?
+    } else if head.starts_with(b"BZh") {
+        #[cfg(not(feature = "bzip2"))]
+        return Ok(None);
+        #[cfg(feature = "bzip2")]
+        {
+            let f = fs::File::open(path)?;
+            compressed(bzip2::read::BzDecoder::new(f), "tar.bz2", "bz2")?
+        }
+    } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        #[cfg(not(feature = "xz"))]
+        return Ok(None);
+        #[cfg(feature = "xz")]
+        {
+            let f = fs::File::open(path)?;
+            compressed(xz2::read::XzDecoder::new(f), "tar.xz", "xz")?
+        }
+    } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        #[cfg(not(feature = "zstd"))]
+        return Ok(None);
+        #[cfg(feature = "zstd")]
+        {
+            let f = fs::File::open(path)?;
+            compressed(zstd::stream::read::Decoder::new(f)?, "tar.zst", "zst")?
+        }
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(format))
+}
+
+fn is_tar(head: &[u8]) -> bool {
+    head.len() >= 262 && &head[257..262] == b"ustar"
+}
+
+fn compressed<R: Read>(
+    reader: R,
+    tar: &'static str,
+    single: &'static str,
+) -> Result<&'static str, Error> {
+    let mut head = Vec::new();
+    reader
+        .take(512)
+        .read_to_end(&mut head)
+        .map_err(|e: io::Error| Error::InvalidArchive(e.to_string()))?;
+
+    Ok(if is_tar(&head) { tar } else { single })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn it_should_keep_entries_inside_dest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dest = tmp.path();
+
+        assert_eq!(
+            entry_path(dest, Path::new("a/b.txt")).unwrap(),
+            dest.join("a/b.txt")
+        );
+        assert_eq!(
+            entry_path(dest, Path::new("./a/../b.txt")).unwrap(),
+            dest.join("b.txt")
+        );
+
+        for name in ["../../../../etc/cron.d/evil", "/etc/passwd", "a/../../b"] {
+            let res = entry_path(dest, Path::new(name));
+            assert!(
+                matches!(res, Err(Error::UnsafeArchivePath { ref entry }) if entry == name),
+                "{}: {:?}",
+                name,
+                res
+            );
+        }
+    }
+
+    #[test]
+    fn it_should_strip_components() {
+        let tests = vec![
+            ("tool-1.2.3/bin/tool", 0, Some("tool-1.2.3/bin/tool")),
+            ("tool-1.2.3/bin/tool", 1, Some("bin/tool")),
+            ("./tool-1.2.3/bin/tool", 2, Some("tool")),
+            ("tool-1.2.3/", 1, None),
+            ("tool-1.2.3/bin/tool", 3, None),
+        ];
+
+        for (name, n, expected) in tests {
+            assert_eq!(
+                strip_components(Path::new(name), n),
+                expected.map(PathBuf::from),
+                "{} {}",
+                name,
+                n
+            );
+        }
+    }
+
+    #[test]
+    fn it_should_check_symlink_targets() {
+        let dest = Path::new("/dest");
+        assert!(link_within(
+            dest,
+            &dest.join("lib/libfoo.so"),
+            Path::new("libfoo.so.1")
+        ));
+        assert!(link_within(
+            dest,
+            &dest.join("lib/libfoo.so"),
+            Path::new("../bin/foo")
+        ));
+        assert!(!link_within(
+            dest,
+            &dest.join("lib/libfoo.so"),
+            Path::new("../../etc")
+        ));
+        assert!(!link_within(dest, &dest.join("link"), Path::new("/etc")));
+    }
+
+    fn tar_bytes() -> Vec<u8> {
+        let mut builder = ::tar::Builder::new(Vec::new());
+        let mut header = ::tar::Header::new_ustar();
+        header.set_size(1);
+        builder
+            .append_data(&mut header, "a.txt", &b"a"[..])
+            .unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn it_should_count_against_the_limits() {
+        let mut budget = Budget::new(&DecompressOpts {
+            limits: DecompressLimits {
+                max_total_bytes: 10,
+                max_entry_bytes: 6,
+                max_entries: 2,
+            },
+            ..Default::default()
+        });
+
+        budget.entry().unwrap();
+        budget.copy(&b"123456"[..], io::sink()).unwrap();
+        assert!(matches!(
+            budget.copy(&b"12345"[..], io::sink()),
+            Err(Error::DecompressLimitExceeded {
+                limit: "total size",
+                ..
+            })
+        ));
+        assert!(matches!(
+            budget.reserve(7),
+            Err(Error::DecompressLimitExceeded {
+                limit: "total size",
+                ..
+            })
+        ));
+        budget.entry().unwrap();
+        assert!(matches!(
+            budget.entry(),
+            Err(Error::DecompressLimitExceeded {
+                limit: "entry count",
+                max: 2
+            })
+        ));
+
+        let mut budget = Budget::new(&DecompressOpts {
+            limits: DecompressLimits::unlimited(),
+            ..Default::default()
+        });
+        budget.reserve(u64::MAX).unwrap();
+    }
+
+    #[test]
+    fn it_should_stop_at_the_deadline() {
+        let mut budget = Budget::new(&DecompressOpts {
+            deadline: Some(Instant::now()),
+            ..Default::default()
+        });
+        assert!(matches!(
+            budget.entry(),
+            Err(Error::Timeout {
+                phase: Phase::Extraction
+            })
+        ));
+        assert!(matches!(
+            budget.copy(&b"test"[..], io::sink()),
+            Err(Error::Timeout { .. })
+        ));
+
+        let mut budget = Budget::new(&DecompressOpts {
+            deadline: Some(Instant::now() + std::time::Duration::from_secs(60)),
+            ..Default::default()
+        });
+        budget.entry().unwrap();
+        budget.copy(&b"test"[..], io::sink()).unwrap();
+    }
+
+    #[test]
+    fn it_should_settle_existing_dests_by_policy() {
+        let tmp = tempfile::tempdir().unwrap();
+        let populate = |name: &str| {
+            let staged = tmp.path().join(format!("{}-staged", name));
+            fs::create_dir_all(staged.join("bin")).unwrap();
+            fs::write(staged.join("bin/tool"), "new").unwrap();
+            fs::write(staged.join("README"), "readme").unwrap();
+
+            let dest = tmp.path().join(name);
+            fs::create_dir_all(dest.join("bin")).unwrap();
+            fs::write(dest.join("bin/other"), "other").unwrap();
+            (staged, dest)
+        };
+        let read = |path: PathBuf| fs::read_to_string(path).ok();
+
+        let (staged, dest) = populate("error");
+        let res = promote(&staged, &dest, OnExists::Error);
+        assert!(
+            matches!(res, Err(Error::DestinationNotEmpty(_))),
+            "{:?}",
+            res
+        );
+
+        let (staged, dest) = populate("merge");
+        promote(&staged, &dest, OnExists::Merge).unwrap();
+        assert_eq!(read(dest.join("bin/tool")).as_deref(), Some("new"));
+        assert_eq!(read(dest.join("bin/other")).as_deref(), Some("other"));
+        assert_eq!(read(dest.join("README")).as_deref(), Some("readme"));
+
+        let (staged, dest) = populate("conflict");
+        fs::write(dest.join("bin/tool"), "old").unwrap();
+        let res = promote(&staged, &dest, OnExists::Merge);
+        assert!(
+            matches!(res, Err(Error::DestinationConflict(_))),
+            "{:?}",
+            res
+        );
+        assert_eq!(read(dest.join("bin/tool")).as_deref(), Some("old"));
+        assert_eq!(read(dest.join("README")), None);
+
+        let (staged, dest) = populate("overwrite");
+        fs::write(dest.join("bin/tool"), "old").unwrap();
+        promote(&staged, &dest, OnExists::Overwrite).unwrap();
+        assert_eq!(read(dest.join("bin/tool")).as_deref(), Some("new"));
+        assert_eq!(read(dest.join("bin/other")).as_deref(), Some("other"));
+
+        let (staged, dest) = populate("clean");
+        promote(&staged, &dest, OnExists::Clean).unwrap();
+        assert_eq!(read(dest.join("bin/tool")).as_deref(), Some("new"));
+        assert_eq!(read(dest.join("bin/other")), None);
+
+        let file = tmp.path().join("file");
+        fs::write(&file, "old").unwrap();
+        let staged = tmp.path().join("staged-file");
+        fs::write(&staged, "new").unwrap();
+        let res = promote(&staged, &file, OnExists::Error);
+        assert!(
+            matches!(res, Err(Error::DestinationConflict(_))),
+            "{:?}",
+            res
+        );
+        promote(&staged, &file, OnExists::Overwrite).unwrap();
+        assert_eq!(read(file).as_deref(), Some("new"));
+        assert!(!staged.exists());
+    }
+
+    #[test]
+    fn it_should_refuse_dangerous_cleans() {
+        let cwd = std::env::current_dir().unwrap();
+        for dest in [Path::new("/"), &cwd, cwd.parent().unwrap()] {
+            let res = check_clean(dest);
+            assert!(matches!(res, Err(Error::DangerousClean(_))), "{:?}", res);
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        check_clean(tmp.path()).unwrap();
+    }
+
+    fn gz(bytes: &[u8]) -> Vec<u8> {
+        let mut enc = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        enc.write_all(bytes).unwrap();
+        enc.finish().unwrap()
+    }
+
+    #[test]
+    fn it_should_sniff_archive_formats() {
+        let tmp = tempfile::tempdir().unwrap();
+        #[cfg_attr(not(feature = "zstd"), allow(unused_mut))]
+        let mut tests = vec![
+            (tar_bytes(), Some("tar")),
+            (gz(&tar_bytes()), Some("tar.gz")),
+            (gz(b"select 1;"), Some("gz")),
+            (b"PK\x03\x04rest".to_vec(), Some("zip")),
+            (b"just some text".to_vec(), None),
+            (Vec::new(), None),
+        ];
+        #[cfg(feature = "zstd")]
+        tests.push((
+            zstd::encode_all(&tar_bytes()[..], 0).unwrap(),
+            Some("tar.zst"),
+        ));
+
+        for (i, (bytes, format)) in tests.into_iter().enumerate() {
+            let path = tmp.path().join(i.to_string());
+            fs::write(&path, bytes).unwrap();
+            assert_eq!(sniff(&path).unwrap(), format, "case {}", i);
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use async_trait::async_trait;
+
+use crate::{DecompressOpts, Error, StreamFn};
+
+/// Extracts the files in the payload of rpm packages, without running any of
+/// their scripts.
+pub struct Rpm;
+
+const LEAD_MAGIC: [u8; 4] = [0xed, 0xab, 0xee, 0xdb];
+const HEADER_MAGIC: [u8; 4] = [0x8e, 0xad, 0xe8, 0x01];
+
+const TAG_PAYLOAD_FORMAT: u32 = 1124;
+const TAG_PAYLOAD_COMPRESSOR: u32 = 1125;
+const TYPE_STRING: u32 = 6;
+
+#[async_trait]
+impl crate::Decompressor for Rpm {
+    fn extensions(&self) -> &[&str] {
+        &["rpm"]
+    }
+
+    async fn decompress(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        opts: &DecompressOpts,
+    ) -> Result<(), Error> {
+        super::unblocked(archive, dest, opts, |archive, dest, opts| {
+            unpack(fs::File::open(archive)?, dest, opts)
+        })
+        .await
+    }
+
+    fn streaming(&self) -> Option<StreamFn> {
+        Some(|r, dest, opts| unpack(r, dest, opts))
+    }
+}
+
+fn unpack<R: Read>(mut reader: R, dest: &Path, opts: &DecompressOpts) -> Result<(), Error> {
+    let mut lead = [0u8; 96];
+    reader.read_exact(&mut lead).map_err(invalid)?;
+    if lead[..4] != LEAD_MAGIC {
+        return Err(Error::InvalidArchive("not an rpm package".to_string()));
+    }
+
+    // the signature header is padded to 8 bytes, the main one isn't
+    let signature = Header::read(&mut reader)?;
+    let padding = (8 - signature.len % 8) % 8;
+    io::copy(&mut (&mut reader).take(padding), &mut io::sink())?;
+    let header = Header::read(&mut reader)?;
+
+    if let Some(format) = header.string(TAG_PAYLOAD_FORMAT) {
+        if format != "cpio" {
+            return Err(Error::InvalidArchive(format!(
+                "unsupported rpm payload format {}",
+                format
+            )));
+        }
+    }
+    // packages old enough not to say are gzipped
+    let compression = header.string(TAG_PAYLOAD_COMPRESSOR).unwrap_or("gzip");
+    super::cpio::unpack(super::decoder(compression, reader)?, dest, opts)
+}
+
+/// A header structure, the index of tags along with the store of their values.
+struct Header {
+    index: Vec<[u32; 4]>,
+    store: Vec<u8>,
+    /// Size of the header in the package.
+    len: u64,
+}
+
+impl Header {
+    fn read<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let mut intro = [0u8; 16];
+        reader.read_exact(&mut intro).map_err(invalid)?;
+        if intro[..4] != HEADER_MAGIC {
+            return Err(Error::InvalidArchive("malformed rpm header".to_string()));
+        }
+        let count = u32::from_be_bytes(intro[8..12].try_into().unwrap()) as u64;
+        let size = u32::from_be_bytes(intro[12..16].try_into().unwrap()) as u64;
+
+        // read through `take` so that a lying header can't make us allocate it all upfront
+        let mut raw = Vec::new();
+        reader.take(count * 16).read_to_end(&mut raw)?;
+        let mut store = Vec::new();
+        reader.take(size).read_to_end(&mut store)?;
+        if raw.len() as u64 != count * 16 || store.len() as u64 != size {
+            return Err(Error::InvalidArchive("rpm header is truncated".to_string()));
+        }
+
+        let index = raw
+            .chunks(16)
+            .map(|entry| {
+                let field =
+                    |i: usize| u32::from_be_bytes(entry[i * 4..i * 4 + 4].try_into().unwrap());
+                [field(0), field(1), field(2), field(3)]
+            })
+            .collect();
+
+        Ok(Self {
+            index,
+            store,
+            len: 16 + count * 16 + size,
+        })
+    }
+
+    fn string(&self, tag: u32) -> Option<&str> {
+        let [_, _, offset, _] = self
+            .index
+            .iter()
+            .find(|[t, kind, ..]| *t == tag && *kind == TYPE_STRING)?;
+        let value = self.store.get(*offset as usize..)?;
+        let end = value.iter().position(|b| *b == 0)?;
+        std::str::from_utf8(&value[..end]).ok()
+    }
+}
+
+fn invalid(e: io::Error) -> Error {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => {
+            Error::InvalidArchive("rpm package is truncated".to_string())
+        }
+        _ => Error::Io(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::Decompressor;
+
+    fn header(tags: &[(u32, &str)]) -> Vec<u8> {
+        let mut index = Vec::new();
+        let mut store = Vec::new();
+        for (tag, value) in tags {
+            for field in [*tag, TYPE_STRING, store.len() as u32, 1] {
+                index.extend(field.to_be_bytes());
+            }
+            store.extend(value.as_bytes());
+            store.push(0);
+        }
+
+        let mut out = HEADER_MAGIC.to_vec();
+        out.extend([0; 4]);
+        out.extend((tags.len() as u32).to_be_bytes());
+        out.extend((store.len() as u32).to_be_bytes());
+        out.extend(index);
+        out.extend(store);
+        out
+    }
+
+    /// Puts together a package with a signature header whose size needs
+    /// padding, and the payload compressed as the main header says.
+    fn rpm(compressor: Option<&str>, payload: &[u8]) -> Vec<u8> {
+        let mut out = LEAD_MAGIC.to_vec();
+        out.resize(96, 0);
+        let signature = header(&[(1000, "sig")]);
+        out.extend(&signature);
+        out.resize(out.len() + (8 - signature.len() % 8) % 8, 0);
+
+        let mut tags = vec![(TAG_PAYLOAD_FORMAT, "cpio")];
+        tags.extend(compressor.map(|c| (TAG_PAYLOAD_COMPRESSOR, c)));
+        out.extend(header(&tags));
+
+        match compressor {
+            None | Some("gzip") => {
+                let mut enc = flate2::write::GzEncoder::new(out, Default::default());
+                enc.write_all(payload).unwrap();
+                enc.finish().unwrap()
+            }
+            #[cfg(feature = "xz")]
+            Some("xz") => {
+                let mut enc = xz2::write::XzEncoder::new(out, 6);
+                enc.write_all(payload).unwrap();
+                enc.finish().unwrap()
+            }
+            #[cfg(feature = "zstd")]
+            Some("zstd") => {
+                out.extend(zstd::encode_all(payload, 0).unwrap());
+                out
+            }
+            Some(_) => {
+                out.extend(payload);
+                out
+            }
+        }
+    }
+
+    fn payload() -> Vec<u8> {
+        super::super::cpio::newc(&[
+            ("./usr", 0o040_755, 1, 2, b""),
+            ("./usr/bin", 0o040_755, 2, 2, b""),
+            ("./usr/bin/tool", 0o100_755, 3, 1, b"#!/bin/sh"),
+            ("./etc/tool.conf", 0o100_644, 4, 1, b"debug = false"),
+        ])
+    }
+
+    #[tokio::test]
+    async fn it_should_extract_the_payload() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut compressors = vec![None, Some("gzip")];
+        if cfg!(feature = "xz") {
+            compressors.push(Some("xz"));
+        }
+        if cfg!(feature = "zstd") {
+            compressors.push(Some("zstd"));
+        }
+
+        for compressor in compressors {
+            let archive = tmp.path().join(format!("{:?}.rpm", compressor));
+            fs::write(&archive, rpm(compressor, &payload())).unwrap();
+
+            let dest = tmp.path().join(format!("{:?}", compressor));
+            Rpm.decompress(&archive, &dest, &DecompressOpts::default())
+                .await
+                .unwrap();
+            assert_eq!(
+                fs::read_to_string(dest.join("usr/bin/tool")).unwrap(),
+                "#!/bin/sh"
+            );
+            assert_eq!(
+                fs::read_to_string(dest.join("etc/tool.conf")).unwrap(),
+                "debug = false"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn it_should_select_files_in_the_payload() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("tool.rpm");
+        fs::write(&archive, rpm(Some("gzip"), &payload())).unwrap();
+
+        let dest = tmp.path().join("tool");
+        let opts = DecompressOpts {
+            subpath: Some("usr/bin/tool".to_string()),
+            ..Default::default()
+        };
+        Rpm.decompress(&archive, &dest, &opts).await.unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "#!/bin/sh");
+    }
+
+    #[tokio::test]
+    async fn it_should_reject_malformed_packages() {
+        let tmp = tempfile::tempdir().unwrap();
+        let package = rpm(Some("gzip"), &payload());
+        let tests = vec![
+            ("not-rpm", b"definitely not an rpm".to_vec()),
+            ("truncated", package[..120].to_vec()),
+            ("unknown", rpm(Some("lz4"), &payload())),
+        ];
+
+        for (name, bytes) in tests {
+            let archive = tmp.path().join(name);
+            fs::write(&archive, bytes).unwrap();
+            let res = Rpm
+                .decompress(
+                    &archive,
+                    &tmp.path().join("dest"),
+                    &DecompressOpts::default(),
+                )
+                .await;
+            assert!(
+                matches!(
+                    res,
+                    Err(Error::InvalidArchive(_) | Error::DecompressorNotFound(_))
+                ),
+                "{}: {:?}",
+                name,
+                res
+            );
+        }
+    }
+}
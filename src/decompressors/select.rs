@@ -0,0 +1,256 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::Error;
+
+/// Where a selected entry ends up.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Target {
+    /// The entry is written to dest itself.
+    Dest,
+    /// The entry is extracted at this path inside dest.
+    Under(PathBuf),
+}
+
+/// Picks the entries of an archive selected by the `//` subpath of a source.
+///
+/// A subpath naming a file extracts just that file to dest, one naming a
+/// directory extracts what's inside of it. Components may use `*` and `?`
+/// globs, in which case every match is extracted into dest under its own name.
+pub(crate) struct Selector {
+    subpath: String,
+    pattern: Vec<String>,
+    glob: bool,
+    matched: bool,
+    seen: Vec<String>,
+}
+
+impl Selector {
+    pub(crate) fn new(subpath: Option<&str>) -> Self {
+        let subpath = subpath.unwrap_or_default();
+        let pattern: Vec<String> = subpath
+            .split('/')
+            .filter(|c| !c.is_empty() && *c != ".")
+            .map(|c| c.to_string())
+            .collect();
+        let glob = pattern.iter().any(|c| c.contains(['*', '?']));
+
+        Self {
+            subpath: subpath.to_string(),
+            pattern,
+            glob,
+            matched: false,
+            seen: Vec::new(),
+        }
+    }
+
+    /// Decides where an entry, named after strip_components was applied, is
+    /// extracted to, or `None` when it isn't selected.
+    pub(crate) fn select(&mut self, name: &Path, is_dir: bool) -> Option<Target> {
+        if self.pattern.is_empty() {
+            return Some(Target::Under(name.to_path_buf()));
+        }
+
+        self.seen.push(name.display().to_string());
+        let target = self.locate(name, is_dir);
+        // an exact match on a directory selects it without extracting anything yet
+        if target.is_some() || (is_dir && self.is_exact(name)) {
+            self.matched = true;
+        }
+
+        target
+    }
+
+    /// Same as [`select`](Selector::select) without recording the entry, for
+    /// finding where the target of a hardlink was extracted.
+    pub(crate) fn locate(&self, name: &Path, is_dir: bool) -> Option<Target> {
+        if self.pattern.is_empty() {
+            return Some(Target::Under(name.to_path_buf()));
+        }
+
+        let components = components(name);
+        if components.len() < self.pattern.len()
+            || !self
+                .pattern
+                .iter()
+                .zip(&components)
+                .all(|(p, c)| glob_match(p.as_bytes(), c.as_bytes()))
+        {
+            return None;
+        }
+
+        if self.glob {
+            // every match keeps its own name
+            let rel: PathBuf = components[self.pattern.len() - 1..].iter().collect();
+            return Some(Target::Under(rel));
+        }
+
+        if components.len() == self.pattern.len() {
+            return (!is_dir).then_some(Target::Dest);
+        }
+
+        let rel: PathBuf = components[self.pattern.len()..].iter().collect();
+        Some(Target::Under(rel))
+    }
+
+    /// Fails when a subpath was given but nothing in the archive matched it.
+    pub(crate) fn finish(self) -> Result<(), Error> {
+        if self.pattern.is_empty() || self.matched {
+            return Ok(());
+        }
+
+        let mut near: Vec<(usize, String)> = self
+            .seen
+            .into_iter()
+            .map(|name| (distance(&self.subpath, &name), name))
+            .collect();
+        near.sort();
+        near.dedup_by(|a, b| a.1 == b.1);
+
+        Err(Error::SubpathNotFound {
+            subpath: self.subpath,
+            near: near.into_iter().take(3).map(|(_, name)| name).collect(),
+        })
+    }
+
+    fn is_exact(&self, name: &Path) -> bool {
+        let components = components(name);
+        components.len() == self.pattern.len()
+            && self
+                .pattern
+                .iter()
+                .zip(&components)
+                .all(|(p, c)| glob_match(p.as_bytes(), c.as_bytes()))
+    }
+}
+
+fn components(name: &Path) -> Vec<String> {
+    name.components()
+        .filter_map(|c| match c {
+            Component::Normal(c) => Some(c.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Matches a single path component against a pattern where `*` stands for any
+/// run of characters and `?` for exactly one.
+pub(crate) fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            glob_match(&pattern[1..], name) || (!name.is_empty() && glob_match(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => glob_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => glob_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// Levenshtein distance, used to suggest entries close to a subpath that
+/// didn't match anything.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let sub = prev[j] + usize::from(ca != *cb);
+            cur[j + 1] = sub.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_select_entries() {
+        let tests = vec![
+            (
+                None,
+                "a/b.txt",
+                false,
+                Some(Target::Under("a/b.txt".into())),
+            ),
+            (Some("bin/tool"), "bin/tool", false, Some(Target::Dest)),
+            (Some("bin/tool"), "bin/tool2", false, None),
+            (Some("bin/tool"), "bin/", true, None),
+            (
+                Some("lib"),
+                "lib/a.so",
+                false,
+                Some(Target::Under("a.so".into())),
+            ),
+            (
+                Some("lib/"),
+                "lib/x/a.so",
+                false,
+                Some(Target::Under("x/a.so".into())),
+            ),
+            (Some("lib"), "lib", true, None),
+            (
+                Some("bin/*"),
+                "bin/tool",
+                false,
+                Some(Target::Under("tool".into())),
+            ),
+            (
+                Some("bin/*"),
+                "bin/sub/a",
+                false,
+                Some(Target::Under("sub/a".into())),
+            ),
+            (Some("bin/*"), "lib/a", false, None),
+            (
+                Some("pkg-?.?/bin"),
+                "pkg-1.2/bin/tool",
+                false,
+                Some(Target::Under("bin/tool".into())),
+            ),
+        ];
+
+        for (subpath, name, is_dir, target) in tests {
+            let mut s = Selector::new(subpath);
+            assert_eq!(
+                s.select(Path::new(name), is_dir),
+                target,
+                "{:?} {}",
+                subpath,
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn it_should_suggest_near_misses() {
+        let mut s = Selector::new(Some("bin/tol"));
+        for name in ["README", "bin/tool", "bin/tools", "lib/libfoo.so"] {
+            assert_eq!(s.select(Path::new(name), false), None);
+        }
+
+        let err = s.finish().unwrap_err();
+        assert!(
+            matches!(&err, Error::SubpathNotFound { near, .. } if near[..2] == ["bin/tool", "bin/tools"]),
+            "{:?}",
+            err
+        );
+
+        let mut s = Selector::new(Some("lib"));
+        s.select(Path::new("lib/"), true);
+        s.finish().unwrap();
+    }
+
+    #[test]
+    fn it_should_match_globs() {
+        assert!(glob_match(b"*", b"anything"));
+        assert!(glob_match(b"*.so*", b"libfoo.so.1"));
+        assert!(glob_match(b"tool-?", b"tool-1"));
+        assert!(!glob_match(b"tool-?", b"tool-12"));
+        assert!(!glob_match(b"*.so", b"libfoo.so.1"));
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+    time::SystemTime,
+};
+
+use async_trait::async_trait;
+use sevenz_rust2::{ArchiveEntry, ArchiveReader, Password};
+
+use super::{Budget, Selector};
+use crate::{DecompressOpts, Error};
+
+/// Extracts 7z archives compressed with LZMA or LZMA2.
+pub struct SevenZip;
+
+/// Set in the windows attributes of entries made on unix, whose mode is then
+/// kept in the upper half.
+const UNIX_EXTENSION: u32 = 0x8000;
+
+#[async_trait]
+impl crate::Decompressor for SevenZip {
+    fn extensions(&self) -> &[&str] {
+        &["7z"]
+    }
+
+    async fn decompress(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        opts: &DecompressOpts,
+    ) -> Result<(), Error> {
+        super::unblocked(archive, dest, opts, unpack).await
+    }
+}
+
+fn unpack(archive: &Path, dest: &Path, opts: &DecompressOpts) -> Result<(), Error> {
+    let mut reader = ArchiveReader::open(archive, Password::empty()).map_err(error)?;
+
+    if opts.subpath.is_none() {
+        fs::create_dir_all(dest).map_err(Error::DestinationNotCreated)?;
+    }
+    let mut budget = Budget::new(opts);
+    budget.total(reader.archive().files.len() as u64);
+    let mut selector = Selector::new(opts.subpath.as_deref());
+
+    // the reader only takes its own errors back, so ours are kept aside
+    let mut failed = None;
+    let res = reader.for_each_entries(|entry, data| {
+        match extract(entry, data, dest, opts, &mut budget, &mut selector) {
+            Ok(()) => Ok(true),
+            Err(e) => {
+                failed = Some(e);
+                Err(sevenz_rust2::Error::Other("extraction failed".into()))
+            }
+        }
+    });
+    if let Some(e) = failed {
+        return Err(e);
+    }
+    res.map_err(error)?;
+
+    selector.finish()
+}
+
+fn extract(
+    entry: &ArchiveEntry,
+    data: &mut dyn Read,
+    dest: &Path,
+    opts: &DecompressOpts,
+    budget: &mut Budget,
+    selector: &mut Selector,
+) -> Result<(), Error> {
+    // entries of a solid archive share one stream, so skipped ones still have
+    // to be read through to get to the next
+    let skip = |data: &mut dyn Read| io::copy(data, &mut io::sink()).map(|_| ());
+
+    budget.entry()?;
+    if entry.is_anti_item() {
+        return Ok(skip(data)?);
+    }
+
+    let name = entry.name().replace('\\', "/");
+    let Some(stripped) = super::strip_components(Path::new(&name), opts.strip_components) else {
+        return Ok(skip(data)?);
+    };
+    let Some(target) = selector.select(&stripped, entry.is_directory()) else {
+        return Ok(skip(data)?);
+    };
+    let path = super::target_path(dest, &stripped, target)?;
+
+    if entry.is_directory() {
+        fs::create_dir_all(&path)?;
+        return Ok(());
+    }
+
+    super::prepare_entry(&path)?;
+
+    let mut out = fs::File::create(&path)?;
+    budget.copy(data, &mut out)?;
+
+    if opts.preserve_mtime && entry.has_last_modified_date {
+        out.set_modified(SystemTime::from(entry.last_modified_date()))?;
+    }
+
+    // entries made on windows don't carry a unix mode, they keep the default one
+    let attributes = entry.windows_attributes();
+    if entry.has_windows_attributes && attributes & UNIX_EXTENSION != 0 {
+        super::set_mode(&path, (attributes >> 16) & 0o777, opts.umask)?;
+    } else if opts.umask.is_some() {
+        super::set_mode(&path, 0o666, opts.umask)?;
+    }
+
+    Ok(())
+}
+
+fn error(e: sevenz_rust2::Error) -> Error {
+    match e {
+        sevenz_rust2::Error::PasswordRequired | sevenz_rust2::Error::MaybeBadPassword(_) => {
+            Error::ArchiveEncrypted
+        }
+        sevenz_rust2::Error::Io(e, _) | sevenz_rust2::Error::FileOpen(e, _) => Error::Io(e),
+        e => Error::InvalidArchive(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sevenz_rust2::{
+        encoder_options::{AesEncoderOptions, Lzma2Options},
+        ArchiveWriter, EncoderMethod,
+    };
+
+    use super::*;
+    use crate::Decompressor;
+
+    fn seven_zip(
+        path: &Path,
+        entries: &[(&str, &str, u32)],
+        methods: Vec<sevenz_rust2::EncoderConfiguration>,
+        encrypt_header: bool,
+    ) {
+        let mut w = ArchiveWriter::create(path).unwrap();
+        w.set_content_methods(methods);
+        w.set_encrypt_header(encrypt_header);
+
+        for (name, content, mode) in entries {
+            let mut entry = match name.strip_suffix('/') {
+                Some(name) => ArchiveEntry::new_directory(name),
+                None => ArchiveEntry::new_file(name),
+            };
+            entry.has_windows_attributes = true;
+            entry.windows_attributes = UNIX_EXTENSION | (mode << 16);
+            w.push_archive_entry(entry, Some(content.as_bytes()))
+                .unwrap();
+        }
+
+        w.finish().unwrap();
+    }
+
+    fn lzma2() -> Vec<sevenz_rust2::EncoderConfiguration> {
+        vec![Lzma2Options::default().into()]
+    }
+
+    #[tokio::test]
+    async fn it_should_extract_lzma_and_lzma2_archives() {
+        let tmp = tempfile::tempdir().unwrap();
+        let entries = [
+            ("tool-1.2.3/", "", 0o755),
+            ("tool-1.2.3/README", "readme", 0o644),
+            ("tool-1.2.3/bin/tool", "#!/bin/sh", 0o755),
+        ];
+
+        for (name, methods) in [
+            ("lzma", vec![EncoderMethod::LZMA.into()]),
+            ("lzma2", lzma2()),
+        ] {
+            let archive = tmp.path().join(format!("{}.7z", name));
+            seven_zip(&archive, &entries, methods, false);
+
+            let dest = tmp.path().join(name);
+            SevenZip
+                .decompress(&archive, &dest, &DecompressOpts::default())
+                .await
+                .unwrap();
+            assert_eq!(
+                fs::read_to_string(dest.join("tool-1.2.3/README")).unwrap(),
+                "readme"
+            );
+            assert_eq!(
+                fs::read_to_string(dest.join("tool-1.2.3/bin/tool")).unwrap(),
+                "#!/bin/sh"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn it_should_select_entries_past_skipped_ones() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.7z");
+        seven_zip(
+            &archive,
+            &[
+                ("tool-1.2.3/README", "readme", 0o644),
+                ("tool-1.2.3/bin/tool", "#!/bin/sh", 0o755),
+                ("tool-1.2.3/lib/libtool.so", "elf", 0o644),
+            ],
+            lzma2(),
+            false,
+        );
+
+        let dest = tmp.path().join("tool");
+        let opts = DecompressOpts {
+            subpath: Some("bin/tool".to_string()),
+            strip_components: 1,
+            ..Default::default()
+        };
+        SevenZip.decompress(&archive, &dest, &opts).await.unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "#!/bin/sh");
+
+        let opts = DecompressOpts {
+            subpath: Some("bin/tols".to_string()),
+            strip_components: 1,
+            ..Default::default()
+        };
+        let res = SevenZip
+            .decompress(&archive, &tmp.path().join("missing"), &opts)
+            .await;
+        assert!(
+            matches!(res, Err(Error::SubpathNotFound { ref near, .. }) if near[0] == "bin/tool"),
+            "{:?}",
+            res
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_reject_entries_escaping_dest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("evil.7z");
+        seven_zip(&archive, &[("../evil.txt", "evil", 0o644)], lzma2(), false);
+
+        let res = SevenZip
+            .decompress(
+                &archive,
+                &tmp.path().join("dest"),
+                &DecompressOpts::default(),
+            )
+            .await;
+        assert!(
+            matches!(res, Err(Error::UnsafeArchivePath { .. })),
+            "{:?}",
+            res
+        );
+        assert!(!tmp.path().join("evil.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn it_should_enforce_limits() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.7z");
+        seven_zip(
+            &archive,
+            &[("a.txt", "aaaa", 0o644), ("b.txt", "bbbb", 0o644)],
+            lzma2(),
+            false,
+        );
+
+        let opts = DecompressOpts {
+            limits: crate::DecompressLimits {
+                max_total_bytes: 6,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let res = SevenZip
+            .decompress(&archive, &tmp.path().join("dest"), &opts)
+            .await;
+        assert!(
+            matches!(res, Err(Error::DecompressLimitExceeded { limit, .. }) if limit == "total size"),
+            "{:?}",
+            res
+        );
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn it_should_preserve_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.7z");
+        seven_zip(
+            &archive,
+            &[("bin/tool", "#!/bin/sh", 0o755), ("data.txt", "x", 0o600)],
+            lzma2(),
+            false,
+        );
+
+        let dest = tmp.path().join("dest");
+        let opts = DecompressOpts {
+            umask: Some(0o027),
+            ..Default::default()
+        };
+        SevenZip.decompress(&archive, &dest, &opts).await.unwrap();
+
+        let mode = |p: &str| fs::metadata(dest.join(p)).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode("bin/tool"), 0o750);
+        assert_eq!(mode("data.txt"), 0o600);
+    }
+
+    #[tokio::test]
+    async fn it_should_report_encrypted_archives() {
+        let tmp = tempfile::tempdir().unwrap();
+        let encrypted = || {
+            let password = sevenz_rust2::Password::new("secret");
+            vec![
+                AesEncoderOptions::new(password).into(),
+                Lzma2Options::default().into(),
+            ]
+        };
+
+        for encrypt_header in [false, true] {
+            let archive = tmp.path().join(format!("{}.7z", encrypt_header));
+            seven_zip(
+                &archive,
+                &[("a.txt", "secret", 0o644)],
+                encrypted(),
+                encrypt_header,
+            );
+
+            let dest = tmp.path().join(format!("dest-{}", encrypt_header));
+            let res = SevenZip
+                .decompress(&archive, &dest, &DecompressOpts::default())
+                .await;
+            assert!(matches!(res, Err(Error::ArchiveEncrypted)), "{:?}", res);
+            assert!(!dest.join("a.txt").exists());
+        }
+    }
+}
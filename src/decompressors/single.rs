@@ -0,0 +1,278 @@
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+
+use crate::{DecompressOpts, Error, StreamFn};
+
+/// Decompresses a single gzip compressed file.
+pub struct Gz;
+
+/// Decompresses a single bzip2 compressed file.
+#[cfg(feature = "bzip2")]
+pub struct Bz2;
+
+/// Decompresses a single xz compressed file.
+#[cfg(feature = "xz")]
+pub struct Xz;
+
+/// Decompresses a single zstd compressed file.
+#[cfg(feature = "zstd")]
+pub struct Zst;
+
+#[async_trait]
+impl crate::Decompressor for Gz {
+    fn extensions(&self) -> &[&str] {
+        &["gz"]
+    }
+
+    async fn decompress(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        opts: &DecompressOpts,
+    ) -> Result<(), Error> {
+        super::unblocked(archive, dest, opts, |archive, dest, opts| {
+            let f = fs::File::open(archive)?;
+            write(flate2::read::MultiGzDecoder::new(f), dest, opts, &["gz"])
+        })
+        .await
+    }
+
+    fn streaming(&self) -> Option<StreamFn> {
+        Some(|r, dest, opts| write(flate2::read::MultiGzDecoder::new(r), dest, opts, &["gz"]))
+    }
+}
+
+#[cfg(feature = "bzip2")]
+#[async_trait]
+impl crate::Decompressor for Bz2 {
+    fn extensions(&self) -> &[&str] {
+        &["bz2"]
+    }
+
+    async fn decompress(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        opts: &DecompressOpts,
+    ) -> Result<(), Error> {
+        super::unblocked(archive, dest, opts, |archive, dest, opts| {
+            let f = fs::File::open(archive)?;
+            write(bzip2::read::MultiBzDecoder::new(f), dest, opts, &["bz2"])
+        })
+        .await
+    }
+
+    fn streaming(&self) -> Option<StreamFn> {
+        Some(|r, dest, opts| write(bzip2::read::MultiBzDecoder::new(r), dest, opts, &["bz2"]))
+    }
+}
+
+#[cfg(feature = "xz")]
+#[async_trait]
+impl crate::Decompressor for Xz {
+    fn extensions(&self) -> &[&str] {
+        &["xz"]
+    }
+
+    async fn decompress(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        opts: &DecompressOpts,
+    ) -> Result<(), Error> {
+        super::unblocked(archive, dest, opts, |archive, dest, opts| {
+            let f = fs::File::open(archive)?;
+            write(
+                xz2::read::XzDecoder::new_multi_decoder(f),
+                dest,
+                opts,
+                &["xz"],
+            )
+        })
+        .await
+    }
+
+    fn streaming(&self) -> Option<StreamFn> {
+        Some(|r, dest, opts| {
+            write(
+                xz2::read::XzDecoder::new_multi_decoder(r),
+                dest,
+                opts,
+                &["xz"],
+            )
+        })
+    }
+}
+
+#[cfg(feature = "zstd")]
+#[async_trait]
+impl crate::Decompressor for Zst {
+    fn extensions(&self) -> &[&str] {
+        &["zst"]
+    }
+
+    async fn decompress(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        opts: &DecompressOpts,
+    ) -> Result<(), Error> {
+        super::unblocked(archive, dest, opts, |archive, dest, opts| {
+            let f = fs::File::open(archive)?;
+            write(zstd::stream::read::Decoder::new(f)?, dest, opts, &["zst"])
+        })
+        .await
+    }
+
+    fn streaming(&self) -> Option<StreamFn> {
+        Some(|r, dest, opts| write(zstd::stream::read::Decoder::new(r)?, dest, opts, &["zst"]))
+    }
+}
+
+/// Writes the decompressed stream to dest, or into dest when it's an
+/// existing directory, named after the source minus its compression suffix.
+fn write<R: Read>(
+    reader: R,
+    dest: &Path,
+    opts: &DecompressOpts,
+    extensions: &[&str],
+) -> Result<(), Error> {
+    let out = match &opts.name {
+        Some(name) if dest.is_dir() => super::entry_path(dest, &output_name(name, extensions))?,
+        _ => dest.to_path_buf(),
+    };
+
+    if let Some(parent) = out.parent() {
+        fs::create_dir_all(parent).map_err(Error::DestinationNotCreated)?;
+    }
+
+    let f = fs::File::create(&out)?;
+    let mut budget = super::Budget::new(opts);
+    budget.entry()?;
+    budget.copy(reader, f)?;
+
+    // there's no mode to restore, but a umask still applies
+    if opts.umask.is_some() {
+        super::set_mode(&out, 0o666, opts.umask)?;
+    }
+
+    Ok(())
+}
+
+fn output_name(name: &str, extensions: &[&str]) -> PathBuf {
+    let stripped = extensions.iter().find_map(|ext| {
+        let lower = name.to_ascii_lowercase();
+        let stem_len = lower.strip_suffix(ext)?.strip_suffix('.')?.len();
+        (stem_len > 0).then(|| &name[..stem_len])
+    });
+
+    PathBuf::from(stripped.unwrap_or(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::Decompressor;
+
+    fn gz(path: &Path, content: &str) {
+        let f = fs::File::create(path).unwrap();
+        let mut enc = flate2::write::GzEncoder::new(f, flate2::Compression::default());
+        enc.write_all(content.as_bytes()).unwrap();
+        enc.finish().unwrap();
+    }
+
+    #[test]
+    fn it_should_strip_the_compression_suffix() {
+        assert_eq!(
+            output_name("database.sql.gz", &["gz"]),
+            PathBuf::from("database.sql")
+        );
+        assert_eq!(
+            output_name("DATABASE.GZ", &["gz"]),
+            PathBuf::from("DATABASE")
+        );
+        assert_eq!(output_name("database", &["gz"]), PathBuf::from("database"));
+        assert_eq!(output_name(".gz", &["gz"]), PathBuf::from(".gz"));
+    }
+
+    #[tokio::test]
+    async fn it_should_decompress_to_dest_verbatim() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("archive");
+        gz(&archive, "select 1;");
+
+        let dest = tmp.path().join("out.sql");
+        let opts = DecompressOpts {
+            name: Some("database.sql.gz".to_string()),
+            ..Default::default()
+        };
+        Gz.decompress(&archive, &dest, &opts).await.unwrap();
+
+        assert_eq!(fs::read_to_string(dest).unwrap(), "select 1;");
+    }
+
+    #[tokio::test]
+    async fn it_should_decompress_into_a_directory_dest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("archive");
+        gz(&archive, "select 1;");
+
+        let dest = tmp.path().join("dest");
+        fs::create_dir(&dest).unwrap();
+        let opts = DecompressOpts {
+            name: Some("database.sql.gz".to_string()),
+            ..Default::default()
+        };
+        Gz.decompress(&archive, &dest, &opts).await.unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dest.join("database.sql")).unwrap(),
+            "select 1;"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_decompress_every_single_file_format() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut enc = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        enc.write_all(b"content").unwrap();
+        #[cfg_attr(
+            not(any(feature = "bzip2", feature = "xz", feature = "zstd")),
+            allow(unused_mut)
+        )]
+        let mut tests: Vec<(Box<dyn Decompressor>, Vec<u8>)> =
+            vec![(Box::new(Gz), enc.finish().unwrap())];
+        #[cfg(feature = "bzip2")]
+        {
+            let mut enc = bzip2::write::BzEncoder::new(Vec::new(), Default::default());
+            enc.write_all(b"content").unwrap();
+            tests.push((Box::new(Bz2), enc.finish().unwrap()));
+        }
+        #[cfg(feature = "xz")]
+        {
+            let mut enc = xz2::write::XzEncoder::new(Vec::new(), 6);
+            enc.write_all(b"content").unwrap();
+            tests.push((Box::new(Xz), enc.finish().unwrap()));
+        }
+        #[cfg(feature = "zstd")]
+        tests.push((Box::new(Zst), zstd::encode_all(&b"content"[..], 0).unwrap()));
+
+        for (i, (d, bytes)) in tests.into_iter().enumerate() {
+            let archive = tmp.path().join(format!("archive-{}", i));
+            fs::write(&archive, bytes).unwrap();
+
+            let dest = tmp.path().join(format!("dest-{}", i));
+            d.decompress(&archive, &dest, &DecompressOpts::default())
+                .await
+                .unwrap();
+            assert_eq!(fs::read_to_string(dest).unwrap(), "content");
+        }
+    }
+}
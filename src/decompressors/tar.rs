@@ -0,0 +1,1083 @@
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+
+use super::Selector;
+use crate::{DecompressOpts, Error, LinkPolicy, StreamFn};
+
+/// Extracts uncompressed tarballs.
+pub struct Tar;
+
+/// Extracts gzip compressed tarballs.
+pub struct TarGz;
+
+/// Extracts bzip2 compressed tarballs.
+#[cfg(feature = "bzip2")]
+pub struct TarBz2;
+
+/// Extracts xz compressed tarballs.
+#[cfg(feature = "xz")]
+pub struct TarXz;
+
+/// Extracts zstd compressed tarballs.
+#[cfg(feature = "zstd")]
+pub struct TarZst;
+
+#[async_trait]
+impl crate::Decompressor for Tar {
+    fn extensions(&self) -> &[&str] {
+        &["tar"]
+    }
+
+    async fn decompress(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        opts: &DecompressOpts,
+    ) -> Result<(), Error> {
+        super::unblocked(archive, dest, opts, |archive, dest, opts| {
+            unpack(fs::File::open(archive)?, dest, opts)
+        })
+        .await
+    }
+
+    fn streaming(&self) -> Option<StreamFn> {
+        Some(|r, dest, opts| unpack(r, dest, opts))
+    }
+}
+
+#[async_trait]
+impl crate::Decompressor for TarGz {
+    fn extensions(&self) -> &[&str] {
+        &["tar.gz", "tgz"]
+    }
+
+    async fn decompress(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        opts: &DecompressOpts,
+    ) -> Result<(), Error> {
+        super::unblocked(archive, dest, opts, |archive, dest, opts| {
+            let f = fs::File::open(archive)?;
+            unpack(flate2::read::GzDecoder::new(f), dest, opts)
+        })
+        .await
+    }
+
+    fn streaming(&self) -> Option<StreamFn> {
+        Some(|r, dest, opts| unpack(flate2::read::GzDecoder::new(r), dest, opts))
+    }
+}
+
+#[cfg(feature = "bzip2")]
+#[async_trait]
+impl crate::Decompressor for TarBz2 {
+    fn extensions(&self) -> &[&str] {
+        &["tar.bz2", "tbz2", "tbz"]
+    }
+
+    async fn decompress(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        opts: &DecompressOpts,
+    ) -> Result<(), Error> {
+        super::unblocked(archive, dest, opts, |archive, dest, opts| {
+            let f = fs::File::open(archive)?;
+            unpack(bzip2::read::BzDecoder::new(f), dest, opts)
+        })
+        .await
+    }
+
+    fn streaming(&self) -> Option<StreamFn> {
+        Some(|r, dest, opts| unpack(bzip2::read::BzDecoder::new(r), dest, opts))
+    }
+}
+
+#[cfg(feature = "xz")]
+#[async_trait]
+impl crate::Decompressor for TarXz {
+    fn extensions(&self) -> &[&str] {
+        &["tar.xz", "txz"]
+    }
+
+    async fn decompress(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        opts: &DecompressOpts,
+    ) -> Result<(), Error> {
+        super::unblocked(archive, dest, opts, |archive, dest, opts| {
+            let f = fs::File::open(archive)?;
+            unpack(xz2::read::XzDecoder::new(f), dest, opts)
+        })
+        .await
+    }
+
+    fn streaming(&self) -> Option<StreamFn> {
+        Some(|r, dest, opts| unpack(xz2::read::XzDecoder::new(r), dest, opts))
+    }
+}
+
+#[cfg(feature = "zstd")]
+#[async_trait]
+impl crate::Decompressor for TarZst {
+    fn extensions(&self) -> &[&str] {
+        &["tar.zst", "tzst"]
+    }
+
+    async fn decompress(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        opts: &DecompressOpts,
+    ) -> Result<(), Error> {
+        super::unblocked(archive, dest, opts, |archive, dest, opts| {
+            let f = fs::File::open(archive)?;
+            unpack(zstd::stream::read::Decoder::new(f)?, dest, opts)
+        })
+        .await
+    }
+
+    fn streaming(&self) -> Option<StreamFn> {
+        Some(|r, dest, opts| unpack(zstd::stream::read::Decoder::new(r)?, dest, opts))
+    }
+}
+
+/// Walks a tar stream and extracts it into dest. Every tar based format is
+/// just a different decoder layered under this.
+pub(super) fn unpack<R: Read>(reader: R, dest: &Path, opts: &DecompressOpts) -> Result<(), Error> {
+    if opts.subpath.is_none() {
+        fs::create_dir_all(dest).map_err(Error::DestinationNotCreated)?;
+    }
+
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_mtime(opts.preserve_mtime);
+    let mut budget = super::Budget::new(opts);
+    let mut selector = Selector::new(opts.subpath.as_deref());
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        budget.entry()?;
+        let kind = entry.header().entry_type();
+        let sparse = if kind.is_file() {
+            pax_sparse(&mut entry)?
+        } else {
+            None
+        };
+        let name = match sparse.as_ref().and_then(|s| s.name.clone()) {
+            Some(name) => name,
+            None => entry.path()?.into_owned(),
+        };
+        let Some(stripped) = super::strip_components(&name, opts.strip_components) else {
+            continue;
+        };
+        let Some(target) = selector.select(&stripped, kind.is_dir()) else {
+            continue;
+        };
+        let path = super::target_path(dest, &stripped, target)?;
+
+        if kind.is_symlink() || kind.is_hard_link() {
+            if opts.links == LinkPolicy::Reject {
+                return Err(Error::ArchiveLinkRejected {
+                    entry: name.display().to_string(),
+                });
+            }
+
+            let target = entry
+                .link_name()?
+                .ok_or_else(|| super::unsafe_path(&name))?
+                .into_owned();
+
+            if kind.is_hard_link() {
+                // hardlink targets are relative to the root of the archive
+                let target = super::strip_components(&target, opts.strip_components)
+                    .and_then(|t| Some((selector.locate(&t, false)?, t)))
+                    .ok_or_else(|| super::unsafe_path(&name))?;
+                let target = super::target_path(dest, &target.1, target.0)?;
+                // only entries extracted earlier can be linked to
+                if !fs::symlink_metadata(&target).is_ok_and(|m| !m.is_dir()) {
+                    return Err(super::unsafe_path(&name));
+                }
+                super::prepare_entry(&path)?;
+                fs::hard_link(target, &path)?;
+                continue;
+            }
+
+            if opts.links == LinkPolicy::AllowRelativeWithinDest
+                && !super::link_within(dest, &path, &target)
+            {
+                return Err(super::unsafe_path(&name));
+            }
+        }
+
+        if let Some(sparse) = sparse {
+            budget.reserve(sparse.size)?;
+            super::prepare_entry(&path)?;
+            unpack_sparse(&mut entry, &path, sparse, opts)?;
+            continue;
+        }
+
+        // the tar reader stops at the size in the header, so it can be trusted,
+        // it's also the real size of gnu sparse entries, whose holes it seeks over
+        budget.reserve(entry.size())?;
+        super::prepare_entry(&path)?;
+        entry.unpack(&path)?;
+
+        // the mode from the header is already applied, it only needs clamping
+        if opts.umask.is_some() && !kind.is_symlink() {
+            super::set_mode(&path, entry.header().mode()? & 0o7777, opts.umask)?;
+        }
+    }
+
+    selector.finish()
+}
+
+/// A sparse file stored the way GNU tar does in its posix format, with the
+/// layout of the file described by PAX headers instead of a gnu sparse header.
+struct Sparse {
+    /// The real name of the file, the one in the header is made up.
+    name: Option<PathBuf>,
+    /// The size of the file once its holes are put back.
+    size: u64,
+    /// Offset and length of every chunk of data, `None` when the map is
+    /// stored at the start of the entry data instead (format 1.0).
+    map: Option<Vec<(u64, u64)>>,
+}
+
+fn pax_sparse<R: Read>(entry: &mut tar::Entry<R>) -> Result<Option<Sparse>, Error> {
+    let Some(extensions) = entry.pax_extensions()? else {
+        return Ok(None);
+    };
+
+    let mut major = None;
+    let mut name = None;
+    let mut size = None;
+    let mut numbers = Vec::new();
+    for extension in extensions {
+        let extension = extension?;
+        let (Ok(key), Ok(value)) = (extension.key(), extension.value()) else {
+            continue;
+        };
+        match key {
+            "GNU.sparse.major" => major = Some(value.to_string()),
+            "GNU.sparse.name" => name = Some(PathBuf::from(value)),
+            "GNU.sparse.size" | "GNU.sparse.realsize" => size = Some(sparse_number(value)?),
+            // 0.1 has the whole map in one header, 0.0 repeats these two per chunk
+            "GNU.sparse.map" if !value.is_empty() => {
+                for n in value.split(',') {
+                    numbers.push(sparse_number(n)?);
+                }
+            }
+            "GNU.sparse.offset" | "GNU.sparse.numbytes" => numbers.push(sparse_number(value)?),
+            _ => {}
+        }
+    }
+
+    let Some(size) = size else {
+        return Ok(None);
+    };
+    if numbers.len() % 2 != 0 {
+        return Err(invalid_sparse());
+    }
+
+    Ok(Some(Sparse {
+        name,
+        size,
+        map: (major.as_deref() != Some("1"))
+            .then(|| numbers.chunks(2).map(|c| (c[0], c[1])).collect()),
+    }))
+}
+
+/// Reads the map a 1.0 sparse entry starts with: decimal numbers on their own
+/// line, the count of chunks followed by the offset and length of each, padded
+/// to a whole block.
+fn read_sparse_map<R: Read>(reader: &mut R) -> Result<Vec<(u64, u64)>, Error> {
+    let mut consumed = 0u64;
+    let mut number = || -> Result<u64, Error> {
+        let mut digits = String::new();
+        loop {
+            let mut byte = [0u8];
+            reader.read_exact(&mut byte)?;
+            consumed += 1;
+            match byte[0] {
+                b'\n' => return sparse_number(&digits),
+                b @ b'0'..=b'9' if digits.len() < 20 => digits.push(b as char),
+                _ => return Err(invalid_sparse()),
+            }
+        }
+    };
+
+    let mut map = Vec::new();
+    for _ in 0..number()? {
+        map.push((number()?, number()?));
+    }
+
+    let padding = (512 - consumed % 512) % 512;
+    io::copy(&mut reader.take(padding), &mut io::sink())?;
+    Ok(map)
+}
+
+/// Writes only the chunks of data of a sparse file, seeking over the holes so
+/// that they don't take up any space on filesystems supporting it.
+fn unpack_sparse<R: Read>(
+    entry: &mut tar::Entry<R>,
+    path: &Path,
+    sparse: Sparse,
+    opts: &DecompressOpts,
+) -> Result<(), Error> {
+    let map = match sparse.map {
+        Some(map) => map,
+        None => read_sparse_map(entry)?,
+    };
+
+    let mut file = fs::File::create(path)?;
+    let mut end = 0;
+    for (offset, len) in map {
+        if offset < end || offset.checked_add(len).is_none_or(|e| e > sparse.size) {
+            return Err(invalid_sparse());
+        }
+        file.seek(SeekFrom::Start(offset))?;
+        if io::copy(&mut entry.take(len), &mut file)? != len {
+            return Err(invalid_sparse());
+        }
+        end = offset + len;
+    }
+    // a trailing hole is only there once the length is set
+    file.set_len(sparse.size)?;
+
+    let header = entry.header();
+    if opts.preserve_mtime {
+        file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(header.mtime()?))?;
+    }
+    super::set_mode(path, header.mode()? & 0o7777, opts.umask)
+}
+
+fn sparse_number(value: &str) -> Result<u64, Error> {
+    value.parse().map_err(|_| invalid_sparse())
+}
+
+fn invalid_sparse() -> Error {
+    Error::InvalidArchive("malformed sparse file map".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::Decompressor;
+
+    fn tar_bytes(entries: &[(&str, &str, u32)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        for (name, content, mode) in entries {
+            let mut header = tar::Header::new_gnu();
+            if name.ends_with('/') {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_size(0);
+            } else {
+                header.set_size(content.len() as u64);
+            }
+            header.set_mode(*mode);
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+
+        builder.into_inner().unwrap()
+    }
+
+    fn tar_gz(path: &Path, entries: &[(&str, &str, u32)]) {
+        let f = fs::File::create(path).unwrap();
+        let mut enc = flate2::write::GzEncoder::new(f, flate2::Compression::default());
+        enc.write_all(&tar_bytes(entries)).unwrap();
+        enc.finish().unwrap();
+    }
+
+    async fn assert_round_trip(d: &dyn Decompressor, encode: fn(&[u8]) -> Vec<u8>) {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("archive");
+        let tar = tar_bytes(&[("a.txt", "a", 0o644), ("b/c.txt", "c", 0o644)]);
+        fs::write(&archive, encode(&tar)).unwrap();
+
+        let dest = tmp.path().join("dest");
+        d.decompress(&archive, &dest, &DecompressOpts::default())
+            .await
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(dest.join("a.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dest.join("b/c.txt")).unwrap(), "c");
+    }
+
+    #[tokio::test]
+    async fn it_should_extract_every_tar_format() {
+        assert_round_trip(&Tar, |b| b.to_vec()).await;
+        assert_round_trip(&TarGz, |b| {
+            let mut enc = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+            enc.write_all(b).unwrap();
+            enc.finish().unwrap()
+        })
+        .await;
+        #[cfg(feature = "bzip2")]
+        assert_round_trip(&TarBz2, |b| {
+            let mut enc = bzip2::write::BzEncoder::new(Vec::new(), Default::default());
+            enc.write_all(b).unwrap();
+            enc.finish().unwrap()
+        })
+        .await;
+        #[cfg(feature = "xz")]
+        assert_round_trip(&TarXz, |b| {
+            let mut enc = xz2::write::XzEncoder::new(Vec::new(), 6);
+            enc.write_all(b).unwrap();
+            enc.finish().unwrap()
+        })
+        .await;
+        #[cfg(feature = "zstd")]
+        assert_round_trip(&TarZst, |b| zstd::encode_all(b, 0).unwrap()).await;
+    }
+
+    /// Appends an entry without the path validation `tar::Builder` does, the
+    /// way a malicious archive would be put together.
+    fn append_raw(
+        builder: &mut tar::Builder<Vec<u8>>,
+        name: &str,
+        kind: tar::EntryType,
+        link: &str,
+        content: &[u8],
+    ) {
+        let mut header = tar::Header::new_gnu();
+        let gnu = header.as_gnu_mut().unwrap();
+        gnu.name[..name.len()].copy_from_slice(name.as_bytes());
+        gnu.linkname[..link.len()].copy_from_slice(link.as_bytes());
+        header.set_entry_type(kind);
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, content).unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_should_reject_entries_escaping_dest() {
+        let tests = vec![
+            ("../../../../tmp/evil", tar::EntryType::Regular, ""),
+            ("/tmp/evil", tar::EntryType::Regular, ""),
+            ("link", tar::EntryType::Symlink, "/etc"),
+            ("link", tar::EntryType::Symlink, "../../outside"),
+            ("link", tar::EntryType::Link, "../outside"),
+        ];
+
+        for (name, kind, link) in tests {
+            let tmp = tempfile::tempdir().unwrap();
+            let archive = tmp.path().join("evil.tar");
+            let mut builder = tar::Builder::new(Vec::new());
+            append_raw(&mut builder, name, kind, link, b"evil");
+            fs::write(&archive, builder.into_inner().unwrap()).unwrap();
+
+            let dest = tmp.path().join("dest");
+            let res = Tar
+                .decompress(&archive, &dest, &DecompressOpts::default())
+                .await;
+            assert!(
+                matches!(res, Err(Error::UnsafeArchivePath { ref entry }) if entry == name || entry == link),
+                "{}: {:?}",
+                name,
+                res
+            );
+            assert!(!tmp.path().join("outside").exists());
+        }
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn it_should_not_write_through_symlinks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("evil.tar");
+        let mut builder = tar::Builder::new(Vec::new());
+        append_raw(&mut builder, "dir", tar::EntryType::Symlink, "sub", b"");
+        append_raw(&mut builder, "sub/", tar::EntryType::Directory, "", b"");
+        append_raw(
+            &mut builder,
+            "dir/ok.txt",
+            tar::EntryType::Regular,
+            "",
+            b"ok",
+        );
+        fs::write(&archive, builder.into_inner().unwrap()).unwrap();
+
+        let dest = tmp.path().join("dest");
+        Tar.decompress(&archive, &dest, &DecompressOpts::default())
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(dest.join("sub/ok.txt")).unwrap(), "ok");
+
+        // a link created by the archive itself can't be used to climb out
+        let archive = tmp.path().join("evil2.tar");
+        let mut builder = tar::Builder::new(Vec::new());
+        fs::create_dir(tmp.path().join("outside")).unwrap();
+        std::os::unix::fs::symlink(tmp.path().join("outside"), dest.join("escape")).unwrap();
+        append_raw(
+            &mut builder,
+            "escape/evil.txt",
+            tar::EntryType::Regular,
+            "",
+            b"evil",
+        );
+        fs::write(&archive, builder.into_inner().unwrap()).unwrap();
+
+        let res = Tar
+            .decompress(&archive, &dest, &DecompressOpts::default())
+            .await;
+        assert!(
+            matches!(res, Err(Error::UnsafeArchivePath { .. })),
+            "{:?}",
+            res
+        );
+        assert!(!tmp.path().join("outside/evil.txt").exists());
+    }
+
+    fn links_tar(path: &Path, entries: &[(&str, tar::EntryType, &str, &[u8])]) {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, kind, link, content) in entries {
+            append_raw(&mut builder, name, *kind, link, content);
+        }
+        fs::write(path, builder.into_inner().unwrap()).unwrap();
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn it_should_apply_the_link_policy() {
+        use tar::EntryType::{Regular, Symlink};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let benign = tmp.path().join("benign.tar");
+        links_tar(
+            &benign,
+            &[
+                ("lib/libfoo.so.1", Regular, "", b"elf"),
+                ("lib/libfoo.so", Symlink, "libfoo.so.1", b""),
+            ],
+        );
+        let absolute = tmp.path().join("absolute.tar");
+        links_tar(&absolute, &[("etc", Symlink, "/etc", b"")]);
+        // each link stays inside dest on its own, but not once followed
+        let chained = tmp.path().join("chained.tar");
+        links_tar(
+            &chained,
+            &[("a", Symlink, ".", b""), ("b", Symlink, "a/a/../..", b"")],
+        );
+
+        let extract = |archive: &Path, links: LinkPolicy, dest: &str| {
+            let opts = DecompressOpts {
+                links,
+                ..Default::default()
+            };
+            let dest = tmp.path().join(dest);
+            let archive = archive.to_path_buf();
+            async move { Tar.decompress(&archive, &dest, &opts).await.map(|_| dest) }
+        };
+
+        let dest = extract(&benign, LinkPolicy::default(), "a").await.unwrap();
+        assert_eq!(
+            fs::read_to_string(dest.join("lib/libfoo.so")).unwrap(),
+            "elf"
+        );
+
+        let res = extract(&benign, LinkPolicy::Reject, "b").await;
+        assert!(
+            matches!(res, Err(Error::ArchiveLinkRejected { ref entry }) if entry == "lib/libfoo.so"),
+            "{:?}",
+            res
+        );
+
+        let res = extract(&absolute, LinkPolicy::default(), "c").await;
+        assert!(
+            matches!(res, Err(Error::UnsafeArchivePath { .. })),
+            "{:?}",
+            res
+        );
+        let dest = extract(&absolute, LinkPolicy::AllowAll, "d").await.unwrap();
+        assert_eq!(fs::read_link(dest.join("etc")).unwrap(), Path::new("/etc"));
+
+        let res = extract(&chained, LinkPolicy::default(), "e").await;
+        assert!(
+            matches!(res, Err(Error::UnsafeArchivePath { ref entry }) if entry == "b"),
+            "{:?}",
+            res
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_extract_hardlink_chains() {
+        use tar::EntryType::{Link, Regular};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("links.tar");
+        links_tar(
+            &archive,
+            &[
+                ("bin/tool", Regular, "", b"tool"),
+                ("bin/tool-1", Link, "bin/tool", b""),
+                ("bin/tool-1.2", Link, "bin/tool-1", b""),
+            ],
+        );
+
+        let dest = tmp.path().join("dest");
+        Tar.decompress(&archive, &dest, &DecompressOpts::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(dest.join("bin/tool-1.2")).unwrap(),
+            "tool"
+        );
+
+        // the target has to be extracted before the link to it
+        let archive = tmp.path().join("dangling.tar");
+        links_tar(
+            &archive,
+            &[
+                ("bin/tool-1", Link, "bin/tool", b""),
+                ("bin/tool", Regular, "", b"tool"),
+            ],
+        );
+        let res = Tar
+            .decompress(
+                &archive,
+                &tmp.path().join("dangling"),
+                &DecompressOpts::default(),
+            )
+            .await;
+        assert!(
+            matches!(res, Err(Error::UnsafeArchivePath { ref entry }) if entry == "bin/tool-1"),
+            "{:?}",
+            res
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_extract_nested_directories() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.tar.gz");
+        tar_gz(
+            &archive,
+            &[
+                ("a.txt", "a", 0o644),
+                ("b/", "", 0o755),
+                ("b/c/d.txt", "d", 0o644),
+            ],
+        );
+
+        let dest = tmp.path().join("dest");
+        TarGz
+            .decompress(&archive, &dest, &DecompressOpts::default())
+            .await
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(dest.join("a.txt")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(dest.join("b/c/d.txt")).unwrap(), "d");
+    }
+
+    #[tokio::test]
+    async fn it_should_extract_a_single_top_level_folder() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.tar.gz");
+        tar_gz(
+            &archive,
+            &[
+                ("tool-1.2.3/", "", 0o755),
+                ("tool-1.2.3/README", "readme", 0o644),
+                ("tool-1.2.3/bin/tool", "#!/bin/sh", 0o755),
+            ],
+        );
+
+        let dest = tmp.path().join("dest");
+        TarGz
+            .decompress(&archive, &dest, &DecompressOpts::default())
+            .await
+            .unwrap();
+
+        let entries: Vec<_> = fs::read_dir(&dest).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+        assert!(dest.join("tool-1.2.3").is_dir());
+        assert_eq!(
+            fs::read_to_string(dest.join("tool-1.2.3/README")).unwrap(),
+            "readme"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_strip_components() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.tar.gz");
+        tar_gz(
+            &archive,
+            &[
+                ("tool-1.2.3/", "", 0o755),
+                ("tool-1.2.3/README", "readme", 0o644),
+                ("tool-1.2.3/bin/", "", 0o755),
+                ("tool-1.2.3/bin/tool", "#!/bin/sh", 0o755),
+            ],
+        );
+
+        let dest = tmp.path().join("strip-1");
+        let opts = DecompressOpts {
+            strip_components: 1,
+            ..Default::default()
+        };
+        TarGz.decompress(&archive, &dest, &opts).await.unwrap();
+        assert_eq!(fs::read_to_string(dest.join("README")).unwrap(), "readme");
+        assert!(dest.join("bin/tool").is_file());
+
+        let dest = tmp.path().join("strip-2");
+        let opts = DecompressOpts {
+            strip_components: 2,
+            ..Default::default()
+        };
+        TarGz.decompress(&archive, &dest, &opts).await.unwrap();
+        let entries: Vec<_> = fs::read_dir(&dest).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+        assert!(dest.join("tool").is_file());
+    }
+
+    #[tokio::test]
+    async fn it_should_extract_the_entries_selected_by_a_subpath() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.tar.gz");
+        tar_gz(
+            &archive,
+            &[
+                ("tool-1.2.3/", "", 0o755),
+                ("tool-1.2.3/README", "readme", 0o644),
+                ("tool-1.2.3/bin/", "", 0o755),
+                ("tool-1.2.3/bin/tool", "#!/bin/sh", 0o755),
+                ("tool-1.2.3/bin/helper", "#!/bin/sh", 0o755),
+                ("tool-1.2.3/lib/libtool.so", "elf", 0o644),
+            ],
+        );
+
+        let extract = |subpath: &str, strip_components: usize| {
+            let dest = tmp.path().join(format!(
+                "{}-{}",
+                subpath.replace('/', "_"),
+                strip_components
+            ));
+            let opts = DecompressOpts {
+                subpath: Some(subpath.to_string()),
+                strip_components,
+                ..Default::default()
+            };
+            let archive = archive.clone();
+            async move { TarGz.decompress(&archive, &dest, &opts).await.map(|_| dest) }
+        };
+
+        let dest = extract("bin/tool", 1).await.unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "#!/bin/sh");
+
+        let dest = extract("tool-1.2.3/bin", 0).await.unwrap();
+        let mut names: Vec<_> = fs::read_dir(&dest)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["helper", "tool"]);
+
+        let dest = extract("*/lib/*.so", 0).await.unwrap();
+        assert_eq!(fs::read_to_string(dest.join("libtool.so")).unwrap(), "elf");
+        assert_eq!(fs::read_dir(&dest).unwrap().count(), 1);
+
+        let res = extract("bin/tols", 1).await;
+        assert!(
+            matches!(res, Err(Error::SubpathNotFound { ref near, .. }) if near[0] == "bin/tool"),
+            "{:?}",
+            res
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_enforce_limits() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.tar.gz");
+        tar_gz(
+            &archive,
+            &[("a.txt", "aaaa", 0o644), ("b.txt", "bbbb", 0o644)],
+        );
+
+        let tests = vec![
+            (
+                crate::DecompressLimits {
+                    max_entries: 1,
+                    ..Default::default()
+                },
+                "entry count",
+            ),
+            (
+                crate::DecompressLimits {
+                    max_entry_bytes: 3,
+                    ..Default::default()
+                },
+                "entry size",
+            ),
+            (
+                crate::DecompressLimits {
+                    max_total_bytes: 6,
+                    ..Default::default()
+                },
+                "total size",
+            ),
+        ];
+
+        for (i, (limits, name)) in tests.into_iter().enumerate() {
+            let dest = tmp.path().join(i.to_string());
+            let opts = DecompressOpts {
+                limits,
+                ..Default::default()
+            };
+            let res = TarGz.decompress(&archive, &dest, &opts).await;
+            assert!(
+                matches!(res, Err(Error::DecompressLimitExceeded { limit, .. }) if limit == name),
+                "{:?}",
+                res
+            );
+        }
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn it_should_preserve_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.tar.gz");
+        tar_gz(
+            &archive,
+            &[("bin/tool", "#!/bin/sh", 0o755), ("data.txt", "x", 0o600)],
+        );
+
+        let dest = tmp.path().join("dest");
+        TarGz
+            .decompress(&archive, &dest, &DecompressOpts::default())
+            .await
+            .unwrap();
+
+        let mode = |p: &str| fs::metadata(dest.join(p)).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode("bin/tool"), 0o755);
+        assert_eq!(mode("data.txt"), 0o600);
+
+        let dest = tmp.path().join("umask");
+        let opts = DecompressOpts {
+            umask: Some(0o027),
+            ..Default::default()
+        };
+        TarGz.decompress(&archive, &dest, &opts).await.unwrap();
+
+        let mode = |p: &str| fs::metadata(dest.join(p)).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode("bin/tool"), 0o750);
+        assert_eq!(mode("data.txt"), 0o600);
+    }
+
+    /// Appends a PAX extended header applying to the entry after it.
+    fn append_pax(builder: &mut tar::Builder<Vec<u8>>, records: &[(&str, &str)]) {
+        let mut data = Vec::new();
+        for (key, value) in records {
+            let record = format!(" {}={}\n", key, value);
+            // the length prefix counts its own digits
+            let mut len = record.len();
+            while len != record.len() + len.to_string().len() {
+                len = record.len() + len.to_string().len();
+            }
+            data.extend(format!("{}{}", len, record).into_bytes());
+        }
+
+        let mut header = tar::Header::new_ustar();
+        header.set_entry_type(tar::EntryType::XHeader);
+        header.set_size(data.len() as u64);
+        header.set_path("PaxHeader").unwrap();
+        header.set_cksum();
+        builder.append(&header, &data[..]).unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_should_extract_long_and_non_ascii_names() {
+        let long = format!("{}/{}.txt", "d".repeat(120), "f".repeat(80));
+        let mut builder = tar::Builder::new(Vec::new());
+        // gnu headers spill long names into a separate entry
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, &long, &b"long"[..])
+            .unwrap();
+        // pax headers override the name in the ustar header
+        append_pax(&mut builder, &[("path", "données/日本語.txt")]);
+        let mut header = tar::Header::new_ustar();
+        header.set_path("placeholder").unwrap();
+        header.set_size(3);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, &b"utf"[..]).unwrap();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("names.tar");
+        fs::write(&archive, builder.into_inner().unwrap()).unwrap();
+
+        let dest = tmp.path().join("dest");
+        Tar.decompress(&archive, &dest, &DecompressOpts::default())
+            .await
+            .unwrap();
+        assert_eq!(long.len(), 205);
+        assert_eq!(fs::read_to_string(dest.join(&long)).unwrap(), "long");
+        assert_eq!(
+            fs::read_to_string(dest.join("données/日本語.txt")).unwrap(),
+            "utf"
+        );
+        assert!(!dest.join("placeholder").exists());
+    }
+
+    fn octal(field: &mut [u8; 12], n: u64) {
+        field.copy_from_slice(format!("{:011o}\0", n).as_bytes());
+    }
+
+    #[tokio::test]
+    async fn it_should_extract_sparse_files_without_filling_holes() {
+        const GB: u64 = 1 << 30;
+        const HALF_MB: u64 = 1 << 19;
+        let chunk = vec![7u8; HALF_MB as usize];
+        let map = [(0, HALF_MB), (GB - HALF_MB, HALF_MB)];
+
+        let mut builder = tar::Builder::new(Vec::new());
+
+        // the old gnu format describes the chunks in the header itself
+        let mut header = tar::Header::new_gnu();
+        header.set_path("gnu.img").unwrap();
+        header.set_entry_type(tar::EntryType::GNUSparse);
+        header.set_size(2 * HALF_MB);
+        header.set_mode(0o644);
+        let gnu = header.as_gnu_mut().unwrap();
+        for (i, (offset, len)) in map.iter().enumerate() {
+            octal(&mut gnu.sparse[i].offset, *offset);
+            octal(&mut gnu.sparse[i].numbytes, *len);
+        }
+        octal(&mut gnu.realsize, GB);
+        header.set_cksum();
+        builder
+            .append(&header, &[chunk.clone(), chunk.clone()].concat()[..])
+            .unwrap();
+
+        // pax 0.1 puts the map in a header
+        let map_01 = format!("0,{},{},{}", HALF_MB, GB - HALF_MB, HALF_MB);
+        append_pax(
+            &mut builder,
+            &[
+                ("GNU.sparse.size", &GB.to_string()),
+                ("GNU.sparse.numblocks", "2"),
+                ("GNU.sparse.map", &map_01),
+                ("GNU.sparse.name", "pax-0.1.img"),
+            ],
+        );
+        let mut header = tar::Header::new_ustar();
+        header.set_path("GNUSparseFile.0/pax-0.1.img").unwrap();
+        header.set_size(2 * HALF_MB);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append(&header, &[chunk.clone(), chunk.clone()].concat()[..])
+            .unwrap();
+
+        // pax 1.0 stores the map at the start of the data
+        append_pax(
+            &mut builder,
+            &[
+                ("GNU.sparse.major", "1"),
+                ("GNU.sparse.minor", "0"),
+                ("GNU.sparse.name", "pax-1.0.img"),
+                ("GNU.sparse.realsize", &GB.to_string()),
+            ],
+        );
+        let mut data = format!("2\n0\n{}\n{}\n{}\n", HALF_MB, GB - HALF_MB, HALF_MB).into_bytes();
+        data.resize(512, 0);
+        data.extend(&chunk);
+        data.extend(&chunk);
+        let mut header = tar::Header::new_ustar();
+        header.set_path("GNUSparseFile.0/pax-1.0.img").unwrap();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, &data[..]).unwrap();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("sparse.tar");
+        fs::write(&archive, builder.into_inner().unwrap()).unwrap();
+
+        let dest = tmp.path().join("dest");
+        Tar.decompress(&archive, &dest, &DecompressOpts::default())
+            .await
+            .unwrap();
+
+        assert!(!dest.join("GNUSparseFile.0").exists());
+        for name in ["gnu.img", "pax-0.1.img", "pax-1.0.img"] {
+            let path = dest.join(name);
+            let mut f = fs::File::open(&path).unwrap();
+            assert_eq!(f.metadata().unwrap().len(), GB, "{}", name);
+
+            let mut byte = [0u8; 1];
+            for (offset, expected) in [(0, 7), (HALF_MB, 0), (GB / 2, 0), (GB - 1, 7)] {
+                f.seek(SeekFrom::Start(offset)).unwrap();
+                f.read_exact(&mut byte).unwrap();
+                assert_eq!(byte[0], expected, "{} at {}", name, offset);
+            }
+
+            #[cfg(target_family = "unix")]
+            {
+                use std::os::unix::fs::MetadataExt;
+                let on_disk = f.metadata().unwrap().blocks() * 512;
+                assert!(on_disk < 64 << 20, "{} takes {} bytes", name, on_disk);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn it_should_restore_mtimes_when_asked() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&archive).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(1);
+        header.set_mode(0o644);
+        header.set_mtime(1_500_000_000);
+        builder
+            .append_data(&mut header, "a.txt", &b"a"[..])
+            .unwrap();
+        builder.finish().unwrap();
+
+        let mtime = |dest: &Path| {
+            fs::metadata(dest.join("a.txt"))
+                .unwrap()
+                .modified()
+                .unwrap()
+        };
+        let epoch = std::time::UNIX_EPOCH;
+
+        let dest = tmp.path().join("preserved");
+        let opts = DecompressOpts {
+            preserve_mtime: true,
+            ..Default::default()
+        };
+        Tar.decompress(&archive, &dest, &opts).await.unwrap();
+        assert_eq!(
+            mtime(&dest),
+            epoch + std::time::Duration::from_secs(1_500_000_000)
+        );
+
+        let dest = tmp.path().join("extracted");
+        Tar.decompress(&archive, &dest, &DecompressOpts::default())
+            .await
+            .unwrap();
+        assert!(mtime(&dest) > epoch + std::time::Duration::from_secs(1_600_000_000));
+    }
+}
@@ -0,0 +1,456 @@
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use zip::result::ZipError;
+
+use super::Selector;
+use crate::{DecompressOpts, Error};
+
+/// Extracts zip archives, including zip64 ones.
+pub struct Zip;
+
+#[async_trait]
+impl crate::Decompressor for Zip {
+    fn extensions(&self) -> &[&str] {
+        &["zip"]
+    }
+
+    async fn decompress(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        opts: &DecompressOpts,
+    ) -> Result<(), Error> {
+        super::unblocked(archive, dest, opts, unpack).await
+    }
+}
+
+fn unpack(archive: &Path, dest: &Path, opts: &DecompressOpts) -> Result<(), Error> {
+    let f = fs::File::open(archive)?;
+    let mut archive = zip::ZipArchive::new(f).map_err(|e| Error::Unknown(Box::new(e)))?;
+
+    if opts.subpath.is_none() {
+        fs::create_dir_all(dest).map_err(Error::DestinationNotCreated)?;
+    }
+    let mut budget = super::Budget::new(opts);
+    budget.total(archive.len() as u64);
+    let mut selector = Selector::new(opts.subpath.as_deref());
+
+    for i in 0..archive.len() {
+        let entry = archive.name_for_index(i).unwrap_or_default().to_string();
+        let mut file = match &opts.password {
+            // entries that aren't encrypted ignore the password
+            Some(password) => archive.by_index_decrypt(i, password.expose().as_bytes()),
+            None => archive.by_index(i),
+        }
+        .map_err(|e| match e {
+            ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED) => Error::ArchiveEncrypted,
+            ZipError::InvalidPassword => Error::ArchiveDecryptionFailed { entry },
+            e => Error::Unknown(Box::new(e)),
+        })?;
+        budget.entry()?;
+
+        // archives made by windows tools sometimes use backslash separators
+        let name = file.name().replace('\\', "/");
+        let Some(stripped) = super::strip_components(Path::new(&name), opts.strip_components)
+        else {
+            continue;
+        };
+        let is_dir = name.ends_with('/') || file.is_dir();
+        let Some(target) = selector.select(&stripped, is_dir) else {
+            continue;
+        };
+        let path = super::target_path(dest, &stripped, target)?;
+
+        if is_dir {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+
+        super::prepare_entry(&path)?;
+
+        let mut out = fs::File::create(&path)?;
+        let encrypted = file.encrypted();
+        match budget.copy(&mut file, &mut out) {
+            // the password checks let a few wrong ones through, those only
+            // show once the checksum of the entry doesn't add up
+            Err(Error::Io(_)) if encrypted => {
+                return Err(Error::ArchiveDecryptionFailed { entry: name })
+            }
+            res => res?,
+        }
+
+        if opts.preserve_mtime {
+            if let Some(modified) = file.last_modified() {
+                out.set_modified(mtime(modified))?;
+            }
+        }
+
+        // entries made on dos don't carry a unix mode, they keep the default one
+        match file.unix_mode() {
+            Some(mode) => super::set_mode(&path, mode & 0o777, opts.umask)?,
+            None if opts.umask.is_some() => super::set_mode(&path, 0o666, opts.umask)?,
+            None => {}
+        }
+    }
+
+    selector.finish()
+}
+
+/// Zip timestamps are in the local time of whoever made the archive, without
+/// saying which zone that was, so they're taken as utc.
+fn mtime(dt: zip::DateTime) -> SystemTime {
+    // days since the epoch from a civil date, after Howard Hinnant's algorithm
+    let (m, d) = (dt.month() as u64, dt.day() as u64);
+    let y = dt.year() as u64 - u64::from(m <= 2);
+    let (era, yoe) = (y / 400, y % 400);
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let secs =
+        days * 86_400 + dt.hour() as u64 * 3_600 + dt.minute() as u64 * 60 + dt.second() as u64;
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::write::SimpleFileOptions;
+
+    use super::*;
+    use crate::Decompressor;
+
+    fn zip_file(path: &Path, entries: &[(&str, &str, u32)], large_file: bool) {
+        let f = fs::File::create(path).unwrap();
+        let mut w = zip::ZipWriter::new(f);
+
+        for (name, content, mode) in entries {
+            let opts = SimpleFileOptions::default()
+                .unix_permissions(*mode)
+                .large_file(large_file);
+            if name.ends_with('/') {
+                w.add_directory(*name, opts).unwrap();
+            } else {
+                w.start_file(*name, opts).unwrap();
+                w.write_all(content.as_bytes()).unwrap();
+            }
+        }
+
+        w.finish().unwrap();
+    }
+
+    /// Rewrites the "version made by" of an entry in the central directory to
+    /// MS-DOS, the way archives produced by windows tools look.
+    fn mark_as_dos(path: &Path, name: &str) {
+        let mut buf = fs::read(path).unwrap();
+        let mut i = 0;
+        while i + 46 < buf.len() {
+            if buf[i..i + 4] == [0x50, 0x4b, 0x01, 0x02] {
+                let len = u16::from_le_bytes([buf[i + 28], buf[i + 29]]) as usize;
+                if &buf[i + 46..i + 46 + len] == name.as_bytes() {
+                    buf[i + 5] = 0;
+                }
+            }
+            i += 1;
+        }
+        fs::write(path, buf).unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_should_extract_zip_archives() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.zip");
+        zip_file(
+            &archive,
+            &[
+                ("a.txt", "a", 0o644),
+                ("empty/", "", 0o755),
+                ("b/c/d.txt", "d", 0o644),
+                ("e.txt", "", 0o644),
+                ("win\\path\\f.txt", "f", 0o644),
+            ],
+            false,
+        );
+
+        let dest = tmp.path().join("dest");
+        Zip.decompress(&archive, &dest, &DecompressOpts::default())
+            .await
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(dest.join("a.txt")).unwrap(), "a");
+        assert!(dest.join("empty").is_dir());
+        assert_eq!(fs::read_to_string(dest.join("b/c/d.txt")).unwrap(), "d");
+        assert_eq!(fs::read_to_string(dest.join("e.txt")).unwrap(), "");
+        assert_eq!(
+            fs::read_to_string(dest.join("win/path/f.txt")).unwrap(),
+            "f"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_reject_entries_escaping_dest() {
+        for name in ["../../../../tmp/evil", "/tmp/evil", "..\\..\\evil"] {
+            let tmp = tempfile::tempdir().unwrap();
+            let archive = tmp.path().join("evil.zip");
+            zip_file(&archive, &[(name, "evil", 0o644)], false);
+
+            let dest = tmp.path().join("dest");
+            let res = Zip
+                .decompress(&archive, &dest, &DecompressOpts::default())
+                .await;
+            assert!(
+                matches!(res, Err(Error::UnsafeArchivePath { .. })),
+                "{}: {:?}",
+                name,
+                res
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn it_should_strip_components() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.zip");
+        zip_file(
+            &archive,
+            &[
+                ("tool-1.2.3/", "", 0o755),
+                ("tool-1.2.3/README", "readme", 0o644),
+                ("tool-1.2.3/bin/tool", "#!/bin/sh", 0o755),
+            ],
+            false,
+        );
+
+        for (n, path) in [(1, "bin/tool"), (2, "tool")] {
+            let dest = tmp.path().join(format!("strip-{}", n));
+            let opts = DecompressOpts {
+                strip_components: n,
+                ..Default::default()
+            };
+            Zip.decompress(&archive, &dest, &opts).await.unwrap();
+            assert!(dest.join(path).is_file(), "{}", n);
+            assert_eq!(dest.join("README").is_file(), n == 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn it_should_extract_a_single_entry() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.zip");
+        zip_file(
+            &archive,
+            &[
+                ("bin/", "", 0o755),
+                ("bin/tool", "#!/bin/sh", 0o755),
+                ("README", "readme", 0o644),
+            ],
+            false,
+        );
+
+        let dest = tmp.path().join("tool");
+        let opts = DecompressOpts {
+            subpath: Some("bin/tool".to_string()),
+            ..Default::default()
+        };
+        Zip.decompress(&archive, &dest, &opts).await.unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "#!/bin/sh");
+
+        let opts = DecompressOpts {
+            subpath: Some("bin/too".to_string()),
+            ..Default::default()
+        };
+        let res = Zip
+            .decompress(&archive, &tmp.path().join("missing"), &opts)
+            .await;
+        assert!(
+            matches!(res, Err(Error::SubpathNotFound { .. })),
+            "{:?}",
+            res
+        );
+        assert!(!tmp.path().join("missing").exists());
+    }
+
+    #[tokio::test]
+    async fn it_should_extract_zip64_archives() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.zip");
+        zip_file(&archive, &[("big/a.txt", "zip64", 0o644)], true);
+
+        let dest = tmp.path().join("dest");
+        Zip.decompress(&archive, &dest, &DecompressOpts::default())
+            .await
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(dest.join("big/a.txt")).unwrap(), "zip64");
+    }
+
+    #[tokio::test]
+    async fn it_should_stop_at_the_entry_size_limit() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("bomb.zip");
+        let zeros = "0".repeat(1 << 20);
+        zip_file(&archive, &[("zeros", &zeros, 0o644)], false);
+        assert!(fs::metadata(&archive).unwrap().len() < 1 << 12);
+
+        let dest = tmp.path().join("dest");
+        let opts = DecompressOpts {
+            limits: crate::DecompressLimits {
+                max_entry_bytes: 1 << 10,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let res = Zip.decompress(&archive, &dest, &opts).await;
+        assert!(
+            matches!(
+                res,
+                Err(Error::DecompressLimitExceeded {
+                    limit: "entry size",
+                    max: 1024
+                })
+            ),
+            "{:?}",
+            res
+        );
+        assert!(fs::metadata(dest.join("zeros")).unwrap().len() <= 1025);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn it_should_restore_unix_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.zip");
+        zip_file(
+            &archive,
+            &[("bin/tool", "#!/bin/sh", 0o755), ("dos.exe", "MZ", 0o755)],
+            false,
+        );
+        mark_as_dos(&archive, "dos.exe");
+
+        let dest = tmp.path().join("dest");
+        Zip.decompress(&archive, &dest, &DecompressOpts::default())
+            .await
+            .unwrap();
+
+        let mode = |p: &str| fs::metadata(dest.join(p)).unwrap().permissions().mode() & 0o111;
+        assert_eq!(mode("bin/tool"), 0o111);
+        assert_eq!(mode("dos.exe"), 0);
+
+        let dest = tmp.path().join("umask");
+        let opts = DecompressOpts {
+            umask: Some(0o077),
+            ..Default::default()
+        };
+        Zip.decompress(&archive, &dest, &opts).await.unwrap();
+
+        let mode = |p: &str| fs::metadata(dest.join(p)).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode("bin/tool"), 0o700);
+        assert_eq!(mode("dos.exe"), 0o600);
+    }
+
+    #[tokio::test]
+    async fn it_should_decrypt_entries_with_the_password() {
+        use zip::unstable::write::FileOptionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let plain = SimpleFileOptions::default();
+        let zip_crypto = plain.with_deprecated_encryption(b"hunter2");
+        let aes = plain.with_aes_encryption(zip::AesMode::Aes256, "hunter2");
+
+        let fixtures = [
+            ("zipcrypto.zip", vec![("a.txt", zip_crypto)]),
+            ("aes.zip", vec![("a.txt", aes)]),
+            (
+                "mixed.zip",
+                vec![("README", plain), ("a.txt", aes), ("b.txt", zip_crypto)],
+            ),
+        ];
+        for (name, entries) in fixtures {
+            let archive = tmp.path().join(name);
+            let mut w = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+            for (entry, opts) in &entries {
+                w.start_file(*entry, *opts).unwrap();
+                w.write_all(entry.as_bytes()).unwrap();
+            }
+            w.finish().unwrap();
+
+            let extract = |password: Option<&str>| {
+                let dest = tmp.path().join(format!("{}-{:?}", name, password));
+                let opts = DecompressOpts {
+                    password: password.map(crate::Password::new),
+                    ..Default::default()
+                };
+                let archive = archive.clone();
+                async move { Zip.decompress(&archive, &dest, &opts).await.map(|_| dest) }
+            };
+
+            let dest = extract(Some("hunter2")).await.unwrap();
+            for (entry, _) in &entries {
+                assert_eq!(fs::read_to_string(dest.join(entry)).unwrap(), *entry);
+            }
+
+            let res = extract(Some("wrong")).await;
+            assert!(
+                matches!(res, Err(Error::ArchiveDecryptionFailed { ref entry }) if entry == "a.txt"),
+                "{}: {:?}",
+                name,
+                res
+            );
+
+            let res = extract(None).await;
+            assert!(
+                matches!(res, Err(Error::ArchiveEncrypted)),
+                "{}: {:?}",
+                name,
+                res
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn it_should_restore_mtimes_when_asked() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.zip");
+        let mut w = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+        let modified = zip::DateTime::from_date_and_time(2020, 6, 15, 12, 30, 44).unwrap();
+        w.start_file(
+            "a.txt",
+            SimpleFileOptions::default().last_modified_time(modified),
+        )
+        .unwrap();
+        w.write_all(b"a").unwrap();
+        w.finish().unwrap();
+
+        let mtime = |dest: &Path| {
+            fs::metadata(dest.join("a.txt"))
+                .unwrap()
+                .modified()
+                .unwrap()
+        };
+
+        let dest = tmp.path().join("preserved");
+        let opts = DecompressOpts {
+            preserve_mtime: true,
+            ..Default::default()
+        };
+        Zip.decompress(&archive, &dest, &opts).await.unwrap();
+        assert_eq!(
+            mtime(&dest),
+            UNIX_EPOCH + Duration::from_secs(1_592_224_244)
+        );
+
+        let dest = tmp.path().join("extracted");
+        Zip.decompress(&archive, &dest, &DecompressOpts::default())
+            .await
+            .unwrap();
+        assert!(mtime(&dest) > UNIX_EPOCH + Duration::from_secs(1_600_000_000));
+    }
+}
@@ -0,0 +1,400 @@
+//! Getting dest ready for a getter to write to.
+
+use std::{
+    fs, io,
+    path::{self, Component, Path, PathBuf},
+};
+
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::{paths, Error, OnConflict};
+
+/// Where a source that isn't extracted ends up: inside dest under the name of
+/// the source when dest is an existing directory, like `cp` does, and at
+/// dest itself otherwise. A symlink to a directory is taken as something to
+/// replace, since it's what symlinking a directory source leaves behind.
+/// Globs fetched whole name no one source, and go to dest itself.
+pub(crate) fn final_path(dest: &Path, url: &Url) -> PathBuf {
+    let is_dir = fs::symlink_metadata(dest).is_ok_and(|m| m.is_dir());
+    let name = url
+        .path_segments()
+        .and_then(|mut s| s.next_back())
+        .map(|s| percent_decode_str(s).decode_utf8_lossy())
+        .filter(|name| !matches!(name.as_ref(), "" | "." | "..") && !name.contains(['/', '\\']))
+        .filter(|name| !name.contains(['*', '[']));
+
+    match (is_dir, name) {
+        (true, Some(name)) => dest.join(name.as_ref()),
+        _ => dest.to_path_buf(),
+    }
+}
+
+/// Refuses dests whose loss would be a disaster, the filesystem root and the
+/// home directory itself, unless `allow_dangerous` says otherwise, as well
+/// as dests outside the jail when there is one. The directories dest is in
+/// are resolved first, so that a symlink among them can't hide where dest
+/// really is. Dest itself is left as it is, since a symlink at dest is
+/// replaced rather than followed.
+pub(crate) fn check(dest: &Path, jail: Option<&Path>, allow_dangerous: bool) -> Result<(), Error> {
+    let resolved = resolve_parent(dest)?;
+
+    if !allow_dangerous {
+        let home = paths::home_dir().and_then(|h| resolve(&h).ok());
+        if resolved.parent().is_none() || home.is_some_and(|h| h == resolved) {
+            return Err(Error::DangerousDest(plain(&resolved).display().to_string()));
+        }
+    }
+    if let Some(jail) = jail {
+        let jail = resolve(jail)?;
+        if !resolved.starts_with(&jail) {
+            return Err(Error::DestOutsideJail {
+                dest: plain(&resolved).display().to_string(),
+                jail: plain(&jail).display().to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Path as an absolute one, with the directories it's in resolved.
+fn resolve_parent(path: &Path) -> io::Result<PathBuf> {
+    let path = path::absolute(path)?;
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => Ok(resolve(parent)?.join(name)),
+        // the root, or a path ending in ..
+        _ => resolve(&path),
+    }
+}
+
+/// Path as an absolute one, with the symlinks of as much of it as exists
+/// resolved, and the rest of it cleaned up.
+fn resolve(path: &Path) -> io::Result<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in path::absolute(path)?.components() {
+        match component {
+            Component::CurDir => {}
+            // what a link is in is where .. after it leads, which is what
+            // popping the resolved link gives
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => {
+                resolved.push(component);
+                if let Ok(real) = resolved.canonicalize() {
+                    resolved = real;
+                }
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+/// Longest path Windows takes in the classic form, the NUL ending it
+/// included.
+#[cfg(windows)]
+const MAX_PATH: usize = 260;
+
+/// Room left under [`MAX_PATH`] for what gette puts next to dest, like the
+/// files it's staged in and its lock.
+#[cfg(windows)]
+const MAX_PATH_MARGIN: usize = 64;
+
+/// Path in the extended-length form Windows needs past the classic limit,
+/// `\\?\C:\...`, when it gets near that limit, and always for paths on a
+/// share, `\\?\UNC\server\share\...`. Windows doesn't clean up paths in
+/// that form, so it's made absolute and cleaned up first. Paths are left as
+/// they are anywhere else.
+pub(crate) fn extended(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    {
+        if let Some(extended) = windows_extended(path) {
+            return extended;
+        }
+    }
+    path.to_path_buf()
+}
+
+#[cfg(windows)]
+fn windows_extended(path: &Path) -> Option<PathBuf> {
+    use std::{ffi::OsString, path::Prefix};
+
+    let absolute = path::absolute(path).ok()?;
+    let mut components = absolute.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return None;
+    };
+    let mut extended = OsString::from(r"\\?\");
+    match prefix.kind() {
+        Prefix::UNC(server, share) => {
+            extended.push(r"UNC\");
+            extended.push(server);
+            extended.push(r"\");
+            extended.push(share);
+        }
+        Prefix::Disk(_) if absolute.as_os_str().len() >= MAX_PATH - MAX_PATH_MARGIN => {
+            extended.push(prefix.as_os_str());
+        }
+        // short, or in the extended form or a device path already
+        _ => return None,
+    }
+    // absolute already did away with . and .. the way Windows would have
+    for component in components {
+        if let Component::Normal(name) = component {
+            extended.push(r"\");
+            extended.push(name);
+        }
+    }
+    Some(PathBuf::from(extended))
+}
+
+/// Path as it's shown to people, without the prefix [`extended`] gives it.
+pub(crate) fn plain(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    {
+        if let Some(path) = path.to_str() {
+            if let Some(share) = path.strip_prefix(r"\\?\UNC\") {
+                return PathBuf::from(format!(r"\\{}", share));
+            }
+            if let Some(path) = path.strip_prefix(r"\\?\") {
+                return PathBuf::from(path);
+            }
+        }
+    }
+    path.to_path_buf()
+}
+
+/// Whether a get should go ahead with this policy, checked before anything
+/// is fetched. Nothing is cleared until what was fetched is ready to take
+/// the place of what's there, see [`make_room`].
+pub(crate) fn should_fetch(dest: &Path, policy: OnConflict) -> Result<bool, Error> {
+    if fs::symlink_metadata(dest).is_err() {
+        return Ok(true);
+    }
+
+    match policy {
+        OnConflict::Error => Err(Error::DestinationExists(plain(dest).display().to_string())),
+        OnConflict::Skip => Ok(false),
+        OnConflict::Overwrite | OnConflict::BackupAndOverwrite => Ok(true),
+    }
+}
+
+/// Clears whatever is at dest according to the policy, returning whether
+/// what was fetched should go ahead and be moved there.
+pub(crate) fn make_room(dest: &Path, policy: OnConflict) -> Result<bool, Error> {
+    let Ok(meta) = fs::symlink_metadata(dest) else {
+        return Ok(true);
+    };
+
+    match policy {
+        OnConflict::Error => {
+            return Err(Error::DestinationExists(plain(dest).display().to_string()))
+        }
+        OnConflict::Skip => return Ok(false),
+        // a symlink is removed itself, never what it points to
+        OnConflict::Overwrite if meta.is_dir() => fs::remove_dir_all(dest)?,
+        OnConflict::Overwrite => fs::remove_file(dest)?,
+        OnConflict::BackupAndOverwrite => fs::rename(dest, backup_path(dest))?,
+    }
+
+    Ok(true)
+}
+
+/// First `<dest>.bak.<n>` that doesn't exist yet.
+fn backup_path(dest: &Path) -> PathBuf {
+    (1..)
+        .map(|n| {
+            let mut path = dest.as_os_str().to_owned();
+            path.push(format!(".bak.{}", n));
+            PathBuf::from(path)
+        })
+        .find(|path| fs::symlink_metadata(path).is_err())
+        .expect("ran out of backup names")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_final_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("dir");
+        fs::create_dir(&dir).unwrap();
+        let file = tmp.path().join("file");
+        fs::write(&file, "").unwrap();
+        let missing = tmp.path().join("missing");
+
+        let tests = vec![
+            (&dir, "file:///src/report.pdf", dir.join("report.pdf")),
+            (
+                &dir,
+                "s3://bucket/a/b/with%20space.txt",
+                dir.join("with space.txt"),
+            ),
+            (&dir, "https://host/path/", dir.clone()),
+            (&dir, "https://host/a/..%2F..%2Fescape", dir.clone()),
+            (&dir, "https://host/a/%2E%2E", dir.clone()),
+            (&dir, "file:///src/build-*.tar.gz", dir.clone()),
+            (&file, "file:///src/report.pdf", file.clone()),
+            (&missing, "file:///src/report.pdf", missing.clone()),
+        ];
+        for (dest, url, expected) in tests {
+            let url = Url::parse(url).unwrap();
+            assert_eq!(final_path(dest, &url), expected, "{}", url);
+        }
+
+        #[cfg(unix)]
+        {
+            let link = tmp.path().join("link");
+            std::os::unix::fs::symlink(&dir, &link).unwrap();
+            let url = Url::parse("file:///src/report.pdf").unwrap();
+            assert_eq!(final_path(&link, &url), link);
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_extended() {
+        let short = Path::new(r"C:\short\path");
+        assert_eq!(extended(short), short);
+        let deep = Path::new(r"C:\").join(vec!["directory"; 30].join(r"\"));
+        let long = extended(&deep);
+        assert!(long.to_str().unwrap().starts_with(r"\\?\C:\directory\"));
+        assert_eq!(plain(&long), deep);
+        assert_eq!(extended(&long), long);
+        // Windows leaves . and .. in the extended form as they are
+        assert_eq!(
+            extended(&deep.join(r"..\other")),
+            extended(&deep.parent().unwrap().join("other"))
+        );
+
+        // paths on a share always take the extended form
+        let unc = Path::new(r"\\server\share\dir");
+        assert_eq!(extended(unc), Path::new(r"\\?\UNC\server\share\dir"));
+        assert_eq!(plain(&extended(unc)), unc);
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = extended(&tmp.path().join(vec!["directory"; 30].join(r"\")));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("file"), "deep").unwrap();
+        assert_eq!(fs::read_to_string(dir.join("file")).unwrap(), "deep");
+    }
+
+    #[test]
+    fn test_make_room() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dest = tmp.path().join("dest");
+
+        // nothing in the way
+        for policy in [
+            OnConflict::Error,
+            OnConflict::Overwrite,
+            OnConflict::Skip,
+            OnConflict::BackupAndOverwrite,
+        ] {
+            assert!(make_room(&dest, policy).unwrap());
+        }
+
+        fs::write(&dest, "old").unwrap();
+        assert!(should_fetch(&dest, OnConflict::Overwrite).unwrap());
+        assert!(!should_fetch(&dest, OnConflict::Skip).unwrap());
+        assert!(should_fetch(&dest, OnConflict::Error).is_err());
+        let err = make_room(&dest, OnConflict::Error).unwrap_err();
+        assert!(matches!(err, Error::DestinationExists(_)));
+        assert!(!make_room(&dest, OnConflict::Skip).unwrap());
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "old");
+
+        assert!(make_room(&dest, OnConflict::BackupAndOverwrite).unwrap());
+        assert!(!dest.exists());
+        fs::write(&dest, "newer").unwrap();
+        assert!(make_room(&dest, OnConflict::BackupAndOverwrite).unwrap());
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("dest.bak.1")).unwrap(),
+            "old"
+        );
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("dest.bak.2")).unwrap(),
+            "newer"
+        );
+
+        fs::create_dir_all(dest.join("sub")).unwrap();
+        fs::write(dest.join("sub/file"), "old").unwrap();
+        assert!(make_room(&dest, OnConflict::Overwrite).unwrap());
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn test_check() {
+        let tmp = tempfile::tempdir().unwrap();
+        let jail = tmp.path().join("jail");
+        fs::create_dir(&jail).unwrap();
+
+        let root = Path::new(if cfg!(windows) { "C:\\" } else { "/" });
+        let err = check(root, None, false).unwrap_err();
+        assert!(matches!(err, Error::DangerousDest(_)), "{:?}", err);
+        assert!(check(root, None, true).is_ok());
+        if let Some(home) = std::env::var_os("HOME").filter(|_| cfg!(unix)) {
+            let err = check(Path::new(&home), None, false).unwrap_err();
+            assert!(matches!(err, Error::DangerousDest(_)), "{:?}", err);
+            assert!(check(&Path::new(&home).join("dest"), None, false).is_ok());
+        }
+
+        let jail = Some(jail.as_path());
+        for dest in ["jail", "jail/a/b", "jail/missing/../dest"] {
+            assert!(
+                check(&tmp.path().join(dest), jail, false).is_ok(),
+                "{}",
+                dest
+            );
+        }
+        for dest in [
+            "outside",
+            "jail/../outside",
+            "jail/missing/../../outside",
+            "jail/..",
+        ] {
+            let err = check(&tmp.path().join(dest), jail, true).unwrap_err();
+            assert!(
+                matches!(err, Error::DestOutsideJail { .. }),
+                "{}: {:?}",
+                dest,
+                err
+            );
+        }
+
+        #[cfg(unix)]
+        {
+            // a link in the jail to outside of it doesn't get dest out
+            let outside = tmp.path().join("outside");
+            fs::create_dir(&outside).unwrap();
+            std::os::unix::fs::symlink(&outside, tmp.path().join("jail/link")).unwrap();
+            let err = check(&tmp.path().join("jail/link/dest"), jail, false).unwrap_err();
+            assert!(matches!(err, Error::DestOutsideJail { .. }), "{:?}", err);
+            // while a link at dest is replaced, not followed
+            assert!(check(&tmp.path().join("jail/link"), jail, false).is_ok());
+            // and a jail reached through a link is the jail all the same
+            let alias = tmp.path().join("alias");
+            std::os::unix::fs::symlink(tmp.path().join("jail"), &alias).unwrap();
+            assert!(check(&alias.join("dest"), jail, false).is_ok());
+            assert!(check(&tmp.path().join("jail/dest"), Some(&alias), false).is_ok());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_make_room_for_symlinks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let target = tmp.path().join("target");
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("file"), "kept").unwrap();
+        let dest = tmp.path().join("dest");
+        std::os::unix::fs::symlink(&target, &dest).unwrap();
+
+        assert!(make_room(&dest, OnConflict::Error).is_err());
+        assert!(make_room(&dest, OnConflict::Overwrite).unwrap());
+        assert!(fs::symlink_metadata(&dest).is_err());
+        assert_eq!(fs::read_to_string(target.join("file")).unwrap(), "kept");
+    }
+}
@@ -0,0 +1,206 @@
+use url::Url;
+
+use crate::ArchiveOption;
+
+/// Inspects a resolved source url and returns the archive format it should be
+/// decompressed with, along with the url stripped of the `archive` parameter
+/// and whether the downloaded bytes should be sniffed for a format because
+/// the url left it inconclusive.
+///
+/// `formats` lists the registered format names along with the extensions
+/// each of them claims. The format is taken from the `archive` query
+/// parameter when present (`?archive=false` disables decompression entirely),
+/// and otherwise from the extension of the path, preferring the longest
+/// matching extension so that `foo.tar.gz` isn't mistaken for `gz`.
+pub fn detect_archive(
+    src: &str,
+    formats: &[(&str, &[&str])],
+) -> Result<(String, Option<String>, bool), crate::Error> {
+    let (src, param) = take_param(src, "archive");
+    let option = param
+        .map(|p| {
+            p.parse().map_err(|reason| crate::Error::InvalidUrl {
+                input: src.clone(),
+                kind: crate::InvalidUrlKind::InvalidParam {
+                    param: "archive",
+                    reason,
+                },
+            })
+        })
+        .transpose()?;
+    let (format, sniff) = archive_format(&src, option.as_ref(), formats)?;
+    Ok((src, format, sniff))
+}
+
+/// Archive format of src, as [`detect_archive`] finds it, with the `archive`
+/// parameter already taken out of it.
+pub(crate) fn archive_format(
+    src: &str,
+    option: Option<&ArchiveOption>,
+    formats: &[(&str, &[&str])],
+) -> Result<(Option<String>, bool), crate::Error> {
+    let format = match option {
+        Some(ArchiveOption::Disabled) => return Ok((None, false)),
+        Some(ArchiveOption::Format(format)) => Some(
+            lookup(formats, format)
+                .ok_or_else(|| crate::Error::DecompressorNotFound(format.to_string()))?
+                .to_string(),
+        ),
+        None => {
+            let u = Url::parse(src)?;
+            // anything after `//` selects a path inside the source, not the source itself
+            let path = u.path().split("//").next().unwrap_or_default();
+            let name = path.rsplit('/').next().unwrap_or_default();
+            // a glob left as it is names many sources, archives or not
+            match name.contains(['*', '[']) {
+                true => None,
+                false => from_extension(formats, name).map(|f| f.to_string()),
+            }
+        }
+    };
+
+    let sniff = format.is_none();
+    Ok((format, sniff))
+}
+
+pub(crate) fn from_extension<'a>(formats: &[(&'a str, &[&str])], name: &str) -> Option<&'a str> {
+    let name = name.to_ascii_lowercase();
+    formats
+        .iter()
+        .flat_map(|(format, exts)| exts.iter().map(move |ext| (*format, *ext)))
+        .filter(|(_, ext)| {
+            name.strip_suffix(&ext.to_ascii_lowercase())
+                .is_some_and(|stem| stem.len() > 1 && stem.ends_with('.'))
+        })
+        .max_by_key(|(_, ext)| ext.len())
+        .map(|(format, _)| format)
+}
+
+fn lookup<'a>(formats: &[(&'a str, &[&str])], name: &str) -> Option<&'a str> {
+    formats
+        .iter()
+        .find(|(format, exts)| {
+            format.eq_ignore_ascii_case(name) || exts.iter().any(|e| e.eq_ignore_ascii_case(name))
+        })
+        .map(|(format, _)| *format)
+}
+
+/// Removes a gette specific query parameter, leaving every other parameter
+/// byte-for-byte intact since some of them (e.g. SAS tokens) are signed.
+pub(crate) fn take_param(src: &str, name: &str) -> (String, Option<String>) {
+    let (rest, fragment) = match src.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (src, None),
+    };
+
+    let Some((base, query)) = rest.split_once('?') else {
+        return (src.to_string(), None);
+    };
+
+    let mut param = None;
+    let kept: Vec<&str> = query
+        .split('&')
+        .filter(|pair| match pair.split_once('=') {
+            Some((key, value)) if key == name => {
+                param = Some(value.to_string());
+                false
+            }
+            _ => *pair != name,
+        })
+        .collect();
+
+    let mut out = base.to_string();
+    if !kept.is_empty() {
+        out.push('?');
+        out.push_str(&kept.join("&"));
+    }
+    if let Some(fragment) = fragment {
+        out.push('#');
+        out.push_str(fragment);
+    }
+
+    (out, param)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS: &[(&str, &[&str])] = &[
+        ("tar.gz", &["tar.gz", "tgz"]),
+        ("tar.xz", &["tar.xz", "txz"]),
+        ("tar.zst", &["tar.zst", "tzst"]),
+        ("tar.bz2", &["tar.bz2", "tbz2"]),
+        ("tar", &["tar"]),
+        ("zip", &["zip"]),
+        ("gz", &["gz"]),
+    ];
+
+    #[test]
+    fn it_should_detect_archive_formats_from_extensions() {
+        let tests = vec![
+            ("https://host/release-v2.zip", Some("zip")),
+            ("https://host/release-v2.ZIP", Some("zip")),
+            ("https://host/release-v2.tar.gz", Some("tar.gz")),
+            ("https://host/release-v2.tgz", Some("tar.gz")),
+            ("https://host/release-v2.tar.xz", Some("tar.xz")),
+            ("https://host/release-v2.tar.zst", Some("tar.zst")),
+            ("https://host/release-v2.tar.bz2", Some("tar.bz2")),
+            ("https://host/release-v2.tar", Some("tar")),
+            ("https://host/database.sql.gz", Some("gz")),
+            ("https://host/bundle.tar.gz//bin/tool", Some("tar.gz")),
+            ("file:///tmp/release-v2.zip", Some("zip")),
+            ("https://host/release-v2.bin", None),
+            ("https://host/release-v2.7z", None),
+            ("https://host/zip", None),
+            ("https://host/.zip", None),
+            ("https://host/download?id=123", None),
+        ];
+
+        for test in tests {
+            let (src, format, sniff) = detect_archive(test.0, FORMATS).unwrap();
+            assert_eq!(src, test.0);
+            assert_eq!(format.as_deref(), test.1, "{}", test.0);
+            assert_eq!(sniff, test.1.is_none());
+        }
+    }
+
+    #[test]
+    fn it_should_honor_the_archive_param() {
+        let tests = vec![
+            (
+                "https://host/release-v2.zip?archive=false",
+                "https://host/release-v2.zip",
+                None,
+            ),
+            (
+                "https://host/release-v2.zip?archive=tar.gz",
+                "https://host/release-v2.zip",
+                Some("tar.gz"),
+            ),
+            (
+                "https://host/download?id=123&archive=tgz",
+                "https://host/download?id=123",
+                Some("tar.gz"),
+            ),
+            (
+                "https://host/download?archive=zip&sig=a%2Bb%2F",
+                "https://host/download?sig=a%2Bb%2F",
+                Some("zip"),
+            ),
+        ];
+
+        for test in tests {
+            let (src, format, sniff) = detect_archive(test.0, FORMATS).unwrap();
+            assert_eq!(src, test.1);
+            assert_eq!(format.as_deref(), test.2, "{}", test.0);
+            assert!(!sniff);
+        }
+    }
+
+    #[test]
+    fn it_should_reject_unknown_archive_params() {
+        let res = detect_archive("https://host/release-v2.zip?archive=rar", FORMATS);
+        assert!(matches!(res, Err(crate::Error::DecompressorNotFound(f)) if f == "rar"));
+    }
+}
@@ -0,0 +1,115 @@
+pub struct Azure;
+
+impl crate::Detector for Azure {
+    fn name(&self) -> &str {
+        "azure"
+    }
+
+    fn detect(&self, path: &str) -> Result<Option<String>, crate::Error> {
+        if url::Url::parse(path).is_ok() {
+            return Ok(None);
+        }
+
+        if path.contains(".blob.core.windows.net") {
+            return self.detect_http(path);
+        }
+
+        Ok(None)
+    }
+}
+
+impl Azure {
+    fn detect_http(&self, path: &str) -> Result<Option<String>, crate::Error> {
+        // SAS tokens are signed, so the query string is carried over verbatim
+        // rather than being round-tripped through the url parser.
+        let (path, query) = match path.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (path, None),
+        };
+
+        let parts: Vec<&str> = path.split('/').collect();
+        if parts.len() < 2 || parts[1].is_empty() {
+            return Err(crate::Error::InvalidUrl {
+                input: path.to_string(),
+                kind: crate::InvalidUrlKind::MissingContainer,
+            });
+        }
+
+        let host: Vec<&str> = parts[0].split('.').collect();
+        if host.len() != 5 || host[0].is_empty() || host[1..] != ["blob", "core", "windows", "net"]
+        {
+            return Err(crate::Error::InvalidUrl {
+                input: path.to_string(),
+                kind: crate::InvalidUrlKind::UnsupportedHostShape {
+                    store: "azure blob",
+                },
+            });
+        }
+
+        let url_string = format!("https://{}/{}", parts[0], parts[1..].join("/"));
+        let url_parsed = url::Url::parse(url_string.as_str())?;
+
+        match query {
+            Some(query) => Ok(Some(format!("azure+{}?{}", url_parsed, query))),
+            None => Ok(Some(format!("azure+{}", url_parsed))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Detector;
+
+    #[test]
+    fn it_should_decode_all_valid_variants_of_azure_urls() {
+        let d = Azure;
+        let tests = vec![
+            (
+                "myaccount.blob.core.windows.net/container/blob.bin",
+                "azure+https://myaccount.blob.core.windows.net/container/blob.bin",
+            ),
+            (
+                "myaccount.blob.core.windows.net/container/path/blob.bin",
+                "azure+https://myaccount.blob.core.windows.net/container/path/blob.bin",
+            ),
+            (
+                "myaccount.blob.core.windows.net/container/blob.bin?sv=2021-08-06&sr=b&sig=a%2Bb%2Fc%3D",
+                "azure+https://myaccount.blob.core.windows.net/container/blob.bin?sv=2021-08-06&sr=b&sig=a%2Bb%2Fc%3D",
+            ),
+        ];
+
+        for test in tests {
+            let res = d.detect(test.0).unwrap();
+            assert!(res.is_some());
+            assert_eq!(res, Some(test.1.to_string()));
+        }
+    }
+
+    #[test]
+    fn it_should_fail_on_invalid_azure_urls() {
+        let d = Azure;
+        let tests = vec![
+            "myaccount.blob.core.windows.net",
+            "myaccount.blob.core.windows.net/",
+            "myaccount.blob.core.windows.net?sv=2021-08-06",
+            "extra.myaccount.blob.core.windows.net/container/blob.bin",
+            ".blob.core.windows.net/container/blob.bin",
+        ];
+
+        for test in tests {
+            let res = d.detect(test);
+            println!("test: ({}) => {:#?}", test, res);
+            assert!(res.is_err());
+        }
+    }
+
+    #[test]
+    fn it_should_ignore_urls_with_proto() {
+        let d = Azure;
+        let res = d
+            .detect("https://myaccount.blob.core.windows.net/container/blob.bin")
+            .unwrap();
+        assert!(res.is_none());
+    }
+}
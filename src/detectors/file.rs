@@ -1,32 +1,132 @@
-use path_clean::PathClean;
-use std::{
-    env,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
+
+use crate::paths::Base;
+
+/// Detects local paths and turns them into absolute `file://` urls.
+///
+/// By default every input that isn't a url is claimed. In strict mode only
+/// paths that exist on disk match, so detectors registered after this one get
+/// a chance at everything else. Relative paths are taken from the working
+/// directory, or from [`File::base_dir`].
+#[derive(Default, Debug, Clone)]
+pub struct File {
+    strict: bool,
+    base: Base,
+}
+
+impl File {
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            ..Default::default()
+        }
+    }
+
+    /// Take relative paths as relative to dir.
+    pub fn base_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.base.dir = Some(dir.into());
+        self
+    }
+
+    /// Take `~` as the home directory, and paths starting with `~/` as
+    /// paths in it, the way a shell would have.
+    pub fn expand_home(mut self, expand: bool) -> Self {
+        self.base.expand_home = expand;
+        self
+    }
+}
 
-pub struct File;
 impl crate::Detector for File {
+    fn name(&self) -> &str {
+        "file"
+    }
+
     fn detect(&self, path: &str) -> Result<Option<String>, crate::Error> {
         if url::Url::parse(path).is_ok() {
             return Ok(None);
         }
 
-        let path = absolute_path(path)?;
+        // the query is the source's own, not part of the path
+        let (path, query) = match path.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (path, None),
+        };
+        let abs = self.base.absolute(Path::new(path))?;
+        if self.strict && !abs.exists() {
+            return Err(crate::Error::PathNotFound(path.to_string()));
+        }
+        let mut url = url::Url::from_file_path(&abs).map_err(|()| crate::Error::InvalidUrl {
+            input: path.to_string(),
+            kind: crate::InvalidUrlKind::NotALocalPath,
+        })?;
+        url.set_query(query);
 
-        Ok(Some(
-            format!("file://{}", path.to_str().unwrap()).to_string(),
-        ))
+        Ok(Some(url.to_string()))
     }
 }
 
-fn absolute_path<P: AsRef<Path>>(path: P) -> Result<PathBuf, crate::Error> {
-    let path = path.as_ref();
-    let abs = if path.is_absolute() {
-        path.to_path_buf()
-    } else {
-        env::current_dir()?.join(path)
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::Detector;
+
+    #[test]
+    fn it_claims_missing_paths_by_default() {
+        let d = File::default();
+        let res = d.detect("does-not-exist.txt").unwrap();
+        assert!(res.is_some());
+    }
+
+    #[test]
+    fn it_only_claims_existing_paths_when_strict() {
+        let d = File::strict();
+        let res = d.detect("Cargo.toml").unwrap();
+        let p = env::current_dir().unwrap().join("Cargo.toml");
+        assert_eq!(res, Some(format!("file://{}", p.to_str().unwrap())));
+
+        let res = d.detect("does-not-exist.txt");
+        assert!(matches!(res, Err(crate::Error::PathNotFound(_))));
     }
-    .clean();
 
-    Ok(abs)
+    #[test]
+    fn it_takes_relative_paths_from_the_base_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("test.txt"), "test").unwrap();
+        let d = File::strict().base_dir(tmp.path());
+        let res = d.detect("test.txt").unwrap();
+        let p = tmp.path().join("test.txt");
+        assert_eq!(res, Some(format!("file://{}", p.to_str().unwrap())));
+        assert!(d.detect("Cargo.toml").is_err());
+    }
+
+    #[test]
+    fn it_expands_home_when_asked_to() {
+        let Some(home) = crate::paths::home_dir() else {
+            return;
+        };
+        let d = File::default().expand_home(true);
+        let res = d.detect("~/test.txt").unwrap();
+        let p = home.join("test.txt");
+        assert_eq!(res, Some(format!("file://{}", p.to_str().unwrap())));
+
+        let res = File::default().detect("~/test.txt").unwrap();
+        let p = env::current_dir().unwrap().join("~/test.txt");
+        assert_eq!(res, Some(format!("file://{}", p.to_str().unwrap())));
+    }
+
+    #[test]
+    fn it_encodes_what_urls_reserve() {
+        let tmp = tempfile::tempdir().unwrap();
+        let d = File::default().base_dir(tmp.path());
+        let res = d.detect("a #1 100%.txt?archive=false").unwrap().unwrap();
+        let url = url::Url::parse(&res).unwrap();
+        assert_eq!(
+            url.to_file_path().unwrap(),
+            tmp.path().join("a #1 100%.txt")
+        );
+        assert_eq!(url.query(), Some("archive=false"));
+        assert_eq!(url.fragment(), None);
+    }
 }
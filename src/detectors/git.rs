@@ -0,0 +1,138 @@
+use regex::Regex;
+
+/// Detects scp-like git remotes (`git@github.com:org/repo.git`) and rewrites
+/// them into `ssh://` urls handled by the git getter.
+pub struct Git;
+
+impl crate::Detector for Git {
+    fn name(&self) -> &str {
+        "git"
+    }
+
+    fn detect(&self, path: &str) -> Result<Option<String>, crate::Error> {
+        if url::Url::parse(path).is_ok() {
+            return Ok(None);
+        }
+
+        let re = Regex::new(r"^([A-Za-z0-9_.\-]+)@([A-Za-z0-9.\-]+):(.*)$").unwrap();
+        if let Some(caps) = re.captures(path) {
+            return self.detect_ssh(
+                path,
+                caps.get(1).unwrap().as_str(),
+                caps.get(2).unwrap().as_str(),
+                caps.get(3).unwrap().as_str(),
+            );
+        }
+
+        Ok(None)
+    }
+}
+
+impl Git {
+    fn detect_ssh(
+        &self,
+        path: &str,
+        user: &str,
+        host: &str,
+        repo: &str,
+    ) -> Result<Option<String>, crate::Error> {
+        if Regex::new(r"^[0-9]+(:|$)").unwrap().is_match(repo) {
+            return Err(crate::Error::InvalidUrl {
+                input: path.to_string(),
+                kind: crate::InvalidUrlKind::PortInScpUrl,
+            });
+        }
+
+        let repo = repo.trim_start_matches('/');
+        if repo.is_empty() {
+            return Err(crate::Error::InvalidUrl {
+                input: path.to_string(),
+                kind: crate::InvalidUrlKind::MissingRepoPath {
+                    expected: "user@host:path",
+                },
+            });
+        }
+
+        let url_string = format!("ssh://{}@{}/{}", user, host, repo);
+        let url_parsed = url::Url::parse(url_string.as_str())?;
+
+        Ok(Some(format!("git+{}", url_parsed)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Detector;
+
+    #[test]
+    fn it_should_rewrite_scp_like_urls() {
+        let d = Git;
+        let tests = vec![
+            (
+                "git@github.com:org/repo.git",
+                "git+ssh://git@github.com/org/repo.git",
+            ),
+            (
+                "git@github.com:org/repo.git//modules/vpc",
+                "git+ssh://git@github.com/org/repo.git//modules/vpc",
+            ),
+            (
+                "git@github.com:org/repo.git?ref=v1.0.0",
+                "git+ssh://git@github.com/org/repo.git?ref=v1.0.0",
+            ),
+            (
+                "deploy@git.example.com:/srv/repo.git",
+                "git+ssh://deploy@git.example.com/srv/repo.git",
+            ),
+        ];
+
+        for test in tests {
+            let res = d.detect(test.0).unwrap();
+            assert!(res.is_some());
+            assert_eq!(res, Some(test.1.to_string()));
+        }
+    }
+
+    #[test]
+    fn it_should_fail_on_invalid_scp_like_urls() {
+        let d = Git;
+        let tests = vec![
+            (
+                "git@host:2222:org/repo.git",
+                crate::InvalidUrlKind::PortInScpUrl,
+            ),
+            ("git@host:2222", crate::InvalidUrlKind::PortInScpUrl),
+            (
+                "git@host:",
+                crate::InvalidUrlKind::MissingRepoPath {
+                    expected: "user@host:path",
+                },
+            ),
+        ];
+
+        for (test, expected) in tests {
+            match d.detect(test) {
+                Err(crate::Error::InvalidUrl { input, kind }) => {
+                    assert_eq!(input, test);
+                    assert_eq!(kind, expected, "{}", test);
+                }
+                res => panic!("{}: {:?}", test, res),
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_ignore_other_sources() {
+        let d = Git;
+        let tests = vec![
+            "ssh://git@github.com:2222/org/repo.git",
+            "github.com/org/repo",
+            "./some/file.txt",
+        ];
+
+        for test in tests {
+            assert!(d.detect(test).unwrap().is_none(), "{}", test);
+        }
+    }
+}
@@ -1,5 +1,9 @@
 pub struct Github;
 impl crate::Detector for Github {
+    fn name(&self) -> &str {
+        "github"
+    }
+
     fn detect(&self, path: &str) -> Result<Option<String>, crate::Error> {
         if path.strip_prefix("github.com/").is_some() {
             return self.detect_http(path);
@@ -11,13 +15,19 @@ impl crate::Detector for Github {
 
 impl Github {
     fn detect_http(&self, path: &str) -> Result<Option<String>, crate::Error> {
+        let (path, query) = match path.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (path, None),
+        };
+
         let parts: Vec<&str> = path.split('/').collect();
         if parts.len() < 3 {
-            return Err(crate::Error::InvalidUrl(
-                path.to_string(),
-                "github.com urls should have the following format github.com/:username/:repo"
-                    .to_string(),
-            ));
+            return Err(crate::Error::InvalidUrl {
+                input: path.to_string(),
+                kind: crate::InvalidUrlKind::MissingRepoPath {
+                    expected: "github.com/:username/:repo",
+                },
+            });
         }
 
         let url_string = format!("https://{}", parts[..3].join("/"));
@@ -31,6 +41,8 @@ impl Github {
             url_parsed.set_path(format!("{}//{}", url_parsed.path(), parts[3..].join("/")).as_str())
         }
 
+        url_parsed.set_query(query);
+
         Ok(Some(url_parsed.to_string()))
     }
 }
@@ -65,6 +77,40 @@ mod tests {
         )
     }
 
+    #[test]
+    fn it_keeps_the_query_string() {
+        let d = Github;
+        let res = d
+            .detect("github.com/chrismckenzie/gette-rs/src/lib.rs?ref=v0.0.2")
+            .unwrap();
+        assert_eq!(
+            res,
+            Some(
+                "https://github.com/chrismckenzie/gette-rs.git//src/lib.rs?ref=v0.0.2".to_string()
+            ),
+        )
+    }
+
+    #[test]
+    fn it_should_fail_without_a_repo() {
+        let err = Github.detect("github.com/chrismckenzie").unwrap_err();
+        assert!(
+            matches!(
+                err,
+                crate::Error::InvalidUrl {
+                    kind: crate::InvalidUrlKind::MissingRepoPath { .. },
+                    ..
+                }
+            ),
+            "{:?}",
+            err
+        );
+        assert_eq!(
+            err.to_string(),
+            "invalid url: github.com/chrismckenzie, reason: the repository path is missing, urls should have the following format github.com/:username/:repo"
+        );
+    }
+
     #[test]
     fn it_detects_url_with_proto() {
         let d = Github;
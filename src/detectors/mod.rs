@@ -1,7 +1,16 @@
+mod archive;
+mod azure;
 mod file;
+mod git;
 mod github;
+#[cfg(feature = "s3")]
 mod s3;
 
+pub use self::archive::detect_archive;
+pub(crate) use self::archive::{archive_format, from_extension, take_param};
+pub use self::azure::Azure;
 pub use self::file::File;
+pub use self::git::Git;
 pub use self::github::Github;
+#[cfg(feature = "s3")]
 pub use self::s3::S3;
@@ -1,8 +1,17 @@
 pub struct S3;
 
 impl crate::Detector for S3 {
+    fn name(&self) -> &str {
+        "s3"
+    }
+
     fn detect(&self, path: &str) -> Result<Option<String>, crate::Error> {
-        if path.contains("amazonaws.com/") {
+        if url::Url::parse(path).is_ok() {
+            return Ok(None);
+        }
+
+        let host = path.split(['/', '?']).next().unwrap_or_default();
+        if host.ends_with("amazonaws.com") && path.contains("amazonaws.com/") {
             return self.detect_http(path);
         }
 
@@ -14,23 +23,31 @@ impl S3 {
     fn detect_http(&self, path: &str) -> Result<Option<String>, crate::Error> {
         let parts: Vec<&str> = path.split('/').collect();
         if parts.len() < 2 {
-            return Err(crate::Error::InvalidUrl(
-                path.to_string(),
-                "not a valid s3 url".to_string(),
-            ));
+            return Err(crate::Error::InvalidUrl {
+                input: path.to_string(),
+                kind: crate::InvalidUrlKind::UnsupportedHostShape { store: "s3" },
+            });
         }
 
         let host: Vec<&str> = parts[0].split('.').collect();
+        if host.iter().any(|label| label.is_empty()) {
+            return Err(crate::Error::InvalidUrl {
+                input: path.to_string(),
+                kind: crate::InvalidUrlKind::UnsupportedHostShape { store: "s3" },
+            });
+        }
+
         match host.len() {
             3 => self.region_path_style(host[0], parts[1..].to_vec()),
+            4 if host[0] == "s3" => self.new_region_path_style(host[1], parts[1..].to_vec()),
             4 => self.vhost_path_style(host[1], host[0], parts[1..].to_vec()),
             5 if host[1] == "s3" => {
                 self.new_vhost_path_style(host[2], host[0], parts[1..].to_vec())
             }
-            _ => Err(crate::Error::InvalidUrl(
-                path.to_string(),
-                "not a valid s3 url".to_string(),
-            )),
+            _ => Err(crate::Error::InvalidUrl {
+                input: path.to_string(),
+                kind: crate::InvalidUrlKind::UnsupportedHostShape { store: "s3" },
+            }),
         }
     }
 
@@ -44,6 +61,16 @@ impl S3 {
         Ok(Some(format!("s3+{}", url_parsed)))
     }
 
+    fn new_region_path_style(
+        &self,
+        region: &str,
+        parts: Vec<&str>,
+    ) -> Result<Option<String>, crate::Error> {
+        let url_string = format!("https://s3.{}.amazonaws.com/{}", region, parts.join("/"));
+        let url_parsed = url::Url::parse(url_string.as_str())?;
+        Ok(Some(format!("s3+{}", url_parsed)))
+    }
+
     fn vhost_path_style(
         &self,
         region: &str,
@@ -98,6 +125,14 @@ mod tests {
                 "us-east-2.amazonaws.com/test/test.txt",
                 "s3+https://us-east-2.amazonaws.com/test/test.txt",
             ),
+            (
+                "s3.us-east-2.amazonaws.com/test/test.txt",
+                "s3+https://s3.us-east-2.amazonaws.com/test/test.txt",
+            ),
+            (
+                "test.s3.us-east-2.amazonaws.com/test.txt?versionId=abc",
+                "s3+https://s3.us-east-2.amazonaws.com/test/test.txt?versionId=abc",
+            ),
         ];
 
         for test in tests {
@@ -107,18 +142,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_should_ignore_non_s3_hosts() {
+        let d = S3;
+        let tests = vec![
+            "https://test.s3.us-east-2.amazonaws.com/test.txt",
+            "example.com/amazonaws.com/test.txt",
+            "./amazonaws.com/test.txt",
+        ];
+
+        for test in tests {
+            assert!(d.detect(test).unwrap().is_none(), "{}", test);
+        }
+    }
+
     #[test]
     fn it_should_fail_on_invalid_s3_urls() {
         let d = S3;
         let tests = vec![
             "wrong.test.us-east-2.amazonaws.com/test.txt",
             "amazonaws.com/test.txt",
+            "..amazonaws.com/test.txt",
         ];
 
         for test in tests {
             let res = d.detect(test);
-            println!("test: ({}) => {:#?}", test, res);
-            assert!(res.is_err());
+            assert!(
+                matches!(
+                    res,
+                    Err(crate::Error::InvalidUrl {
+                        kind: crate::InvalidUrlKind::UnsupportedHostShape { store: "s3" },
+                        ..
+                    })
+                ),
+                "{}: {:?}",
+                test,
+                res
+            );
         }
     }
 }
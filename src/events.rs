@@ -0,0 +1,50 @@
+//! Callbacks on what a get goes through, for keeping a record of it.
+
+use std::path::Path;
+
+use url::Url;
+
+use crate::{Checksum, Detection, Error, ExtractStats, GetResult};
+
+/// Is told what a get goes through, from detection to its result, set with
+/// [`RequestBuilder::events`](crate::RequestBuilder::events). Called from
+/// the get itself, so that every getter is covered alike. Every method has
+/// a no-op default, implement the ones you care about.
+///
+/// ```
+/// use gette::{EventHandler, GetResult};
+///
+/// struct AuditLog;
+///
+/// impl EventHandler for AuditLog {
+///     fn on_complete(&self, result: &GetResult) {
+///         println!("{} -> {}", result.source_url, result.final_path.display());
+///     }
+/// }
+///
+/// let builder = gette::RequestBuilder::builder().events(std::sync::Arc::new(AuditLog));
+/// ```
+pub trait EventHandler: Send + Sync {
+    /// Called once the source is resolved to a getter and a url.
+    fn on_detect(&self, _detection: &Detection) {}
+    /// Called as the getter starts fetching url, which ends up at dest.
+    /// Gets that are skipped, as well as dry runs, never start.
+    fn on_start(&self, _url: &Url, _dest: &Path) {}
+    /// Called when the attempt, counting from 1, failed with err and the
+    /// getter is about to be tried again, see
+    /// [`RequestBuilder::retry`](crate::RequestBuilder::retry).
+    fn on_retry(&self, _attempt: u32, _err: &Error) {}
+    /// Called for every checksum what was fetched is checked against and
+    /// matches, the checksum of the download and the tree checksum alike.
+    fn on_verified(&self, _checksum: &Checksum) {}
+    /// Called once an archive has been extracted.
+    fn on_extracted(&self, _stats: &ExtractStats) {}
+    /// Called for what the getter left out without failing the get, like
+    /// the special files of a directory it copied, see
+    /// [`GetResult::warnings`](crate::GetResult::warnings).
+    fn on_warning(&self, _warning: &str) {}
+    /// Called once the get is done, skipped or not.
+    fn on_complete(&self, _result: &GetResult) {}
+    /// Called when the get fails.
+    fn on_failed(&self, _err: &Error) {}
+}
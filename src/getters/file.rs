@@ -1,129 +1,1763 @@
-use crate::Error;
+use super::{Hashing, DEFAULT_BUFFER_SIZE};
+use crate::{
+    deadline, decompressors, dest,
+    paths::Base,
+    staging::{copy_file, copy_link, TempDest},
+    Checksum, Error, GetMode, GetOptions, GetReport, InvalidUrlKind,
+};
 
 use async_trait::async_trait;
-use std::path::PathBuf;
-use std::{env, fs, path::Path};
-use url::{Position, Url};
+use percent_encoding::percent_decode_str;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Component, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::UNIX_EPOCH;
+use std::{fs, path::Path};
+use tokio::io::AsyncRead;
+use url::Url;
 
-use path_clean::PathClean;
+/// How the file getter puts a source at dest.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileMode {
+    /// Link dest to the source, so that it follows changes to it.
+    #[default]
+    Symlink,
+    /// Copy the source to dest, so that dest outlives it.
+    Copy,
+    /// Hardlink the files of the source to dest, with real directories in
+    /// between, so that dest outlives it without anything being copied.
+    /// Falls back to copying when dest is on another filesystem, see
+    /// [`GetResult::fallback`](crate::GetResult::fallback).
+    Hardlink,
+}
 
-pub struct File;
+impl FromStr for FileMode {
+    type Err = String;
 
-#[async_trait]
-impl crate::Getter for File {
-    async fn get(&self, dest: &str, source: &str) -> Result<(), crate::Error> {
-        self.get(dest, source)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "symlink" => Ok(FileMode::Symlink),
+            "copy" => Ok(FileMode::Copy),
+            "hardlink" => Ok(FileMode::Hardlink),
+            _ => Err("mode must be one of symlink, copy or hardlink".to_string()),
+        }
     }
 }
 
+/// What the symlinks the file getter makes point to.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkTarget {
+    /// The absolute path of the source.
+    #[default]
+    Absolute,
+    /// The path of the source relative to the directory dest is in, so that
+    /// the link survives moving both of them together. Falls back to the
+    /// absolute path when they are on different drives.
+    Relative,
+}
+
+impl FromStr for SymlinkTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "absolute" => Ok(SymlinkTarget::Absolute),
+            "relative" => Ok(SymlinkTarget::Relative),
+            _ => Err("symlink must be one of absolute or relative".to_string()),
+        }
+    }
+}
+
+/// What the file getter does with the symlinks in the directories it copies
+/// or hardlinks.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyLinks {
+    /// Copy them as symlinks, pointing where they did, `?links=preserve`.
+    #[default]
+    Preserve,
+    /// Copy what symlinks pointing inside the source point to in their
+    /// place, keeping those pointing out of it as symlinks,
+    /// `?links=follow`.
+    Follow,
+    /// Copy what any symlink points to in its place, wherever that is,
+    /// `?links=follow_all`. Only for sources trusted not to link to what
+    /// shouldn't end up in dest.
+    FollowAll,
+}
+
+impl FromStr for CopyLinks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preserve" => Ok(CopyLinks::Preserve),
+            "follow" => Ok(CopyLinks::Follow),
+            "follow_all" => Ok(CopyLinks::FollowAll),
+            _ => Err("links must be one of preserve, follow or follow_all".to_string()),
+        }
+    }
+}
+
+/// Which of the files a glob source, like `file:///builds/app-*.tar.gz`,
+/// matches the file getter fetches. Matches are ordered by path, so that
+/// ties always go the same way.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlobSelect {
+    /// The one modified last, `?glob=newest`.
+    #[default]
+    Newest,
+    /// The one with the highest version in its path, numbers compared as
+    /// numbers so that `app-1.10` comes after `app-1.9`, `?glob=version`.
+    Version,
+    /// The only one, failing with [`Error::GlobMatches`] when there are
+    /// none or more, `?glob=one`.
+    One,
+    /// All of them, placed in dest as a directory along with the
+    /// directories they're in below the start of the pattern, `?glob=all`.
+    /// Archives among them are placed as they are rather than extracted.
+    All,
+}
+
+impl FromStr for GlobSelect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "newest" => Ok(GlobSelect::Newest),
+            "version" => Ok(GlobSelect::Version),
+            "one" => Ok(GlobSelect::One),
+            "all" => Ok(GlobSelect::All),
+            _ => Err("glob must be one of newest, version, one or all".to_string()),
+        }
+    }
+}
+
+/// Gets sources from the local filesystem, symlinking them to dest unless
+/// `?mode=copy` or [`File::with_mode`] says otherwise. Sources whose path
+/// holds `*`, `?` or `[` and that aren't there as they are are globs, see
+/// [`GlobSelect`].
+#[derive(Default, Debug, Clone)]
+pub struct File {
+    pub(crate) mode: Option<FileMode>,
+    pub(crate) symlink: Option<SymlinkTarget>,
+    pub(crate) links: Option<CopyLinks>,
+    pub(crate) glob: Option<GlobSelect>,
+    pub(crate) require_symlinks: bool,
+    pub(crate) allow_special: bool,
+    pub(crate) base: Base,
+}
+
 impl File {
-    #[cfg(target_family = "unix")]
-    fn get(&self, dest: &str, source: &str) -> Result<(), crate::Error> {
-        let u = Url::parse(source)?;
+    /// A file getter that always uses this mode, whatever `?mode=` says.
+    pub fn with_mode(mode: FileMode) -> Self {
+        Self {
+            mode: Some(mode),
+            ..Default::default()
+        }
+    }
 
-        // validate source
-        let source = absolute_path(Path::new(&u[Position::BeforeUsername..]))?;
-        let dest = absolute_path(Path::new(dest))?;
+    /// Make symlinks pointing to this kind of path, whatever `?symlink=`
+    /// says.
+    pub fn symlink_target(mut self, target: SymlinkTarget) -> Self {
+        self.symlink = Some(target);
+        self
+    }
 
-        let source = source.as_path();
-        let dest = dest.as_path();
+    /// Do this with the symlinks in the directories it copies, whatever
+    /// `?links=` says.
+    pub fn copy_links(mut self, links: CopyLinks) -> Self {
+        self.links = Some(links);
+        self
+    }
+
+    /// Fetch this one of the files glob sources match, whatever `?glob=`
+    /// says.
+    pub fn glob_select(mut self, select: GlobSelect) -> Self {
+        self.glob = Some(select);
+        self
+    }
+
+    /// Symlink sources that are special files, like FIFOs, sockets and
+    /// devices, whatever the mode, instead of failing with
+    /// [`Error::UnsupportedSourceType`]. They're never copied or hardlinked,
+    /// since copying a FIFO waits for something to write to it.
+    pub fn allow_special_files(mut self, allow: bool) -> Self {
+        self.allow_special = allow;
+        self
+    }
 
-        if !source.exists() {
-            return Err(Error::SourceNotFound);
+    /// Fail when creating a symlink isn't permitted, as on Windows without
+    /// Developer Mode, instead of copying files and making junctions to
+    /// directories in their place.
+    pub fn require_symlinks(mut self, require: bool) -> Self {
+        self.require_symlinks = require;
+        self
+    }
+
+    /// Take relative paths, those of `file://./` urls and relative dests,
+    /// as relative to dir rather than to the working directory.
+    pub fn base_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.base.dir = Some(dir.into());
+        self
+    }
+
+    fn absolute(&self, path: &Path) -> Result<PathBuf, Error> {
+        self.base.absolute(path)
+    }
+
+    /// What url stands for on the filesystem, with globs expanded. Only
+    /// sources being fetched are expanded, as a glob may walk whole trees.
+    fn source(&self, url: &Url, options: &GetOptions) -> Result<Source, Error> {
+        let path = self.absolute(&source_path(url)?)?;
+        if !is_pattern(&path) {
+            return Ok(Source::Path(path));
         }
 
-        if dest.exists() {
-            let meta = fs::symlink_metadata(dest).map_err(Error::Io)?.file_type();
-            if !meta.is_symlink() {
-                return Err(Error::DestinationExists);
+        let pattern = path.to_string_lossy().into_owned();
+        let paths = glob::glob_with(
+            &pattern,
+            glob::MatchOptions {
+                case_sensitive: true,
+                require_literal_separator: true,
+                require_literal_leading_dot: true,
+            },
+        )
+        .map_err(|e| Error::InvalidUrl {
+            input: url.to_string(),
+            kind: InvalidUrlKind::InvalidGlob(e.to_string()),
+        })?;
+        // entries that can't be read are left out, like a shell does
+        let mut matches: Vec<_> = paths.filter_map(Result::ok).collect();
+        matches.sort();
+
+        let select = self.glob.or(options.glob).unwrap_or_default();
+        let picked = match select {
+            _ if matches.is_empty() && select != GlobSelect::One => {
+                return Err(Error::source_not_found(format!(
+                    "nothing matches {}",
+                    pattern
+                )))
+            }
+            GlobSelect::All => {
+                // what's in a directory that matched is placed along with it,
+                // and comes right after it by path
+                let mut outermost: Vec<PathBuf> = Vec::new();
+                for path in matches {
+                    if !outermost.last().is_some_and(|dir| path.starts_with(dir)) {
+                        outermost.push(path);
+                    }
+                }
+                return Ok(Source::Matches {
+                    pattern,
+                    matches: outermost,
+                });
+            }
+            GlobSelect::One if matches.len() == 1 => matches.pop(),
+            GlobSelect::One => {
+                return Err(Error::GlobMatches {
+                    pattern,
+                    matched: matches.len(),
+                })
             }
+            GlobSelect::Newest => {
+                let modified = |p: &PathBuf| fs::metadata(p).and_then(|m| m.modified()).ok();
+                // the last by path of those modified at the same time
+                matches.into_iter().max_by_key(modified)
+            }
+            GlobSelect::Version => matches
+                .into_iter()
+                .max_by(|a, b| version_cmp(&a.to_string_lossy(), &b.to_string_lossy())),
+        };
+
+        Ok(Source::Path(picked.unwrap_or_default()))
+    }
+}
+
+/// A source on the filesystem, see [`File::source`].
+enum Source {
+    Path(PathBuf),
+    /// What a glob matched, when all of them are fetched.
+    Matches {
+        pattern: String,
+        matches: Vec<PathBuf>,
+    },
+}
+
+fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// Whether path is a glob to be expanded, rather than a file that happens
+/// to be named like one.
+fn is_pattern(path: &Path) -> bool {
+    is_glob(path) && fs::symlink_metadata(path).is_err()
+}
+
+/// Directory the matches of a glob are all in, where the pattern starts.
+fn glob_root(pattern: &Path) -> PathBuf {
+    pattern
+        .ancestors()
+        .skip(1)
+        .find(|dir| !is_glob(dir))
+        .unwrap_or(pattern)
+        .to_path_buf()
+}
+
+/// Compares a and b the way versions are, runs of digits as the numbers
+/// they are and everything else as it is.
+fn version_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    fn runs(s: &str) -> impl Iterator<Item = (bool, &str)> {
+        let mut rest = s;
+        std::iter::from_fn(move || {
+            let digits = rest.starts_with(|c: char| c.is_ascii_digit());
+            let end = rest
+                .find(|c: char| c.is_ascii_digit() != digits)
+                .unwrap_or(rest.len());
+            let (run, tail) = rest.split_at(end);
+            rest = tail;
+            (!run.is_empty()).then_some((digits, run))
+        })
+    }
 
-            fs::remove_file(dest).map_err(Error::Io)?
+    let (mut a_runs, mut b_runs) = (runs(a), runs(b));
+    loop {
+        let ordering = match (a_runs.next(), b_runs.next()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return std::cmp::Ordering::Less,
+            (Some(_), None) => return std::cmp::Ordering::Greater,
+            (Some((true, a)), Some((true, b))) => {
+                let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+                a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+            }
+            (Some((_, a)), Some((_, b))) => a.cmp(b),
+        };
+        if ordering.is_ne() {
+            return ordering;
         }
+    }
+}
 
-        fs::create_dir_all(dest.parent().unwrap()).map_err(|_| Error::DestinationNotCreated)?;
+#[async_trait]
+impl crate::Getter for File {
+    async fn get(&self, dest: &Path, url: &Url, options: &GetOptions) -> Result<(), Error> {
+        self.get_with_report(dest, url, options).await.map(|_| ())
+    }
 
-        std::os::unix::fs::symlink(source, dest).map_err(Error::Io)?;
+    /// Puts the source at dest off the runtime, a copy stopping at the next
+    /// file once the transfer times out.
+    async fn get_with_report(
+        &self,
+        dest: &Path,
+        url: &Url,
+        options: &GetOptions,
+    ) -> Result<GetReport, Error> {
+        let (file, dest, url, options) = (
+            self.clone(),
+            dest.to_path_buf(),
+            url.clone(),
+            options.clone(),
+        );
+        deadline::blocking(move || file.fetch(&dest, &url, &options)).await
+    }
 
-        Ok(())
+    /// Globs are told by what's fetched of them, without expanding them.
+    async fn mode(&self, url: &Url, options: &GetOptions) -> Result<Option<GetMode>, Error> {
+        // a missing source is left for the get to report
+        let Ok(source) = source_path(url).and_then(|path| self.absolute(&path)) else {
+            return Ok(None);
+        };
+        if is_pattern(&source) {
+            let select = self.glob.or(options.glob).unwrap_or_default();
+            return Ok((select == GlobSelect::All).then_some(GetMode::Dir));
+        }
+        Ok(fs::metadata(source).ok().map(|m| match m.is_dir() {
+            true => GetMode::Dir,
+            false => GetMode::File,
+        }))
     }
 
-    #[cfg(target_family = "windows")]
-    fn get(&self, dest: &str, source: &str) -> Result<(), crate::Error> {
-        let u = Url::parse(source)?;
+    /// Opens a file source where it is, directories can only be placed at
+    /// dest.
+    async fn open(
+        &self,
+        url: &Url,
+        options: &GetOptions,
+    ) -> Result<Option<Pin<Box<dyn AsyncRead + Send>>>, Error> {
+        let Source::Path(source) = self.source(url, options)? else {
+            return Ok(None);
+        };
+        // opening a FIFO waits for something to write to it, so specials
+        // are told apart before
+        let meta = fs::metadata(&source).map_err(Error::source_io)?;
+        if let Some(kind) = special_kind(meta.file_type()) {
+            return match self.allow_special {
+                true => Ok(None),
+                false => Err(Error::UnsupportedSourceType { kind }),
+            };
+        }
+        let file = tokio::fs::File::open(source)
+            .await
+            .map_err(Error::source_io)?;
+        if file.metadata().await?.is_dir() {
+            return Ok(None);
+        }
+        Ok(Some(Box::pin(file)))
+    }
 
-        // validate source
-        let source = absolute_path(Path::new(&u[Position::BeforeUsername..]))?;
-        let dest = absolute_path(Path::new(dest))?;
+    /// The modification time and size of a file source. Directories have
+    /// none, since what's changed deep inside of them doesn't show on them.
+    async fn fingerprint(&self, url: &Url, options: &GetOptions) -> Result<Option<String>, Error> {
+        let Source::Path(source) = self.source(url, options)? else {
+            return Ok(None);
+        };
+        let meta = fs::metadata(source).map_err(Error::source_io)?;
+        if !meta.is_file() {
+            return Ok(None);
+        }
+        let modified = meta
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(Some(format!(
+            "mtime:{}:size:{}",
+            modified.as_nanos(),
+            meta.len()
+        )))
+    }
 
-        let source = source.as_path();
+    /// Size of a file source, directories and globs are left uncounted.
+    async fn size(&self, url: &Url, _options: &GetOptions) -> Result<Option<u64>, Error> {
+        let source = self.absolute(&source_path(url)?)?;
+        if is_pattern(&source) {
+            return Ok(None);
+        }
+        let meta = fs::metadata(source).map_err(Error::source_io)?;
+        Ok(meta.is_file().then_some(meta.len()))
+    }
+
+    /// Local sources never go over the network.
+    fn network_url(&self, _url: &Url) -> Option<Url> {
+        None
+    }
+}
+
+impl File {
+    fn fetch(&self, dest: &Path, url: &Url, options: &GetOptions) -> Result<GetReport, Error> {
+        let mode = self.mode.or(options.file_mode).unwrap_or_default();
+        let target = self.symlink.or(options.symlink_target).unwrap_or_default();
+        let links = self.links.or(options.copy_links).unwrap_or_default();
+        let exclude = &options.exclude;
+        if !exclude.is_empty() && mode == FileMode::Symlink {
+            return Err(Error::InvalidUrl {
+                input: url.to_string(),
+                kind: InvalidUrlKind::InvalidParam {
+                    param: "exclude",
+                    reason: "exclude doesn't apply to symlinked sources".to_string(),
+                },
+            });
+        }
+
+        // validate source, deep paths only being usable on Windows in the
+        // extended form
+        let source = self.source(url, options)?;
+        let dest = dest::extended(&self.absolute(dest)?);
         let dest = dest.as_path();
 
-        if !source.exists() {
-            return Err(Error::SourceNotFound);
+        // the builder clears dest according to its on_conflict policy
+        if fs::symlink_metadata(dest).is_ok() {
+            return Err(Error::DestinationExists(
+                dest::plain(dest).display().to_string(),
+            ));
+        }
+
+        let mut report = GetReport::default();
+        let tree = Tree {
+            exclude,
+            links,
+            hashed: None,
+        };
+        let fallback = match source {
+            Source::Path(source) => {
+                let source = dest::extended(&source);
+                let tree = Tree {
+                    hashed: Some(options),
+                    ..tree
+                };
+                self.fetch_one(&source, dest, mode, target, &tree, &mut report)?
+            }
+            Source::Matches { pattern, matches } => {
+                let root = glob_root(Path::new(&pattern));
+                let mut fallback = None;
+                for source in &matches {
+                    let to = dest.join(source.strip_prefix(&root).unwrap_or(source));
+                    let source = dest::extended(source);
+                    let meta = fs::metadata(&source).map_err(Error::source_io)?;
+                    let placed = match special_kind(meta.file_type()) {
+                        Some(kind) if !self.allow_special => {
+                            skip_special(&source, kind, &mut report.warnings);
+                            continue;
+                        }
+                        _ => self.fetch_one(&source, &to, mode, target, &tree, &mut report)?,
+                    };
+                    fallback = fallback.or(placed);
+                }
+                fallback
+            }
+        };
+
+        report.fallback = fallback.map(|f| f.to_string());
+        Ok(report)
+    }
+
+    /// Puts the one source at dest, both absolute, making the directories
+    /// dest is in.
+    fn fetch_one(
+        &self,
+        source: &Path,
+        dest: &Path,
+        mode: FileMode,
+        target: SymlinkTarget,
+        tree: &Tree,
+        report: &mut GetReport,
+    ) -> Result<Option<&'static str>, Error> {
+        deadline::checkpoint()?;
+        let meta = fs::metadata(source).map_err(Error::source_io)?;
+        let special = special_kind(meta.file_type());
+        if let (Some(kind), false) = (special, self.allow_special) {
+            return Err(Error::UnsupportedSourceType { kind });
+        }
+
+        // a root or a bare name has no directory to create
+        if let Some(parent) = dest.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(Error::DestinationNotCreated)?;
         }
 
-        if dest.exists() {
-            let meta = fs::symlink_metadata(dest).map_err(Error::Io)?.file_type();
-            if !meta.is_symlink() {
-                return Err(Error::DestinationExists);
+        let placed = match special {
+            Some(_) if mode != FileMode::Symlink => self
+                .symlink(source, dest, target)
+                .map(|fallback| fallback.or(Some(SPECIAL_FALLBACK))),
+            _ => self.place(source, dest, mode, target, tree, report),
+        };
+        placed.map_err(|e| match e {
+            // dest's directory was just made, so what's gone by now is
+            // the source
+            Error::Io(e) => Error::source_io(e),
+            e => e,
+        })
+    }
+
+    /// Puts source at dest the way mode says, returning what was done
+    /// instead when that couldn't be. What was left out of a directory is
+    /// added to the report's warnings, and the checksum of a file copied as
+    /// the tree says to hash it to its checksum.
+    fn place(
+        &self,
+        source: &Path,
+        dest: &Path,
+        mode: FileMode,
+        target: SymlinkTarget,
+        tree: &Tree,
+        report: &mut GetReport,
+    ) -> Result<Option<&'static str>, Error> {
+        let warnings = &mut report.warnings;
+        Ok(match mode {
+            FileMode::Symlink => self.symlink(source, dest, target)?,
+            FileMode::Copy if source.is_dir() => {
+                copy_dir(source, dest, tree, false, warnings).map(|_| None)?
+            }
+            FileMode::Copy => {
+                report.checksum = copy(source, dest, tree.hashed)?;
+                None
+            }
+            FileMode::Hardlink if source.is_dir() => {
+                let linked = copy_dir(source, dest, tree, true, warnings)?;
+                (!linked).then_some(CROSS_DEVICE_FALLBACK)
+            }
+            FileMode::Hardlink => match fs::hard_link(source, dest) {
+                Ok(()) => None,
+                Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+                    report.checksum = copy(source, dest, tree.hashed)?;
+                    Some(CROSS_DEVICE_FALLBACK)
+                }
+                Err(e) => return Err(e.into()),
+            },
+        })
+    }
+
+    /// Links dest to source, both absolute, pointing to the kind of path
+    /// asked for. Returns what was done instead when symlinks can't be made.
+    fn symlink(
+        &self,
+        source: &Path,
+        dest: &Path,
+        target: SymlinkTarget,
+    ) -> Result<Option<&'static str>, Error> {
+        let relative = match (target, dest.parent()) {
+            (SymlinkTarget::Relative, Some(dir)) => match relative_to(source, dir) {
+                Some(relative) => Some(relative),
+                None => {
+                    tracing::warn!(
+                        source = %source.display(),
+                        dest = %dest.display(),
+                        "source and dest are on different drives, linking to the absolute path",
+                    );
+                    None
+                }
+            },
+            _ => None,
+        };
+        let link = relative.as_deref().unwrap_or(source);
+
+        #[cfg(target_family = "unix")]
+        std::os::unix::fs::symlink(link, dest)?;
+        #[cfg(target_family = "windows")]
+        {
+            let res = match source.is_dir() {
+                true => std::os::windows::fs::symlink_dir(link, dest),
+                false => std::os::windows::fs::symlink_file(link, dest),
+            };
+            match res {
+                Ok(()) => {}
+                Err(e) if is_privilege_error(&e) && !self.require_symlinks => {
+                    tracing::warn!(
+                        source = %source.display(),
+                        dest = %dest.display(),
+                        "creating symlinks isn't permitted without Developer Mode, falling back to a {}",
+                        if source.is_dir() { "junction" } else { "copy" },
+                    );
+                    return match source.is_dir() {
+                        true => junction(source, dest).map(|()| Some(JUNCTION_FALLBACK)),
+                        false => copy(source, dest, None).map(|_| Some(COPY_FALLBACK)),
+                    };
+                }
+                Err(e) if is_privilege_error(&e) => {
+                    return Err(Error::SymlinkNotPermitted(dest.display().to_string()));
+                }
+                Err(e) => return Err(e.into()),
             }
-            fs::remove_file(dest).map_err(crate::Error::Io)?
         }
 
-        fs::create_dir_all(dest.parent().unwrap()).map_err(|_| Error::DestinationNotCreated)?;
+        Ok(None)
+    }
+}
+
+const CROSS_DEVICE_FALLBACK: &str = "copied, dest is on another filesystem than the source";
+#[cfg(target_family = "windows")]
+const COPY_FALLBACK: &str = "copied, creating symlinks isn't permitted";
+#[cfg(target_family = "windows")]
+const JUNCTION_FALLBACK: &str = "made a junction, creating symlinks isn't permitted";
+const SPECIAL_FALLBACK: &str = "symlinked, the source is a special file";
+
+/// Whether creating a symlink failed for lack of the privilege to, which
+/// Windows requires unless Developer Mode is on.
+#[cfg(target_family = "windows")]
+fn is_privilege_error(e: &std::io::Error) -> bool {
+    const ERROR_PRIVILEGE_NOT_HELD: i32 = 1314;
+    e.raw_os_error() == Some(ERROR_PRIVILEGE_NOT_HELD)
+}
+
+/// Makes dest a junction to the directory source, which unlike a symlink
+/// needs no privilege to create.
+#[cfg(target_family = "windows")]
+fn junction(source: &Path, dest: &Path) -> Result<(), Error> {
+    let out = std::process::Command::new("cmd")
+        .args(["/C", "mklink", "/J"])
+        .arg(dest)
+        .arg(source)
+        .output()?;
+    if !out.status.success() {
+        let message = String::from_utf8_lossy(&out.stderr).trim().to_string();
+        return Err(Error::Io(std::io::Error::other(message)));
+    }
+
+    Ok(())
+}
+
+/// Path to target from the directory dir, both absolute, or `None` when
+/// they don't share a root, like paths on two drives on Windows.
+fn relative_to(target: &Path, dir: &Path) -> Option<PathBuf> {
+    let target: Vec<_> = target.components().collect();
+    let dir: Vec<_> = dir.components().collect();
+    if target.first() != dir.first() {
+        return None;
+    }
+
+    let common = target.iter().zip(&dir).take_while(|(t, d)| t == d).count();
+    let mut relative: PathBuf = dir[common..].iter().map(|_| Component::ParentDir).collect();
+    relative.extend(&target[common..]);
+    Some(relative)
+}
 
-        if source.is_dir() {
-            std::os::windows::fs::symlink_dir(source, dest)?;
-        } else {
-            std::os::windows::fs::symlink_file(source, dest)?;
+/// Copies source to dest through a file next to it, so that a failed copy
+/// never leaves a truncated dest behind. Returns the checksum of the copy
+/// when the options it's hashed by ask for one.
+fn copy(
+    source: &Path,
+    dest: &Path,
+    hashed: Option<&GetOptions>,
+) -> Result<Option<Checksum>, Error> {
+    let partial = TempDest::new(dest);
+    let checksum = match hashed.and_then(|o| Some((o, Hashing::new(o)?))) {
+        Some((options, hashing)) => Some(copy_hashed(source, partial.path(), options, hashing)?),
+        None => {
+            copy_file(source, partial.path())?;
+            None
         }
+    };
+    partial.promote(dest)?;
+    Ok(checksum)
+}
+
+/// Copies a file like [`copy_file`] does, but through a buffer of the size
+/// the options say that the file is hashed from as well, instead of
+/// leaving the copy to the kernel. It stops between chunks once the
+/// transfer times out.
+fn copy_hashed(
+    source: &Path,
+    dest: &Path,
+    options: &GetOptions,
+    mut hashing: Hashing,
+) -> Result<Checksum, Error> {
+    let mut from = fs::File::open(source)?;
+    let mut to = fs::File::create(dest)?;
+    let mut buf = vec![0; options.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE)];
+    loop {
+        deadline::checkpoint()?;
+        let n = match from.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        to.write_all(&buf[..n])?;
+        hashing.update(&buf[..n]);
+    }
+
+    let meta = from.metadata()?;
+    to.set_permissions(meta.permissions())?;
+    to.set_modified(meta.modified()?)?;
+    hashing.finish()
+}
+
+/// What the source is copied with, besides where from and to.
+struct Tree<'a> {
+    /// Globs of the paths left out.
+    exclude: &'a [String],
+    links: CopyLinks,
+    /// Options a file source is hashed by as it's copied, see
+    /// [`GetOptions::hash`]. Never given for the matches of a glob, which
+    /// are fetched into a directory.
+    hashed: Option<&'a GetOptions>,
+}
+
+/// Copies the directory at source to dest the way [`copy`] copies a file,
+/// with the symlinks inside of it copied the way the tree says, and leaving
+/// out entries whose name, or path relative to source, matches one of its
+/// exclude globs. Files are hardlinked instead when asked to and dest is on
+/// the same filesystem, returning whether they were. Special files, like
+/// FIFOs, are left out too, with a warning for each of them.
+fn copy_dir(
+    source: &Path,
+    dest: &Path,
+    tree: &Tree,
+    link: bool,
+    warnings: &mut Vec<String>,
+) -> Result<bool, Error> {
+    let partial = TempDest::new(dest);
+    let linked = copy_tree(source, partial.path(), tree, link, warnings)?;
+    partial.promote(dest)?;
+    Ok(linked)
+}
 
-        Ok(())
+/// Most symlinks to directories followed one inside the other, the limit
+/// Linux puts on nested links.
+const MAX_LINK_DEPTH: usize = 40;
+
+/// A directory being copied.
+struct CopiedDir {
+    from: PathBuf,
+    to: PathBuf,
+    /// Index of the directory it's in, none for the root.
+    parent: Option<usize>,
+    /// What tells it apart from the directories it's in, only looked up
+    /// when symlinks are followed.
+    id: Option<DirId>,
+    /// How many symlinks were followed to get to it.
+    depth: usize,
+}
+
+#[cfg(target_family = "unix")]
+type DirId = (u64, u64);
+#[cfg(not(target_family = "unix"))]
+type DirId = PathBuf;
+
+/// The device and inode of the directory at path, or where it really is
+/// where there are none, following symlinks.
+fn dir_id(path: &Path) -> std::io::Result<DirId> {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let meta = fs::metadata(path)?;
+        Ok((meta.dev(), meta.ino()))
     }
+    #[cfg(not(target_family = "unix"))]
+    fs::canonicalize(path)
 }
 
-fn absolute_path<P: AsRef<Path>>(path: P) -> Result<PathBuf, crate::Error> {
-    let path = path.as_ref();
-    let abs = if path.is_absolute() {
-        path.to_path_buf()
-    } else {
-        env::current_dir()?.join(path)
+fn copy_tree(
+    source: &Path,
+    out: &Path,
+    tree: &Tree,
+    link: bool,
+    warnings: &mut Vec<String>,
+) -> Result<bool, Error> {
+    let follow = tree.links != CopyLinks::Preserve;
+    let mut dirs = vec![CopiedDir {
+        from: source.to_path_buf(),
+        to: out.to_path_buf(),
+        parent: None,
+        id: follow.then(|| dir_id(source)).transpose()?,
+        depth: 0,
+    }];
+    let mut files = Vec::new();
+    // paths under source are extended as they get deeper, source may not be
+    let root = dest::plain(source);
+    // where links have to point for them to be followed
+    let within = match tree.links {
+        CopyLinks::Follow => Some(fs::canonicalize(source)?),
+        _ => None,
+    };
+
+    let mut i = 0;
+    while let Some(dir) = dirs.get(i) {
+        let (from, to, depth) = (dir.from.clone(), dir.to.clone(), dir.depth);
+        let parent = i;
+        i += 1;
+        fs::create_dir(&to)?;
+        for entry in fs::read_dir(&from)? {
+            deadline::checkpoint()?;
+            let entry = entry?;
+            let from = entry.path();
+            let plain = dest::plain(&from);
+            if is_excluded(tree.exclude, plain.strip_prefix(&root).unwrap_or(&plain)) {
+                continue;
+            }
+
+            let from = dest::extended(&from);
+            let to = dest::extended(&to.join(entry.file_name()));
+            let kind = entry.file_type()?;
+            if let Some(special) = special_kind(kind) {
+                skip_special(&from, special, warnings);
+            } else if kind.is_symlink() {
+                match follow_link(&from, &dirs, parent, within.as_deref(), tree.links)? {
+                    Followed::Dir(id) => dirs.push(CopiedDir {
+                        from,
+                        to,
+                        parent: Some(parent),
+                        id: Some(id),
+                        depth: depth + 1,
+                    }),
+                    Followed::File(real) => match fs::metadata(&real)
+                        .ok()
+                        .and_then(|meta| special_kind(meta.file_type()))
+                    {
+                        Some(special) => skip_special(&from, special, warnings),
+                        None => files.push((real, to)),
+                    },
+                    Followed::Not => copy_link(&from, &to)?,
+                }
+            } else if kind.is_dir() {
+                let id = follow.then(|| dir_id(&from)).transpose()?;
+                dirs.push(CopiedDir {
+                    from,
+                    to,
+                    parent: Some(parent),
+                    id,
+                    depth,
+                });
+            } else {
+                files.push((from, to));
+            }
+        }
+    }
+
+    // the first hardlink tells whether dest is on the same filesystem
+    let (mut files, mut link) = (&files[..], link);
+    if let (true, Some(((from, to), rest))) = (link, files.split_first()) {
+        match fs::hard_link(from, to) {
+            Ok(()) => files = rest,
+            Err(e) if e.kind() == ErrorKind::CrossesDevices => link = false,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    copy_files(files, link)?;
+
+    // directories are finished once filled, deepest first, so that neither
+    // their permissions nor their mtimes get in the way
+    for dir in dirs.iter().rev() {
+        let meta = fs::metadata(&dir.from)?;
+        fs::set_permissions(&dir.to, meta.permissions())?;
+        #[cfg(target_family = "unix")]
+        fs::File::open(&dir.to)?.set_modified(meta.modified()?)?;
     }
-    .clean();
 
-    Ok(abs)
+    Ok(link)
+}
+
+/// What following a symlink leads to.
+enum Followed {
+    Dir(DirId),
+    /// A file, where it really is.
+    File(PathBuf),
+    /// Nothing, the symlink is copied as it is.
+    Not,
+}
+
+/// Follows the symlink at path in the directory at index parent of dirs,
+/// when links says to and it points within where it has to. Links that
+/// dangle are copied as they are, and those leading back into a directory
+/// they're in, or nested too deep, fail the copy.
+fn follow_link(
+    path: &Path,
+    dirs: &[CopiedDir],
+    parent: usize,
+    within: Option<&Path>,
+    links: CopyLinks,
+) -> Result<Followed, Error> {
+    if links == CopyLinks::Preserve {
+        return Ok(Followed::Not);
+    }
+    let Ok(real) = fs::canonicalize(path) else {
+        return Ok(Followed::Not);
+    };
+    if within.is_some_and(|within| !real.starts_with(within)) {
+        return Ok(Followed::Not);
+    }
+    if !real.is_dir() {
+        return Ok(Followed::File(real));
+    }
+
+    let looped = || Error::SymlinkLoop {
+        path: dest::plain(path).display().to_string(),
+    };
+    if dirs[parent].depth >= MAX_LINK_DEPTH {
+        return Err(looped());
+    }
+    let id = dir_id(&real)?;
+    let mut ancestor = Some(parent);
+    while let Some(i) = ancestor {
+        if dirs[i].id.as_ref() == Some(&id) {
+            return Err(looped());
+        }
+        ancestor = dirs[i].parent;
+    }
+    Ok(Followed::Dir(id))
+}
+
+/// What kind of special file this is, if it's one that can't be copied or
+/// hardlinked like a regular file: reading a FIFO waits for something to
+/// write to it, and sockets and devices have no contents to copy.
+fn special_kind(kind: fs::FileType) -> Option<&'static str> {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if kind.is_fifo() {
+            return Some("fifo");
+        }
+        if kind.is_socket() {
+            return Some("socket");
+        }
+        if kind.is_block_device() {
+            return Some("block device");
+        }
+        if kind.is_char_device() {
+            return Some("character device");
+        }
+    }
+    #[cfg(not(target_family = "unix"))]
+    let _ = kind;
+
+    None
+}
+
+/// Leaves the special file at path out of a directory copy, rather than
+/// giving up on the rest of the directory.
+fn skip_special(path: &Path, kind: &str, warnings: &mut Vec<String>) {
+    let warning = format!("skipped {}, a {}", dest::plain(path).display(), kind);
+    tracing::warn!("{}", warning);
+    warnings.push(warning);
+}
+
+fn is_excluded(exclude: &[String], path: &Path) -> bool {
+    let components: Vec<_> = path.iter().map(|c| c.to_string_lossy()).collect();
+    let name = components.last().cloned().unwrap_or_default();
+
+    exclude.iter().any(|glob| match glob.contains('/') {
+        true => {
+            let pattern: Vec<_> = glob.split('/').filter(|p| !p.is_empty()).collect();
+            pattern.len() == components.len()
+                && pattern
+                    .iter()
+                    .zip(&components)
+                    .all(|(p, c)| decompressors::glob_match(p.as_bytes(), c.as_bytes()))
+        }
+        false => decompressors::glob_match(glob.as_bytes(), name.as_bytes()),
+    })
+}
+
+/// Most threads a directory is copied with.
+const COPY_WORKERS: usize = 8;
+
+/// Copies or hardlinks files a few at a time, which is what makes trees of
+/// many small files quick to get, stopping at the next file once the get is
+/// given up on.
+fn copy_files(files: &[(PathBuf, PathBuf)], link: bool) -> Result<(), Error> {
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(COPY_WORKERS)
+        .min(files.len());
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let cancel = deadline::current();
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    while !failed.load(Ordering::Relaxed) {
+                        let Some((from, to)) = files.get(next.fetch_add(1, Ordering::Relaxed))
+                        else {
+                            break;
+                        };
+                        let res = match cancel.as_ref().map_or(Ok(()), deadline::Cancel::check) {
+                            Ok(()) if link => fs::hard_link(from, to).map_err(Error::Io),
+                            Ok(()) => copy_file(from, to).map_err(Error::Io),
+                            Err(e) => Err(e),
+                        };
+                        if let Err(e) = res {
+                            failed.store(true, Ordering::Relaxed);
+                            return Err(e);
+                        }
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .try_for_each(|h| h.join().expect("copy worker panicked"))
+    })
+}
+
+/// Local path a file url points to. `file://./path` and `file://../path`
+/// are relative to the working directory, and the only host allowed
+/// otherwise is `localhost`.
+fn source_path(u: &Url) -> Result<PathBuf, Error> {
+    let invalid = |kind| Error::InvalidUrl {
+        input: u.to_string(),
+        kind,
+    };
+
+    match u.host_str() {
+        Some(dir @ ("." | "..")) => {
+            let path = percent_decode_str(u.path().trim_start_matches('/'))
+                .decode_utf8()
+                .map_err(|_| invalid(InvalidUrlKind::PathNotUtf8))?;
+            Ok(Path::new(dir).join(path.as_ref()))
+        }
+        None | Some("" | "localhost") => u
+            .to_file_path()
+            .map_err(|()| invalid(InvalidUrlKind::NotALocalPath)),
+        Some(host) => Err(invalid(InvalidUrlKind::RemoteFileHost(host.to_string()))),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::io::Read;
-    use std::{fs::File, io::Write};
+    use std::{env, fs::File, io::Write};
 
     use super::*;
+    use crate::ChecksumAlgo;
+
+    /// Hands sources to the getter the way the builder does.
+    trait GetSource {
+        fn get(&self, dest: &Path, source: &str) -> Result<GetReport, Error>;
+    }
+
+    impl GetSource for super::File {
+        fn get(&self, dest: &Path, source: &str) -> Result<GetReport, Error> {
+            let (source, options) = GetOptions::parse(source, false)?;
+            self.fetch(dest, &Url::parse(&source)?, &options)
+        }
+    }
 
     #[test]
     fn test_get_file_from_tmp() {
-        let source = "./test-1.txt";
-        if !Path::new(source).exists() {
-            let mut f = File::create(source).unwrap();
-            f.write_all("test".as_bytes()).unwrap();
+        let tmp = tempfile::tempdir().unwrap();
+        let mut f = File::create(tmp.path().join("test-1.txt")).unwrap();
+        f.write_all("test".as_bytes()).unwrap();
+
+        // relative paths are taken from the base dir, not the working directory
+        let getter = super::File::default().base_dir(tmp.path());
+        getter
+            .get(Path::new("test-2.txt"), "file://./test-1.txt")
+            .unwrap();
+
+        let mut df = File::open(tmp.path().join("test-2.txt")).unwrap();
+        let mut buf = Vec::new();
+        df.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, "test".as_bytes());
+        assert!(!Path::new("test-2.txt").exists());
+    }
+
+    #[test]
+    fn test_relative_to() {
+        let tests = vec![
+            ("/a/b/c", "/a/d", Some("../b/c")),
+            ("/a/b", "/a", Some("b")),
+            ("/a", "/a/b/c", Some("../..")),
+            ("/x/y", "/a/b", Some("../../x/y")),
+            ("/a", "relative", None),
+        ];
+        for (target, dir, expected) in tests {
+            assert_eq!(
+                relative_to(Path::new(target), Path::new(dir)),
+                expected.map(PathBuf::from),
+                "{} from {}",
+                target,
+                dir
+            );
         }
+    }
 
-        let dest = "test-2.txt";
+    #[cfg(unix)]
+    #[test]
+    fn test_relative_symlinks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("project");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/test.txt"), "test").unwrap();
+        let url = format!(
+            "file://{}?symlink=relative",
+            root.join("src/test.txt").to_str().unwrap()
+        );
 
-        let getter = File;
-        getter.get(dest, "file://./test-1.txt").unwrap();
+        let dest = root.join("out/nested/test.txt");
+        super::File::default().get(&dest, &url).unwrap();
+        assert_eq!(
+            fs::read_link(&dest).unwrap(),
+            Path::new("../../src/test.txt")
+        );
 
-        assert!(Path::new(dest).exists());
+        // the link still works once the whole tree moves
+        let moved = tmp.path().join("moved");
+        fs::rename(&root, &moved).unwrap();
+        let dest = moved.join("out/nested/test.txt");
+        assert_eq!(fs::read_to_string(dest).unwrap(), "test");
 
-        let mut df = File::open(dest).unwrap();
-        let mut buf = Vec::new();
-        df.read_to_end(&mut buf).unwrap();
+        let dest = moved.join("absolute.txt");
+        super::File::default()
+            .symlink_target(SymlinkTarget::Absolute)
+            .get(&dest, &url.replace("project", "moved"))
+            .unwrap();
+        assert!(fs::read_link(&dest).unwrap().is_absolute());
+    }
 
-        println!("file contents = {:?}", std::str::from_utf8(&buf).unwrap());
-        assert_eq!(buf, "test".as_bytes());
-        fs::remove_file(source).unwrap();
-        fs::remove_file(dest).unwrap();
+    #[cfg(windows)]
+    #[test]
+    fn test_privilege_errors() {
+        use std::io;
+
+        assert!(is_privilege_error(&io::Error::from_raw_os_error(1314)));
+        // access denied is a problem with dest, not with symlinks
+        assert!(!is_privilege_error(&io::Error::from_raw_os_error(5)));
+        assert!(!is_privilege_error(&io::Error::other("other")));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_symlink_privilege_fallback() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("source");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("test.txt"), "test").unwrap();
+
+        for name in ["test.txt", ""] {
+            let url = Url::from_file_path(source.join(name)).unwrap().to_string();
+            let dest = tmp.path().join(format!("fallback-{}", name));
+            // whether it falls back depends on Developer Mode being on
+            let report = super::File::default().get(&dest, &url).unwrap();
+            if report.fallback.is_none() {
+                assert!(dest.is_symlink());
+            }
+            let file = if name.is_empty() {
+                dest.join("test.txt")
+            } else {
+                dest
+            };
+            assert_eq!(fs::read_to_string(file).unwrap(), "test");
+
+            let dest = tmp.path().join(format!("required-{}", name));
+            match super::File::default()
+                .require_symlinks(true)
+                .get(&dest, &url)
+            {
+                Ok(report) => {
+                    assert_eq!(report.fallback, None);
+                    assert!(dest.is_symlink());
+                }
+                Err(e) => assert!(matches!(e, Error::SymlinkNotPermitted(_)), "{:?}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_source_paths() {
+        let cwd = env::current_dir().unwrap();
+        let tests = vec![
+            ("file:///tmp/x.txt", Some(PathBuf::from("/tmp/x.txt"))),
+            (
+                "file://localhost/tmp/x.txt",
+                Some(PathBuf::from("/tmp/x.txt")),
+            ),
+            (
+                "file:///tmp/with%20space.txt",
+                Some(PathBuf::from("/tmp/with space.txt")),
+            ),
+            (
+                "file:///tmp/with space.txt",
+                Some(PathBuf::from("/tmp/with space.txt")),
+            ),
+            (
+                "file:///tmp/r%C3%A9sum%C3%A9.txt",
+                Some(PathBuf::from("/tmp/résumé.txt")),
+            ),
+            (
+                "file:///tmp/x.txt?mode=copy",
+                Some(PathBuf::from("/tmp/x.txt")),
+            ),
+            ("file://./rel/x%20y.txt", Some(cwd.join("rel/x y.txt"))),
+            ("file://../rel", Some(cwd.parent().unwrap().join("rel"))),
+            ("file://example.com/tmp/x.txt", None),
+        ];
+        for (url, expected) in tests {
+            let path =
+                source_path(&Url::parse(url).unwrap()).and_then(|p| Base::default().absolute(&p));
+            match expected {
+                #[cfg(unix)]
+                Some(expected) => assert_eq!(path.unwrap(), expected, "{}", url),
+                #[cfg(not(unix))]
+                Some(_) => {}
+                None => assert!(matches!(path, Err(Error::InvalidUrl { .. })), "{}", url),
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_files_with_spaces() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("with space é.txt");
+        fs::write(&source, "test").unwrap();
+        let dest = tmp.path().join("dest.txt");
+
+        let url = Url::from_file_path(&source).unwrap();
+        assert!(url.as_str().contains("%20"));
+        super::File::default().get(&dest, url.as_str()).unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+    }
+
+    #[test]
+    fn test_dest_errors() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+        let url = format!("file://{}", source.to_str().unwrap());
+
+        let root = if cfg!(windows) { "C:\\" } else { "/" };
+        let err = super::File::default()
+            .get(Path::new(root), &url)
+            .unwrap_err();
+        assert!(matches!(err, Error::DestinationExists(_)), "{:?}", err);
+
+        // a file is in the way of the directories dest would be in
+        let dest = source.join("nested").join("dest.txt");
+        let err = super::File::default().get(&dest, &url).unwrap_err();
+        match err {
+            Error::DestinationNotCreated(e) => assert_ne!(e.kind(), ErrorKind::NotFound),
+            e => panic!("{:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_copy_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("source").join("tool");
+        fs::create_dir(source.parent().unwrap()).unwrap();
+        fs::write(&source, "test").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&source, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let url = format!("file://{}?mode=copy", source.to_str().unwrap());
+
+        let dest = tmp.path().join("dest").join("tool");
+        super::File::default().get(&dest, &url).unwrap();
+        fs::remove_dir_all(source.parent().unwrap()).unwrap();
+
+        assert!(!dest.is_symlink());
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&dest).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+        // nothing is left next to the copy
+        let names: Vec<_> = fs::read_dir(dest.parent().unwrap())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["tool"]);
+    }
+
+    #[test]
+    fn test_hashed_copies() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("tool");
+        fs::write(&source, "test").unwrap();
+        let url = Url::parse(&format!("file://{}", source.to_str().unwrap())).unwrap();
+        let getter = super::File::with_mode(FileMode::Copy);
+
+        let mut options = GetOptions {
+            hash: Some(ChecksumAlgo::Sha256),
+            buffer_size: Some(3),
+            ..Default::default()
+        };
+        let dest = tmp.path().join("dest");
+        let report = getter.fetch(&dest, &url, &options).unwrap();
+        let sha256 = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        assert_eq!(report.checksum, Some(Checksum::parse(sha256).unwrap()));
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+        assert_eq!(
+            fs::metadata(&dest).unwrap().modified().unwrap(),
+            fs::metadata(&source).unwrap().modified().unwrap()
+        );
+
+        // a copy that isn't what's expected never shows up at dest
+        options.checksum = Some(Checksum::parse(&sha256.replace('9', "0")).unwrap());
+        let dest = tmp.path().join("mismatch");
+        let err = getter.fetch(&dest, &url, &options).unwrap_err();
+        assert!(matches!(err, Error::ChecksumMismatch { .. }), "{:?}", err);
+        assert!(!dest.exists());
+
+        // linked sources are left for the builder to hash
+        let dest = tmp.path().join("link");
+        let report = super::File::default().fetch(&dest, &url, &options).unwrap();
+        assert_eq!(report.checksum, None);
+    }
+
+    #[test]
+    fn test_missing_source() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = format!("file://{}", tmp.path().join("missing").to_str().unwrap());
+        for mode in ["copy", "hardlink"] {
+            let err = super::File::default()
+                .get(&tmp.path().join(mode), &format!("{}?mode={}", source, mode))
+                .unwrap_err();
+            assert!(
+                matches!(err, Error::SourceNotFound { source: Some(_) }),
+                "{}: {:?}",
+                mode,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_file_modes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+        let url = |mode: &str| format!("file://{}?mode={}", source.to_str().unwrap(), mode);
+
+        let dest = tmp.path().join("linked.txt");
+        super::File::default().get(&dest, &url("symlink")).unwrap();
+        assert!(dest.is_symlink());
+
+        // a mode set on the getter wins over the url
+        let dest = tmp.path().join("copied.txt");
+        super::File::with_mode(FileMode::Copy)
+            .get(&dest, &url("symlink"))
+            .unwrap();
+        assert!(!dest.is_symlink());
+
+        let err = super::File::default()
+            .get(&tmp.path().join("other.txt"), &url("move"))
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidUrl { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn test_glob_sources() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let builds = tmp.path().join("builds");
+        fs::create_dir_all(builds.join("nightly")).unwrap();
+        // the newest isn't the highest version, nor the last by name
+        for (name, secs) in [("app-1.9.txt", 3), ("app-1.10.txt", 2), ("app-1.2.txt", 1)] {
+            fs::write(builds.join(name), name).unwrap();
+            File::options()
+                .write(true)
+                .open(builds.join(name))
+                .unwrap()
+                .set_modified(UNIX_EPOCH + Duration::from_secs(secs * 1000))
+                .unwrap();
+        }
+        fs::write(builds.join("nightly/app-2.0.txt"), "nightly").unwrap();
+        let glob = |pattern: &str, select: &str| {
+            format!(
+                "file://{}/{}?mode=copy&glob={}",
+                builds.to_str().unwrap(),
+                pattern,
+                select
+            )
+        };
+        let get = |dest: &str, src: &str| {
+            super::File::default().get(&tmp.path().join(dest), src)?;
+            Ok::<_, Error>(fs::read_to_string(tmp.path().join(dest)).unwrap())
+        };
+
+        assert_eq!(
+            get("newest", &glob("app-*.txt", "newest")).unwrap(),
+            "app-1.9.txt"
+        );
+        assert_eq!(
+            get("version", &glob("app-*.txt", "version")).unwrap(),
+            "app-1.10.txt"
+        );
+        assert_eq!(
+            get("one", &glob("app-1.1[0-9].txt", "one")).unwrap(),
+            "app-1.10.txt"
+        );
+        let err = get("many", &glob("app-*.txt", "one")).unwrap_err();
+        assert!(
+            matches!(&err, Error::GlobMatches { matched: 3, .. }),
+            "{:?}",
+            err
+        );
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{}/app-*.txt matched 3 files, expected exactly one",
+                builds.display()
+            )
+        );
+        let err = get("none", &glob("lib-*.txt", "newest")).unwrap_err();
+        assert!(matches!(err, Error::SourceNotFound { .. }), "{:?}", err);
+        let err = get("invalid", &glob("app-[.txt", "newest")).unwrap_err();
+        assert!(matches!(err, Error::InvalidUrl { .. }), "{:?}", err);
+
+        // all of them keep the directories they're in below the pattern
+        let dest = tmp.path().join("all");
+        super::File::default()
+            .get(&dest, &glob("*/app-*.txt", "all"))
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(dest.join("nightly/app-2.0.txt")).unwrap(),
+            "nightly"
+        );
+        assert!(!dest.join("app-1.9.txt").exists());
+        let dest = tmp.path().join("linked");
+        super::File::default()
+            .get(&dest, &glob("app-1.*", "all").replace("copy", "symlink"))
+            .unwrap();
+        assert_eq!(fs::read_dir(&dest).unwrap().count(), 3);
+        assert!(dest.join("app-1.2.txt").is_symlink());
+
+        // paths that are there as they are aren't globs
+        fs::write(builds.join("app-[1].txt"), "literal").unwrap();
+        assert_eq!(
+            get("literal", &glob("app-[1].txt", "one")).unwrap(),
+            "literal"
+        );
+    }
+
+    #[test]
+    fn test_version_cmp() {
+        let mut versions = vec![
+            "app-1.10.0",
+            "app-1.9.2",
+            "app-1.9.10",
+            "app-01.9.3",
+            "app-2",
+            "app-1.9",
+            "app",
+        ];
+        versions.sort_by(|a, b| version_cmp(a, b));
+        assert_eq!(
+            versions,
+            [
+                "app",
+                "app-1.9",
+                "app-1.9.2",
+                "app-01.9.3",
+                "app-1.9.10",
+                "app-1.10.0",
+                "app-2"
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_directories() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("source");
+        for dir in [
+            "bin",
+            "lib",
+            ".git",
+            "node_modules/dep",
+            "docs/node_modules",
+        ] {
+            fs::create_dir_all(source.join(dir)).unwrap();
+        }
+        for file in [
+            "bin/tool",
+            "lib/data.txt",
+            ".git/config",
+            "node_modules/dep/index.js",
+            "docs/node_modules/readme",
+            "docs/build.log",
+        ] {
+            fs::write(source.join(file), file).unwrap();
+        }
+        fs::set_permissions(source.join("bin/tool"), fs::Permissions::from_mode(0o755)).unwrap();
+        symlink("lib/data.txt", source.join("data")).unwrap();
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        File::open(source.join("lib/data.txt"))
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        File::open(source.join("lib"))
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        let dest = tmp.path().join("dest");
+        let url = format!(
+            "file://{}?mode=copy&exclude=.git,node_modules,docs/*.log",
+            source.to_str().unwrap()
+        );
+        super::File::default().get(&dest, &url).unwrap();
+        fs::remove_dir_all(&source).unwrap();
+
+        let mut entries = Vec::new();
+        let mut dirs = vec![dest.clone()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() && !path.is_symlink() {
+                    dirs.push(path.clone());
+                }
+                entries.push(path.strip_prefix(&dest).unwrap().display().to_string());
+            }
+        }
+        entries.sort();
+        assert_eq!(
+            entries,
+            ["bin", "bin/tool", "data", "docs", "lib", "lib/data.txt"]
+        );
+
+        assert_eq!(
+            fs::read_link(dest.join("data")).unwrap(),
+            Path::new("lib/data.txt")
+        );
+        assert_eq!(
+            fs::read_to_string(dest.join("data")).unwrap(),
+            "lib/data.txt"
+        );
+        let mode = fs::metadata(dest.join("bin/tool"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+        for path in ["lib/data.txt", "lib"] {
+            let modified = fs::metadata(dest.join(path)).unwrap().modified().unwrap();
+            assert_eq!(modified, mtime, "{}", path);
+        }
+
+        // excluding only makes sense for what's copied
+        let err = super::File::default()
+            .get(
+                &tmp.path().join("linked"),
+                &format!("file://{}?exclude=.git", tmp.path().to_str().unwrap()),
+            )
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidUrl { .. }), "{:?}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_directory_links() {
+        use std::os::unix::fs::symlink;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("source");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("sub/file.txt"), "file").unwrap();
+        symlink("sub", source.join("inner")).unwrap();
+        symlink("sub/file.txt", source.join("file")).unwrap();
+        symlink("/etc", source.join("etc")).unwrap();
+        symlink("missing", source.join("dangling")).unwrap();
+        let outside = tmp.path().join("outside");
+        fs::create_dir(&outside).unwrap();
+        fs::write(outside.join("secret"), "secret").unwrap();
+        let copy = |dest: &str, links: &str| {
+            let url = format!(
+                "file://{}?mode=copy&links={}",
+                source.to_str().unwrap(),
+                links
+            );
+            super::File::default().get(&tmp.path().join(dest), &url)
+        };
+
+        // symlinks are copied as they are unless asked otherwise
+        copy("preserved", "preserve").unwrap();
+        let preserved = tmp.path().join("preserved");
+        for link in ["inner", "file", "etc", "dangling"] {
+            assert!(preserved.join(link).is_symlink(), "{}", link);
+        }
+
+        // only those pointing inside the source are followed
+        copy("followed", "follow").unwrap();
+        let followed = tmp.path().join("followed");
+        assert!(!followed.join("inner").is_symlink());
+        assert_eq!(
+            fs::read_to_string(followed.join("inner/file.txt")).unwrap(),
+            "file"
+        );
+        assert!(!followed.join("file").is_symlink());
+        assert_eq!(
+            fs::read_link(followed.join("etc")).unwrap(),
+            Path::new("/etc")
+        );
+        assert!(followed.join("dangling").is_symlink());
+
+        // unless asked to follow any of them
+        fs::remove_file(source.join("etc")).unwrap();
+        symlink(&outside, source.join("outside")).unwrap();
+        copy("all", "follow_all").unwrap();
+        let all = tmp.path().join("all");
+        assert!(!all.join("outside").is_symlink());
+        assert_eq!(
+            fs::read_to_string(all.join("outside/secret")).unwrap(),
+            "secret"
+        );
+
+        // following a link back into where it is never ends
+        symlink("..", source.join("sub/loop")).unwrap();
+        assert!(copy("preserved_loop", "preserve").is_ok());
+        for links in ["follow", "follow_all"] {
+            let err = copy("loop", links).unwrap_err();
+            assert!(matches!(err, Error::SymlinkLoop { .. }), "{:?}", err);
+            assert!(!tmp.path().join("loop").exists());
+        }
+
+        let err = copy("invalid", "sometimes").unwrap_err();
+        assert!(matches!(err, Error::InvalidUrl { .. }), "{:?}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_special_files() {
+        use std::{ffi::CString, os::unix::ffi::OsStrExt, os::unix::fs::symlink};
+
+        let mkfifo = |path: &Path| {
+            let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+            // SAFETY: path is nul terminated
+            assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o644) }, 0);
+        };
+        let tmp = tempfile::tempdir().unwrap();
+        let fifo = tmp.path().join("fifo");
+        mkfifo(&fifo);
+        let url = |path: &Path, mode: &str| format!("file://{}?mode={}", path.display(), mode);
+
+        // copying a fifo would wait for a writer forever
+        for mode in ["copy", "hardlink", "symlink"] {
+            let err = super::File::default()
+                .get(&tmp.path().join(mode), &url(&fifo, mode))
+                .unwrap_err();
+            assert!(
+                matches!(err, Error::UnsupportedSourceType { kind: "fifo" }),
+                "{}: {:?}",
+                mode,
+                err
+            );
+        }
+        let dest = tmp.path().join("linked");
+        let report = super::File::default()
+            .allow_special_files(true)
+            .get(&dest, &url(&fifo, "copy"))
+            .unwrap();
+        assert_eq!(fs::read_link(&dest).unwrap(), fifo);
+        assert_eq!(report.fallback.as_deref(), Some(SPECIAL_FALLBACK));
+
+        // the rest of a directory is copied without them
+        let source = tmp.path().join("source");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("a.txt"), "a").unwrap();
+        mkfifo(&source.join("fifo"));
+        symlink("fifo", source.join("link")).unwrap();
+        for (mode, links, skipped) in [("copy", "preserve", 1), ("hardlink", "follow", 2)] {
+            let dest = tmp.path().join(mode);
+            let report = super::File::default()
+                .get(&dest, &format!("{}&links={}", url(&source, mode), links))
+                .unwrap();
+            assert_eq!(fs::read_to_string(dest.join("a.txt")).unwrap(), "a");
+            assert!(!dest.join("fifo").exists());
+            assert_eq!(report.warnings.len(), skipped, "{:?}", report);
+            assert!(report.warnings.iter().all(|w| w.ends_with("a fifo")));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlink_files() {
+        use std::os::unix::fs::MetadataExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("source");
+        fs::create_dir_all(source.join("lib")).unwrap();
+        fs::write(source.join("tool"), "tool").unwrap();
+        fs::write(source.join("lib/data.txt"), "data").unwrap();
+        let url = |path: &Path| format!("file://{}?mode=hardlink", path.to_str().unwrap());
+        let ino = |path: &Path| fs::metadata(path).unwrap().ino();
+
+        let dest = tmp.path().join("tool");
+        let report = super::File::default()
+            .get(&dest, &url(&source.join("tool")))
+            .unwrap();
+        assert_eq!(report.fallback, None);
+        assert_eq!(ino(&dest), ino(&source.join("tool")));
+
+        let dest = tmp.path().join("tree");
+        let report = super::File::default().get(&dest, &url(&source)).unwrap();
+        assert_eq!(report.fallback, None);
+        assert!(!dest.join("lib").is_symlink());
+        assert_ne!(ino(&dest.join("lib")), ino(&source.join("lib")));
+        for file in ["tool", "lib/data.txt"] {
+            assert_eq!(ino(&dest.join(file)), ino(&source.join(file)), "{}", file);
+        }
     }
 }
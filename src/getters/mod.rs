@@ -1,5 +1,82 @@
 mod file;
+#[cfg(feature = "s3")]
 pub mod s3;
 
-pub use file::File;
-pub use s3::S3;
+pub use file::{CopyLinks, File, FileMode, GlobSelect, SymlinkTarget};
+#[cfg(feature = "s3")]
+pub use s3::{S3Config, S3};
+
+use std::io::Write;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{checksum::Hasher, Checksum, ChecksumAlgo, Error, GetOptions};
+
+/// Bytes streaming gets read and write at a time, unless
+/// [`RequestBuilder::buffer_size`](crate::RequestBuilder::buffer_size) says
+/// otherwise.
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Writes what body reads to file through a single buffer of the size the
+/// options say, so that a download takes up as much memory whatever its
+/// size. Failing reads are taken for a connection breaking off, worth
+/// trying again. Returns how many bytes were written, along with their
+/// checksum when [`GetOptions::hash`] asks for one, which is checked against
+/// [`GetOptions::checksum`] once the body ends.
+pub async fn copy_to_file(
+    body: impl AsyncRead,
+    file: &mut std::fs::File,
+    options: &GetOptions,
+) -> Result<(u64, Option<Checksum>), Error> {
+    let mut body = std::pin::pin!(body);
+    let mut buf = vec![0; options.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE)];
+    let mut hashing = Hashing::new(options);
+    let mut written = 0;
+    loop {
+        let n = body.read(&mut buf).await.map_err(|e| Error::Transfer {
+            retryable: true,
+            source: Box::new(e),
+        })?;
+        if n == 0 {
+            return Ok((written, hashing.map(Hashing::finish).transpose()?));
+        }
+        file.write_all(&buf[..n])?;
+        if let Some(hashing) = &mut hashing {
+            hashing.update(&buf[..n]);
+        }
+        written += n as u64;
+    }
+}
+
+/// Hashes what a getter writes as it writes it, in the algorithm
+/// [`GetOptions::hash`] asks for.
+pub(crate) struct Hashing<'a> {
+    algo: ChecksumAlgo,
+    hasher: Hasher,
+    expected: Option<&'a Checksum>,
+}
+
+impl<'a> Hashing<'a> {
+    pub(crate) fn new(options: &'a GetOptions) -> Option<Self> {
+        let algo = options.hash?;
+        Some(Self {
+            algo,
+            hasher: algo.hasher(),
+            expected: options.checksum.as_ref().filter(|c| c.algo() == algo),
+        })
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        self.hasher.update(bytes);
+    }
+
+    /// The checksum of everything written, failing with
+    /// [`Error::ChecksumMismatch`] when it isn't the one expected.
+    pub(crate) fn finish(self) -> Result<Checksum, Error> {
+        let actual = self.algo.checksum(self.hasher);
+        if let Some(expected) = self.expected {
+            expected.verify(&actual)?;
+        }
+        Ok(actual)
+    }
+}
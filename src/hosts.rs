@@ -0,0 +1,218 @@
+//! The allow-list of hosts a get may contact, see
+//! [`RequestBuilder::allowed_hosts`](crate::RequestBuilder::allowed_hosts).
+
+use std::str::FromStr;
+
+use crate::{Error, Url};
+
+/// Hosts a get may contact, checked against the url of the source once it's
+/// detected, against those of the sums and signature files, and by getters
+/// that follow redirects against each of them with [`AllowedHosts::check`].
+///
+/// Patterns are made of a host, optionally preceded by a scheme and followed
+/// by a path:
+///
+/// - `github.com` allows the host, whatever the scheme and the path.
+/// - `*.internal.corp` allows any host one or more labels under it, but not
+///   `internal.corp` itself.
+/// - `https://github.com` only allows it over https.
+/// - `s3://prod-artifacts/*` only allows s3 urls of the bucket, `*` standing
+///   for anything at the end of the path. A path without one has to be the
+///   same.
+///
+/// Getters tell what they contact with [`Getter::network_url`], like the S3
+/// getter does with `s3://<bucket>/<key>`.
+///
+/// [`Getter::network_url`]: crate::Getter::network_url
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowedHosts {
+    patterns: Vec<HostPattern>,
+}
+
+impl AllowedHosts {
+    /// Parses the patterns, failing on the first that isn't one.
+    pub fn new<I>(patterns: I) -> Result<Self, Error>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let patterns = patterns
+            .into_iter()
+            .map(|p| p.as_ref().parse())
+            .collect::<Result<_, _>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// Fails with [`Error::HostNotAllowed`] unless a pattern allows url.
+    pub fn check(&self, url: &Url) -> Result<(), Error> {
+        if self.patterns.iter().any(|p| p.matches(url)) {
+            return Ok(());
+        }
+        Err(Error::HostNotAllowed {
+            url: url.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HostPattern {
+    scheme: Option<String>,
+    host: Host,
+    path: Option<Path>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Host {
+    Any,
+    Exact(String),
+    /// Hosts ending in this, which starts with a `.`.
+    Under(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Path {
+    Exact(String),
+    Prefix(String),
+}
+
+impl HostPattern {
+    fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let host = match &self.host {
+            Host::Any => true,
+            Host::Exact(exact) => host == *exact,
+            Host::Under(parent) => host
+                .strip_suffix(parent.as_str())
+                .is_some_and(|labels| !labels.is_empty()),
+        };
+        let path = match &self.path {
+            None => true,
+            Some(Path::Exact(exact)) => url.path() == exact,
+            Some(Path::Prefix(prefix)) => url.path().starts_with(prefix.as_str()),
+        };
+        host && path && self.scheme.iter().all(|s| s == url.scheme())
+    }
+}
+
+impl FromStr for HostPattern {
+    type Err = Error;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| Error::InvalidHostPattern {
+            pattern: pattern.to_string(),
+            reason: reason.to_string(),
+        };
+
+        let (scheme, rest) = match pattern.split_once("://") {
+            Some((scheme, rest)) if !scheme.is_empty() => (Some(scheme.to_ascii_lowercase()), rest),
+            Some(_) => return Err(invalid("the scheme is empty")),
+            None => (None, pattern),
+        };
+        let (host, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], Some(&rest[i..])),
+            None => (rest, None),
+        };
+
+        let host = host.to_ascii_lowercase();
+        let host = match host.strip_prefix("*.") {
+            _ if host == "*" => Host::Any,
+            Some(parent) => Host::Under(format!(".{}", parent.trim_end_matches('.'))),
+            None => Host::Exact(host.trim_end_matches('.').to_string()),
+        };
+        match &host {
+            Host::Exact(h) | Host::Under(h) if h.trim_start_matches('.').is_empty() => {
+                return Err(invalid("the host is empty"))
+            }
+            Host::Exact(h) | Host::Under(h) if h.contains(['*', ':', '@']) => {
+                return Err(invalid(
+                    "hosts can only start with a wildcard, and take no port or user",
+                ))
+            }
+            _ => {}
+        }
+
+        let path = match path {
+            None => None,
+            Some(path) => match path.strip_suffix('*') {
+                Some(prefix) if !prefix.contains('*') => Some(Path::Prefix(prefix.to_string())),
+                None if !path.contains('*') => Some(Path::Exact(path.to_string())),
+                _ => return Err(invalid("paths can only end with a wildcard")),
+            },
+        };
+
+        Ok(Self { scheme, host, path })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(patterns: &[&str], url: &str) -> bool {
+        let hosts = AllowedHosts::new(patterns).unwrap();
+        hosts.check(&Url::parse(url).unwrap()).is_ok()
+    }
+
+    #[test]
+    fn it_should_match_hosts() {
+        assert!(allowed(&["github.com"], "https://github.com/org/repo"));
+        assert!(allowed(&["GitHub.com."], "ssh://git@github.com/org/repo"));
+        assert!(!allowed(&["github.com"], "https://api.github.com/"));
+        assert!(!allowed(&["github.com"], "https://evilgithub.com/"));
+
+        assert!(allowed(&["*.internal.corp"], "https://a.b.internal.corp/x"));
+        assert!(!allowed(&["*.internal.corp"], "https://internal.corp/x"));
+        assert!(!allowed(
+            &["*.internal.corp"],
+            "https://evilinternal.corp/x"
+        ));
+        assert!(!allowed(
+            &["*.internal.corp"],
+            "https://internal.corp.evil/x"
+        ));
+
+        assert!(allowed(&["https://github.com"], "https://github.com/org"));
+        assert!(!allowed(&["https://github.com"], "http://github.com/org"));
+        assert!(allowed(
+            &["s3://prod-artifacts/*"],
+            "s3://prod-artifacts/app.tar.gz"
+        ));
+        assert!(!allowed(
+            &["s3://prod-artifacts/*"],
+            "s3://staging/app.tar.gz"
+        ));
+        assert!(allowed(&["s3://*/releases/*"], "s3://any/releases/v1"));
+        assert!(!allowed(&["s3://*/releases/*"], "s3://any/nightly/v1"));
+        assert!(allowed(&["host/SUMS"], "https://host/SUMS"));
+        assert!(!allowed(&["host/SUMS"], "https://host/SUMS.sig"));
+
+        assert!(allowed(&["a.com", "b.com"], "https://b.com/"));
+        assert!(!allowed(&[], "https://a.com/"));
+        assert!(!allowed(&["*"], "file:///etc/passwd"));
+    }
+
+    #[test]
+    fn it_should_reject_invalid_patterns() {
+        for pattern in [
+            "",
+            "://host",
+            "*.",
+            "a.*.com",
+            "host:8080",
+            "user@host",
+            "host/*/x",
+        ] {
+            assert!(
+                matches!(
+                    pattern.parse::<HostPattern>(),
+                    Err(Error::InvalidHostPattern { .. })
+                ),
+                "{}",
+                pattern
+            );
+        }
+    }
+}
@@ -1,15 +1,86 @@
 use async_trait::async_trait;
+use checksum::Record;
+use context::Context;
+use deadline::{within, Deadlines};
 use regex::Regex;
-use std::collections::HashMap;
-use url::Url;
+use staging::TempDest;
+use std::{
+    collections::HashMap,
+    fs,
+    future::Future,
+    io::Read,
+    iter,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use timing::Stopwatch;
+use tokio::io::AsyncRead;
+use tracing::Instrument;
+pub use url::Url;
 
+mod batch;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod cache;
+pub mod checksum;
+mod client;
+mod context;
+mod deadline;
+pub mod decompressors;
+mod dest;
 pub mod detectors;
+mod events;
 pub mod getters;
+mod hosts;
+mod lock;
+#[cfg(feature = "serde")]
+mod lockfile;
+mod memory;
+mod options;
+mod paths;
+mod perms;
+mod postprocess;
+mod progress;
+#[cfg(feature = "serde")]
+pub mod receipts;
+pub mod registry;
+mod retry;
+mod signature;
+mod space;
+#[cfg(feature = "serde")]
+mod spec;
+mod staging;
+mod template;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+mod throttle;
+mod timing;
+
+pub use batch::MultiRequestBuilder;
+pub use cache::{CacheKey, CacheStore, DirCache};
+pub use checksum::{Checksum, ChecksumAlgo, ChecksumSource};
+pub use client::{get, get_with, Client, Options};
+pub use context::{ErrorContext, Phase};
+pub use events::EventHandler;
+pub use hosts::AllowedHosts;
+#[cfg(feature = "serde")]
+pub use lockfile::{LockedSource, Lockfile};
+pub use options::{ArchiveOption, GetMode, GetOptions};
+pub use postprocess::PostProcessor;
+pub use progress::{BatchProgress, ExtractProgress, ExtractStats, ProgressReporter};
+pub use retry::RetryPolicy;
+pub use space::{OnEstimate, SpaceCheck};
+#[cfg(feature = "serde")]
+pub use spec::RequestSpec;
+pub use template::DestTemplate;
+pub use timing::Timings;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error("invalid url: {0}, reason: {1}")]
-    InvalidUrl(String, String),
+    #[error("invalid url: {input}, reason: {kind}")]
+    InvalidUrl { input: String, kind: InvalidUrlKind },
 
     #[error(transparent)]
     Io(#[from] std::io::Error),
@@ -17,25 +88,450 @@ pub enum Error {
     #[error("client not set")]
     ClientNotSet,
 
-    #[error("source file not found")]
-    SourceNotFound,
+    /// The source doesn't exist, with what the getter was told about it,
+    /// see [`Error::source_not_found`].
+    #[error("source not found")]
+    SourceNotFound {
+        source: Option<Box<dyn std::error::Error + Send + Sync>>,
+    },
+
+    #[error("getter for {0} not found, {}", display_registered(.1))]
+    GetterNotFound(String, Vec<String>),
+
+    #[error("source path {0} does not exist, check the path")]
+    PathNotFound(String),
+
+    #[error("decompressor for {0} not found")]
+    DecompressorNotFound(String),
+
+    #[error("archive could not be read: {0}")]
+    InvalidArchive(String),
+
+    #[error("archive is encrypted and can't be extracted without a password")]
+    ArchiveEncrypted,
+
+    #[error("archive entry {entry} could not be decrypted, check the archive password")]
+    ArchiveDecryptionFailed { entry: String },
+
+    #[error("archive entry {entry} would be extracted outside of the destination")]
+    UnsafeArchivePath { entry: String },
+
+    #[error("no archive entry matches {subpath}, closest entries: {}", .near.join(", "))]
+    SubpathNotFound { subpath: String, near: Vec<String> },
+
+    #[error("archive entry {entry} is a link, which the link policy rejects")]
+    ArchiveLinkRejected { entry: String },
+
+    #[error("archive exceeds the {limit} limit of {max}")]
+    DecompressLimitExceeded { limit: &'static str, max: u64 },
+
+    #[error("no detector matched {input} ({})", display_attempts(.attempts))]
+    DetectionFailed {
+        input: String,
+        attempts: Vec<(String, DetectAttempt)>,
+    },
+
+    #[error("invalid checksum: {0}")]
+    InvalidChecksum(String),
+
+    #[error("{algo} checksum mismatch, expected {expected} but got {actual}")]
+    ChecksumMismatch {
+        algo: &'static str,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("{file} has no checksum for {name}, it lists {}", .entries.join(", "))]
+    ChecksumNotFound {
+        file: String,
+        name: String,
+        entries: Vec<String>,
+    },
+
+    #[error("{file} has conflicting checksums for {name}, from {}", .entries.join(", "))]
+    ChecksumAmbiguous {
+        file: String,
+        name: String,
+        entries: Vec<String>,
+    },
+
+    #[error("no key to check the signature against was set")]
+    SignatureKeyMissing,
+
+    #[error("signature could not be read: {0}")]
+    MalformedSignature(String),
+
+    #[error("signature made by key {key} doesn't match what it signs or isn't trusted")]
+    SignatureInvalid { key: String },
+
+    #[error("destination {0} already exists, set an on_conflict policy to replace it")]
+    DestinationExists(String),
+
+    #[error("expected a {expected} at dest, but the source is a {found}")]
+    ModeMismatch { expected: GetMode, found: GetMode },
+    #[error("destination {0} is not empty, set an on_exists policy to extract into it")]
+    DestinationNotEmpty(String),
+    #[error("{0} already exists in the destination")]
+    DestinationConflict(String),
+    #[error("refusing to clean {0}, set allow_dangerous_clean to do it anyway")]
+    DangerousClean(String),
+
+    #[error("refusing to get anything to {0}, set allow_dangerous_dest to do it anyway")]
+    DangerousDest(String),
+
+    #[error("dest {dest} is outside of {jail}, the directory dests are kept in")]
+    DestOutsideJail { dest: String, jail: String },
+    #[error("destination {dest} is locked by {}", display_holder(.pid))]
+    DestinationLocked { dest: String, pid: Option<u32> },
+    #[error("destination could not be created: {0}")]
+    DestinationNotCreated(std::io::Error),
+    #[error("creating the symlink {0} isn't permitted, turn on Developer Mode or let the file getter fall back")]
+    SymlinkNotPermitted(String),
+
+    #[error("access to the source was denied: {source}")]
+    AccessDenied {
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[error("transfer failed: {source}")]
+    Transfer {
+        /// Whether trying again might work, see [`Error::is_retryable`].
+        retryable: bool,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    /// The server asked for requests to slow down, like an HTTP 429 or an
+    /// S3 `SlowDown`, see [`Error::is_throttled`].
+    #[error("throttled by the server: {source}")]
+    Throttled {
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[error("timed out during {phase}")]
+    Timeout { phase: Phase },
+
+    #[error("request for {src} has unknown fields {}", .fields.join(", "))]
+    UnknownSpecFields { src: String, fields: Vec<String> },
+
+    #[error("invalid dest template {template}: {reason}")]
+    InvalidDestTemplate { template: String, reason: String },
+
+    #[error("cancelled after another source of the batch failed")]
+    Cancelled,
+
+    #[error("blocking gets can't be made from within a Tokio runtime, use the async API there")]
+    BlockingInRuntime,
+
+    #[error("the default registry was already installed or used by a builder, set it up before making any")]
+    RegistryInUse,
 
-    #[error("getter for {0} not found")]
-    GetterNotFound(String),
+    #[error("source is larger than the {max} bytes a get into memory may take up")]
+    TooLargeForMemory { max: u64 },
 
-    #[error("destination path already exists and is not a symlink")]
-    DestinationExists,
-    #[error("destination could not be created")]
-    DestinationNotCreated,
+    #[error("source is not valid UTF-8")]
+    NotUtf8(#[source] std::string::FromUtf8Error),
+
+    #[error("{what} isn't supported")]
+    Unsupported { what: String },
+
+    #[error("{url} isn't on the list of allowed hosts")]
+    HostNotAllowed { url: String },
+
+    #[error("invalid allowed host {pattern}: {reason}")]
+    InvalidHostPattern { pattern: String, reason: String },
+
+    #[error("{needed} bytes are needed at dest, but only {available} are free")]
+    InsufficientSpace { needed: u64, available: u64 },
+
+    #[error("source is a {kind}, which can only be symlinked, set allow_special_files to do so")]
+    UnsupportedSourceType { kind: &'static str },
+
+    #[error("{pattern} matched {matched} files, expected exactly one")]
+    GlobMatches { pattern: String, matched: usize },
+
+    #[error("following symlink {path} would loop back into where it is, or nest too deep")]
+    SymlinkLoop { path: String },
+
+    #[error("{src} doesn't match the lockfile: {reason}")]
+    LockfileMismatch { src: String, reason: String },
+
+    /// An error of a get along with the source, dest and phase it failed in.
+    /// Match on [`Error::inner`] for what went wrong.
+    #[error("{context}: {source}")]
+    Context {
+        context: Box<ErrorContext>,
+        source: Box<Error>,
+    },
 
     #[error(transparent)]
     UrlParseError(#[from] url::ParseError),
 
     #[error(transparent)]
-    Unknown(#[from] Box<dyn std::error::Error>),
+    Unknown(#[from] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Error {
+    /// The source doesn't exist, as the store it's in said with source,
+    /// like a 404 or a missing key, for every getter to tell the same way.
+    pub fn source_not_found(source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Error::SourceNotFound {
+            source: Some(source.into()),
+        }
+    }
+
+    /// An io error on the source, which is [`Error::SourceNotFound`] when
+    /// the source isn't there, even when it was a moment before.
+    pub fn source_io(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => Error::source_not_found(e),
+            _ => Error::Io(e),
+        }
+    }
+
+    /// What sort of error this is, for telling a missing source from a
+    /// passing network problem or a misconfigured request without matching
+    /// on every variant.
+    pub fn kind(&self) -> ErrorKind {
+        use std::io::ErrorKind as Io;
+
+        match self.inner() {
+            Error::Io(e) => match e.kind() {
+                Io::NotFound => ErrorKind::NotFound,
+                Io::PermissionDenied => ErrorKind::Auth,
+                Io::ConnectionReset
+                | Io::ConnectionAborted
+                | Io::ConnectionRefused
+                | Io::BrokenPipe
+                | Io::TimedOut
+                | Io::UnexpectedEof
+                | Io::Interrupted => ErrorKind::Transient,
+                _ => ErrorKind::Other,
+            },
+            Error::SourceNotFound { .. }
+            | Error::PathNotFound(_)
+            | Error::SubpathNotFound { .. } => ErrorKind::NotFound,
+            Error::AccessDenied { .. } => ErrorKind::Auth,
+            Error::Transfer { retryable, .. } => match retryable {
+                true => ErrorKind::Transient,
+                false => ErrorKind::Other,
+            },
+            Error::Throttled { .. } => ErrorKind::Transient,
+            Error::Timeout { .. } => ErrorKind::Timeout,
+            Error::InvalidChecksum(_)
+            | Error::ChecksumMismatch { .. }
+            | Error::ChecksumNotFound { .. }
+            | Error::ChecksumAmbiguous { .. }
+            | Error::MalformedSignature(_)
+            | Error::SignatureInvalid { .. } => ErrorKind::Integrity,
+            Error::InvalidArchive(_)
+            | Error::ArchiveEncrypted
+            | Error::ArchiveDecryptionFailed { .. }
+            | Error::UnsafeArchivePath { .. }
+            | Error::ArchiveLinkRejected { .. }
+            | Error::DecompressLimitExceeded { .. } => ErrorKind::Archive,
+            Error::DestinationExists(_)
+            | Error::DestinationNotEmpty(_)
+            | Error::DestinationConflict(_)
+            | Error::DangerousClean(_)
+            | Error::DangerousDest(_)
+            | Error::DestOutsideJail { .. }
+            | Error::DestinationLocked { .. }
+            | Error::DestinationNotCreated(_)
+            | Error::SymlinkNotPermitted(_)
+            | Error::InsufficientSpace { .. } => ErrorKind::Destination,
+            Error::InvalidUrl { .. }
+            | Error::UrlParseError(_)
+            | Error::ClientNotSet
+            | Error::GetterNotFound(..)
+            | Error::DecompressorNotFound(_)
+            | Error::DetectionFailed { .. }
+            | Error::SignatureKeyMissing
+            | Error::ModeMismatch { .. }
+            | Error::BlockingInRuntime
+            | Error::RegistryInUse
+            | Error::TooLargeForMemory { .. }
+            | Error::Unsupported { .. }
+            | Error::UnsupportedSourceType { .. }
+            | Error::GlobMatches { .. }
+            | Error::UnknownSpecFields { .. }
+            | Error::LockfileMismatch { .. }
+            | Error::HostNotAllowed { .. }
+            | Error::InvalidHostPattern { .. }
+            | Error::InvalidDestTemplate { .. } => ErrorKind::Config,
+            Error::Cancelled => ErrorKind::Cancelled,
+            Error::NotUtf8(_)
+            | Error::SymlinkLoop { .. }
+            | Error::Unknown(_)
+            | Error::Context { .. } => ErrorKind::Other,
+        }
+    }
+
+    /// Whether the source doesn't exist, be it a missing file, an S3 key
+    /// that isn't there or a subpath the archive doesn't have.
+    pub fn is_not_found(&self) -> bool {
+        self.kind() == ErrorKind::NotFound
+    }
+
+    /// Whether the error might go away when the getter is tried again, like
+    /// a connection reset or a server error, as opposed to a missing source
+    /// or a checksum mismatch. Only these are retried, see
+    /// [`RequestBuilder::retry`].
+    pub fn is_retryable(&self) -> bool {
+        self.kind() == ErrorKind::Transient
+    }
+
+    /// Whether the server asked for requests to slow down. Retryable, and
+    /// what makes a batch lower how many sources it fetches from the server
+    /// at once, see [`MultiRequestBuilder::host_concurrency`].
+    pub fn is_throttled(&self) -> bool {
+        matches!(self.inner(), Error::Throttled { .. })
+    }
+
+    /// Whether credentials are missing or don't grant access to the source.
+    pub fn is_auth(&self) -> bool {
+        self.kind() == ErrorKind::Auth
+    }
+
+    /// Whether getting the same source the same way is bound to fail again,
+    /// until the source, the credentials or the request are fixed.
+    pub fn is_permanent(&self) -> bool {
+        matches!(
+            self.kind(),
+            ErrorKind::NotFound
+                | ErrorKind::Auth
+                | ErrorKind::Integrity
+                | ErrorKind::Archive
+                | ErrorKind::Config
+        )
+    }
+
+    /// The error itself, without the [`ErrorContext`] a get wraps it in.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.inner(),
+            e => e,
+        }
+    }
+
+    /// Which get the error came from and what it was doing, for errors
+    /// returned by a get.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::Context { context, .. } => Some(context.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// What's wrong with the source of an [`Error::InvalidUrl`], to match on
+/// rather than on the message. Kinds may be added, so matches on them need
+/// a wildcard arm.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum InvalidUrlKind {
+    #[error("s3 urls need a bucket")]
+    MissingBucket,
+    #[error("azure blob urls should have the following format :account.blob.core.windows.net/:container/:blob")]
+    MissingContainer,
+    /// A git or GitHub source without the path of the repository, where
+    /// expected is what the source should look like.
+    #[error("the repository path is missing, urls should have the following format {expected}")]
+    MissingRepoPath { expected: &'static str },
+    /// A host that doesn't look like one of the store's.
+    #[error("not a valid {store} url")]
+    UnsupportedHostShape { store: &'static str },
+    #[error("scp-like git urls cannot specify a port, use ssh://user@host:port/path instead")]
+    PortInScpUrl,
+    /// A file url pointing to another machine.
+    #[error("file urls can only point to this machine, not {0}")]
+    RemoteFileHost(String),
+    #[error("not a path on this system")]
+    NotALocalPath,
+    #[error("file path isn't valid utf-8")]
+    PathNotUtf8,
+    /// A file source with wildcards that don't make a glob.
+    #[error("not a valid glob: {0}")]
+    InvalidGlob(String),
+    /// A query parameter gette reads, with a value it can't make sense of.
+    #[error("{reason}")]
+    InvalidParam { param: &'static str, reason: String },
+    /// A query parameter gette doesn't know, in strict mode.
+    #[error("unknown parameter {0}")]
+    UnknownParam(String),
+}
+
+/// Broad class of an [`Error`], see [`Error::kind`]. Kinds may be added, so
+/// matches on them need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The source doesn't exist.
+    NotFound,
+    /// Credentials are missing or don't grant access to the source.
+    Auth,
+    /// A problem that may go away on its own, like a dropped connection.
+    Transient,
+    /// The get or one of its phases ran out of time.
+    Timeout,
+    /// What was fetched doesn't match its checksum or signature.
+    Integrity,
+    /// The archive can't be extracted, or may not be.
+    Archive,
+    /// Something at dest is in the way.
+    Destination,
+    /// The request can't be made as it is, like an invalid url or a source
+    /// no getter is registered for.
+    Config,
+    /// The get was given up on for another one that failed.
+    Cancelled,
+    /// Anything else.
+    Other,
+}
+
+/// Outcome of a detector that did not match a source, recorded in
+/// [`Error::DetectionFailed`].
+#[derive(Debug)]
+pub enum DetectAttempt {
+    /// The detector did not recognize the source.
+    Declined,
+    /// The detector recognized the source but could not make sense of it.
+    Errored(Box<Error>),
+}
+
+impl std::fmt::Display for DetectAttempt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DetectAttempt::Declined => write!(f, "declined"),
+            DetectAttempt::Errored(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+fn display_attempts(attempts: &[(String, DetectAttempt)]) -> String {
+    attempts
+        .iter()
+        .map(|(name, attempt)| format!("{}: {}", name, attempt))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn display_registered(getters: &[String]) -> String {
+    match getters {
+        [] => "no getters are registered".to_string(),
+        getters => format!("the registered getters are {}", getters.join(", ")),
+    }
+}
+
+fn display_holder(pid: &Option<u32>) -> String {
+    match pid {
+        Some(pid) => format!("process {}", pid),
+        None => "another process".to_string(),
+    }
 }
 
 pub trait Detector {
+    /// Name used to identify the detector in error reports.
+    fn name(&self) -> &str;
     fn detect(&self, path: &str) -> Result<Option<String>, Error>;
 }
 
@@ -47,15 +543,17 @@ pub trait Detector {
 /// Gette is designed to be extensible. You can add your own getters by implementing this trait.
 /// the first step is to create a struct that implements this trait:
 ///
-/// ```ignore
-/// use gette::getter;
+/// ```
+/// use gette::{GetOptions, Getter, Url};
 /// use async_trait::async_trait;
+/// use std::path::Path;
 ///
-/// pub struct mygetter;
+/// pub struct Mygetter;
 /// #[async_trait]
-/// impl getter for mygetter {
-///     async fn get(&self, _dest: &str, _source: &str) -> result<(), gette::error> {
-///       ok(())   
+/// impl Getter for Mygetter {
+///     async fn get(&self, dest: &Path, url: &Url, _options: &GetOptions) -> Result<(), gette::Error> {
+///         std::fs::write(dest, url.path())?;
+///         Ok(())
 ///     }
 /// }
 ///```
@@ -64,101 +562,751 @@ pub trait Detector {
 ///
 ///```rust
 /// use gette::RequestBuilder;
-/// # use gette::Getter;
+/// # use gette::{GetOptions, Getter, Url};
 /// # use async_trait::async_trait;
+/// # use std::path::Path;
 ///
 /// # pub struct Mygetter;
 /// # #[async_trait]
 /// # impl Getter for Mygetter {
-/// #     async fn get(&self, _dest: &str, _source: &str) -> Result<(), gette::Error> {
-/// #       Ok(())   
+/// #     async fn get(&self, _dest: &Path, _url: &Url, _options: &GetOptions) -> Result<(), gette::Error> {
+/// #         Ok(())
 /// #     }
 /// # }
 ///
 /// # tokio_test::block_on(async {
-/// let b = RequestBuilder::builder().src("mygetter://test.txt".to_string())
-///     .dest("test2.txt".to_string())
+/// let b = RequestBuilder::builder().src("mygetter://test.txt")
+///     .dest("test2.txt")
 ///     .add_getter("mygetter", Box::new(Mygetter))
 ///     .get()
 ///     .await
 ///     .unwrap();
 /// # })
 ///```
+///
+/// Getters tell callers what went wrong through the error they return: a
+/// source that isn't there is [`Error::SourceNotFound`], made with
+/// [`Error::source_not_found`] or [`Error::source_io`], one the credentials
+/// don't grant access to is [`Error::AccessDenied`], and anything worth
+/// trying again is a retryable [`Error::Transfer`], see [`Error::kind`].
 #[async_trait]
-pub trait Getter {
-    async fn get(&self, dest: &str, source: &str) -> Result<(), Error>;
+pub trait Getter: Sync {
+    /// Fetches the source at url into dest. The url is the one detection
+    /// ended up with, parsed once for every getter, without the forced
+    /// getter prefix, the `//` subpath or the query parameters gette reads,
+    /// which are in options instead.
+    async fn get(&self, dest: &Path, url: &Url, options: &GetOptions) -> Result<(), Error>;
+    /// Sets up the getter ahead of its first use. The builder only ever
+    /// shares its getters, so those that need a client have to be able to
+    /// set it up on first use as well, like [`getters::S3`] does.
     async fn set_client(&mut self) -> Result<(), Error> {
         Ok(())
     }
+    /// Opens the source as a stream of its bytes. Getters that can do this
+    /// let archives be extracted while they download, instead of going
+    /// through a temporary file first.
+    async fn open(
+        &self,
+        _url: &Url,
+        _options: &GetOptions,
+    ) -> Result<Option<Pin<Box<dyn AsyncRead + Send>>>, Error> {
+        Ok(None)
+    }
+    /// Looks up a checksum the store keeps for the source, like the ones S3
+    /// computes on upload. The download is checked against it when the
+    /// source isn't given a checksum otherwise.
+    async fn remote_checksum(
+        &self,
+        _url: &Url,
+        _options: &GetOptions,
+    ) -> Result<Option<Checksum>, Error> {
+        Ok(None)
+    }
+    /// Tells whether the source at url is a file or a directory, if the
+    /// getter can without fetching it, so that gets expecting the other one
+    /// fail before anything is transferred. See [`GetMode`].
+    async fn mode(&self, _url: &Url, _options: &GetOptions) -> Result<Option<GetMode>, Error> {
+        Ok(None)
+    }
+    /// Tells how many bytes the source at url is, if the getter can without
+    /// fetching it, failing like [`get`](Getter::get) would when it isn't
+    /// there. Only asked by dry runs, see [`RequestBuilder::dry_run`].
+    async fn size(&self, _url: &Url, _options: &GetOptions) -> Result<Option<u64>, Error> {
+        Ok(None)
+    }
+    /// A token on one line that changes whenever the source at url does,
+    /// like an ETag or a modification time, if the getter can tell it
+    /// without fetching the source. Only asked by gets keeping dest up to
+    /// date, see [`RequestBuilder::update`].
+    async fn fingerprint(
+        &self,
+        _url: &Url,
+        _options: &GetOptions,
+    ) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
+    /// What fetching the source at url contacts, in the terms the hosts
+    /// allowed are given in, or nothing for sources that aren't fetched over
+    /// the network, see [`RequestBuilder::allowed_hosts`]. The url itself by
+    /// default.
+    fn network_url(&self, url: &Url) -> Option<Url> {
+        Some(url.clone())
+    }
+    /// Pins url to the one source it stands for right now, like a moving
+    /// tag to the release it points at, or nothing to keep it as it is.
+    /// Asked once detection is done, before anything else is asked about
+    /// the source, and the archive format is found from the url it returns.
+    /// Dry runs and [`Client::resolve`] ask it too, so it's to be cheap:
+    /// what has to be searched for, like the matches of a file glob, is
+    /// better left for [`get`](Getter::get).
+    fn resolve(&self, _url: &Url, _options: &GetOptions) -> Result<Option<Url>, Error> {
+        Ok(None)
+    }
+    /// Fetches the source into dest like [`get`](Getter::get), also saying
+    /// how it went. Getters that can fall back to another way of fetching
+    /// than the one asked for, like the file getter copying what it can't
+    /// hardlink, report it here.
+    async fn get_with_report(
+        &self,
+        dest: &Path,
+        url: &Url,
+        options: &GetOptions,
+    ) -> Result<GetReport, Error> {
+        self.get(dest, url, options)
+            .await
+            .map(|()| GetReport::default())
+    }
+}
+
+/// Decompressor trait
+/// Implement this trait to add a new archive format
+///
+/// Once the getter has downloaded an archive, the decompressor registered
+/// for its format extracts it into the destination.
+///
+/// ## Extending Gette
+///
+/// Decompressors are registered under a format name, the same way getters
+/// are. The first step is to create a struct that implements this trait:
+///
+/// ```ignore
+/// use gette::{DecompressOpts, Decompressor};
+/// use async_trait::async_trait;
+/// use std::path::Path;
+///
+/// pub struct Reversed;
+/// #[async_trait]
+/// impl Decompressor for Reversed {
+///     fn extensions(&self) -> &[&str] {
+///         &["rev"]
+///     }
+///     async fn decompress(&self, archive: &Path, dest: &Path, _opts: &DecompressOpts) -> Result<(), gette::Error> {
+///         let mut bytes = std::fs::read(archive)?;
+///         bytes.reverse();
+///         std::fs::write(dest, bytes)?;
+///         Ok(())
+///     }
+/// }
+///```
+///
+/// the next step is to add it to the builder. Sources ending in one of its
+/// extensions are now decompressed with it, and `?archive=<name>` forces it
+/// for any other source:
+///
+///```rust
+/// use gette::RequestBuilder;
+/// # use gette::{DecompressOpts, Decompressor};
+/// # use async_trait::async_trait;
+/// # use std::path::Path;
+///
+/// # pub struct Reversed;
+/// # #[async_trait]
+/// # impl Decompressor for Reversed {
+/// #     fn extensions(&self) -> &[&str] {
+/// #         &["rev"]
+/// #     }
+/// #     async fn decompress(&self, archive: &Path, dest: &Path, _opts: &DecompressOpts) -> Result<(), gette::Error> {
+/// #         let mut bytes = std::fs::read(archive)?;
+/// #         bytes.reverse();
+/// #         std::fs::write(dest, bytes)?;
+/// #         Ok(())
+/// #     }
+/// # }
+///
+/// # tokio_test::block_on(async {
+/// # let tmp = tempfile::tempdir().unwrap();
+/// # let src = tmp.path().join("notes.txt.rev");
+/// # std::fs::write(&src, "olleh").unwrap();
+/// # let dest = tmp.path().join("notes.txt");
+/// let client = RequestBuilder::builder()
+///     .add_decompressor("reversed", Box::new(Reversed))
+///     .build();
+/// client.get(src.to_str().unwrap(), &dest).await.unwrap();
+/// # assert_eq!(std::fs::read_to_string(&dest).unwrap(), "hello");
+///
+/// # let src = tmp.path().join("download");
+/// # std::fs::write(&src, "dlrow").unwrap();
+/// # let dest = tmp.path().join("forced.txt");
+/// let forced = format!("file://{}?archive=reversed", src.to_str().unwrap());
+/// client.get(&forced, &dest).await.unwrap();
+/// # assert_eq!(std::fs::read_to_string(&dest).unwrap(), "world");
+/// # })
+///```
+#[async_trait]
+pub trait Decompressor {
+    /// File extensions, without the leading dot, that select this decompressor.
+    fn extensions(&self) -> &[&str];
+    async fn decompress(
+        &self,
+        archive: &Path,
+        dest: &Path,
+        opts: &DecompressOpts,
+    ) -> Result<(), Error>;
+    /// Extracts the archive from a stream as it's downloaded. Formats that
+    /// need to seek around the archive, like zip, can't and return `None`.
+    fn streaming(&self) -> Option<StreamFn> {
+        None
+    }
 }
 
-pub trait Decompressor {}
+/// Extracts an archive read front to back from a stream into dest.
+pub type StreamFn = fn(&mut dyn Read, &Path, &DecompressOpts) -> Result<(), Error>;
 
-#[derive(Default, Debug)]
+/// Options passed to a [`Decompressor`].
+#[derive(Default, Debug, Clone)]
+#[non_exhaustive]
+pub struct DecompressOpts {
+    /// File name of the archive as it appears in the source url.
+    pub name: Option<String>,
+    /// Number of leading path components dropped from every entry, entries
+    /// with fewer components are skipped. Applied before any subpath matching.
+    pub strip_components: usize,
+    /// Entries to extract, from the `//` subpath of the source. A file is
+    /// extracted to dest itself, a directory has its contents extracted into
+    /// dest. `*` and `?` globs select every matching entry.
+    pub subpath: Option<String>,
+    /// Caps on what the extraction may produce.
+    pub limits: DecompressLimits,
+    /// Which symlink and hardlink entries are extracted.
+    pub links: LinkPolicy,
+    /// Bits cleared from the mode of every extracted file and directory.
+    pub umask: Option<u32>,
+    /// Restore modification times recorded in the archive.
+    pub preserve_mtime: bool,
+    /// Receives the progress of the extraction.
+    pub progress: Option<Arc<dyn ProgressReporter>>,
+    /// Decrypts the encrypted entries of archives.
+    pub password: Option<Password>,
+    /// When the extraction has to be done by. Past it the extraction gives
+    /// up with [`Error::Timeout`] at its next entry or read.
+    pub deadline: Option<Instant>,
+}
+
+/// A password for encrypted archives. Its `Debug` output is redacted so that
+/// it doesn't end up in logs along with the options it's part of.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Password(String);
+
+impl Password {
+    pub fn new(password: impl Into<String>) -> Self {
+        Self(password.into())
+    }
+
+    /// The password itself.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Password {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Password(***)")
+    }
+}
+
+/// What to do when an archive is extracted into a destination that already
+/// has something in it.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnExists {
+    /// Fail the get, leaving the destination untouched.
+    #[default]
+    Error,
+    /// Keep what's there and add the new entries, failing if any of them
+    /// would replace an existing file.
+    Merge,
+    /// Add the new entries, replacing existing files that are in the way.
+    Overwrite,
+    /// Delete everything in the destination before adding the new entries.
+    Clean,
+}
+
+impl std::str::FromStr for OnExists {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(OnExists::Error),
+            "merge" => Ok(OnExists::Merge),
+            "overwrite" => Ok(OnExists::Overwrite),
+            "clean" => Ok(OnExists::Clean),
+            _ => Err("on_exists must be one of error, merge, overwrite or clean".to_string()),
+        }
+    }
+}
+
+/// What to do when a getter is about to write a source that isn't extracted
+/// to a destination that already exists.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Fail the get, leaving the destination untouched.
+    #[default]
+    Error,
+    /// Remove what's there, file, symlink or directory, and write the
+    /// source in its place.
+    Overwrite,
+    /// Leave what's there and fetch nothing, see [`GetResult::skipped`].
+    Skip,
+    /// Move what's there to `<dest>.bak.<n>`, with the first `n` from 1 up
+    /// that's free, and write the source in its place.
+    BackupAndOverwrite,
+}
+
+/// When a get is skipped because dest already holds the source, see
+/// [`RequestBuilder::skip_existing`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipExisting {
+    /// Always go through with the get.
+    #[default]
+    Never,
+    /// Skip when dest matches the `?checksum=` of the source, or when gette
+    /// recorded fetching the same source url into it. A source with a
+    /// `?tree_checksum=` is only skipped when dest matches that.
+    IfMatching,
+    /// Skip on anything [`SkipExisting::IfMatching`] does, and otherwise
+    /// whenever dest exists at all, whatever it holds.
+    IfPresent,
+}
+
+impl From<bool> for SkipExisting {
+    fn from(skip: bool) -> Self {
+        match skip {
+            true => SkipExisting::IfMatching,
+            false => SkipExisting::Never,
+        }
+    }
+}
+
+/// Why a get was skipped, see [`GetResult::skipped`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum SkipReason {
+    /// Dest matched the `?checksum=` or the `?tree_checksum=` of the
+    /// source.
+    ChecksumMatched,
+    /// Gette recorded fetching the same source url into dest.
+    Recorded,
+    /// Dest existed and [`SkipExisting::IfPresent`] takes that as enough.
+    Present,
+    /// Dest existed and [`OnConflict::Skip`] left it as it was.
+    OnConflict,
+    /// The source hadn't changed since gette recorded fetching it into
+    /// dest, see [`RequestBuilder::update`].
+    Unchanged,
+}
+
+/// How symlink and hardlink entries of an archive are treated.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkPolicy {
+    /// Fail the extraction on any link entry.
+    Reject,
+    /// Allow links whose target resolves inside the destination, following
+    /// links extracted before them.
+    #[default]
+    AllowRelativeWithinDest,
+    /// Allow links pointing anywhere, including absolute targets. Entries are
+    /// still never written through a link.
+    AllowAll,
+}
+
+/// Caps on what a single extraction may produce, so that a small archive
+/// can't fill up the disk. Exceeding any of them aborts the extraction with
+/// [`Error::DecompressLimitExceeded`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecompressLimits {
+    /// Bytes written across every entry of the archive.
+    pub max_total_bytes: u64,
+    /// Bytes written for any single entry.
+    pub max_entry_bytes: u64,
+    /// Number of entries in the archive.
+    pub max_entries: u64,
+}
+
+impl DecompressLimits {
+    /// Limits that never trip.
+    pub fn unlimited() -> Self {
+        Self {
+            max_total_bytes: u64::MAX,
+            max_entry_bytes: u64::MAX,
+            max_entries: u64::MAX,
+        }
+    }
+}
+
+impl Default for DecompressLimits {
+    fn default() -> Self {
+        Self {
+            max_total_bytes: 32 << 30,
+            max_entry_bytes: 8 << 30,
+            max_entries: 1_000_000,
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone)]
 pub struct NoSrc;
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Src(String);
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct NoDest;
-#[derive(Default, Debug)]
-pub struct Dest(String);
+#[derive(Debug, Clone)]
+pub struct Dest(DestPath);
+
+/// Where a get puts the source, a path or one derived from the source.
+#[derive(Debug, Clone)]
+pub(crate) enum DestPath {
+    Path(PathBuf),
+    Template(DestTemplate),
+}
+
+impl DestPath {
+    /// The dest as it was given, for errors from before it's known.
+    fn given(&self) -> PathBuf {
+        match self {
+            DestPath::Path(path) => path.clone(),
+            DestPath::Template(template) => PathBuf::from(template.as_str()),
+        }
+    }
+}
 
+/// Builds up a get. Getters, detectors and decompressors are shared rather
+/// than copied, so a configured builder is cheap to clone.
+#[derive(Clone)]
 pub struct RequestBuilder<S, D> {
     src: S,
     dest: D,
-    detectors: Vec<Box<dyn Detector>>,
-    getters: HashMap<String, Box<dyn Getter + Send>>,
+    detectors: Vec<Arc<dyn Detector + Send + Sync>>,
+    getters: HashMap<String, Arc<dyn Getter + Send + Sync>>,
+    decompressors: HashMap<String, Arc<dyn Decompressor + Send + Sync>>,
+    decompress_opts: DecompressOpts,
+    options: GetOptions,
+    strict_params: bool,
+    on_conflict: OnConflict,
+    skip_existing: SkipExisting,
+    update: bool,
+    lock_timeout: Option<Duration>,
+    timeouts: deadline::Timeouts,
+    retry: Option<RetryPolicy>,
+    allow_dangerous_clean: bool,
+    allow_dangerous_dest: bool,
+    dest_jail: Option<PathBuf>,
+    /// What relative sources and dests are relative to, in place of the
+    /// working directory.
+    base: paths::Base,
+    strict_file_detection: bool,
+    perms: perms::Perms,
+    keys: signature::TrustedKeys,
+    /// Settings of the file getter the builder registers.
+    file: getters::File,
+    /// Settings of the S3 getter the builder registers.
+    #[cfg(feature = "s3")]
+    s3: getters::S3Config,
+    /// Settings of getters of your own, by the name they're registered under.
+    getter_config: HashMap<String, HashMap<String, String>>,
+    record_checksum: bool,
+    tree_checksum: bool,
+    verify_remote_checksums: bool,
+    dry_run: bool,
+    /// Bytes a get into memory may take up, see
+    /// [`RequestBuilder::max_in_memory`].
+    max_in_memory: u64,
+    /// Checks that what's fetched fits at dest, none to not check.
+    space: Option<SpaceCheck>,
+    events: Option<Arc<dyn EventHandler>>,
+    post_processors: Vec<Arc<dyn PostProcessor>>,
+    cache: Option<Arc<dyn CacheStore>>,
+    /// Where temporary files and extractions go, next to dest without one.
+    staging_dir: Option<PathBuf>,
+    #[cfg(feature = "serde")]
+    write_receipt: bool,
+    /// Where receipts are kept, next to dest without one.
+    #[cfg(feature = "serde")]
+    receipts_dir: Option<PathBuf>,
+    /// Place of the get among those of a batch in flight to its host, told
+    /// when the host throttles it.
+    throttle: Option<Arc<throttle::HostPermit>>,
 }
 
 impl Default for RequestBuilder<NoSrc, NoDest> {
+    /// The built-in getters, detectors and decompressors, along with those
+    /// of the [`registry`].
     fn default() -> Self {
-        let mut getters: HashMap<String, Box<dyn Getter + Send>> = HashMap::new();
-        getters.insert("file".to_string(), Box::new(getters::File));
+        Self::from_registry(registry::current())
+    }
+}
+
+impl RequestBuilder<NoSrc, NoDest> {
+    /// The built-ins, with the getters of registry in place of those going
+    /// by the same name and its detectors tried first.
+    fn from_registry(registry: &registry::Registry) -> Self {
+        let mut getters: HashMap<String, Arc<dyn Getter + Send + Sync>> = HashMap::new();
+        getters.insert("file".to_string(), Arc::new(getters::File::default()));
+
+        #[cfg(feature = "s3")]
+        getters.insert("s3".to_string(), Arc::new(getters::S3::default()));
+        getters.extend(
+            registry
+                .getters
+                .iter()
+                .map(|(name, getter)| (name.clone(), getter.clone())),
+        );
+
+        let mut decompressors: HashMap<String, Arc<dyn Decompressor + Send + Sync>> =
+            HashMap::new();
+        decompressors.insert("tar".to_string(), Arc::new(decompressors::Tar));
+        decompressors.insert("tar.gz".to_string(), Arc::new(decompressors::TarGz));
+        #[cfg(feature = "bzip2")]
+        decompressors.insert("tar.bz2".to_string(), Arc::new(decompressors::TarBz2));
+        #[cfg(feature = "xz")]
+        decompressors.insert("tar.xz".to_string(), Arc::new(decompressors::TarXz));
+        #[cfg(feature = "zstd")]
+        decompressors.insert("tar.zst".to_string(), Arc::new(decompressors::TarZst));
+        decompressors.insert("zip".to_string(), Arc::new(decompressors::Zip));
+        decompressors.insert("deb".to_string(), Arc::new(decompressors::Deb));
+        decompressors.insert("rpm".to_string(), Arc::new(decompressors::Rpm));
+        #[cfg(feature = "7z")]
+        decompressors.insert("7z".to_string(), Arc::new(decompressors::SevenZip));
+        decompressors.insert("gz".to_string(), Arc::new(decompressors::Gz));
+        #[cfg(feature = "bzip2")]
+        decompressors.insert("bz2".to_string(), Arc::new(decompressors::Bz2));
+        #[cfg(feature = "xz")]
+        decompressors.insert("xz".to_string(), Arc::new(decompressors::Xz));
+        #[cfg(feature = "zstd")]
+        decompressors.insert("zst".to_string(), Arc::new(decompressors::Zst));
 
-        let s3 = getters::S3::default();
-        getters.insert("s3".to_string(), Box::new(s3));
+        // the file detector claims anything that isn't a url, so it has to run last
+        let builtin: Vec<Arc<dyn Detector + Send + Sync>> = vec![
+            #[cfg(feature = "s3")]
+            Arc::new(detectors::S3),
+            Arc::new(detectors::Azure),
+            Arc::new(detectors::Git),
+            Arc::new(detectors::File::default()),
+        ];
+        let detectors = registry.detectors.iter().cloned().chain(builtin).collect();
 
         Self {
             src: NoSrc,
             dest: NoDest,
             getters,
-            detectors: vec![Box::new(detectors::File), Box::new(detectors::S3)],
+            decompressors,
+            decompress_opts: DecompressOpts::default(),
+            options: GetOptions::default(),
+            strict_params: false,
+            on_conflict: OnConflict::default(),
+            skip_existing: SkipExisting::default(),
+            update: false,
+            lock_timeout: None,
+            timeouts: Default::default(),
+            retry: None,
+            allow_dangerous_clean: false,
+            allow_dangerous_dest: false,
+            dest_jail: None,
+            base: paths::Base::default(),
+            strict_file_detection: false,
+            perms: perms::Perms::default(),
+            keys: Default::default(),
+            file: getters::File::default(),
+            #[cfg(feature = "s3")]
+            s3: getters::S3Config::default(),
+            getter_config: HashMap::new(),
+            record_checksum: false,
+            tree_checksum: false,
+            verify_remote_checksums: true,
+            dry_run: false,
+            max_in_memory: memory::DEFAULT_MAX_IN_MEMORY,
+            space: Some(SpaceCheck::default()),
+            events: None,
+            post_processors: Vec::new(),
+            cache: None,
+            staging_dir: None,
+            #[cfg(feature = "serde")]
+            write_receipt: false,
+            #[cfg(feature = "serde")]
+            receipts_dir: None,
+            throttle: None,
+            detectors,
         }
     }
-}
 
-impl RequestBuilder<NoSrc, NoDest> {
     pub fn builder() -> Self {
         Default::default()
     }
 }
 
 impl<D> RequestBuilder<NoSrc, D> {
-    pub fn src(self, src: String) -> RequestBuilder<Src, D> {
+    pub fn src(self, src: impl Into<String>) -> RequestBuilder<Src, D> {
         let Self {
             src: _,
             dest,
             detectors,
             getters,
+            decompressors,
+            decompress_opts,
+            options,
+            strict_params,
+            on_conflict,
+            skip_existing,
+            update,
+            lock_timeout,
+            timeouts,
+            retry,
+            allow_dangerous_clean,
+            allow_dangerous_dest,
+            dest_jail,
+            base,
+            strict_file_detection,
+            perms,
+            keys,
+            file,
+            #[cfg(feature = "s3")]
+            s3,
+            getter_config,
+            record_checksum,
+            tree_checksum,
+            verify_remote_checksums,
+            dry_run,
+            max_in_memory,
+            space,
+            events,
+            post_processors,
+            cache,
+            staging_dir,
+            #[cfg(feature = "serde")]
+            write_receipt,
+            #[cfg(feature = "serde")]
+            receipts_dir,
+            throttle,
         } = self;
 
         RequestBuilder {
-            src: Src(src),
+            src: Src(src.into()),
             dest,
             detectors,
             getters,
+            decompressors,
+            decompress_opts,
+            options,
+            strict_params,
+            on_conflict,
+            skip_existing,
+            update,
+            lock_timeout,
+            timeouts,
+            retry,
+            allow_dangerous_clean,
+            allow_dangerous_dest,
+            dest_jail,
+            base,
+            strict_file_detection,
+            perms,
+            keys,
+            file,
+            #[cfg(feature = "s3")]
+            s3,
+            getter_config,
+            record_checksum,
+            tree_checksum,
+            verify_remote_checksums,
+            dry_run,
+            max_in_memory,
+            space,
+            events,
+            post_processors,
+            cache,
+            staging_dir,
+            #[cfg(feature = "serde")]
+            write_receipt,
+            #[cfg(feature = "serde")]
+            receipts_dir,
+            throttle,
         }
     }
 }
 
 impl<S> RequestBuilder<S, NoDest> {
-    pub fn dest(self, dest: String) -> RequestBuilder<S, Dest> {
+    pub fn dest(self, dest: impl AsRef<Path>) -> RequestBuilder<S, Dest> {
+        self.with_dest(DestPath::Path(dest.as_ref().to_path_buf()))
+    }
+
+    /// Derive dest from the source, see [`DestTemplate`] for the variables
+    /// the template may have. Fails on variables it doesn't know.
+    ///
+    /// ```
+    /// # fn run() -> Result<(), gette::Error> {
+    /// let builder = gette::RequestBuilder::builder()
+    ///     .src("s3+https://s3.amazonaws.com/bucket/tool.tar.gz")
+    ///     .dest_template("./cache/{host}/{basename}")?;
+    /// # Ok(())
+    /// # }
+    /// # run().unwrap();
+    /// ```
+    pub fn dest_template(self, template: &str) -> Result<RequestBuilder<S, Dest>, Error> {
+        Ok(self.with_dest(DestPath::Template(template.parse()?)))
+    }
+
+    fn with_dest(self, dest: DestPath) -> RequestBuilder<S, Dest> {
         let Self {
             src,
             dest: _,
             detectors,
             getters,
+            decompressors,
+            decompress_opts,
+            options,
+            strict_params,
+            on_conflict,
+            skip_existing,
+            update,
+            lock_timeout,
+            timeouts,
+            retry,
+            allow_dangerous_clean,
+            allow_dangerous_dest,
+            dest_jail,
+            base,
+            strict_file_detection,
+            perms,
+            keys,
+            file,
+            #[cfg(feature = "s3")]
+            s3,
+            getter_config,
+            record_checksum,
+            tree_checksum,
+            verify_remote_checksums,
+            dry_run,
+            max_in_memory,
+            space,
+            events,
+            post_processors,
+            cache,
+            staging_dir,
+            #[cfg(feature = "serde")]
+            write_receipt,
+            #[cfg(feature = "serde")]
+            receipts_dir,
+            throttle,
         } = self;
 
         RequestBuilder {
@@ -166,128 +1314,5689 @@ impl<S> RequestBuilder<S, NoDest> {
             dest: Dest(dest),
             detectors,
             getters,
+            decompressors,
+            decompress_opts,
+            options,
+            strict_params,
+            on_conflict,
+            skip_existing,
+            update,
+            lock_timeout,
+            timeouts,
+            retry,
+            allow_dangerous_clean,
+            allow_dangerous_dest,
+            dest_jail,
+            base,
+            strict_file_detection,
+            perms,
+            keys,
+            file,
+            #[cfg(feature = "s3")]
+            s3,
+            getter_config,
+            record_checksum,
+            tree_checksum,
+            verify_remote_checksums,
+            dry_run,
+            max_in_memory,
+            space,
+            events,
+            post_processors,
+            cache,
+            staging_dir,
+            #[cfg(feature = "serde")]
+            write_receipt,
+            #[cfg(feature = "serde")]
+            receipts_dir,
+            throttle,
         }
     }
 }
 
 impl<S, D> RequestBuilder<S, D> {
-    pub fn add_getter(mut self, name: &str, getter: Box<dyn Getter + Send>) -> Self {
-        self.getters.insert(name.to_string(), getter);
+    pub fn add_getter(mut self, name: &str, getter: Box<dyn Getter + Send + Sync>) -> Self {
+        self.getters.insert(name.to_string(), getter.into());
         self
     }
 
-    pub fn add_detector(mut self, detector: Box<dyn Detector>) -> Self {
-        self.detectors.push(detector);
+    /// Remove the getter registered under name, if any, built-in ones
+    /// included.
+    pub fn remove_getter(mut self, name: &str) -> Self {
+        self.getters.remove(name);
         self
     }
-}
 
-impl RequestBuilder<Src, Dest> {
-    fn detect(&self) -> Result<String, Error> {
-        let (is_force, _) = get_forced_proto(&self.src.0);
+    /// Remove every registered getter, including the built-in ones, so that
+    /// only those added afterwards are used.
+    pub fn clear_getters(mut self) -> Self {
+        self.getters.clear();
+        self
+    }
 
-        if Url::parse(self.src.0.as_str()).is_ok() {
-            return Ok(self.src.0.clone());
-        }
+    /// Names of the registered getters, sorted.
+    pub fn getter_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.getters.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
 
-        for d in self.detectors.iter() {
-            let res = d.detect(&self.src.0)?;
+    pub fn add_detector(mut self, detector: Box<dyn Detector + Send + Sync>) -> Self {
+        self.detectors.push(detector.into());
+        self
+    }
 
-            println!("res: {:?}", res);
+    /// Remove the detectors going by name, built-in ones included.
+    pub fn remove_detector(mut self, name: &str) -> Self {
+        self.detectors.retain(|d| d.name() != name);
+        self
+    }
 
-            if res.is_none() {
-                continue;
-            }
+    /// Remove every registered detector, including the built-in ones, so
+    /// that only sources with a scheme, and those detected by detectors added
+    /// afterwards, are fetched.
+    pub fn clear_detectors(mut self) -> Self {
+        self.detectors.clear();
+        self
+    }
 
-            let src: &str = &res.unwrap();
-            let (forced_proto, src) = get_forced_proto(src);
+    /// Names of the registered detectors, in the order they're tried in.
+    pub fn detector_names(&self) -> Vec<&str> {
+        self.detectors.iter().map(|d| d.name()).collect()
+    }
 
-            if is_force.is_some() {
-                return Ok(format!("{}+{}", is_force.unwrap(), src));
-            } else if forced_proto.is_some() {
-                return Ok(format!("{}+{}", forced_proto.unwrap(), src));
-            }
+    /// Register a decompressor under a format name, replacing any registered
+    /// under the same name. The name is what `?archive=` selects it with.
+    pub fn add_decompressor(
+        mut self,
+        name: &str,
+        decompressor: Box<dyn Decompressor + Send + Sync>,
+    ) -> Self {
+        self.decompressors
+            .insert(name.to_string(), decompressor.into());
+        self
+    }
 
-            return Ok(src.to_string());
-        }
+    /// Remove the decompressor registered under name, if any, built-in ones
+    /// included.
+    pub fn remove_decompressor(mut self, name: &str) -> Self {
+        self.decompressors.remove(name);
+        self
+    }
 
-        Err(Error::GetterNotFound(self.src.0.clone()))
+    /// Remove every registered decompressor, including the built-in ones, so
+    /// that only those added afterwards are used.
+    pub fn clear_decompressors(mut self) -> Self {
+        self.decompressors.clear();
+        self
     }
 
-    pub async fn get(&self) -> Result<(), Error> {
-        let src = self.detect()?;
+    /// Names of the registered decompressors, sorted.
+    pub fn decompressor_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.decompressors.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
 
-        let (mut forced, src) = get_forced_proto(&src);
+    /// Drop the first `n` path components of every archive entry, like
+    /// `tar --strip-components`. Takes precedence over `?strip_components=`.
+    pub fn strip_components(mut self, n: usize) -> Self {
+        self.options.strip_components = Some(n);
+        self
+    }
 
-        let parsed_url = Url::parse(src)?;
-        if forced.is_none() {
-            forced = Some(parsed_url.scheme());
-        }
+    /// Clear these bits from the modes archive entries are extracted with,
+    /// e.g. `0o077` to keep the extracted tree private. Ignored outside unix.
+    pub fn umask(mut self, umask: u32) -> Self {
+        self.decompress_opts.umask = Some(umask);
+        self
+    }
 
-        if let Some(getter) = self.getters.get(forced.unwrap()) {
-            return getter.get(&self.dest.0, src).await;
-        }
+    /// Give the files the get creates this mode, whatever the getter or the
+    /// archive would leave them with. Symlinks, and files hardlinked to
+    /// what they were fetched from, keep theirs. Ignored outside unix.
+    pub fn file_permissions(mut self, mode: u32) -> Self {
+        self.perms.file_mode = Some(mode);
+        self
+    }
 
-        Ok(())
+    /// Give the directories the get creates this mode, the ones dest is
+    /// created in included, see [`RequestBuilder::file_permissions`].
+    /// Ignored outside unix.
+    pub fn dir_permissions(mut self, mode: u32) -> Self {
+        self.perms.dir_mode = Some(mode);
+        self
     }
-}
 
-fn get_forced_proto(v: &str) -> (Option<&str>, &str) {
-    if let Some(re) = Regex::new(r"^([A-Za-z0-9]+)\+(.*)$").unwrap().captures(v) {
-        return (
-            Some(re.get(1).unwrap().as_str()),
-            re.get(2).unwrap().as_str(),
-        );
+    /// Give whatever the get creates this owner and group, which takes the
+    /// privileges to, see [`RequestBuilder::file_permissions`].
+    #[cfg(unix)]
+    pub fn chown(mut self, uid: u32, gid: u32) -> Self {
+        self.perms.owner = Some((uid, gid));
+        self
     }
 
-    (None, v)
-}
+    /// Report the progress of the get as it happens.
+    pub fn progress(mut self, reporter: Arc<dyn ProgressReporter>) -> Self {
+        self.decompress_opts.progress = Some(reporter);
+        self
+    }
 
-#[cfg(test)]
-mod tests {
+    /// Tell the handler what the get goes through, see [`EventHandler`].
+    pub fn events(mut self, handler: Arc<dyn EventHandler>) -> Self {
+        self.events = Some(handler);
+        self
+    }
 
-    use std::{
-        env,
-        fs::{self, File},
-        io::Write,
-    };
+    /// Transform what's fetched before it's put at dest, see
+    /// [`PostProcessor`]. Post-processors run in the order they're added.
+    pub fn post_process(mut self, processor: Arc<dyn PostProcessor>) -> Self {
+        self.post_processors.push(processor);
+        self
+    }
 
-    use super::*;
+    /// Keep verified downloads in the store, and take them from it rather
+    /// than fetching them again. Only sources with a checksum are cached,
+    /// looked up or not, keyed by their url and checksum, and local sources
+    /// never are. See [`GetResult::cached`].
+    pub fn cache(mut self, store: Arc<dyn CacheStore>) -> Self {
+        self.cache = Some(store);
+        self
+    }
 
-    #[tokio::test]
-    async fn test_simple_detect() {
-        let b = RequestBuilder::builder()
-            .src("file://test.txt".to_string())
-            .dest("test2.txt".to_string());
-        let res = b.detect().unwrap();
-        assert_eq!("file://test.txt", res);
+    /// Keep verified downloads in a [`DirCache`] in dir, see
+    /// [`RequestBuilder::cache`].
+    pub fn cache_dir(self, dir: impl Into<PathBuf>) -> Self {
+        self.cache(Arc::new(DirCache::new(dir)))
     }
 
-    #[tokio::test]
-    async fn test_file_detect_without_proto() {
+    /// Write downloads and extractions to dir before they're moved to dest,
+    /// rather than next to dest. Where dir is on another filesystem than
+    /// dest, what's moved is copied over, synced to disk and renamed into
+    /// place, which keeps dest from ever holding something incomplete but
+    /// isn't atomic for archives merged into an existing directory. A
+    /// [`RequestBuilder::cache_dir`] is best kept on the same filesystem as
+    /// dir, for what's taken from it to be linked rather than copied.
+    pub fn staging_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.staging_dir = Some(dir.into());
+        self
+    }
+
+    /// Write a [`Receipt`](receipts::Receipt) of every get that fetched
+    /// something, with the source, the url it resolved to, the checksum and
+    /// the timings, to [`receipts::DEFAULT_DIR`] next to dest, where
+    /// [`receipts::lookup`] finds it. Receipts are written in place of the
+    /// hidden files skip_existing and update otherwise record the source in,
+    /// and they go by the receipt of dest wherever there's one.
+    #[cfg(feature = "serde")]
+    pub fn write_receipt(mut self, write: bool) -> Self {
+        self.write_receipt = write;
+        self
+    }
+
+    /// Keep receipts in dir rather than next to their dests, see
+    /// [`RequestBuilder::write_receipt`], which this turns on. They're
+    /// found with [`receipts::lookup_in`].
+    #[cfg(feature = "serde")]
+    pub fn receipts_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.write_receipt = true;
+        self.receipts_dir = Some(dir.into());
+        self
+    }
+
+    /// Decrypt encrypted archive entries with this password. Takes precedence
+    /// over `?archive_password=`.
+    pub fn archive_password(mut self, password: impl Into<String>) -> Self {
+        self.options.archive_password = Some(Password::new(password));
+        self
+    }
+
+    /// Restore the modification times recorded in archives instead of
+    /// leaving the time of extraction.
+    pub fn preserve_mtime(mut self, preserve: bool) -> Self {
+        self.decompress_opts.preserve_mtime = preserve;
+        self
+    }
+
+    /// Set what happens when an archive is extracted into a destination that
+    /// already has files in it. Takes precedence over `?on_exists=`.
+    pub fn on_exists(mut self, policy: OnExists) -> Self {
+        self.options.on_exists = Some(policy);
+        self
+    }
+
+    /// Set the options the source would otherwise be given in its query
+    /// string. Those given here take precedence over the query string, as
+    /// well as over those set before, while the rest are kept.
+    pub fn options(mut self, options: GetOptions) -> Self {
+        self.options = options.or(self.options);
+        self
+    }
+
+    /// Fail on query parameters of the source that neither gette nor the
+    /// getters read, catching misspelled options like `?strip_component=1`,
+    /// instead of leaving them in the url for the remote.
+    pub fn strict_params(mut self, strict: bool) -> Self {
+        self.strict_params = strict;
+        self
+    }
+
+    /// Only contact hosts the patterns allow, see [`AllowedHosts`] for what
+    /// they look like. The source is checked once it's detected, and so are
+    /// sums and signature files, failing with [`Error::HostNotAllowed`]
+    /// before anything goes over the network. Getters that follow redirects
+    /// check each of them against [`GetOptions::allowed_hosts`]. Fails on
+    /// patterns that aren't valid.
+    pub fn allowed_hosts<I>(mut self, patterns: I) -> Result<Self, Error>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.options.allowed_hosts = Some(AllowedHosts::new(patterns)?);
+        Ok(self)
+    }
+
+    /// Set how the file getter puts sources at dest. Takes precedence over
+    /// `?mode=`.
+    pub fn file_mode(mut self, mode: getters::FileMode) -> Self {
+        self.options.file_mode = Some(mode);
+        self
+    }
+
+    /// Set whether a file or a directory is expected at dest, see
+    /// [`GetMode`]. Either is fine by default.
+    pub fn get_mode(mut self, mode: GetMode) -> Self {
+        self.options.get_mode = Some(mode);
+        self
+    }
+
+    /// Set what the symlinks the file getter makes point to. Takes
+    /// precedence over `?symlink=`.
+    pub fn symlink_target(mut self, target: getters::SymlinkTarget) -> Self {
+        self.options.symlink_target = Some(target);
+        self
+    }
+
+    /// Set what the file getter does with the symlinks in the directories it
+    /// copies or hardlinks. Takes precedence over `?links=`.
+    pub fn copy_links(mut self, links: getters::CopyLinks) -> Self {
+        self.options.copy_links = Some(links);
+        self
+    }
+
+    /// Set which of the files a glob source matches the file getter
+    /// fetches, see [`getters::GlobSelect`]. Takes precedence over
+    /// `?glob=`.
+    pub fn glob_select(mut self, select: getters::GlobSelect) -> Self {
+        self.options.glob = Some(select);
+        self
+    }
+
+    /// Let the file getter symlink sources that are special files, like
+    /// FIFOs, sockets and devices, whatever the mode, instead of failing
+    /// with [`Error::UnsupportedSourceType`], registering a new file getter
+    /// in place of the current one. Special files inside of directories
+    /// that are copied are left out either way, see [`GetResult::warnings`].
+    pub fn allow_special_files(mut self, allow: bool) -> Self {
+        self.file = self.file.allow_special_files(allow);
+        let file = self.file.clone();
+        self.add_getter("file", Box::new(file))
+    }
+
+    /// Fail when the file getter isn't permitted to create a symlink, as on
+    /// Windows without Developer Mode, instead of letting it copy files and
+    /// make junctions to directories in their place, registering a new file
+    /// getter in place of the current one.
+    pub fn require_symlinks(mut self, require: bool) -> Self {
+        self.file.require_symlinks = require;
+        let file = self.file.clone();
+        self.add_getter("file", Box::new(file))
+    }
+
+    /// Configure the S3 getter, registering a new one with the settings
+    /// `configure` returns in place of the current one.
+    ///
+    /// ```
+    /// let builder = gette::RequestBuilder::builder()
+    ///     .s3(|s3| s3.endpoint("http://localhost:9000").force_path_style(true));
+    /// ```
+    #[cfg(feature = "s3")]
+    pub fn s3(mut self, configure: impl FnOnce(getters::S3Config) -> getters::S3Config) -> Self {
+        self.s3 = configure(self.s3);
+        let s3 = getters::S3::with_config(self.s3.clone());
+        self.add_getter("s3", Box::new(s3))
+    }
+
+    /// Hand a setting to the getter registered under name, for getters of
+    /// your own to read from [`GetOptions::getter_config`]. Built-in getters
+    /// have settings of their own, like [`RequestBuilder::s3`].
+    pub fn getter_config(
+        mut self,
+        getter: &str,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.getter_config
+            .entry(getter.to_string())
+            .or_default()
+            .insert(key.into(), value.into());
+        self
+    }
+
+    /// Set what happens when the source isn't extracted and dest already
+    /// exists, which is an error by default. A dest that's an existing
+    /// directory gets the source put inside of it instead, under its own
+    /// name, which is where the policy then applies.
+    pub fn on_conflict(mut self, policy: OnConflict) -> Self {
+        self.on_conflict = policy;
+        self
+    }
+
+    /// Skip the get, before anything goes over the network, when dest
+    /// already holds the source, see [`SkipExisting`]. `true` skips only
+    /// when that can be told from the checksum or from what gette recorded.
+    pub fn skip_existing(mut self, skip: impl Into<SkipExisting>) -> Self {
+        self.skip_existing = skip.into();
+        self
+    }
+
+    /// Keep dest in sync with the source, fetching it again only when it
+    /// changed since the last get into dest. Gette records the fingerprint
+    /// the getter gives the source, like an ETag or a modification time,
+    /// next to dest, and skips gets that find it unchanged with
+    /// [`SkipReason::Unchanged`]. Sources the getter can't fingerprint are
+    /// always fetched.
+    ///
+    /// A source that changed is put at dest the way [`on_conflict`] and
+    /// [`on_exists`] say, which have to let it replace what's there.
+    ///
+    /// ```no_run
+    /// # async fn run() -> Result<(), gette::Error> {
+    /// gette::RequestBuilder::builder()
+    ///     .update(true)
+    ///     .on_conflict(gette::OnConflict::Overwrite)
+    ///     .src("s3+https://s3.amazonaws.com/bucket/nightly.db")
+    ///     .dest("./nightly.db")
+    ///     .get()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`on_conflict`]: RequestBuilder::on_conflict
+    /// [`on_exists`]: RequestBuilder::on_exists
+    pub fn update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    /// Give up on the get with [`Error::DestinationLocked`] when another
+    /// process has been holding the lock on dest for this long. Gets into
+    /// the same dest take turns through an advisory lock on `<dest>.lock`,
+    /// and wait for each other for as long as it takes by default.
+    pub fn lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = Some(timeout);
+        self
+    }
+
+    /// Give up on the get with [`Error::Timeout`] when it takes longer than
+    /// this altogether, from detection through the transfer and verification
+    /// to extraction. Copies and extractions stop at the next file or entry
+    /// they get to, and whatever was staged by then is removed.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.total = Some(timeout);
+        self
+    }
+
+    /// Give up on the get with [`Error::Timeout`] when the getter takes
+    /// longer than this to transfer the source.
+    pub fn transfer_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.transfer = Some(timeout);
+        self
+    }
+
+    /// Give up on the get with [`Error::Timeout`] when extracting the source
+    /// takes longer than this.
+    pub fn extract_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.extract = Some(timeout);
+        self
+    }
+
+    /// Try the getter again, as the policy says, when it fails with an
+    /// error that [`Error::is_retryable`]. Getters are tried once by default.
+    /// A source streamed into its extraction is only retried until it starts
+    /// coming in.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Let [`OnExists::Clean`] empty the filesystem root, the home directory,
+    /// or the working directory and its parents, which it otherwise refuses.
+    pub fn allow_dangerous_clean(mut self, allow: bool) -> Self {
+        self.allow_dangerous_clean = allow;
+        self
+    }
+
+    /// Let dest be the filesystem root or the home directory itself, which
+    /// is otherwise refused with [`Error::DangerousDest`]. A dest jail holds
+    /// all the same.
+    pub fn allow_dangerous_dest(mut self, allow: bool) -> Self {
+        self.allow_dangerous_dest = allow;
+        self
+    }
+
+    /// Fail gets whose dest is outside of dir with
+    /// [`Error::DestOutsideJail`], symlinks among the directories dest is in
+    /// resolved, like dests derived from sources with
+    /// [`RequestBuilder::dest_template`].
+    pub fn dest_jail(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dest_jail = Some(dir.into());
+        self
+    }
+
+    /// Take relative sources, relative dests and a relative dest jail as
+    /// relative to dir rather than to the working directory of the process,
+    /// which is shared by everything running in it. Sources are only taken
+    /// from it by the built-in file detector and getter.
+    pub fn base_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        self.base.dir = Some(dir.clone());
+        self.file = self.file.base_dir(dir);
+        let file = self.file.clone();
+        self.add_getter("file", Box::new(file)).with_file_detector()
+    }
+
+    /// Take sources of `~` as the home directory, and those starting with
+    /// `~/` as paths in it, the way a shell would have before handing them
+    /// over. Off unless set, so that a directory named `~` can be fetched
+    /// as it is. Only the built-in file detector expands them.
+    pub fn expand_home(mut self, expand: bool) -> Self {
+        self.base.expand_home = expand;
+        self.with_file_detector()
+    }
+
+    /// path, made relative to the base dir when it's relative.
+    fn in_base_dir(&self, path: &Path) -> PathBuf {
+        match &self.base.dir {
+            Some(base) => base.join(path),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Trust signatures made by this armored OpenPGP public key when checking
+    /// a `?signature=`. Call it again to trust more keys.
+    #[cfg(feature = "gpg")]
+    pub fn gpg_key(mut self, armored: impl Into<String>) -> Self {
+        self.keys.gpg.push(armored.into());
+        self
+    }
+
+    /// Trust signatures made by this minisign public key, given as the
+    /// contents of its `.pub` file or just its base64 line, when checking a
+    /// `?signature=` in the `.minisig` format. Call it again to trust more
+    /// keys.
+    #[cfg(feature = "minisign")]
+    pub fn minisign_key(mut self, key: impl Into<String>) -> Self {
+        self.keys.minisign.push(key.into());
+        self
+    }
+
+    /// Hash the downloaded bytes even without a `?checksum=` to check them
+    /// against, see [`GetResult::checksum`].
+    pub fn record_checksum(mut self, record: bool) -> Self {
+        self.record_checksum = record;
+        self
+    }
+
+    /// Check downloads against the checksums stores like S3 keep for them
+    /// when the source isn't given one, which is the default. Turning it off
+    /// saves the lookup and the hashing.
+    pub fn verify_remote_checksums(mut self, verify: bool) -> Self {
+        self.verify_remote_checksums = verify;
+        self
+    }
+
+    /// Work out what the get would do without transferring or writing
+    /// anything, see [`GetResult::dry_run`]. Detection runs as usual, and
+    /// the getter is asked what it can tell cheaply, like whether the source
+    /// exists and how big it is. Sums files aren't fetched, so sources are
+    /// only checked against what's at dest with a `?checksum=` of their own.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Cap the bytes a get into memory may take up, 64 MiB unless set, see
+    /// [`RequestBuilder::get_bytes`]. Larger sources fail with
+    /// [`Error::TooLargeForMemory`] rather than being buffered.
+    pub fn max_in_memory(mut self, bytes: u64) -> Self {
+        self.max_in_memory = bytes;
+        self
+    }
+
+    /// Bytes streaming gets read and write at a time, 64 KiB unless set.
+    /// Downloads streamed to dest or into an extraction only ever hold a
+    /// few buffers of this size, however large they are, so a smaller one
+    /// suits containers short on memory and a larger one fast links.
+    /// Getters of your own get it as [`GetOptions::buffer_size`], and
+    /// [`getters::copy_to_file`] copies with it.
+    pub fn buffer_size(mut self, bytes: usize) -> Self {
+        // an empty buffer reads nothing, which is the end of any download
+        self.options.buffer_size = Some(bytes.max(1));
+        self
+    }
+
+    /// How gets check that what they fetch fits at dest before writing it,
+    /// see [`SpaceCheck`], or none to not check. Checked with the defaults
+    /// unless set.
+    pub fn space_check(mut self, check: Option<SpaceCheck>) -> Self {
+        self.space = check;
+        self
+    }
+
+    /// Hash the tree the source ends up as at dest, see
+    /// [`GetResult::tree_checksum`]. Always done when `?tree_checksum=` is
+    /// given to check it against.
+    pub fn tree_checksum(mut self, compute: bool) -> Self {
+        self.tree_checksum = compute;
+        self
+    }
+
+    /// Set how symlink and hardlink entries of archives are treated.
+    pub fn link_policy(mut self, policy: LinkPolicy) -> Self {
+        self.decompress_opts.links = policy;
+        self
+    }
+
+    /// Override the limits archives are extracted under, see
+    /// [`DecompressLimits`] for the defaults.
+    pub fn decompress_limits(mut self, limits: DecompressLimits) -> Self {
+        self.decompress_opts.limits = limits;
+        self
+    }
+
+    /// Only let the file detector match paths that exist on disk, so that
+    /// other detectors get a chance at inputs that aren't local files.
+    pub fn strict_file_detection(mut self, strict: bool) -> Self {
+        self.strict_file_detection = strict;
+        self.with_file_detector()
+    }
+
+    /// Replaces the file detector with one made from the settings.
+    fn with_file_detector(mut self) -> Self {
+        let mut detector = match self.strict_file_detection {
+            true => detectors::File::strict(),
+            false => detectors::File::default(),
+        };
+        if let Some(base) = &self.base.dir {
+            detector = detector.base_dir(base);
+        }
+        detector = detector.expand_home(self.base.expand_home);
+        for d in self.detectors.iter_mut() {
+            if d.name() == "file" {
+                *d = Arc::new(detector.clone());
+            }
+        }
+        self
+    }
+}
+
+impl<D> RequestBuilder<Src, D> {
+    /// Runs detection without fetching anything, returning what [`get`] is
+    /// about to do with the source.
+    ///
+    /// [`get`]: RequestBuilder::get
+    pub fn resolve(&self) -> Result<Detection, Error> {
+        self.resolve_src(&self.src.0)
+    }
+}
+
+impl RequestBuilder<Src, Dest> {
+    pub async fn get(&self) -> Result<GetResult, Error> {
+        self.get_src(&self.src.0, &self.dest.0).await
+    }
+}
+
+impl RequestBuilder<NoSrc, NoDest> {
+    /// Queue up a source to be fetched to dest along with others, with the
+    /// settings of this builder, see [`MultiRequestBuilder`].
+    pub fn queue(self, src: impl Into<String>, dest: impl AsRef<Path>) -> MultiRequestBuilder {
+        MultiRequestBuilder::new(self).queue(src, dest)
+    }
+
+    /// Queue up a source to be fetched to the dest the template derives
+    /// from it along with others, see [`MultiRequestBuilder::queue_template`].
+    pub fn queue_template(
+        self,
+        src: impl Into<String>,
+        template: &str,
+    ) -> Result<MultiRequestBuilder, Error> {
+        MultiRequestBuilder::new(self).queue_template(src, template)
+    }
+
+    /// Finishes the builder into a [`Client`] that fetches any number of
+    /// sources with its settings, from any number of tasks at once.
+    pub fn build(self) -> Client {
+        Client::new(self)
+    }
+}
+
+impl<S, D> RequestBuilder<S, D> {
+    fn detect(&self, src: &str) -> Result<String, Error> {
+        let (is_force, input) = get_forced_proto(src);
+
+        if Url::parse(input).is_ok() {
+            return Ok(src.to_string());
+        }
+
+        let mut attempts = Vec::new();
+        for d in self.detectors.iter() {
+            // the file detector claims anything that isn't a url unless it's
+            // strict, which would hide why the detectors before it failed
+            let errored = attempts
+                .iter()
+                .any(|(_, a)| matches!(a, DetectAttempt::Errored(_)));
+            if errored && d.name() == "file" && !self.strict_file_detection {
+                break;
+            }
+            // detectors see the source without the forced getter, it's re-applied below
+            let res = match d.detect(input) {
+                Ok(Some(res)) => res,
+                Ok(None) => {
+                    attempts.push((d.name().to_string(), DetectAttempt::Declined));
+                    continue;
+                }
+                Err(e) => {
+                    attempts.push((d.name().to_string(), DetectAttempt::Errored(Box::new(e))));
+                    continue;
+                }
+            };
+
+            let (forced_proto, src) = get_forced_proto(&res);
+
+            if let Some(is_force) = is_force {
+                return Ok(format!("{}+{}", is_force, src));
+            } else if let Some(forced_proto) = forced_proto {
+                return Ok(format!("{}+{}", forced_proto, src));
+            }
+
+            return Ok(src.to_string());
+        }
+
+        Err(Error::DetectionFailed {
+            input: src.to_string(),
+            attempts,
+        })
+    }
+
+    /// Host the source is fetched from, which the gets of a batch are
+    /// limited by, none for local sources and those that don't resolve.
+    pub(crate) fn host_of(&self, src: &str) -> Option<String> {
+        let detection = self.resolve_src(src).ok()?;
+        let getter = self.getter(&detection.getter).ok()?;
+        throttle::host_key(&getter.network_url(&detection.url)?)
+    }
+
+    fn resolve_src(&self, src: &str) -> Result<Detection, Error> {
+        let src = self.detect(src)?;
+
+        let (forced, src) = get_forced_proto(&src);
+        let (src, query) = GetOptions::parse(src, self.strict_params)?;
+        let options = self.options.clone().or(query);
+        let (src, subpath) = get_subpath(&src);
+
+        let url = Url::parse(&src)?;
+        let getter = forced.unwrap_or(url.scheme()).to_string();
+        let url = match self.getter(&getter) {
+            Ok(g) => g.resolve(&url, &options)?.unwrap_or(url),
+            Err(_) => url,
+        };
+        let (archive, sniff_archive) =
+            detectors::archive_format(url.as_str(), options.archive.as_ref(), &self.formats())?;
+        let params = url.query_pairs().into_owned().collect();
+
+        // relative sums and signature files sit next to the source and are
+        // fetched the same way
+        let beside = |file: Option<String>| -> Result<Option<String>, Error> {
+            Ok(match file {
+                Some(file) if Url::parse(get_forced_proto(&file).1).is_err() => {
+                    let joined = url.join(&file)?;
+                    Some(match forced {
+                        Some(forced) => format!("{}+{}", forced, joined),
+                        None => joined.to_string(),
+                    })
+                }
+                file => file,
+            })
+        };
+        let options = GetOptions {
+            checksum_file: beside(options.checksum_file)?,
+            signature: beside(options.signature)?,
+            getter_config: match self.getter_config.get(&getter) {
+                Some(config) => config.clone(),
+                None => options.getter_config,
+            },
+            ..options
+        };
+
+        // an unknown getter fails the get before it goes anywhere anyway
+        if let Ok(g) = self.getter(&getter) {
+            check_host(options.allowed_hosts.as_ref(), g, &url)?;
+        }
+
+        Ok(Detection {
+            getter,
+            url,
+            subpath,
+            archive,
+            sniff_archive,
+            options,
+            params,
+        })
+    }
+
+    /// Registered archive formats along with the extensions they claim.
+    fn formats(&self) -> Vec<(&str, &[&str])> {
+        self.decompressors
+            .iter()
+            .map(|(name, d)| (name.as_str(), d.extensions()))
+            .collect()
+    }
+
+    /// Fetches src to dest, what [`RequestBuilder::get`] does for the source
+    /// and dest it was built with.
+    pub(crate) async fn get_src(&self, src: &str, dest: &DestPath) -> Result<GetResult, Error> {
+        // the timings are recorded on the span as it closes
+        let span = tracing::info_span!(
+            "get",
+            src,
+            getter = tracing::field::Empty,
+            bytes = tracing::field::Empty,
+            total_ms = tracing::field::Empty,
+            detection_ms = tracing::field::Empty,
+            locking_ms = tracing::field::Empty,
+            transfer_ms = tracing::field::Empty,
+            verification_ms = tracing::field::Empty,
+            extraction_ms = tracing::field::Empty,
+            post_processing_ms = tracing::field::Empty,
+            average_rate = tracing::field::Empty,
+            peak_rate = tracing::field::Empty,
+        );
+        let stopwatch = Stopwatch::start();
+        let res = self
+            .dispatch(src, dest, &stopwatch)
+            .instrument(span.clone())
+            .await
+            .map(|result| {
+                // a download taken from the cache wasn't transferred
+                let transferred = if result.cached { 0 } else { result.bytes };
+                GetResult {
+                    timings: stopwatch.finish(transferred),
+                    ..result
+                }
+            });
+        if let Ok(result) = &res {
+            span.record("getter", result.getter.as_str());
+            span.record("bytes", result.bytes);
+            result.timings.record(&span);
+        }
+        if let Some(events) = &self.events {
+            match &res {
+                Ok(result) => events.on_complete(result),
+                Err(e) => events.on_failed(e),
+            }
+        }
+        res
+    }
+
+    async fn dispatch(
+        &self,
+        src: &str,
+        dest: &DestPath,
+        stopwatch: &Stopwatch,
+    ) -> Result<GetResult, Error> {
+        let deadlines = self.timeouts.start();
+        let mut context = ErrorContext::new(src, &dest.given());
+        let mut detection = self.resolve_src(src).context(&context, Phase::Detection)?;
+        if let Some(events) = &self.events {
+            events.on_detect(&detection);
+        }
+        context.url = Some(detection.url.clone());
+
+        let getter = self
+            .getter(&detection.getter)
+            .context(&context, Phase::Detection)?;
+
+        // a dest named after the checksum of the source is only known once
+        // it's looked up, the sums file being fetched to scratch for it
+        let looked_up = match dest {
+            DestPath::Template(template)
+                if template.uses_checksum()
+                    && (!self.dry_run || detection.options.checksum_file.is_none()) =>
+            {
+                let scratch = staging::scratch();
+                let looked_up = self.lookup_checksum(getter, &mut detection, &scratch, &deadlines);
+                Some(looked_up.await.context(&context, Phase::Detection)?)
+            }
+            _ => None,
+        };
+        let given = match dest {
+            DestPath::Path(path) => self.in_base_dir(path),
+            DestPath::Template(template) => self.in_base_dir(
+                &template
+                    .render(&detection)
+                    .context(&context, Phase::Detection)?,
+            ),
+        };
+        let dest = match detection.archive {
+            Some(_) => given.clone(),
+            None => dest::final_path(&given, &detection.url),
+        };
+        context.dest = dest.clone();
+        // deep dests only fit on Windows in the extended form, errors
+        // showing dest as it was given
+        let given = dest::extended(&given);
+        let dest = dest::extended(&dest);
+        let dest = dest.as_path();
+        let jail = self.dest_jail.as_deref().map(|jail| self.in_base_dir(jail));
+        dest::check(dest, jail.as_deref(), self.allow_dangerous_dest)
+            .context(&context, Phase::Detection)?;
+        // the lock is the first to create them
+        let created_dirs = self.perms.missing_dirs(dest);
+        stopwatch.enter(Phase::Locking);
+        // a source that may be sniffed to be an archive may be extracted into
+        // the directory it was given, which is locked as a whole
+        let locked = match detection.sniff_archive {
+            true => given.as_path(),
+            false => dest,
+        };
+        // a lock is a file of its own, which a dry run may not write
+        let _lock = match self.dry_run {
+            true => None,
+            false => Some(
+                within(
+                    deadlines.end(),
+                    Phase::Locking,
+                    lock::DestLock::acquire(locked, self.lock_timeout),
+                )
+                .await
+                .context(&context, Phase::Locking)?,
+            ),
+        };
+        if let (Some(dir), false) = (&self.staging_dir, self.dry_run) {
+            let created = fs::create_dir_all(dir).map_err(Error::DestinationNotCreated);
+            created.context(&context, Phase::Locking)?;
+        }
+
+        let sidecar = checksum::sidecar_path(dest);
+        // what a receipt records takes the place of the sidecar
+        #[cfg(feature = "serde")]
+        let recorded = receipts::recorded(&context.dest, self.receipts_dir.as_deref());
+        #[cfg(not(feature = "serde"))]
+        let recorded = None;
+        let recorded = recorded.or_else(|| {
+            let record = fs::read_to_string(sidecar.as_ref()?).ok()?;
+            Some(Record::parse(&record))
+        });
+        // nothing may go over the network before this
+        stopwatch.enter(Phase::Verification);
+        let skipped = self
+            .skip_reason(&detection, dest, recorded.as_ref())
+            .await
+            .context(&context, Phase::Verification)?;
+        stopwatch.enter(Phase::Detection);
+        // a fingerprint is only looked up for gets that weren't skipped
+        // already, and only ever recorded when it's looked up
+        let fingerprint = match skipped.is_none() && self.update {
+            true => {
+                let lookup =
+                    self.retrying(|| getter.fingerprint(&detection.url, &detection.options));
+                within(deadlines.end(), Phase::Detection, lookup)
+                    .await
+                    .context(&context, Phase::Detection)?
+                    .filter(|f| !f.is_empty() && !f.contains('\n'))
+            }
+            false => None,
+        };
+        let skipped = match &fingerprint {
+            Some(fingerprint)
+                if is_unchanged(&detection.url, fingerprint, dest, recorded.as_ref()) =>
+            {
+                Some(SkipReason::Unchanged)
+            }
+            _ => skipped,
+        };
+        if skipped.is_none() {
+            self.check_mode(getter, &detection)
+                .await
+                .context(&context, Phase::Detection)?;
+        }
+
+        let mut checksum_source = match &looked_up {
+            Some(Some(source)) => Some(source.clone()),
+            _ => detection
+                .options
+                .checksum
+                .as_ref()
+                .map(|_| ChecksumSource::Url),
+        };
+        if let Some(reason) = skipped {
+            // a skipped source was never checked against anything looked up
+            checksum_source = checksum_source.filter(|_| reason == SkipReason::ChecksumMatched);
+        } else if looked_up.is_none()
+            && (!self.dry_run || detection.options.checksum_file.is_none())
+        {
+            // a dry run fetches no sums file
+            let looked_up = self.lookup_checksum(getter, &mut detection, dest, &deadlines);
+            if let Some(source) = looked_up.await.context(&context, Phase::Detection)? {
+                checksum_source = Some(source);
+            }
+        }
+
+        let result = |fetched: Fetched, not_modified| GetResult {
+            final_path: dest.to_path_buf(),
+            bytes: fetched.bytes,
+            getter: detection.getter.clone(),
+            source_url: detection.url.clone(),
+            not_modified,
+            skipped: None,
+            archive: fetched.archive,
+            checksum: fetched.checksum,
+            checksum_source: checksum_source.clone(),
+            tree_checksum: fetched.tree_checksum,
+            fallback: fetched.fallback,
+            warnings: fetched.warnings,
+            dry_run: self.dry_run,
+            cached: fetched.cached,
+            timings: Timings::default(),
+        };
+
+        if let Some(reason) = skipped {
+            let matched = reason == SkipReason::ChecksumMatched;
+            let fetched = Fetched {
+                checksum: detection.options.checksum.clone().filter(|_| matched),
+                ..Default::default()
+            };
+            let not_modified = matched || reason == SkipReason::Unchanged;
+            return Ok(GetResult {
+                skipped: Some(reason),
+                ..result(fetched, not_modified)
+            });
+        }
+        if let Some(checksum) = &detection.options.checksum {
+            stopwatch.enter(Phase::Verification);
+            let current = is_current(checksum, dest, recorded.as_ref()).await;
+            if current.context(&context, Phase::Verification)? {
+                let fetched = Fetched {
+                    checksum: Some(checksum.clone()),
+                    ..Default::default()
+                };
+                return Ok(result(fetched, true));
+            }
+            stopwatch.enter(Phase::Detection);
+        }
+        // only what's extracted merges with dest, anything else replaces it.
+        // A source that may yet be sniffed to be an archive is only refused
+        // once it's known not to be one
+        if detection.archive.is_none() {
+            let should_fetch = match dest::should_fetch(dest, self.on_conflict) {
+                Err(_) if detection.sniff_archive && !self.dry_run => true,
+                should_fetch => should_fetch.context(&context, Phase::Transfer)?,
+            };
+            if !should_fetch {
+                return Ok(GetResult {
+                    skipped: Some(SkipReason::OnConflict),
+                    ..result(Fetched::default(), false)
+                });
+            }
+        }
+        if self.dry_run {
+            let size = getter.size(&detection.url, &detection.options).await;
+            let fetched = Fetched {
+                archive: detection.archive.clone(),
+                bytes: size.context(&context, Phase::Transfer)?.unwrap_or_default(),
+                checksum: detection.options.checksum.clone(),
+                ..Default::default()
+            };
+            return Ok(result(fetched, false));
+        }
+        // whatever ends up in dest from here on isn't what was recorded
+        if let Some(sidecar) = &sidecar {
+            remove_download(sidecar).context(&context, Phase::Transfer)?;
+        }
+        #[cfg(feature = "serde")]
+        if let Some(receipt) = receipts::path(&context.dest, self.receipts_dir.as_deref()) {
+            remove_download(&receipt).context(&context, Phase::Transfer)?;
+        }
+
+        let signature = self.fetch_signature(&detection, dest, &deadlines);
+        let signature = signature.await.context(&context, Phase::Detection)?;
+
+        if let Some(events) = &self.events {
+            events.on_start(&detection.url, dest);
+        }
+        stopwatch.enter(Phase::Transfer);
+        let template = result(Fetched::default(), false);
+        let fetched = self
+            .fetch(
+                getter,
+                &detection,
+                signature.as_deref(),
+                Target {
+                    template: &template,
+                    given: &given,
+                },
+                Clock {
+                    deadlines: &deadlines,
+                    stopwatch,
+                },
+                &context,
+            )
+            .await
+            .context(&context, Phase::Transfer)?;
+        let created = self.perms.apply_dirs(&created_dirs);
+        created.context(&context, Phase::Transfer)?;
+        if let Some(events) = &self.events {
+            let verified = [
+                (&detection.options.checksum, &fetched.checksum),
+                (&detection.options.tree_checksum, &fetched.tree_checksum),
+            ];
+            for (_, checksum) in verified
+                .into_iter()
+                .filter(|(expected, _)| expected.is_some())
+            {
+                checksum.iter().for_each(|c| events.on_verified(c));
+            }
+        }
+        // a receipt records the source in place of a sidecar
+        #[cfg(feature = "serde")]
+        let sidecar = match receipts::path(&context.dest, self.receipts_dir.as_deref())
+            .filter(|_| self.write_receipt)
+        {
+            Some(path) => {
+                let transferred = if fetched.cached { 0 } else { fetched.bytes };
+                let result = GetResult {
+                    timings: stopwatch.finish(transferred),
+                    ..fetched.clone()
+                };
+                let receipt = receipts::Receipt::new(src, fingerprint.clone(), result);
+                receipt
+                    .write(&path)
+                    .context(&context, Phase::Verification)?;
+                None
+            }
+            None => sidecar,
+        };
+        if let Some(sidecar) = &sidecar {
+            let source = self.skip_existing != SkipExisting::Never || self.update;
+            let record = Record::of(&fetched, source, fingerprint);
+            if record.checksum.is_some() || record.source.is_some() {
+                let written = fs::write(sidecar, record.to_string()).map_err(Error::from);
+                written.context(&context, Phase::Verification)?;
+            }
+        }
+
+        Ok(fetched)
+    }
+
+    /// Fetches the source into the dest of the target, extracting it there if
+    /// it's an archive, and returns its template filled in with what was done.
+    async fn fetch(
+        &self,
+        getter: &(dyn Getter + Send + Sync),
+        detection: &Detection,
+        signature: Option<&[u8]>,
+        target: Target<'_>,
+        clock: Clock<'_>,
+        context: &ErrorContext,
+    ) -> Result<GetResult, Error> {
+        let Clock {
+            deadlines,
+            stopwatch,
+        } = clock;
+        let Target {
+            mut template,
+            given,
+        } = target;
+        let dest = template.final_path.as_path();
+        let (downloaded, bytes, sniffed);
+        let (format, download) = match &detection.archive {
+            Some(format) => {
+                // a signed archive is checked as a whole before it's extracted,
+                // and a cached one is kept whole
+                let streaming = self
+                    .decompressors
+                    .get(format)
+                    .and_then(|d| d.streaming())
+                    .filter(|_| signature.is_none() && self.cache_key(detection).is_none());
+                self.check_space(getter, detection, streaming.is_some(), dest, deadlines)
+                    .await?;
+                if let Some(extract) = streaming {
+                    let transfer = deadlines.transfer();
+                    let body = self.retrying(|| getter.open(&detection.url, &detection.options));
+                    if let Some(body) = within(transfer, Phase::Transfer, body).await? {
+                        let staged = Staged {
+                            format,
+                            detection,
+                            deadline: deadlines.extract(),
+                            template,
+                            stopwatch,
+                            context,
+                        };
+                        return self
+                            .staged(staged, |out, opts| async {
+                                let download = decompressors::Download {
+                                    body,
+                                    deadline: transfer,
+                                    buffer_size: detection
+                                        .options
+                                        .buffer_size
+                                        .unwrap_or(getters::DEFAULT_BUFFER_SIZE),
+                                    algo: self.checksum_algo(detection),
+                                    expected: detection.options.checksum.as_ref(),
+                                    stopwatch,
+                                };
+                                let (bytes, checksum) =
+                                    decompressors::stream(extract, download, out, opts).await?;
+                                Ok(Fetched {
+                                    bytes,
+                                    checksum,
+                                    ..Default::default()
+                                })
+                            })
+                            .await
+                            .context(context, Phase::Extraction);
+                    }
+                }
+
+                let download = TempDest::new_in(dest, self.staging_dir.as_deref());
+                downloaded = self
+                    .download(getter, detection, signature, &download, clock, context)
+                    .await?;
+                bytes = fs::metadata(download.path())?.len();
+                (format.clone(), download)
+            }
+            None => {
+                self.check_space(getter, detection, false, dest, deadlines)
+                    .await?;
+                // kept out of the directory it may be extracted into
+                let download = TempDest::new_in(given, self.staging_dir.as_deref());
+                downloaded = self
+                    .download(getter, detection, signature, &download, clock, context)
+                    .await?;
+                bytes = download_size(download.path())?;
+
+                let mode = detection.options.get_mode.unwrap_or_default();
+                let format = match detection.sniff_archive && download.path().is_file() {
+                    true => decompressors::sniff(download.path())
+                        .context(context, Phase::Extraction)?
+                        .filter(|format| self.decompressors.contains_key(*format)),
+                    false => None,
+                };
+                let Some(format) = format else {
+                    stopwatch.enter(Phase::Verification);
+                    mode.check(download.path())
+                        .context(context, Phase::Verification)?;
+                    let hashed = self.hash_tree(detection, download.path());
+                    let tree_checksum = within(deadlines.end(), Phase::Verification, hashed)
+                        .await
+                        .context(context, Phase::Verification)?;
+                    self.perms.apply_tree(download.path())?;
+                    let fetched = Fetched {
+                        bytes,
+                        tree_checksum,
+                        ..downloaded
+                    };
+                    let mut result = fetched.into_result(template);
+                    stopwatch.enter(Phase::PostProcessing);
+                    self.run_post_processors(download.path(), &mut result, context)
+                        .await?;
+                    // dest is only touched once what replaces it is complete
+                    self.make_room(dest)?;
+                    download.promote(dest)?;
+                    return Ok(result);
+                };
+                // an archive goes into dest only once it's extracted in full,
+                // and into a directory rather than next to what's in it
+                sniffed = GetResult {
+                    final_path: given.to_path_buf(),
+                    ..template.clone()
+                };
+                template = &sniffed;
+                (format.to_string(), download)
+            }
+        };
+
+        let staged = Staged {
+            format: &format,
+            detection,
+            deadline: deadlines.extract(),
+            template,
+            stopwatch,
+            context,
+        };
+        let fetched = Fetched {
+            bytes,
+            ..downloaded
+        };
+        let extracted = self.decompress(staged, download.path(), fetched);
+        within(staged.deadline, Phase::Extraction, extracted)
+            .await
+            .context(context, Phase::Extraction)
+    }
+
+    /// Fails before anything is transferred when the source is fetched over
+    /// the network, its getter tells its size and it doesn't fit at dest.
+    /// Archives extracted as they're streamed are checked against what
+    /// they're estimated to extract to instead.
+    async fn check_space(
+        &self,
+        getter: &(dyn Getter + Send + Sync),
+        detection: &Detection,
+        streaming: bool,
+        dest: &Path,
+        deadlines: &Deadlines,
+    ) -> Result<(), Error> {
+        let Some(space) = &self.space else {
+            return Ok(());
+        };
+        if getter.network_url(&detection.url).is_none() {
+            return Ok(());
+        }
+        // a size that can't be told leaves the transfer to find out
+        let size = getter.size(&detection.url, &detection.options);
+        let Ok(Some(size)) = within(deadlines.transfer(), Phase::Transfer, size).await else {
+            return Ok(());
+        };
+        // it's written to the staging directory before it's moved to dest
+        for path in iter::once(dest).chain(self.staging_dir.as_deref()) {
+            match &detection.archive {
+                Some(format) if streaming => {
+                    space.ensure_estimate(path, space::estimated_size(format, size))?
+                }
+                _ => space.ensure(path, size)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Has the getter download the source to the temporary path, checking
+    /// what it downloaded, or takes it from the cache when it's there.
+    async fn download(
+        &self,
+        getter: &(dyn Getter + Send + Sync),
+        detection: &Detection,
+        signature: Option<&[u8]>,
+        download: &TempDest,
+        clock: Clock<'_>,
+        context: &ErrorContext,
+    ) -> Result<Fetched, Error> {
+        let Clock {
+            deadlines,
+            stopwatch,
+        } = clock;
+        let cache = self.cache.as_ref().zip(self.cache_key(detection));
+        // the cache is only ever a shortcut, a get never fails because of it
+        if let Some((cache, key)) = &cache {
+            match cache.get(key, download.path()) {
+                Ok(true) => match self
+                    .check_download(detection, signature, download.path(), None)
+                    .await
+                {
+                    Ok(checksum) => {
+                        return Ok(Fetched {
+                            checksum,
+                            cached: true,
+                            ..Default::default()
+                        })
+                    }
+                    Err(e) => {
+                        tracing::warn!(%key, error = %e, "cached download doesn't check out, dropping it");
+                        if let Err(e) = cache.remove(key) {
+                            tracing::warn!(%key, error = %e, "failed to drop cached download");
+                        }
+                        download.clear()?;
+                    }
+                },
+                Ok(false) => {}
+                Err(e) => tracing::warn!(%key, error = %e, "failed to read the cache"),
+            }
+        }
+
+        // the getter hashes what it writes as it goes, sparing a second read
+        let options = GetOptions {
+            hash: self.checksum_algo(detection),
+            ..detection.options.clone()
+        };
+        let got = self.retrying(|| async {
+            download.clear()?;
+            getter
+                .get_with_report(download.path(), &detection.url, &options)
+                .await
+        });
+        let got = async {
+            tokio::select! {
+                report = got => report,
+                never = stopwatch.watch(download.path()) => match never {},
+            }
+        };
+        let report = within(deadlines.transfer(), Phase::Transfer, got).await?;
+        stopwatch.enter(Phase::Verification);
+        let streamed = report.checksum.clone();
+        let checked = self.check_download(detection, signature, download.path(), streamed);
+        let checksum = within(deadlines.end(), Phase::Verification, checked)
+            .await
+            .context(context, Phase::Verification)?;
+
+        // only files are kept, a directory is as good as a local source
+        let is_file = fs::symlink_metadata(download.path()).is_ok_and(|m| m.is_file());
+        if let Some((cache, key)) = cache.filter(|_| is_file) {
+            if let Err(e) = cache.put(&key, download.path()) {
+                tracing::warn!(%key, error = %e, "failed to cache the download");
+            }
+        }
+        if let Some(events) = &self.events {
+            report.warnings.iter().for_each(|w| events.on_warning(w));
+        }
+        Ok(Fetched {
+            fallback: report.fallback,
+            warnings: report.warnings,
+            checksum,
+            ..Default::default()
+        })
+    }
+
+    /// What the download of the source is cached under, if it's cached.
+    /// Local sources are as close at hand as the cache is, and only a
+    /// source with a checksum can be told apart from other versions of it.
+    fn cache_key(&self, detection: &Detection) -> Option<CacheKey> {
+        let checksum = detection.options.checksum.as_ref()?;
+        (self.cache.is_some() && detection.url.scheme() != "file")
+            .then(|| CacheKey::new(&detection.url, checksum))
+    }
+
+    /// Why the get is to be skipped under the skip_existing policy, if it
+    /// is, going by what's already at dest alone.
+    async fn skip_reason(
+        &self,
+        detection: &Detection,
+        dest: &Path,
+        recorded: Option<&Record>,
+    ) -> Result<Option<SkipReason>, Error> {
+        if self.skip_existing == SkipExisting::Never || !dest.exists() {
+            return Ok(None);
+        }
+        // a tree pinned as a whole is only current when it's the same all
+        // through, which no record of the source can vouch for
+        if let Some(tree) = &detection.options.tree_checksum {
+            if checksum::tree(dest).await? == *tree {
+                return Ok(Some(SkipReason::ChecksumMatched));
+            }
+            return Ok(
+                (self.skip_existing == SkipExisting::IfPresent).then_some(SkipReason::Present)
+            );
+        }
+        if let Some(checksum) = &detection.options.checksum {
+            if is_current(checksum, dest, recorded).await? {
+                return Ok(Some(SkipReason::ChecksumMatched));
+            }
+        }
+        if recorded.and_then(|r| r.source.as_ref()) == Some(&detection.url) {
+            return Ok(Some(SkipReason::Recorded));
+        }
+
+        Ok((self.skip_existing == SkipExisting::IfPresent).then_some(SkipReason::Present))
+    }
+
+    /// Runs a call to a getter, trying it again under the retry policy if
+    /// there is one, each time from scratch.
+    async fn retrying<T, F, Fut>(&self, mut attempt: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        match &self.retry {
+            Some(policy) => {
+                let on_retry = |n: u32, e: &Error| {
+                    if let (true, Some(permit)) = (e.is_throttled(), &self.throttle) {
+                        permit.throttled();
+                    }
+                    if let Some(events) = &self.events {
+                        events.on_retry(n, e);
+                    }
+                };
+                policy.run(attempt, on_retry).await
+            }
+            None => attempt().await,
+        }
+    }
+
+    /// Clears dest for what was fetched according to the on_conflict policy.
+    fn make_room(&self, dest: &Path) -> Result<(), Error> {
+        match dest::make_room(dest, self.on_conflict)? {
+            true => Ok(()),
+            // something turned up at dest while the source was fetched
+            false => Err(Error::DestinationExists(dest.display().to_string())),
+        }
+    }
+
+    /// Algorithm the downloaded bytes are hashed with, if they are at all.
+    fn checksum_algo(&self, detection: &Detection) -> Option<ChecksumAlgo> {
+        match &detection.options.checksum {
+            Some(checksum) => Some(checksum.algo()),
+            None => self.record_checksum.then_some(ChecksumAlgo::Sha256),
+        }
+    }
+
+    /// Checks a downloaded file against the signature and the checksum of the
+    /// source, returning its checksum if it's to be checked or recorded. The
+    /// file is only hashed when the getter didn't as it wrote it, like for
+    /// cached downloads and local sources that were linked.
+    async fn check_download(
+        &self,
+        detection: &Detection,
+        signature: Option<&[u8]>,
+        path: &Path,
+        streamed: Option<Checksum>,
+    ) -> Result<Option<Checksum>, Error> {
+        if let Some(signature) = signature {
+            let keys = self.keys.clone();
+            let (signature, path) = (signature.to_vec(), path.to_path_buf());
+            deadline::blocking(move || {
+                keys.verify(&signature, deadline::Checked(fs::File::open(path)?))
+            })
+            .await?;
+        }
+
+        let Some(algo) = self.checksum_algo(detection) else {
+            return Ok(None);
+        };
+        // a getter may fetch a whole directory, which only a tree checksum covers
+        if detection.options.checksum.is_none() && !path.is_file() {
+            return Ok(None);
+        }
+
+        let actual = match streamed.filter(|c| c.algo() == algo) {
+            Some(streamed) => streamed,
+            None => algo.of_file(path).await?,
+        };
+        if let Some(expected) = &detection.options.checksum {
+            expected.verify(&actual)?;
+        }
+        Ok(Some(actual))
+    }
+
+    /// Hashes the tree at path if it was asked for, checking it against
+    /// `?tree_checksum=`.
+    async fn hash_tree(
+        &self,
+        detection: &Detection,
+        path: &Path,
+    ) -> Result<Option<Checksum>, Error> {
+        if !self.tree_checksum && detection.options.tree_checksum.is_none() {
+            return Ok(None);
+        }
+
+        let actual = checksum::tree(path).await?;
+        if let Some(expected) = &detection.options.tree_checksum {
+            expected.verify_tree(&actual)?;
+        }
+        Ok(Some(actual))
+    }
+
+    /// Looks up the checksum the source is to be checked against, in its
+    /// sums file or, when it wasn't given one, from the store it's kept in,
+    /// and tells where it came from. Sums files are fetched next to dest.
+    async fn lookup_checksum(
+        &self,
+        getter: &(dyn Getter + Send + Sync),
+        detection: &mut Detection,
+        dest: &Path,
+        deadlines: &Deadlines,
+    ) -> Result<Option<ChecksumSource>, Error> {
+        if let Some(file) = detection.options.checksum_file.clone() {
+            let checksum = self.fetch_checksum(&file, detection, dest);
+            detection.options.checksum =
+                Some(within(deadlines.end(), Phase::Detection, checksum).await?);
+            return Ok(Some(ChecksumSource::SumsFile(file)));
+        }
+        if detection.options.checksum.is_none() && self.verify_remote_checksums {
+            let checksum =
+                self.retrying(|| getter.remote_checksum(&detection.url, &detection.options));
+            detection.options.checksum =
+                within(deadlines.end(), Phase::Detection, checksum).await?;
+            let source = detection.options.checksum.as_ref();
+            return Ok(source.map(|_| ChecksumSource::Remote));
+        }
+        Ok(None)
+    }
+
+    /// Fetches the signature over the source itself next to dest, if it has
+    /// one, see [`GetOptions::source_signature`].
+    async fn fetch_signature(
+        &self,
+        detection: &Detection,
+        dest: &Path,
+        deadlines: &Deadlines,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let Some(signature) = detection.options.source_signature() else {
+            return Ok(None);
+        };
+        let signature = self.fetch_file(signature, dest);
+        within(deadlines.end(), Phase::Detection, signature)
+            .await
+            .map(Some)
+    }
+
+    /// Fetches a sums file and picks the checksum of the source out of it,
+    /// once its signature checks out if it has one.
+    async fn fetch_checksum(
+        &self,
+        file: &str,
+        detection: &Detection,
+        dest: &Path,
+    ) -> Result<Checksum, Error> {
+        let sums = self.fetch_file(file, dest).await?;
+        if let Some(signature) = &detection.options.signature {
+            let signature = self.fetch_file(signature, dest).await?;
+            self.keys.verify(&signature, &sums[..])?;
+        }
+
+        let name = detection
+            .url
+            .path_segments()
+            .and_then(|mut s| s.next_back())
+            .unwrap_or_default();
+        checksum::from_sums(file, &String::from_utf8_lossy(&sums), name)
+    }
+
+    /// Fails when the getter can tell the source isn't what the get mode
+    /// expects. Archives may go from being files to being directories, so
+    /// they're only checked once extracted.
+    async fn check_mode(
+        &self,
+        getter: &(dyn Getter + Send + Sync),
+        detection: &Detection,
+    ) -> Result<(), Error> {
+        let expected = detection.options.get_mode.unwrap_or_default();
+        if expected == GetMode::Any || detection.archive.is_some() {
+            return Ok(());
+        }
+        match getter.mode(&detection.url, &detection.options).await? {
+            Some(GetMode::File) if detection.sniff_archive => Ok(()),
+            Some(found) => expected.check_found(found),
+            None => Ok(()),
+        }
+    }
+
+    fn getter(&self, name: &str) -> Result<&(dyn Getter + Send + Sync), Error> {
+        match self.getters.get(name) {
+            Some(getter) => Ok(getter.as_ref()),
+            None => Err(Error::GetterNotFound(
+                name.to_string(),
+                self.getter_names().into_iter().map(String::from).collect(),
+            )),
+        }
+    }
+
+    /// Fetches a small file that goes along with the source, like its sums.
+    async fn fetch_file(&self, file: &str, dest: &Path) -> Result<Vec<u8>, Error> {
+        let (forced, url) = get_forced_proto(file);
+        let url = Url::parse(url)?;
+        let getter = self.getter(forced.unwrap_or(url.scheme()))?;
+        let allowed_hosts = self.options.allowed_hosts.clone();
+        check_host(allowed_hosts.as_ref(), getter, &url)?;
+
+        let options = GetOptions {
+            allowed_hosts,
+            ..Default::default()
+        };
+        let download = TempDest::new_in(dest, self.staging_dir.as_deref());
+        self.retrying(|| async {
+            download.clear()?;
+            getter.get(download.path(), &url, &options).await
+        })
+        .await?;
+        Ok(fs::read(download.path())?)
+    }
+
+    /// Runs the post-processors on what's staged for dest, in the order
+    /// they were added.
+    async fn run_post_processors(
+        &self,
+        staged: &Path,
+        result: &mut GetResult,
+        context: &ErrorContext,
+    ) -> Result<(), Error> {
+        for processor in &self.post_processors {
+            let processed = processor.process(staged, result).await;
+            processed.context(context, Phase::PostProcessing)?;
+        }
+        Ok(())
+    }
+
+    /// Extracts the archive downloaded to archive, fetched being what the
+    /// download did.
+    async fn decompress(
+        &self,
+        staged: Staged<'_>,
+        archive: &Path,
+        fetched: Fetched,
+    ) -> Result<GetResult, Error> {
+        staged.stopwatch.enter(Phase::Extraction);
+        let decompressor = self
+            .decompressors
+            .get(staged.format)
+            .ok_or_else(|| Error::DecompressorNotFound(staged.format.to_string()))?;
+        if let Some(space) = &self.space {
+            if let Some(size) = space::extracted_size(staged.format, archive) {
+                let dest = staged.template.final_path.as_path();
+                for path in iter::once(dest).chain(self.staging_dir.as_deref()) {
+                    space.ensure_estimate(path, size)?;
+                }
+            }
+        }
+
+        self.staged(staged, |out, opts| async move {
+            decompressor.decompress(archive, &out, &opts).await?;
+            Ok(fetched)
+        })
+        .await
+    }
+
+    /// Runs an extraction into a staging directory next to dest and only
+    /// moves the result over once it's complete and post-processed, so that
+    /// a failed extraction leaves nothing behind. Returns the result of the
+    /// get, with the tree checksum of what was extracted if it was asked
+    /// for.
+    async fn staged<F, Fut>(&self, staged: Staged<'_>, extract: F) -> Result<GetResult, Error>
+    where
+        F: FnOnce(PathBuf, DecompressOpts) -> Fut,
+        Fut: Future<Output = Result<Fetched, Error>>,
+    {
+        let Staged {
+            format,
+            detection,
+            deadline,
+            template,
+            stopwatch,
+            context,
+        } = staged;
+        let dest = template.final_path.as_path();
+        let mut opts = DecompressOpts {
+            name: detection
+                .url
+                .path_segments()
+                .and_then(|mut s| s.next_back())
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string()),
+            ..self.decompress_opts.clone()
+        };
+        opts.strip_components = detection.options.strip_components.unwrap_or_default();
+        opts.subpath = detection.subpath.clone();
+        opts.deadline = deadline;
+        opts.password = detection.options.archive_password.clone();
+        // extractions are tallied for the events as well
+        let progress = opts.progress.take();
+        let tally = (progress.is_some() || self.events.is_some())
+            .then(|| Arc::new(progress::Tally::new(progress)));
+        if let Some(tally) = &tally {
+            opts.progress = Some(tally.clone());
+        }
+
+        let on_exists = detection.options.on_exists.unwrap_or_default();
+        if on_exists == OnExists::Clean && !self.allow_dangerous_clean {
+            decompressors::check_clean(dest)?;
+        }
+
+        // removed however the extraction ends, being dropped included
+        let staging = TempDest::staging(dest, self.staging_dir.as_deref());
+        fs::create_dir_all(staging.path()).map_err(Error::DestinationNotCreated)?;
+        let out = if dest.is_dir() {
+            staging.path().to_path_buf()
+        } else {
+            staging.path().join("out")
+        };
+
+        let depth = detection.options.decompress_depth.unwrap_or(1);
+        let res = match extract(out.clone(), opts.clone()).await {
+            Ok(fetched) => {
+                stopwatch.enter(Phase::Extraction);
+                self.nested(&out, opts, depth).await.map(|()| fetched)
+            }
+            Err(e) => Err(e),
+        };
+        stopwatch.enter(Phase::Verification);
+        let mode = detection.options.get_mode.unwrap_or_default();
+        let res = match res.and_then(|fetched| mode.check(&out).map(|()| fetched)) {
+            Ok(fetched) => self
+                .hash_tree(detection, &out)
+                .await
+                .map(|tree_checksum| Fetched {
+                    archive: Some(format.to_string()),
+                    tree_checksum,
+                    ..fetched
+                }),
+            Err(e) => Err(e),
+        }
+        .and_then(|fetched| self.perms.apply_tree(&out).map(|()| fetched));
+        let res = match res {
+            Ok(fetched) => {
+                let mut result = fetched.into_result(template);
+                stopwatch.enter(Phase::PostProcessing);
+                let processed = self.run_post_processors(&out, &mut result, context).await;
+                processed.map(|()| result)
+            }
+            Err(e) => Err(e),
+        }
+        .and_then(|result| decompressors::promote(&out, dest, on_exists).map(|()| result));
+        drop(staging);
+        if let (Ok(_), Some(tally)) = (&res, tally) {
+            let stats = tally.finish(format);
+            if let Some(events) = &self.events {
+                events.on_extracted(&stats);
+            }
+        }
+        res
+    }
+
+    /// Extracts again what an extraction produced, for as long as it's a
+    /// single archive and the depth allows, e.g. a tarball holding a zip.
+    async fn nested(
+        &self,
+        out: &Path,
+        mut opts: DecompressOpts,
+        depth: usize,
+    ) -> Result<(), Error> {
+        opts.strip_components = 0;
+        opts.subpath = None;
+
+        for _ in 1..depth {
+            let Some(inner) = decompressors::single_file(out)? else {
+                return Ok(());
+            };
+            let name = inner
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let format = match detectors::from_extension(&self.formats(), &name) {
+                Some(format) => format.to_string(),
+                None => match decompressors::sniff(&inner)? {
+                    Some(format) => format.to_string(),
+                    None => return Ok(()),
+                },
+            };
+            let Some(decompressor) = self.decompressors.get(&format) else {
+                return Ok(());
+            };
+
+            // what was extracted so far counts against the limits too
+            let size = fs::metadata(&inner)?.len();
+            opts.limits.max_total_bytes = opts.limits.max_total_bytes.saturating_sub(size);
+            opts.name = Some(name);
+
+            // the archive makes way for what's extracted from it
+            let archive = staging::archive_path(out);
+            fs::rename(&inner, &archive)?;
+            if inner != out {
+                fs::remove_dir(out)?;
+            }
+            let res = decompressor.decompress(&archive, out, &opts).await;
+            remove_download(&archive)?;
+            res?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes what a getter downloaded, if it got as far as creating it.
+fn remove_download(path: &Path) -> Result<(), Error> {
+    if fs::symlink_metadata(path).is_ok() {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+/// Size of what a getter downloaded, adding up the files of a directory.
+fn download_size(path: &Path) -> Result<u64, Error> {
+    // not every getter has something to put there
+    let Ok(meta) = fs::metadata(path) else {
+        return Ok(0);
+    };
+    if !meta.is_dir() {
+        return Ok(meta.len());
+    }
+
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_symlink() {
+            size += download_size(&dest::extended(&entry.path()))?;
+        }
+    }
+    Ok(size)
+}
+
+/// Whether dest already holds the source with this checksum, either as the
+/// file itself or as what was extracted from an archive with it.
+async fn is_current(
+    checksum: &Checksum,
+    dest: &Path,
+    recorded: Option<&Record>,
+) -> Result<bool, Error> {
+    if !dest.exists() {
+        return Ok(false);
+    }
+    if let Some(recorded) = recorded.and_then(|r| r.checksum.as_ref()) {
+        return Ok(recorded == checksum);
+    }
+
+    Ok(dest.is_file() && checksum.matches_file(dest).await?)
+}
+
+/// Fails unless the hosts allowed, if there's a list of them, allow what
+/// the getter contacts to fetch the source at url.
+fn check_host(
+    allowed: Option<&AllowedHosts>,
+    getter: &(dyn Getter + Send + Sync),
+    url: &Url,
+) -> Result<(), Error> {
+    match (allowed, getter.network_url(url)) {
+        (Some(allowed), Some(url)) => allowed.check(&url),
+        _ => Ok(()),
+    }
+}
+
+/// Whether gette recorded fetching the source at url into dest when it had
+/// the same fingerprint it has now.
+fn is_unchanged(url: &Url, fingerprint: &str, dest: &Path, recorded: Option<&Record>) -> bool {
+    let Some(record) = recorded else {
+        return false;
+    };
+    dest.exists()
+        && record.source.as_ref() == Some(url)
+        && record.fingerprint.as_deref() == Some(fingerprint)
+}
+
+/// What a get did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct GetResult {
+    /// Where the source ended up. On Windows, in the `\\?\` extended-length
+    /// form when it's too long for the classic one.
+    pub final_path: PathBuf,
+    /// How many bytes were downloaded, counting every file of sources that
+    /// are fetched as a directory.
+    pub bytes: u64,
+    /// Name of the getter that fetched the source.
+    pub getter: String,
+    /// Url the getter fetched the source from.
+    pub source_url: Url,
+    /// Dest already held the source with the checksum it was asked for, so
+    /// nothing was fetched.
+    pub not_modified: bool,
+    /// Why nothing was fetched because of what dest already held, under
+    /// [`RequestBuilder::skip_existing`] or [`OnConflict::Skip`].
+    pub skipped: Option<SkipReason>,
+    /// Archive format the source was extracted with, if it was.
+    pub archive: Option<String>,
+    /// Checksum of the downloaded bytes, of the archive itself for sources
+    /// that are extracted. Computed in the algorithm of the checksum it was
+    /// checked against, or in sha256 when [`RequestBuilder::record_checksum`]
+    /// asks for it.
+    pub checksum: Option<Checksum>,
+    /// Where the checksum the download was checked against came from, if it
+    /// was checked.
+    pub checksum_source: Option<ChecksumSource>,
+    /// Tree checksum of what the source ended up as, before it was merged
+    /// with whatever dest held already. Only computed when asked for with
+    /// [`RequestBuilder::tree_checksum`] or `?tree_checksum=`.
+    pub tree_checksum: Option<Checksum>,
+    /// What the getter did instead of what it was asked to, like copying a
+    /// source it couldn't hardlink because dest is on another filesystem.
+    pub fallback: Option<String>,
+    /// What the getter left out without failing the get, see
+    /// [`GetReport::warnings`]. Each was passed to
+    /// [`EventHandler::on_warning`] as well.
+    pub warnings: Vec<String>,
+    /// Nothing was transferred or written, this is what the get would do,
+    /// see [`RequestBuilder::dry_run`]. Bytes are the size of the source
+    /// when the getter could tell, and the archive is only known when the
+    /// source isn't sniffed for one.
+    pub dry_run: bool,
+    /// The download was taken from the cache rather than fetched, see
+    /// [`RequestBuilder::cache`].
+    pub cached: bool,
+    /// How long the get took, phase by phase, and how fast it transferred.
+    /// Also recorded on the `get` tracing span as it closes.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub timings: Timings,
+}
+
+/// How a getter went about fetching a source, see
+/// [`Getter::get_with_report`].
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct GetReport {
+    /// What the getter did instead of what it was asked to, and why.
+    pub fallback: Option<String>,
+    /// What the getter left out without failing the get, like the special
+    /// files of a directory the file getter copied.
+    pub warnings: Vec<String>,
+    /// Checksum of the bytes the getter wrote, in the algorithm
+    /// [`GetOptions::hash`] asks for, hashed as they were written. Without
+    /// it, what was written is read back to be hashed.
+    pub checksum: Option<Checksum>,
+}
+
+/// What fetching the source put at dest.
+#[derive(Default)]
+struct Fetched {
+    /// Format the source was extracted with, if it wasn't kept as is.
+    archive: Option<String>,
+    fallback: Option<String>,
+    warnings: Vec<String>,
+    bytes: u64,
+    checksum: Option<Checksum>,
+    tree_checksum: Option<Checksum>,
+    cached: bool,
+}
+
+impl Fetched {
+    /// The result of a get that fetched this, filled in from template.
+    fn into_result(self, template: &GetResult) -> GetResult {
+        GetResult {
+            archive: self.archive,
+            fallback: self.fallback,
+            warnings: self.warnings,
+            bytes: self.bytes,
+            checksum: self.checksum,
+            tree_checksum: self.tree_checksum,
+            cached: self.cached,
+            ..template.clone()
+        }
+    }
+}
+
+/// When a get has to be done by and how long it spends in each phase, for
+/// the steps of it that go by both.
+#[derive(Clone, Copy)]
+struct Clock<'a> {
+    deadlines: &'a Deadlines,
+    stopwatch: &'a Stopwatch,
+}
+
+/// Where a get puts what it fetches, see [`RequestBuilder::fetch`].
+#[derive(Clone, Copy)]
+struct Target<'a> {
+    /// The result of the get, as far as it's known before the transfer.
+    template: &'a GetResult,
+    /// The dest the get was given, which a source only sniffed to be an
+    /// archive once it's downloaded is extracted into in place of the dest
+    /// of template.
+    given: &'a Path,
+}
+
+/// What an extraction into a staging directory goes by, see
+/// [`RequestBuilder::staged`].
+#[derive(Clone, Copy)]
+struct Staged<'a> {
+    format: &'a str,
+    detection: &'a Detection,
+    deadline: Option<Instant>,
+    /// The result of the get, as far as it's known before the extraction.
+    template: &'a GetResult,
+    stopwatch: &'a Stopwatch,
+    context: &'a ErrorContext,
+}
+
+/// The result of running detection on a source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Detection {
+    /// Name of the getter the source is dispatched to.
+    pub getter: String,
+    /// Final url handed to the getter, without the forced getter prefix or subpath.
+    pub url: Url,
+    /// Path selected inside the source with the `//` syntax.
+    pub subpath: Option<String>,
+    /// Archive format the source is decompressed with.
+    pub archive: Option<String>,
+    /// Whether the downloaded bytes are inspected for an archive format,
+    /// because neither the url nor the `archive` parameter settled it.
+    pub sniff_archive: bool,
+    /// Options of the get, those of the builder along with those of the
+    /// query string. Relative sums and signature files are resolved against
+    /// the url of the source.
+    pub options: GetOptions,
+    /// Query parameters of the final url, the ones gette doesn't read.
+    pub params: HashMap<String, String>,
+}
+
+fn get_forced_proto(v: &str) -> (Option<&str>, &str) {
+    if let Some(re) = Regex::new(r"(?s)^([A-Za-z0-9]+)\+(.*)$")
+        .unwrap()
+        .captures(v)
+    {
+        return (
+            Some(re.get(1).unwrap().as_str()),
+            re.get(2).unwrap().as_str(),
+        );
+    }
+
+    (None, v)
+}
+
+/// Splits a `//` subpath off the path of a url, keeping the query string on
+/// the url.
+fn get_subpath(v: &str) -> (String, Option<String>) {
+    let Some((scheme, rest)) = v.split_once("://") else {
+        return (v.to_string(), None);
+    };
+
+    let end = rest.find(['?', '#']).unwrap_or(rest.len());
+    let path_start = rest[..end].find('/').unwrap_or(end);
+    let Some(idx) = rest[path_start..end].find("//") else {
+        return (v.to_string(), None);
+    };
+
+    let idx = path_start + idx;
+    let subpath = &rest[idx + 2..end];
+    let url = format!("{}://{}{}", scheme, &rest[..idx], &rest[end..]);
+
+    if subpath.is_empty() {
+        return (url, None);
+    }
+
+    (url, Some(subpath.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::{
+        env,
+        fs::{self, File},
+        io::Write,
+    };
+
+    use super::*;
+
+    // builders, clients and their gets have to be usable from spawned tasks
+    #[test]
+    fn test_send_sync() {
+        fn assert_send<T: Send>(_: &T) {}
+        fn assert_sync<T: Send + Sync>(_: &T) {}
+
+        let builder = RequestBuilder::builder().src("a.txt").dest("b.txt");
+        assert_sync(&builder);
+        assert_send(&builder.get());
+        let client = RequestBuilder::builder().build();
+        assert_sync(&client);
+        assert_send(&client.get("a.txt", "b.txt"));
+        let batch = client.queue("a.txt", "b.txt");
+        assert_sync(&batch);
+        assert_send(&batch.get_all());
+
+        // errors are sent back from spawned tasks and boxed into others
+        fn assert_error<T: std::error::Error + Send + Sync + 'static>() {}
+        assert_error::<Error>();
+    }
+
+    #[tokio::test]
+    async fn test_simple_detect() {
+        let b = RequestBuilder::builder()
+            .src("file://test.txt")
+            .dest("test2.txt");
+        let res = b.detect(&b.src.0).unwrap();
+        assert_eq!("file://test.txt", res);
+    }
+
+    #[tokio::test]
+    async fn test_file_detect_without_proto() {
+        let b = RequestBuilder::builder().src("test.txt").dest("test2.txt");
+
+        let res = b.detect(&b.src.0).unwrap();
+        let p = env::current_dir().unwrap().join("test.txt");
+        assert_eq!(format!("file://{}", p.to_str().unwrap()), res);
+    }
+
+    #[tokio::test]
+    async fn test_azure_detect_without_proto() {
+        let b = RequestBuilder::builder()
+            .src("myaccount.blob.core.windows.net/container/test.txt")
+            .dest("test2.txt");
+
+        let res = b.detect(&b.src.0).unwrap();
+        assert_eq!(
+            "azure+https://myaccount.blob.core.windows.net/container/test.txt",
+            res
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scp_like_git_detect() {
+        let b = RequestBuilder::builder()
+            .src("git@github.com:org/repo.git")
+            .dest("repo");
+
+        let res = b.detect(&b.src.0).unwrap();
+        assert_eq!("git+ssh://git@github.com/org/repo.git", res);
+    }
+
+    #[tokio::test]
+    async fn test_strict_file_detection_falls_through() {
+        let shorthand = test_util::RecordingDetector::new("shorthand")
+            .claim("@corp/", "https://artifacts.corp/");
+        let b = RequestBuilder::builder()
+            .src("@corp/tool.tar.gz")
+            .dest("tool")
+            .add_detector(Box::new(shorthand.clone()))
+            .strict_file_detection(true);
+        assert_eq!(
+            b.detect(&b.src.0).unwrap(),
+            "https://artifacts.corp/tool.tar.gz"
+        );
+        assert_eq!(shorthand.seen(), ["@corp/tool.tar.gz"]);
+
+        let b = RequestBuilder::builder()
+            .src("does-not-exist.txt")
+            .dest("test2.txt")
+            .strict_file_detection(true);
+        let err = b.detect(&b.src.0).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("file: source path does-not-exist.txt does not exist, check the path"));
+    }
+
+    #[cfg(feature = "s3")]
+    #[tokio::test]
+    #[cfg(feature = "s3")]
+    async fn test_detection_failure_reports_attempts() {
+        // the default detectors, the file detector catching everything last
+        let b = RequestBuilder::builder()
+            .src("a.b.c.d.e.f.amazonaws.com/x.txt")
+            .dest("test2.txt");
+
+        let err = b.resolve().unwrap_err();
+        let Error::DetectionFailed { input, attempts } = &err else {
+            panic!("unexpected error: {:?}", err);
+        };
+
+        assert_eq!(input, "a.b.c.d.e.f.amazonaws.com/x.txt");
+        let names: Vec<_> = attempts.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["s3", "azure", "git"]);
+        assert!(matches!(
+            &attempts[0].1,
+            DetectAttempt::Errored(e) if matches!(
+                **e,
+                Error::InvalidUrl { kind: InvalidUrlKind::UnsupportedHostShape { .. }, .. }
+            )
+        ));
+        assert!(matches!(attempts[1].1, DetectAttempt::Declined));
+        assert_eq!(
+            err.to_string(),
+            "no detector matched a.b.c.d.e.f.amazonaws.com/x.txt (s3: invalid url: a.b.c.d.e.f.amazonaws.com/x.txt, reason: not a valid s3 url, azure: declined, git: declined)"
+        );
+
+        // a strict file detector only claims what's there, which it may
+        let b = RequestBuilder::builder()
+            .strict_file_detection(true)
+            .src("a.b.c.d.e.f.amazonaws.com/x.txt")
+            .dest("test2.txt");
+        assert!(matches!(b.resolve(), Err(Error::DetectionFailed { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let b = RequestBuilder::builder()
+            .src("git@github.com:org/repo.git//modules/vpc?ref=v1.0.0")
+            .dest("vpc");
+
+        let res = b.resolve().unwrap();
+        assert_eq!(res.getter, "git");
+        assert_eq!(
+            res.url.as_str(),
+            "ssh://git@github.com/org/repo.git?ref=v1.0.0"
+        );
+        assert_eq!(res.subpath.as_deref(), Some("modules/vpc"));
+        assert_eq!(res.archive, None);
+        assert_eq!(res.params.get("ref").map(|r| r.as_str()), Some("v1.0.0"));
+
         let b = RequestBuilder::builder()
-            .src("test.txt".to_string())
-            .dest("test2.txt".to_string());
+            .src("https://host/bundle.tar.gz//bin/tool?archive=tgz")
+            .dest("tool");
+
+        let res = b.resolve().unwrap();
+        assert_eq!(res.getter, "https");
+        assert_eq!(res.url.as_str(), "https://host/bundle.tar.gz");
+        assert_eq!(res.subpath.as_deref(), Some("bin/tool"));
+        assert_eq!(res.archive.as_deref(), Some("tar.gz"));
+        assert!(res.params.is_empty());
+
+        let b = RequestBuilder::builder()
+            .src("https://host/vendor.zip?archive_password=s%26cr3t&sig=x")
+            .dest("vendor");
+
+        let res = b.resolve().unwrap();
+        assert_eq!(res.url.as_str(), "https://host/vendor.zip?sig=x");
+        assert_eq!(res.options.archive_password, Some(Password::new("s&cr3t")));
+        assert!(!format!("{:?}", res).contains("cr3t"));
+
+        let tests = vec![
+            ("file:./SHA256SUMS", "s3+https://host/v1/SHA256SUMS"),
+            ("file:../SHA256SUMS", "s3+https://host/SHA256SUMS"),
+            (
+                "file:https://sums/SHA256SUMS%3Fv%3D1",
+                "https://sums/SHA256SUMS?v=1",
+            ),
+        ];
+        for (checksum, file) in tests {
+            let b = RequestBuilder::builder()
+                .src(format!("s3+https://host/v1/tool.tgz?checksum={}", checksum))
+                .dest("tool");
+
+            let res = b.resolve().unwrap();
+            assert_eq!(res.url.as_str(), "https://host/v1/tool.tgz");
+            assert_eq!(res.options.checksum, None);
+            assert_eq!(res.options.checksum_file.as_deref(), Some(file));
+        }
+
+        let b = RequestBuilder::builder()
+            .src("https://host/tool.tgz?checksum=file:./SUMS&signature=./SUMS.sig")
+            .dest("tool");
+        let res = b.resolve().unwrap();
+        assert_eq!(
+            res.options.signature.as_deref(),
+            Some("https://host/SUMS.sig")
+        );
+
+        let b = RequestBuilder::builder()
+            .src("https://host/tool.tgz?signature=./tool.tgz.minisig")
+            .dest("tool");
+        let res = b.resolve().unwrap();
+        assert_eq!(
+            res.options.signature.as_deref(),
+            Some("https://host/tool.tgz.minisig")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_archive_formats() {
+        let mut tests = vec![
+            ("https://host/tool.tar", Some("tar")),
+            ("https://host/tool.tgz", Some("tar.gz")),
+            ("https://host/tool.zip", Some("zip")),
+            ("https://host/tool.zip.gz", Some("gz")),
+            ("https://host/database.sql.gz", Some("gz")),
+        ];
+        if cfg!(feature = "zstd") {
+            tests.push(("https://host/tool.tar.zst", Some("tar.zst")));
+            tests.push(("https://host/tool.zst", Some("zst")));
+        }
+        if cfg!(feature = "xz") {
+            tests.push(("https://host/tool.tar.xz", Some("tar.xz")));
+        }
+        if cfg!(feature = "bzip2") {
+            tests.push(("https://host/tool.tar.bz2", Some("tar.bz2")));
+        }
+
+        for test in tests {
+            let res = RequestBuilder::builder()
+                .src(test.0.to_string())
+                .dest("tool")
+                .resolve()
+                .unwrap();
+            assert_eq!(res.archive.as_deref(), test.1, "{}", test.0);
+        }
+    }
+
+    #[test]
+    fn test_clear_decompressors() {
+        let b = RequestBuilder::builder()
+            .src("https://host/tool.tar.gz")
+            .dest("tool")
+            .clear_decompressors();
+        let res = b.resolve().unwrap();
+        assert_eq!(res.archive, None);
+
+        let res = b
+            .add_decompressor("tgz", Box::new(decompressors::TarGz))
+            .resolve();
+        assert_eq!(res.unwrap().archive.as_deref(), Some("tgz"));
+    }
+
+    #[test]
+    fn test_get_subpath() {
+        let tests = vec![
+            ("file:///tmp/test.txt", "file:///tmp/test.txt", None),
+            (
+                "https://host/a.git//sub/dir",
+                "https://host/a.git",
+                Some("sub/dir"),
+            ),
+            (
+                "https://host/a.git//sub?ref=x",
+                "https://host/a.git?ref=x",
+                Some("sub"),
+            ),
+            ("https://host/a.git//", "https://host/a.git", None),
+            (
+                "https://host/a?u=http://x//y",
+                "https://host/a?u=http://x//y",
+                None,
+            ),
+        ];
+
+        for test in tests {
+            let (url, subpath) = get_subpath(test.0);
+            assert_eq!(url, test.1);
+            assert_eq!(subpath.as_deref(), test.2);
+        }
+    }
+
+    struct Suffix(&'static str);
+
+    #[async_trait]
+    impl PostProcessor for Suffix {
+        async fn process(&self, staged: &Path, result: &mut GetResult) -> Result<(), Error> {
+            let file = match result.archive {
+                Some(_) => staged.join("bin/tool"),
+                None => staged.to_path_buf(),
+            };
+            let contents = fs::read_to_string(&file)? + self.0;
+            fs::write(&file, contents)?;
+            result.bytes += self.0.len() as u64;
+            Ok(())
+        }
+    }
+
+    struct Failing;
+
+    #[async_trait]
+    impl PostProcessor for Failing {
+        async fn process(&self, staged: &Path, _: &mut GetResult) -> Result<(), Error> {
+            fs::write(staged, "half done")?;
+            Err(Error::Unknown("failed".into()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_post_processes() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "bin/tool", "test".as_bytes())
+            .unwrap();
+        let tarball = builder.into_inner().unwrap().finish().unwrap();
+        let getter = test_util::InMemoryGetter::new()
+            .file("host/test.txt", "test")
+            .file("host/bundle.tar.gz", tarball);
+        let tmp = tempfile::tempdir().unwrap();
+        let get = |src: &str| {
+            test_util::stream_builder(&getter, tmp.path())
+                .post_process(Arc::new(Suffix("-a")))
+                .post_process(Arc::new(Suffix("-b")))
+                .src(src)
+        };
+
+        let res = get("stream://host/test.txt")
+            .dest("test.txt")
+            .get()
+            .await
+            .unwrap();
+        let dest = tmp.path().join("test.txt");
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test-a-b");
+        assert_eq!(res.bytes, 8);
+        let res = get("stream://host/bundle.tar.gz")
+            .dest("bundle")
+            .get()
+            .await
+            .unwrap();
+        let tool = tmp.path().join("bundle/bin/tool");
+        assert_eq!(fs::read_to_string(tool).unwrap(), "test-a-b");
+        assert_eq!(res.archive.as_deref(), Some("tar.gz"));
+
+        // dest is left as it was, and so is the directory it's in
+        let err = get("stream://host/test.txt")
+            .post_process(Arc::new(Failing))
+            .dest("test.txt")
+            .get()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, Error::Context { context, .. } if context.phase == Phase::PostProcessing),
+            "{:?}",
+            err
+        );
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test-a-b");
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 2);
+    }
+
+    /// Records where what's fetched was staged.
+    struct StagedAt(Arc<std::sync::Mutex<Vec<PathBuf>>>);
+
+    #[async_trait]
+    impl PostProcessor for StagedAt {
+        async fn process(&self, staged: &Path, _: &mut GetResult) -> Result<(), Error> {
+            self.0.lock().unwrap().push(staged.to_path_buf());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_staging_dir() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "bin/tool", "test".as_bytes())
+            .unwrap();
+        let tarball = builder.into_inner().unwrap().finish().unwrap();
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("bin/tool", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"test").unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+        let getter = test_util::InMemoryGetter::new()
+            .file("host/test.txt", "test")
+            .file("host/bundle.tar.gz", tarball)
+            .file("host/bundle.zip", zip);
+
+        let mut stagings = vec![tempfile::tempdir().unwrap()];
+        // one on another filesystem is copied from, where there's one
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            let dev = |path: &Path| fs::metadata(path).map(|m| m.dev()).ok();
+            let shm = Path::new("/dev/shm");
+            if dev(shm).is_some() && dev(shm) != dev(&std::env::temp_dir()) {
+                stagings.extend(tempfile::tempdir_in(shm).ok());
+            }
+        }
+        for staging in &stagings {
+            let staging = staging.path().join("staging");
+            let tmp = tempfile::tempdir().unwrap();
+            let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let get = |src: &str, dest: &str| {
+                RequestBuilder::builder()
+                    .add_getter("mem", Box::new(getter.clone()))
+                    .base_dir(tmp.path())
+                    .staging_dir(&staging)
+                    .on_exists(OnExists::Merge)
+                    .post_process(Arc::new(StagedAt(seen.clone())))
+                    .src(src)
+                    .dest(dest)
+            };
+
+            get("mem://host/test.txt", "test.txt").get().await.unwrap();
+            assert_eq!(
+                fs::read_to_string(tmp.path().join("test.txt")).unwrap(),
+                "test"
+            );
+            // streamed, and downloaded before it's extracted into what's there
+            get("mem://host/bundle.tar.gz", "tgz").get().await.unwrap();
+            fs::create_dir_all(tmp.path().join("zip/bin")).unwrap();
+            fs::write(tmp.path().join("zip/bin/other"), "other").unwrap();
+            get("mem://host/bundle.zip", "zip").get().await.unwrap();
+            for dest in ["tgz", "zip"] {
+                let tool = tmp.path().join(dest).join("bin/tool");
+                assert_eq!(fs::read_to_string(tool).unwrap(), "test");
+            }
+            assert!(tmp.path().join("zip/bin/other").exists());
+
+            let seen = seen.lock().unwrap();
+            assert_eq!(seen.len(), 3);
+            assert!(seen.iter().all(|p| p.starts_with(&staging)), "{:?}", seen);
+            // nothing is left behind in either
+            assert_eq!(fs::read_dir(&staging).unwrap().count(), 0);
+            assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 3);
+        }
+    }
+
+    /// A getter redirected to another url, which it checks against the hosts
+    /// allowed before it follows it.
+    struct Redirected(Url);
+
+    #[async_trait]
+    impl Getter for Redirected {
+        async fn get(&self, dest: &Path, _: &Url, options: &GetOptions) -> Result<(), Error> {
+            if let Some(allowed) = &options.allowed_hosts {
+                allowed.check(&self.0)?;
+            }
+            fs::write(dest, self.0.as_str())?;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_allowed_hosts() {
+        let getter = test_util::InMemoryGetter::new()
+            .file("origin.example/test.txt", "test")
+            .file("evil.test/test.txt", "test")
+            .file("evil.test/SUMS", "");
+        let tmp = tempfile::tempdir().unwrap();
+        let get = |src: &str, allowed: &[&str]| {
+            let redirected = Url::parse("https://evil.test/test.txt").unwrap();
+            RequestBuilder::builder()
+                .add_getter("stream", Box::new(getter.clone()))
+                .add_getter("redirect", Box::new(Redirected(redirected)))
+                .base_dir(tmp.path())
+                .on_conflict(OnConflict::Overwrite)
+                .allowed_hosts(allowed)
+                .unwrap()
+                .src(src)
+                .dest("test.txt")
+        };
+        let dest = tmp.path().join("test.txt");
+
+        get("stream://origin.example/test.txt", &["*.example"])
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(getter.downloads(), 1);
+        fs::remove_file(&dest).unwrap();
+
+        // refused before anything's downloaded, sums files included
+        for src in [
+            "stream://evil.test/test.txt",
+            "stream://origin.example/test.txt?checksum=file:stream://evil.test/SUMS",
+        ] {
+            let err = get(src, &["origin.example"]).get().await.unwrap_err();
+            assert!(
+                matches!(err.inner(), Error::HostNotAllowed { url } if url.contains("evil.test")),
+                "{:?}",
+                err
+            );
+        }
+        assert_eq!(getter.downloads(), 1);
+
+        // and redirected elsewhere in the middle of the get
+        let err = get("redirect://origin.example/moved", &["origin.example"])
+            .get()
+            .await
+            .unwrap_err();
+        assert!(matches!(err.inner(), Error::HostNotAllowed { .. }));
+        assert!(!dest.exists());
+        get(
+            "redirect://origin.example/moved",
+            &["origin.example", "https://evil.test"],
+        )
+        .get()
+        .await
+        .unwrap();
+        assert!(dest.exists());
+
+        assert!(matches!(
+            RequestBuilder::builder().allowed_hosts(["*.*.example"]),
+            Err(Error::InvalidHostPattern { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_checks_space() {
+        use std::io::Write;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("test.zip");
+        let mut w = zip::ZipWriter::new(File::create(&archive).unwrap());
+        w.start_file("test.txt", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        w.write_all(b"test").unwrap();
+        w.finish().unwrap();
+        let getter = test_util::InMemoryGetter::new().file("host/test.txt", "test");
+        // a margin no disk has room for stands in for a full one
+        let full = |on_estimate| SpaceCheck {
+            margin: u64::MAX / 2,
+            on_estimate,
+        };
+        let builder = |src: &str, dest: &str, check| {
+            RequestBuilder::builder()
+                .add_getter("stream", Box::new(getter.clone()))
+                .base_dir(tmp.path())
+                .space_check(check)
+                .src(src)
+                .dest(dest)
+        };
+
+        // sources of a known size fail before they're transferred
+        let err = builder(
+            "stream://host/test.txt",
+            "test.txt",
+            Some(full(OnEstimate::Fail)),
+        )
+        .get()
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::InsufficientSpace { available, .. } if *available > 0),
+            "{:?}",
+            err
+        );
+        assert_eq!(err.kind(), ErrorKind::Destination);
+        assert_eq!(getter.downloads(), 0);
+        assert!(!tmp.path().join("test.txt").exists());
+        builder("stream://host/test.txt", "test.txt", None)
+            .get()
+            .await
+            .unwrap();
+
+        // archives are checked against an estimate before they're extracted
+        let src = archive.to_str().unwrap();
+        let err = builder(src, "failed", Some(full(OnEstimate::Fail)))
+            .get()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::InsufficientSpace { .. }),
+            "{:?}",
+            err
+        );
+        assert!(!tmp.path().join("failed").exists());
+        builder(src, "warned", Some(full(OnEstimate::Warn)))
+            .get()
+            .await
+            .unwrap();
+        assert!(tmp.path().join("warned/test.txt").is_file());
+    }
+
+    /// Serves zeros, as a file or as a tar of one, generated as they're read
+    /// so that the source itself takes up no memory.
+    #[cfg(target_os = "linux")]
+    struct Zeros(u64);
+
+    #[cfg(target_os = "linux")]
+    #[async_trait]
+    impl Getter for Zeros {
+        async fn get(&self, dest: &Path, _: &Url, options: &GetOptions) -> Result<(), Error> {
+            use tokio::io::AsyncReadExt;
+
+            let zeros = tokio::io::repeat(0).take(self.0);
+            getters::copy_to_file(zeros, &mut File::create(dest)?, options).await?;
+            Ok(())
+        }
+
+        async fn open(
+            &self,
+            _: &Url,
+            _: &GetOptions,
+        ) -> Result<Option<Pin<Box<dyn AsyncRead + Send>>>, Error> {
+            use tokio::io::AsyncReadExt;
+
+            let mut header = tar::Header::new_gnu();
+            header.set_path("zeros").unwrap();
+            header.set_size(self.0);
+            header.set_mode(0o644);
+            header.set_cksum();
+            let header = std::io::Cursor::new(header.as_bytes().to_vec());
+            // the entry padded to a block, and the two empty blocks that end a tar
+            let zeros = tokio::io::repeat(0).take(self.0.next_multiple_of(512) + 1024);
+            let tar = AsyncReadExt::chain(header, zeros);
+            Ok(Some(Box::pin(tar)))
+        }
+    }
+
+    /// Highest resident memory of the process since it was last reset.
+    #[cfg(target_os = "linux")]
+    fn peak_rss() -> u64 {
+        let status = fs::read_to_string("/proc/self/status").unwrap();
+        let kb = status
+            .lines()
+            .find_map(|l| l.strip_prefix("VmHWM:"))
+            .and_then(|l| l.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+            .unwrap();
+        kb << 10
+    }
+
+    // other tests running alongside add to the peak, so it's only measured
+    // when asked to, with GETTE_RSS_TEST=1 and this test on its own
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_get_memory_stays_bounded() {
+        if env::var_os("GETTE_RSS_TEST").is_none() {
+            return;
+        }
+        const SIZE: u64 = 384 << 20;
+        let tmp = tempfile::tempdir().unwrap();
+        for (src, dest) in [
+            ("zeros://host/zeros", "zeros"),
+            ("zeros://host/zeros.tar", "tar"),
+        ] {
+            // resets the peak to what's resident now
+            fs::write("/proc/self/clear_refs", "5").unwrap();
+            let before = peak_rss();
+            RequestBuilder::builder()
+                .add_getter("zeros", Box::new(Zeros(SIZE)))
+                .base_dir(tmp.path())
+                .src(src)
+                .dest(dest)
+                .get()
+                .await
+                .unwrap();
+            let grown = peak_rss().saturating_sub(before);
+            assert!(grown < 32 << 20, "{} took up {} MiB", src, grown >> 20);
+        }
+        assert_eq!(fs::metadata(tmp.path().join("zeros")).unwrap().len(), SIZE);
+        let extracted = fs::metadata(tmp.path().join("tar/zeros")).unwrap();
+        assert_eq!(extracted.len(), SIZE);
+    }
+
+    #[cfg(windows)]
+    #[tokio::test]
+    async fn test_get_extracts_deep_archives() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("deep.tar");
+        // far past the classic limit, like node_modules trees get
+        let name = format!("{}/tool", vec!["node_modules/package"; 16].join("/"));
+        let mut builder = tar::Builder::new(File::create(&source).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, &name, "test".as_bytes())
+            .unwrap();
+        builder.into_inner().unwrap();
+
+        let dest = tmp.path().join("dest");
+        let res = RequestBuilder::builder()
+            .src(source.to_str().unwrap())
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.final_path, dest);
+        assert_eq!(res.bytes, 4);
+        let tool = dest::extended(&dest.join(name.replace('/', "\\")));
+        assert_eq!(fs::read_to_string(tool).unwrap(), "test");
+    }
+
+    #[tokio::test]
+    async fn test_get_extracts_archives() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("bundle.tar.gz");
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            File::create(&source).unwrap(),
+            Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "bin/tool", "test".as_bytes())
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let dest = tmp.path().join("dest");
+        RequestBuilder::builder()
+            .src(source.to_str().unwrap())
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(dest.join("bin/tool")).unwrap(), "test");
+        // only the extracted tree is left behind, not the downloaded archive
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 2);
+
+        let err = RequestBuilder::builder()
+            .src(format!("{}?strip_components=x", source.to_str().unwrap()))
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap_err();
+        assert!(matches!(err.inner(), Error::InvalidUrl { .. }), "{:?}", err);
+
+        let dest = tmp.path().join("stripped");
+        RequestBuilder::builder()
+            .src(format!("{}?strip_components=1", source.to_str().unwrap()))
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(dest.join("tool")).unwrap(), "test");
+
+        let dest = tmp.path().join("tool");
+        RequestBuilder::builder()
+            .src(format!("file://{}//bin/tool", source.to_str().unwrap()))
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                fs::metadata(&dest).unwrap().permissions().mode() & 0o777,
+                0o644
+            );
+        }
+
+        let dest = tmp.path().join("raw.tar.gz");
+        RequestBuilder::builder()
+            .src(format!("file://{}?archive=false", source.to_str().unwrap()))
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap();
+        assert!(dest.is_file());
+    }
+
+    #[tokio::test]
+    async fn test_get_extracts_nested_archives() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let mut inner = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        inner
+            .append_data(&mut header, "inner.txt", "test".as_bytes())
+            .unwrap();
+        let inner = inner.into_inner().unwrap();
+
+        let mut zip = ::zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = ::zip::write::SimpleFileOptions::default();
+        zip.start_file("bin/tool", options).unwrap();
+        zip.write_all(b"test").unwrap();
+        zip.start_file("bin/inner.tar", options).unwrap();
+        zip.write_all(&inner).unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+
+        let source = tmp.path().join("bundle.tar.gz");
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            File::create(&source).unwrap(),
+            Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(zip.len() as u64);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "tool.zip", &zip[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let get = |query: &str, dest: &str, limits: DecompressLimits| {
+            let dest = tmp.path().join(dest);
+            let req = RequestBuilder::builder()
+                .src(format!("{}{}", source.to_str().unwrap(), query))
+                .dest(&dest)
+                .decompress_limits(limits);
+            async move { req.get().await.map(|_| dest) }
+        };
+
+        // nested archives are left alone by default
+        let dest = get("", "default", Default::default()).await.unwrap();
+        assert!(dest.join("tool.zip").is_file());
+
+        // the inner tarball is one level too deep to be extracted
+        let dest = get("?decompress_depth=2", "nested", Default::default())
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(dest.join("bin/tool")).unwrap(), "test");
+        assert!(dest.join("bin/inner.tar").is_file());
+        assert!(!dest.join("tool.zip").exists());
+
+        // what the outer archive extracted counts against the limits
+        let limits = DecompressLimits {
+            max_total_bytes: zip.len() as u64 + 8,
+            ..Default::default()
+        };
+        let err = get("?decompress_depth=2", "limited", limits)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::DecompressLimitExceeded { .. }),
+            "{:?}",
+            err
+        );
+        assert!(!tmp.path().join("limited").exists());
+
+        for query in ["?decompress_depth=0", "?decompress_depth=x"] {
+            let err = get(query, "invalid", Default::default()).await.unwrap_err();
+            assert!(matches!(err.inner(), Error::InvalidUrl { .. }), "{:?}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_into_existing_dest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("bundle.tar");
+        let mut builder = tar::Builder::new(File::create(&source).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "a.txt", "test".as_bytes())
+            .unwrap();
+        builder.finish().unwrap();
+
+        let dest = tmp.path().join("dest");
+        fs::create_dir(&dest).unwrap();
+        fs::write(dest.join("keep.txt"), "keep").unwrap();
+        let get = |query: &str, on_exists: Option<OnExists>| {
+            let b = RequestBuilder::builder()
+                .src(format!("{}{}", source.to_str().unwrap(), query))
+                .dest(&dest);
+            match on_exists {
+                Some(policy) => b.on_exists(policy),
+                None => b,
+            }
+        };
+
+        let err = get("", None).get().await.unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::DestinationNotEmpty(_)),
+            "{:?}",
+            err
+        );
+        assert!(!dest.join("a.txt").exists());
+
+        let err = get("?on_exists=nope", None).get().await.unwrap_err();
+        assert!(matches!(err.inner(), Error::InvalidUrl { .. }), "{:?}", err);
+
+        get("?on_exists=merge", None).get().await.unwrap();
+        assert_eq!(fs::read_to_string(dest.join("a.txt")).unwrap(), "test");
+        assert!(dest.join("keep.txt").exists());
+
+        // the builder wins over the query
+        get("?on_exists=merge", Some(OnExists::Clean))
+            .get()
+            .await
+            .unwrap();
+        assert!(dest.join("a.txt").exists());
+        assert!(!dest.join("keep.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_get_cleans_up_when_limits_are_exceeded() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("bundle.tar");
+        let mut builder = tar::Builder::new(File::create(&source).unwrap());
+        for name in ["a.txt", "b.txt"] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(4);
+            header.set_mode(0o644);
+            builder
+                .append_data(&mut header, name, "test".as_bytes())
+                .unwrap();
+        }
+        builder.finish().unwrap();
+
+        let dest = tmp.path().join("dest");
+        let err = RequestBuilder::builder()
+            .src(source.to_str().unwrap())
+            .dest(&dest)
+            .decompress_limits(DecompressLimits {
+                max_entries: 1,
+                ..Default::default()
+            })
+            .get()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err.inner(),
+                Error::DecompressLimitExceeded {
+                    limit: "entry count",
+                    ..
+                }
+            ),
+            "{:?}",
+            err
+        );
+        // neither dest nor the staging directory or archive are left behind
+        assert!(!dest.exists());
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+
+        RequestBuilder::builder()
+            .src(source.to_str().unwrap())
+            .dest(&dest)
+            .decompress_limits(DecompressLimits::unlimited())
+            .get()
+            .await
+            .unwrap();
+        assert!(dest.join("b.txt").is_file());
+    }
+
+    #[tokio::test]
+    async fn test_get_sniffs_extensionless_archives() {
+        use std::io::Write;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("download");
+        let mut w = zip::ZipWriter::new(File::create(&source).unwrap());
+        w.start_file("bin/tool", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        w.write_all(b"test").unwrap();
+        w.finish().unwrap();
+
+        let dest = tmp.path().join("dest");
+        RequestBuilder::builder()
+            .src(source.to_str().unwrap())
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(dest.join("bin/tool")).unwrap(), "test");
+
+        // run again, it's extracted into dest as on_exists says rather
+        // than put in it as a file would be
+        let get = |dest: &Path| {
+            RequestBuilder::builder()
+                .src(source.to_str().unwrap())
+                .dest(dest)
+        };
+        let err = get(&dest).get().await.unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::DestinationNotEmpty(_)),
+            "{:?}",
+            err
+        );
+        let res = get(&dest).on_exists(OnExists::Overwrite).get().await;
+        assert_eq!(res.unwrap().final_path, dest);
+        assert!(!dest.join("download").exists());
+
+        // as it is into a directory that's already there
+        let dir = tmp.path().join("dir");
+        fs::create_dir(&dir).unwrap();
+        let res = get(&dir).get().await.unwrap();
+        assert_eq!(res.final_path, dir);
+        assert_eq!(fs::read_to_string(dir.join("bin/tool")).unwrap(), "test");
+
+        let dest = tmp.path().join("raw");
+        RequestBuilder::builder()
+            .src(format!("file://{}?archive=false", source.to_str().unwrap()))
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap();
+        assert!(dest.is_file());
+    }
+
+    #[tokio::test]
+    async fn test_get_keeps_dest_when_sniffed_archive_fails() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let data = vec![b'x'; 1 << 20];
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        builder.append_data(&mut header, "tool", &data[..]).unwrap();
+        let tarball = builder.into_inner().unwrap().finish().unwrap();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("download");
+        // sniffed as a tarball all the same, failing only once it's extracted
+        fs::write(&source, &tarball[..tarball.len() / 2]).unwrap();
+        let dest = tmp.path().join("old.txt");
+        fs::write(&dest, "old").unwrap();
+
+        let err = RequestBuilder::builder()
+            .on_conflict(OnConflict::Overwrite)
+            .src(source.to_str().unwrap())
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap_err();
+        assert_eq!(err.context().unwrap().phase, Phase::Extraction, "{:?}", err);
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "old");
+    }
+
+    #[tokio::test]
+    async fn test_get_rejects_fake_gzip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("download");
+        fs::write(&source, [0x1f, 0x8b, b'n', b'o', b'p', b'e']).unwrap();
+
+        let dest = tmp.path().join("dest");
+        let err = RequestBuilder::builder()
+            .src(source.to_str().unwrap())
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap_err();
+        assert!(matches!(err.inner(), Error::InvalidArchive(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_get_streams_archives() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o755);
+        builder
+            .append_data(&mut header, "bin/tool", "test".as_bytes())
+            .unwrap();
+        let tarball = builder.into_inner().unwrap().finish().unwrap();
+
+        let mut w = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        w.start_file("bin/tool", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        w.write_all(b"test").unwrap();
+        let zip = w.finish().unwrap().into_inner();
+
+        let tmp = tempfile::tempdir().unwrap();
+        // zip needs the central directory at the end, so it can't be streamed
+        for (name, body, downloads) in [("bundle.tar.gz", tarball, 0), ("bundle.zip", zip, 1)] {
+            let getter = test_util::InMemoryGetter::new().file(&format!("host/{}", name), body);
+            fs::create_dir(tmp.path().join(name)).unwrap();
+            let dest = tmp.path().join(name).join("dest");
+            RequestBuilder::builder()
+                .src(format!("stream://host/{}", name))
+                .dest(&dest)
+                .add_getter("stream", Box::new(getter.clone()))
+                .get()
+                .await
+                .unwrap();
+
+            assert_eq!(fs::read_to_string(dest.join("bin/tool")).unwrap(), "test");
+            assert_eq!(getter.downloads(), downloads, "{}", name);
+            // only dest is left behind, no archive or staging directory
+            assert_eq!(fs::read_dir(tmp.path().join(name)).unwrap().count(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_verifies_checksums() {
+        use flate2::{write::GzEncoder, Compression};
+
+        const MD5: &str = "098f6bcd4621d373cade4e832627b4f6";
+        const SHA256: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+
+        for (i, checksum) in [
+            format!("md5:{}", MD5),
+            format!("sha256:{}", SHA256),
+            SHA256.to_string(),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let dest = tmp.path().join(format!("dest-{}", i));
+            RequestBuilder::builder()
+                .src(format!(
+                    "{}?checksum={}",
+                    source.to_str().unwrap(),
+                    checksum
+                ))
+                .dest(&dest)
+                .get()
+                .await
+                .unwrap();
+            assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+        }
+
+        let err = RequestBuilder::builder()
+            .src(format!("{}?checksum=md5:1234", source.to_str().unwrap()))
+            .dest(tmp.path().join("invalid"))
+            .get()
+            .await
+            .unwrap_err();
+        assert!(matches!(err.inner(), Error::InvalidUrl { .. }), "{:?}", err);
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "bin/tool", "test".as_bytes())
+            .unwrap();
+        let tarball = builder.into_inner().unwrap().finish().unwrap();
+        let archive = tmp.path().join("bundle.tar.gz");
+        fs::write(&archive, &tarball).unwrap();
+
+        // a mismatch leaves nothing behind, whether the source was kept as
+        // is, downloaded before extracting or extracted as it streamed in
+        let wrong = SHA256.replace('f', "e");
+        let query = format!("?checksum=sha256:{}", wrong);
+        let tests = vec![
+            (format!("{}{}", source.to_str().unwrap(), query), None),
+            (format!("{}{}", archive.to_str().unwrap(), query), None),
+            (
+                format!("stream://host/bundle.tar.gz{}", query),
+                Some(tarball),
+            ),
+        ];
+        for (i, (src, body)) in tests.into_iter().enumerate() {
+            let dir = tmp.path().join(format!("mismatch-{}", i));
+            fs::create_dir(&dir).unwrap();
+            let mut req = RequestBuilder::builder().src(src).dest(dir.join("dest"));
+            if let Some(body) = body {
+                let getter = test_util::InMemoryGetter::new().file("host/bundle.tar.gz", body);
+                req = req.add_getter("stream", Box::new(getter));
+            }
+
+            let err = req.get().await.unwrap_err();
+            assert!(
+                matches!(err.inner(), Error::ChecksumMismatch { algo: "sha256", ref expected, .. } if *expected == wrong),
+                "{}: {:?}",
+                i,
+                err
+            );
+            assert_eq!(fs::read_dir(&dir).unwrap().count(), 0, "{}", i);
+        }
+        // the source of a mismatched file is left alone
+        assert_eq!(fs::read_to_string(&source).unwrap(), "test");
+        assert!(archive.is_file());
+    }
+
+    #[tokio::test]
+    async fn test_get_verifies_checksums_from_sums_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dist = tmp.path().join("dist");
+        fs::create_dir(&dist).unwrap();
+        let source = dist.join("tool.txt");
+        fs::write(&source, "test").unwrap();
+        fs::write(
+            dist.join("SHA256SUMS"),
+            "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08  tool.txt\n\
+             9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a07  other.txt\n",
+        )
+        .unwrap();
+
+        let sums = dist.join("SHA256SUMS");
+        for (i, checksum) in [
+            "file:./SHA256SUMS".to_string(),
+            format!("file:file://{}", sums.to_str().unwrap()),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let dest = tmp.path().join(format!("dest-{}", i));
+            RequestBuilder::builder()
+                .src(format!(
+                    "file://{}?checksum={}",
+                    source.to_str().unwrap(),
+                    checksum
+                ))
+                .dest(&dest)
+                .get()
+                .await
+                .unwrap();
+            assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+        }
+
+        // a file the sums don't mention, or whose sum doesn't match
+        let other = dist.join("other.txt");
+        fs::write(&other, "test").unwrap();
+        let missing = dist.join("missing.txt");
+        fs::write(&missing, "test").unwrap();
+        for source in [other, missing] {
+            let dir = tmp.path().join(source.file_name().unwrap());
+            fs::create_dir(&dir).unwrap();
+            let err = RequestBuilder::builder()
+                .src(format!(
+                    "file://{}?checksum=file:./SHA256SUMS",
+                    source.to_str().unwrap()
+                ))
+                .dest(dir.join("dest"))
+                .get()
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(
+                    err.inner(),
+                    Error::ChecksumMismatch { .. } | Error::ChecksumNotFound { .. }
+                ),
+                "{:?}",
+                err
+            );
+            assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        }
+    }
+
+    #[cfg(feature = "gpg")]
+    #[tokio::test]
+    async fn test_get_verifies_signed_sums_files() {
+        use crate::signature::gpg_fixtures::{key, public, sign};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("tool.txt");
+        fs::write(&source, "test").unwrap();
+        let sums = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08  tool.txt\n";
+        fs::write(tmp.path().join("SHA256SUMS"), sums).unwrap();
+        let signer = key(1);
+        fs::write(
+            tmp.path().join("SHA256SUMS.asc"),
+            sign(&signer, sums.as_bytes()),
+        )
+        .unwrap();
+
+        let get = |dest: &str, key: Option<String>| {
+            let dest = tmp.path().join(dest);
+            let mut req = RequestBuilder::builder()
+                .src(format!(
+                    "file://{}?checksum=file:./SHA256SUMS&signature=./SHA256SUMS.asc",
+                    source.to_str().unwrap()
+                ))
+                .dest(&dest);
+            if let Some(key) = key {
+                req = req.gpg_key(key);
+            }
+            async move { req.get().await.map(|_| dest) }
+        };
+
+        let dest = get("dest", Some(public(&signer))).await.unwrap();
+        assert_eq!(fs::read_to_string(dest).unwrap(), "test");
+
+        let err = get("untrusted", Some(public(&key(2)))).await.unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::SignatureInvalid { .. }),
+            "{:?}",
+            err
+        );
+        let err = get("keyless", None).await.unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::SignatureKeyMissing),
+            "{:?}",
+            err
+        );
+
+        // sums rewritten to match a tampered source are caught before use
+        fs::write(&source, "evil").unwrap();
+        fs::write(
+            tmp.path().join("SHA256SUMS"),
+            "b5c1fb2efc6d6b4674c2fdcc48ce01b43a3b7c03763c0c3355de0099ee0f8c73  tool.txt\n",
+        )
+        .unwrap();
+        let err = get("tampered", Some(public(&signer))).await.unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::SignatureInvalid { .. }),
+            "{:?}",
+            err
+        );
+        assert!(!tmp.path().join("tampered").exists());
+    }
+
+    #[cfg(feature = "minisign")]
+    #[tokio::test]
+    async fn test_get_verifies_minisign_signatures() {
+        use crate::signature::minisign_fixtures::{key, public, sign};
+        use flate2::{write::GzEncoder, Compression};
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "bin/tool", "test".as_bytes())
+            .unwrap();
+        let tarball = builder.into_inner().unwrap().finish().unwrap();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let signer = key(1);
+        for (name, body) in [("tool.txt", b"test".to_vec()), ("bundle.tar.gz", tarball)] {
+            let source = tmp.path().join(name);
+            fs::write(&source, &body).unwrap();
+            fs::write(
+                tmp.path().join(format!("{}.minisig", name)),
+                sign(&signer, &body, true),
+            )
+            .unwrap();
+
+            let get = |dest: &str, key: Option<String>| {
+                let dest = tmp.path().join(dest);
+                let mut req = RequestBuilder::builder()
+                    .src(format!(
+                        "file://{}?signature=./{}.minisig",
+                        source.to_str().unwrap(),
+                        name
+                    ))
+                    .dest(&dest);
+                if let Some(key) = key {
+                    req = req.minisign_key(key);
+                }
+                async move { req.get().await.map(|_| dest) }
+            };
+
+            let dest = get(&format!("{}.dest", name), Some(public(&signer)))
+                .await
+                .unwrap();
+            assert!(dest.exists(), "{}", name);
+
+            let err = get("keyless", None).await.unwrap_err();
+            assert!(
+                matches!(err.inner(), Error::SignatureKeyMissing),
+                "{:?}",
+                err
+            );
+
+            let err = get("untrusted", Some(public(&key(2)))).await.unwrap_err();
+            assert!(
+                matches!(err.inner(), Error::SignatureInvalid { ref key } if key == "0101010101010101"),
+                "{:?}",
+                err
+            );
+        }
+
+        // nothing is extracted from an archive that doesn't match its signature
+        fs::write(tmp.path().join("bundle.tar.gz"), b"evil").unwrap();
+        let err = RequestBuilder::builder()
+            .src(format!(
+                "file://{}?signature=./bundle.tar.gz.minisig",
+                tmp.path().join("bundle.tar.gz").to_str().unwrap()
+            ))
+            .dest(tmp.path().join("tampered"))
+            .minisign_key(public(&signer))
+            .get()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::SignatureInvalid { .. }),
+            "{:?}",
+            err
+        );
+        assert!(fs::symlink_metadata(tmp.path().join("tampered")).is_err());
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_get_hashes_trees() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("bundle.tar.gz");
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            File::create(&source).unwrap(),
+            Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "bin/tool", "test".as_bytes())
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let get = |src: String, dest: &str| {
+            let dest = tmp.path().join(dest);
+            async move { RequestBuilder::builder().src(src).dest(&dest).get().await }
+        };
+
+        let res = get(source.to_str().unwrap().to_string(), "plain")
+            .await
+            .unwrap();
+        assert_eq!(res.tree_checksum, None);
+
+        let dest = tmp.path().join("dest");
+        let res = RequestBuilder::builder()
+            .src(source.to_str().unwrap())
+            .dest(&dest)
+            .tree_checksum(true)
+            .get()
+            .await
+            .unwrap();
+        let tree = res.tree_checksum.unwrap();
+        assert_eq!(tree, checksum::tree(&dest).await.unwrap());
+
+        let res = get(
+            format!("{}?tree_checksum={}", source.to_str().unwrap(), tree),
+            "pinned",
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.tree_checksum, Some(tree.clone()));
+
+        // a directory fetched as is hashes the same as one extracted
+        let res = get(
+            format!("file://{}?tree_checksum={}", dest.to_str().unwrap(), tree),
+            "linked",
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.tree_checksum, Some(tree.clone()));
+
+        let other = checksum::tree(&source).await.unwrap();
+        for (src, name) in [
+            (source.to_str().unwrap().to_string(), "extracted"),
+            (format!("file://{}", dest.to_str().unwrap()), "fetched"),
+        ] {
+            let err = get(format!("{}?tree_checksum={}", src, other), name)
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(err.inner(), Error::ChecksumMismatch { algo: "tree", .. }),
+                "{:?}",
+                err
+            );
+            assert!(
+                fs::symlink_metadata(tmp.path().join(name)).is_err(),
+                "{}",
+                name
+            );
+        }
+
+        let err = get(
+            format!(
+                "{}?tree_checksum=md5:098f6bcd4621d373cade4e832627b4f6",
+                source.to_str().unwrap()
+            ),
+            "md5",
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err.inner(), Error::InvalidUrl { .. }), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_get_reports_what_it_did() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+
+        let dest = tmp.path().join("dest");
+        let res = RequestBuilder::builder()
+            .src(source.to_str().unwrap())
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.final_path, dest);
+        assert_eq!(res.bytes, 4);
+        assert_eq!(res.getter, "file");
+        assert_eq!(res.source_url, Url::from_file_path(&source).unwrap());
+        assert!(!res.not_modified);
+        assert_eq!(res.archive, None);
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "bin/tool", "test".as_bytes())
+            .unwrap();
+        let tarball = builder.into_inner().unwrap().finish().unwrap();
+        let archive = tmp.path().join("bundle.tar.gz");
+        fs::write(&archive, &tarball).unwrap();
+
+        for (src, getter, dest) in [
+            (archive.to_str().unwrap().to_string(), "file", "downloaded"),
+            (
+                "stream://host/bundle.tar.gz".to_string(),
+                "stream",
+                "streamed",
+            ),
+        ] {
+            let dest = tmp.path().join(dest);
+            let res = RequestBuilder::builder()
+                .src(src)
+                .dest(&dest)
+                .add_getter(
+                    "stream",
+                    Box::new(
+                        test_util::InMemoryGetter::new()
+                            .file("host/bundle.tar.gz", tarball.clone()),
+                    ),
+                )
+                .get()
+                .await
+                .unwrap();
+            assert_eq!(res.final_path, dest);
+            assert_eq!(res.bytes, tarball.len() as u64, "{}", getter);
+            assert_eq!(res.getter, getter);
+            assert_eq!(res.archive.as_deref(), Some("tar.gz"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_records_checksums() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+        let get = |src: String, dest: &str| {
+            RequestBuilder::builder()
+                .src(src)
+                .dest(tmp.path().join(dest))
+        };
+
+        let res = get(source.to_str().unwrap().to_string(), "plain")
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.checksum, None);
+
+        let res = get(source.to_str().unwrap().to_string(), "recorded")
+            .record_checksum(true)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(
+            res.checksum,
+            Some(Checksum::of_file(&source).await.unwrap())
+        );
+
+        let md5 = "md5:098f6bcd4621d373cade4e832627b4f6";
+        let res = get(
+            format!("{}?checksum={}", source.to_str().unwrap(), md5),
+            "md5",
+        )
+        .record_checksum(true)
+        .get()
+        .await
+        .unwrap();
+        assert_eq!(res.checksum.unwrap().to_string(), md5);
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "bin/tool", "test".as_bytes())
+            .unwrap();
+        let tarball = builder.into_inner().unwrap().finish().unwrap();
+        let archive = tmp.path().join("bundle.tar.gz");
+        fs::write(&archive, &tarball).unwrap();
+        let expected = Checksum::of_file(&archive).await.unwrap();
+
+        // both the archive and what it's extracted to, whether it's streamed
+        // or downloaded first
+        for (src, dest) in [
+            (archive.to_str().unwrap().to_string(), "downloaded"),
+            ("stream://host/bundle.tar.gz".to_string(), "streamed"),
+        ] {
+            let res = get(src, dest)
+                .add_getter(
+                    "stream",
+                    Box::new(
+                        test_util::InMemoryGetter::new()
+                            .file("host/bundle.tar.gz", tarball.clone()),
+                    ),
+                )
+                .record_checksum(true)
+                .tree_checksum(true)
+                .get()
+                .await
+                .unwrap();
+            assert_eq!(res.checksum.as_ref(), Some(&expected), "{}", dest);
+            assert_eq!(
+                res.tree_checksum,
+                Some(checksum::tree(tmp.path().join(dest)).await.unwrap()),
+                "{}",
+                dest
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_hashes_copies_in_every_algorithm() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "abc").unwrap();
+
+        // digests of "abc"
+        let mut tests = vec![
+            "sha1:a9993e364706816aba3e25717850c26c9cd0d89d",
+            "sha512:ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            "crc32c:364b3fb7",
+        ];
+        if cfg!(feature = "blake3") {
+            tests.push("b3:6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85");
+        }
+        for checksum in tests {
+            let (algo, digest) = checksum.split_once(':').unwrap();
+            let src = format!("{}?mode=copy&checksum={}", source.display(), checksum);
+            let res = RequestBuilder::builder()
+                .src(src)
+                .dest(tmp.path().join(algo))
+                .buffer_size(2)
+                .get()
+                .await
+                .unwrap();
+            assert_eq!(res.checksum.unwrap().to_string(), checksum);
+
+            let corrupted = format!("{}:{}", algo, digest.replace('3', "4"));
+            let src = format!("{}?mode=copy&checksum={}", source.display(), corrupted);
+            let err = RequestBuilder::builder()
+                .src(src)
+                .dest(tmp.path().join(format!("{}-corrupted", algo)))
+                .get()
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(err.inner(), Error::ChecksumMismatch { .. }),
+                "{}: {:?}",
+                algo,
+                err
+            );
+        }
+    }
+
+    /// A file getter on a store that keeps a checksum for what it holds.
+    struct RemoteGetter(&'static str);
+
+    #[async_trait]
+    impl Getter for RemoteGetter {
+        async fn get(&self, dest: &Path, url: &Url, options: &GetOptions) -> Result<(), Error> {
+            let url = Url::parse(&url.as_str().replacen("remote", "file", 1))?;
+            getters::File::default().get(dest, &url, options).await
+        }
+
+        async fn remote_checksum(
+            &self,
+            _: &Url,
+            _: &GetOptions,
+        ) -> Result<Option<Checksum>, Error> {
+            Checksum::parse(self.0).map(Some)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_dest_templates() {
+        const SHA256: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+        let src = format!("{}?checksum=sha256:{}", source.to_str().unwrap(), SHA256);
+        let template = format!("{}/{{sha256:8}}/{{basename}}", tmp.path().display());
+
+        let res = RequestBuilder::builder()
+            .src(&src)
+            .dest_template(&template)
+            .unwrap()
+            .get()
+            .await
+            .unwrap();
+        let dest = tmp.path().join("9f86d081/test.txt");
+        assert_eq!(res.final_path, dest);
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+
+        // or in a sums file next to it
+        fs::write(
+            tmp.path().join("SHA256SUMS"),
+            format!("{}  test.txt\n", SHA256),
+        )
+        .unwrap();
+        let src = format!("file://{}?checksum=file:./SHA256SUMS", source.display());
+        let template = format!("{}/sums/{{sha256:8}}", tmp.path().display());
+        let res = RequestBuilder::builder()
+            .src(&src)
+            .dest_template(&template)
+            .unwrap()
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.final_path, tmp.path().join("sums/9f86d081"));
+        assert!(matches!(
+            res.checksum_source,
+            Some(ChecksumSource::SumsFile(_))
+        ));
+
+        let template = format!("{}/{{sha256:8}}/{{basename}}", tmp.path().display());
+        let results = RequestBuilder::builder()
+            .queue_template(source.to_str().unwrap(), &template)
+            .unwrap()
+            .get_all()
+            .await;
+        let err = results[0].as_ref().unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::InvalidDestTemplate { .. }),
+            "{:?}",
+            err
+        );
+        assert_eq!(err.context().unwrap().dest, PathBuf::from(&template));
+
+        let err = RequestBuilder::builder()
+            .src(&src)
+            .dest_template("{version}")
+            .err()
+            .unwrap();
+        assert!(
+            matches!(err, Error::InvalidDestTemplate { .. }),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_refuses_dests_outside_the_jail() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+        let jail = tmp.path().join("jail");
+        let get = |dest: &str| {
+            let builder = RequestBuilder::builder()
+                .src(source.to_str().unwrap())
+                .dest(jail.join(dest))
+                .file_mode(getters::FileMode::Copy)
+                .dest_jail(&jail);
+            async move { builder.get().await }
+        };
+
+        get("a/test.txt").await.unwrap();
+        assert_eq!(fs::read_to_string(jail.join("a/test.txt")).unwrap(), "test");
+
+        let err = get("../escaped.txt").await.unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::DestOutsideJail { .. }),
+            "{:?}",
+            err
+        );
+        assert_eq!(err.kind(), ErrorKind::Destination);
+        // refused before anything is written, the lock included
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_get_sets_permissions() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        // the umask is the whole process's, so the test runs again in a
+        // process of its own where it's the only one to see it changed
+        if env::var_os("GETTE_TEST_UMASK").is_none() {
+            let output = std::process::Command::new(env::current_exe().unwrap())
+                .args(["--exact", "tests::test_get_sets_permissions"])
+                .env("GETTE_TEST_UMASK", "1")
+                .output()
+                .unwrap();
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            assert!(
+                output.status.success() && stdout.contains("1 passed"),
+                "{}{}",
+                stdout,
+                String::from_utf8_lossy(&output.stderr)
+            );
+            return;
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let mode = |path: &str| {
+            let meta = fs::metadata(tmp.path().join(path)).unwrap();
+            meta.permissions().mode() & 0o777
+        };
+        let owner = fs::metadata(tmp.path()).unwrap();
+        let get = |src: &Path, dest: &str| {
+            let builder = RequestBuilder::builder()
+                .src(format!("remote://{}?mode=copy", src.to_str().unwrap()))
+                .dest(tmp.path().join(dest))
+                .add_getter("remote", Box::new(RemoteGetter("md5:0")))
+                .verify_remote_checksums(false)
+                .file_permissions(0o644)
+                .dir_permissions(0o755)
+                .chown(owner.uid(), owner.gid());
+            async move { builder.get().await }
+        };
+
+        // whatever the umask leaves things with is overridden
+        unsafe { libc::umask(0o077) };
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+        let archive = tmp.path().join("bundle.tar.gz");
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            File::create(&archive).unwrap(),
+            Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o600);
+        builder
+            .append_data(&mut header, "bin/tool", "test".as_bytes())
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        get(&source, "a/b/test.txt").await.unwrap();
+        get(&archive, "bundle").await.unwrap();
+
+        assert_eq!(mode("test.txt"), 0o600);
+        assert_eq!(mode("a"), 0o755);
+        assert_eq!(mode("a/b"), 0o755);
+        assert_eq!(mode("a/b/test.txt"), 0o644);
+        assert_eq!(mode("bundle"), 0o755);
+        assert_eq!(mode("bundle/bin"), 0o755);
+        assert_eq!(mode("bundle/bin/tool"), 0o644);
+    }
+
+    /// Writes the greeting it was configured with to dest.
+    struct GreetingGetter;
+
+    #[async_trait]
+    impl Getter for GreetingGetter {
+        async fn get(&self, dest: &Path, _: &Url, options: &GetOptions) -> Result<(), Error> {
+            let greeting = options.getter_config.get("greeting");
+            fs::write(dest, greeting.map_or("", String::as_str))?;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_configures_getters() {
+        let tmp = tempfile::tempdir().unwrap();
+        let builder = RequestBuilder::builder()
+            .add_getter("greeting", Box::new(GreetingGetter))
+            .getter_config("greeting", "greeting", "hello")
+            .getter_config("other", "greeting", "bye");
+        #[cfg(feature = "s3")]
+        {
+            let builder = RequestBuilder::builder()
+                .s3(|s3| s3.region("eu-west-1"))
+                .s3(|s3| s3.force_path_style(true));
+            assert_eq!(
+                builder.s3,
+                getters::S3Config::default()
+                    .region("eu-west-1")
+                    .force_path_style(true)
+            );
+        }
+
+        let dest = tmp.path().join("greeting.txt");
+        builder
+            .src("greeting://host/greeting.txt")
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn test_get_caches_downloads() {
+        const MD5: &str = "098f6bcd4621d373cade4e832627b4f6";
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+        let cache_dir = tmp.path().join("cache");
+        let get = |query: &str, dest: &str| {
+            let builder = RequestBuilder::builder()
+                .src(format!(
+                    "remote://{}?mode=copy{}",
+                    source.to_str().unwrap(),
+                    query
+                ))
+                .dest(tmp.path().join(dest))
+                .add_getter("remote", Box::new(RemoteGetter(MD5)))
+                .cache_dir(&cache_dir);
+            async move { builder.get().await }
+        };
+        let query = format!("&checksum=md5:{}", MD5);
+
+        let res = get(&query, "a.txt").await.unwrap();
+        assert!(!res.cached);
+        // what's cached is taken from the cache, with the source gone
+        fs::remove_file(&source).unwrap();
+        let res = get(&query, "b.txt").await.unwrap();
+        assert!(res.cached);
+        assert_eq!(res.checksum, Checksum::parse(&format!("md5:{}", MD5)).ok());
+        assert_eq!(res.bytes, 4);
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("b.txt")).unwrap(),
+            "test"
+        );
+
+        // a cached download that went bad is fetched again
+        let entry = fs::read_dir(&cache_dir).unwrap().next().unwrap().unwrap();
+        fs::remove_file(entry.path()).unwrap();
+        fs::write(entry.path(), "tset").unwrap();
+        fs::write(&source, "test").unwrap();
+        let res = get(&query, "c.txt").await.unwrap();
+        assert!(!res.cached);
+        assert_eq!(fs::read_to_string(entry.path()).unwrap(), "test");
+
+        // only sources with a checksum are cached, looked up or not
+        fs::remove_dir_all(&cache_dir).unwrap();
+        RequestBuilder::builder()
+            .src(format!("remote://{}?mode=copy", source.to_str().unwrap()))
+            .dest(tmp.path().join("d.txt"))
+            .add_getter("remote", Box::new(RemoteGetter(MD5)))
+            .cache_dir(&cache_dir)
+            .verify_remote_checksums(false)
+            .get()
+            .await
+            .unwrap();
+        assert!(!cache_dir.exists());
+        get("", "e.txt").await.unwrap();
+        assert!(get("", "f.txt").await.unwrap().cached);
+    }
+
+    #[tokio::test]
+    async fn test_get_verifies_remote_checksums() {
+        const SHA256: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+        let get = |query: &str, remote: &'static str, dest: &str| {
+            RequestBuilder::builder()
+                .src(format!("remote://{}{}", source.to_str().unwrap(), query))
+                .dest(tmp.path().join(dest))
+                .add_getter("remote", Box::new(RemoteGetter(remote)))
+        };
 
-        let res = b.detect().unwrap();
-        let p = env::current_dir().unwrap().join("test.txt");
-        assert_eq!(format!("file://{}", p.to_str().unwrap()), res);
+        let res = get("", "crc32c:86a072c0", "dest").get().await.unwrap();
+        assert_eq!(res.checksum_source, Some(ChecksumSource::Remote));
+        assert_eq!(res.checksum.unwrap().to_string(), "crc32c:86a072c0");
+
+        let err = get("", "crc32c:00000000", "corrupt")
+            .get()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::ChecksumMismatch { algo: "crc32c", .. }),
+            "{:?}",
+            err
+        );
+        assert!(fs::symlink_metadata(tmp.path().join("corrupt")).is_err());
+
+        let res = get("", "crc32c:00000000", "unchecked")
+            .verify_remote_checksums(false)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.checksum_source, None);
+
+        // a checksum given with the source takes precedence
+        let res = get(&format!("?checksum={}", SHA256), "crc32c:00000000", "given")
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.checksum_source, Some(ChecksumSource::Url));
     }
 
     #[tokio::test]
-    async fn test_get_call() {
-        let source = "./test-get-call.txt";
-        let dest = "./test-get-call-destination.txt";
-        let mut f = File::create(source).unwrap();
+    async fn test_get_skips_sources_already_at_dest() {
+        use sha2::{Digest, Sha256};
+
+        let mut w = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        w.start_file("bin/tool", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        w.write_all(b"test").unwrap();
+        let zip = w.finish().unwrap().into_inner();
+
+        let tmp = tempfile::tempdir().unwrap();
+        for (name, body) in [("tool.txt", b"test".to_vec()), ("bundle.zip", zip)] {
+            let digest: String = Sha256::digest(&body)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            let getter = test_util::InMemoryGetter::new().file(&format!("host/{}", name), body);
+            fs::create_dir(tmp.path().join(name)).unwrap();
+            let dest = tmp.path().join(name).join("dest");
+            let get = |checksum: &str| {
+                RequestBuilder::builder()
+                    .src(format!("stream://host/{}?checksum={}", name, checksum))
+                    .dest(&dest)
+                    .on_exists(OnExists::Overwrite)
+                    .on_conflict(OnConflict::Overwrite)
+                    .add_getter("stream", Box::new(getter.clone()))
+            };
+
+            let res = get(&digest).get().await.unwrap();
+            assert!(!res.not_modified, "{}", name);
+            assert_eq!(getter.downloads(), 1, "{}", name);
+
+            let res = get(&digest).get().await.unwrap();
+            assert!(res.not_modified, "{}", name);
+            assert_eq!(getter.downloads(), 1, "{}", name);
+
+            // another checksum has to be fetched, and fails to verify
+            let other = digest.replace('a', "b");
+            let err = get(&other).get().await.unwrap_err();
+            assert!(
+                matches!(err.inner(), Error::ChecksumMismatch { .. }),
+                "{:?}",
+                err
+            );
+            assert_eq!(getter.downloads(), 2, "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_on_conflict() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+        let dest = tmp.path().join("dest.txt");
+        let get = |policy| {
+            RequestBuilder::builder()
+                .src(source.to_str().unwrap())
+                .dest(&dest)
+                .on_conflict(policy)
+        };
+
+        // a symlink left by an earlier get is in the way like anything else
+        get(OnConflict::Error).get().await.unwrap();
+        let err = get(OnConflict::Error).get().await.unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::DestinationExists(_)),
+            "{:?}",
+            err
+        );
+
+        fs::remove_file(&dest).unwrap();
+        fs::write(&dest, "old").unwrap();
+        let res = get(OnConflict::Skip).get().await.unwrap();
+        assert_eq!(res.skipped, Some(SkipReason::OnConflict));
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "old");
+
+        let res = get(OnConflict::BackupAndOverwrite).get().await.unwrap();
+        assert_eq!(res.skipped, None);
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+        let backup = tmp.path().join("dest.txt.bak.1");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "old");
+
+        fs::remove_file(&dest).unwrap();
+        get(OnConflict::Error).get().await.unwrap();
+        get(OnConflict::Overwrite).get().await.unwrap();
+        assert!(dest.is_symlink());
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+        // the source itself is never touched
+        assert_eq!(fs::read_to_string(&source).unwrap(), "test");
+    }
+
+    #[tokio::test]
+    async fn test_get_skip_existing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dest = tmp.path().join("dest.txt");
+        let getter = test_util::InMemoryGetter::new()
+            .file("host/test.txt", "test")
+            .file("host/other.txt", "test")
+            .file("host/third.txt", "test");
+        let get = |src: &str, skip: SkipExisting| {
+            RequestBuilder::builder()
+                .src(src)
+                .dest(&dest)
+                .on_conflict(OnConflict::Overwrite)
+                .skip_existing(skip)
+                .add_getter("stream", Box::new(getter.clone()))
+        };
+
+        // nothing's recorded without asking for it
+        let res = get("stream://host/test.txt", SkipExisting::Never)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.skipped, None);
+        assert!(!tmp.path().join(".dest.txt.gette-checksum").exists());
+        let res = get("stream://host/test.txt", true.into())
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.skipped, None);
+        assert_eq!(getter.downloads(), 2);
+
+        let res = get("stream://host/test.txt", true.into())
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.skipped, Some(SkipReason::Recorded));
+        assert_eq!(getter.downloads(), 2);
+
+        // the checksum is preferred to what's recorded
+        let sha256 = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        let res = get(
+            &format!("stream://host/test.txt?checksum={}", sha256),
+            true.into(),
+        )
+        .get()
+        .await
+        .unwrap();
+        assert_eq!(res.skipped, Some(SkipReason::ChecksumMatched));
+        assert!(res.not_modified);
+        assert_eq!(getter.downloads(), 2);
+
+        // a source other than the one recorded is fetched over dest
+        let res = get("stream://host/other.txt", true.into())
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.skipped, None);
+        assert_eq!(getter.downloads(), 3);
+
+        let res = get("stream://host/third.txt", SkipExisting::IfPresent)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.skipped, Some(SkipReason::Present));
+        assert_eq!(getter.downloads(), 3);
+
+        fs::remove_file(&dest).unwrap();
+        let res = get("stream://host/third.txt", SkipExisting::IfPresent)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.skipped, None);
+        assert_eq!(getter.downloads(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_get_update() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join("test.txt"), "test").unwrap();
+        let get = |src: &str, getter: &test_util::InMemoryGetter| {
+            test_util::stream_builder(getter, tmp.path())
+                .file_mode(getters::FileMode::Copy)
+                .update(true)
+                .src(src)
+                .dest("./dest.txt")
+        };
+        let getter = test_util::InMemoryGetter::new().file("host/test.txt", "test");
+
+        let res = get("./test.txt", &getter).get().await.unwrap();
+        assert_eq!((res.skipped, res.not_modified), (None, false));
+        let res = get("./test.txt", &getter).get().await.unwrap();
+        assert_eq!(res.skipped, Some(SkipReason::Unchanged));
+        assert!(res.not_modified);
+
+        fs::write(tmp.path().join("test.txt"), "changed").unwrap();
+        let res = get("./test.txt", &getter).get().await.unwrap();
+        assert_eq!(res.skipped, None);
+        let dest = tmp.path().join("dest.txt");
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "changed");
+        let res = get("./test.txt", &getter).get().await.unwrap();
+        assert_eq!(res.skipped, Some(SkipReason::Unchanged));
+
+        // what was recorded is of another source
+        let res = get("stream://host/test.txt", &getter).get().await.unwrap();
+        assert_eq!(res.skipped, None);
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+        // and sources without a fingerprint are fetched every time
+        get("stream://host/test.txt", &getter).get().await.unwrap();
+        assert_eq!(getter.downloads(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_waits_for_locked_dests() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+        let dest = tmp.path().join("out").join("dest.txt");
+        let get = || {
+            RequestBuilder::builder()
+                .src(source.to_str().unwrap())
+                .dest(&dest)
+                .on_conflict(OnConflict::Overwrite)
+                .lock_timeout(Duration::from_millis(100))
+        };
+
+        let lock = lock::DestLock::acquire(&dest, None).await.unwrap();
+        let err = get().get().await.unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::DestinationLocked { .. }),
+            "{:?}",
+            err
+        );
+        assert!(!dest.exists());
+
+        drop(lock);
+        let (a, b) = (get(), get());
+        let (a, b) = tokio::join!(a.get(), b.get());
+        a.unwrap();
+        b.unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+        assert!(!tmp.path().join("out").join("dest.txt.lock").exists());
+    }
+
+    #[tokio::test]
+    async fn test_get_into_existing_directories() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("report.pdf");
+        fs::write(&source, "test").unwrap();
+        let dir = tmp.path().join("dir");
+        fs::create_dir(&dir).unwrap();
+        let get = |policy| {
+            RequestBuilder::builder()
+                .src(source.to_str().unwrap())
+                .dest(&dir)
+                .on_conflict(policy)
+                .file_mode(getters::FileMode::Copy)
+        };
+
+        let res = get(OnConflict::Error).get().await.unwrap();
+        assert_eq!(res.final_path, dir.join("report.pdf"));
+        assert_eq!(fs::read_to_string(dir.join("report.pdf")).unwrap(), "test");
+
+        // the policy applies to what's inside
+        let err = get(OnConflict::Error).get().await.unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::DestinationExists(_)),
+            "{:?}",
+            err
+        );
+        fs::write(&source, "newer").unwrap();
+        get(OnConflict::Overwrite).get().await.unwrap();
+        assert_eq!(fs::read_to_string(dir.join("report.pdf")).unwrap(), "newer");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_copies_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+
+        let dest = tmp.path().join("from-url.txt");
+        RequestBuilder::builder()
+            .src(format!("{}?mode=copy", source.to_str().unwrap()))
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap();
+        let builder_dest = tmp.path().join("from-builder.txt");
+        RequestBuilder::builder()
+            .src(source.to_str().unwrap())
+            .dest(&builder_dest)
+            .file_mode(getters::FileMode::Copy)
+            .get()
+            .await
+            .unwrap();
+
+        fs::remove_file(&source).unwrap();
+        for dest in [dest, builder_dest] {
+            assert!(!dest.is_symlink());
+            assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_dry_run() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+        fs::write(tmp.path().join("bundle.tar.gz"), "not even gzip").unwrap();
+        fs::create_dir(tmp.path().join("dir")).unwrap();
+        let listing = || {
+            let mut names: Vec<_> = fs::read_dir(tmp.path())
+                .unwrap()
+                .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+        let before = listing();
+        let get = |src: &str, dest: &str| {
+            RequestBuilder::builder()
+                .src(tmp.path().join(src).to_str().unwrap())
+                .dest(tmp.path().join(dest))
+                .dry_run(true)
+        };
+
+        let res = get("test.txt", "dir").get().await.unwrap();
+        assert!(res.dry_run);
+        assert_eq!(res.final_path, tmp.path().join("dir/test.txt"));
+        assert_eq!((res.getter.as_str(), res.bytes), ("file", 4));
+        assert_eq!((res.skipped, res.archive), (None, None));
+
+        let res = get("bundle.tar.gz", "out").get().await.unwrap();
+        assert_eq!(res.archive.as_deref(), Some("tar.gz"));
+        assert_eq!(res.final_path, tmp.path().join("out"));
+
+        let err = get("missing.txt", "out").get().await.unwrap_err();
+        assert!(err.is_not_found(), "{:?}", err);
+        assert_eq!(listing(), before);
+
+        // dest is evaluated as a real run would
+        fs::write(tmp.path().join("dest.txt"), "test").unwrap();
+        let err = get("test.txt", "dest.txt").get().await.unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::DestinationExists(_)),
+            "{:?}",
+            err
+        );
+        let res = get(
+            "test.txt?checksum=md5:098f6bcd4621d373cade4e832627b4f6",
+            "dest.txt",
+        )
+        .get()
+        .await
+        .unwrap();
+        assert!(res.dry_run && res.not_modified);
+        let res = get("test.txt", "dest.txt")
+            .on_conflict(OnConflict::Skip)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.skipped, Some(SkipReason::OnConflict));
+        let res = get("test.txt", "dest.txt")
+            .on_conflict(OnConflict::BackupAndOverwrite)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!((res.skipped, res.bytes), (None, 4));
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("dest.txt")).unwrap(),
+            "test"
+        );
+        assert_eq!(listing().len(), before.len() + 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_get_reports_hardlink_fallbacks() {
+        use std::os::unix::fs::MetadataExt;
+
+        // hardlinking needs another filesystem to fail on
+        let tmp = tempfile::tempdir().unwrap();
+        let device = |path: &Path| fs::metadata(path).map(|m| m.dev()).ok();
+        let shm = Path::new("/dev/shm");
+        if device(shm).is_none() || device(shm) == device(tmp.path()) {
+            return;
+        }
+
+        let other = tempfile::tempdir_in(shm).unwrap();
+        fs::create_dir(other.path().join("tree")).unwrap();
+        fs::write(other.path().join("tree/test.txt"), "test").unwrap();
+        fs::write(other.path().join("test.txt"), "test").unwrap();
+        for name in ["tree", "test.txt"] {
+            let dest = tmp.path().join(name);
+            let res = RequestBuilder::builder()
+                .src(other.path().join(name).to_str().unwrap())
+                .dest(&dest)
+                .file_mode(getters::FileMode::Hardlink)
+                .get()
+                .await
+                .unwrap();
+            assert!(res.fallback.is_some(), "{}", name);
+            assert_eq!(download_size(&dest).unwrap(), 4, "{}", name);
+        }
+    }
+
+    /// A getter that dies halfway through writing the source.
+    struct CrashingGetter;
+
+    #[async_trait]
+    impl Getter for CrashingGetter {
+        async fn get(&self, dest: &Path, _: &Url, _: &GetOptions) -> Result<(), Error> {
+            fs::write(dest, "te")?;
+            Err(Error::Io(std::io::Error::other("connection reset")))
+        }
+    }
+
+    /// A getter that stalls halfway through writing the source.
+    struct StallingGetter;
+
+    #[async_trait]
+    impl Getter for StallingGetter {
+        async fn get(&self, dest: &Path, _: &Url, _: &GetOptions) -> Result<(), Error> {
+            fs::write(dest, "te")?;
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_timeouts() {
+        use flate2::{write::GzEncoder, Compression};
+
+        type Timeout = fn(RequestBuilder<NoSrc, NoDest>) -> RequestBuilder<NoSrc, NoDest>;
+        let listing = |dir: &Path| fs::read_dir(dir).unwrap().count();
+        let timeouts: [(&str, Timeout); 2] = [
+            ("total", |b| b.timeout(Duration::from_millis(50))),
+            ("transfer", |b| {
+                b.transfer_timeout(Duration::from_millis(50))
+            }),
+        ];
+        for (name, timeout) in timeouts {
+            let tmp = tempfile::tempdir().unwrap();
+            for src in ["stall://host/test.txt", "stall://host/test.tar.gz"] {
+                let err = timeout(RequestBuilder::builder())
+                    .src(src)
+                    .dest(tmp.path().join("dest"))
+                    .add_getter("stall", Box::new(StallingGetter))
+                    .get()
+                    .await
+                    .unwrap_err();
+                assert!(
+                    matches!(
+                        err.inner(),
+                        Error::Timeout {
+                            phase: Phase::Transfer
+                        }
+                    ),
+                    "{}: {:?}",
+                    name,
+                    err
+                );
+                assert_eq!(listing(tmp.path()), 0, "{}: {}", name, src);
+            }
+        }
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        builder
+            .append_data(&mut header, "bin/tool", "test".as_bytes())
+            .unwrap();
+        let tarball = builder.into_inner().unwrap().finish().unwrap();
+        // streamed and downloaded first
+        for name in ["bundle.tar.gz", "bundle"] {
+            let tmp = tempfile::tempdir().unwrap();
+            let err = RequestBuilder::builder()
+                .src(format!("stream://host/{}", name))
+                .dest(tmp.path().join("dest"))
+                .extract_timeout(Duration::ZERO)
+                .add_getter(
+                    "stream",
+                    Box::new(
+                        test_util::InMemoryGetter::new()
+                            .file(&format!("host/{}", name), tarball.clone()),
+                    ),
+                )
+                .get()
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(
+                    err.inner(),
+                    Error::Timeout {
+                        phase: Phase::Extraction
+                    }
+                ),
+                "{}: {:?}",
+                name,
+                err
+            );
+            assert_eq!(listing(tmp.path()), 0, "{}", name);
+        }
+
+        // a copy stops at the next file, leaving none of it behind
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("source");
+        fs::create_dir(&source).unwrap();
+        for i in 0..64 {
+            fs::write(source.join(i.to_string()), "test").unwrap();
+        }
+        let err = RequestBuilder::builder()
+            .file_mode(getters::FileMode::Copy)
+            .transfer_timeout(Duration::ZERO)
+            .src(source.to_str().unwrap())
+            .dest(tmp.path().join("dest"))
+            .get()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err.inner(),
+                Error::Timeout {
+                    phase: Phase::Transfer
+                }
+            ),
+            "{:?}",
+            err
+        );
+        assert_eq!(listing(tmp.path()), 1);
+    }
+
+    /// A getter whose connection resets on the first tries, leaving half
+    /// of the source behind.
+    fn flaky_getter(failures: usize) -> test_util::FailingGetter {
+        let reset = || Error::from(std::io::Error::from(std::io::ErrorKind::ConnectionReset));
+        test_util::FailingGetter::new(
+            test_util::InMemoryGetter::new().file("host/test.txt", "test"),
+        )
+        .fail_with((0..failures).map(|_| reset()))
+        .partial("te")
+    }
+
+    #[tokio::test]
+    async fn test_get_retries() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dest = tmp.path().join("dest.txt");
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let get = |failures, retry: Option<RetryPolicy>| {
+            let builder = RequestBuilder::builder()
+                .src("flaky://host/test.txt")
+                .dest(&dest)
+                .add_getter("flaky", Box::new(flaky_getter(failures)));
+            match retry {
+                Some(policy) => builder.retry(policy),
+                None => builder,
+            }
+        };
+
+        let err = get(1, None).get().await.unwrap_err();
+        assert!(err.is_retryable(), "{:?}", err);
+        let err = get(3, Some(policy)).get().await.unwrap_err();
+        assert!(err.is_retryable(), "{:?}", err);
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+
+        get(2, Some(policy)).get().await.unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+
+        let mismatch = Error::ChecksumMismatch {
+            algo: "sha256",
+            expected: "a".to_string(),
+            actual: "b".to_string(),
+        };
+        assert!(!mismatch.is_retryable());
+        assert!(!Error::SourceNotFound { source: None }.is_retryable());
+    }
+
+    #[derive(Default)]
+    struct EventLog(std::sync::Mutex<Vec<String>>);
+
+    impl EventHandler for EventLog {
+        fn on_detect(&self, detection: &Detection) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("detect {}", detection.getter));
+        }
+        fn on_start(&self, url: &Url, _: &Path) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("start {}", url.scheme()));
+        }
+        fn on_retry(&self, attempt: u32, _: &Error) {
+            self.0.lock().unwrap().push(format!("retry {}", attempt));
+        }
+        fn on_verified(&self, checksum: &Checksum) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("verified {}", checksum));
+        }
+        fn on_extracted(&self, stats: &ExtractStats) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("extracted {} {}", stats.format, stats.entries));
+        }
+        fn on_warning(&self, warning: &str) {
+            self.0.lock().unwrap().push(format!("warning {}", warning));
+        }
+        fn on_complete(&self, result: &GetResult) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("complete {}", result.bytes));
+        }
+        fn on_failed(&self, err: &Error) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("failed {:?}", err.kind()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_timings() {
+        use std::io::Write;
+
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("a.txt", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(&[b'a'; 4096]).unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+        let getter = test_util::InMemoryGetter::new()
+            .file("host/test.txt", "test")
+            .file("host/bundle.zip", zip);
+        let tmp = tempfile::tempdir().unwrap();
+        let get = |src: &str, dest: &str| {
+            RequestBuilder::builder()
+                .add_getter("mem", Box::new(getter.clone()))
+                .record_checksum(true)
+                .tree_checksum(true)
+                .src(src)
+                .dest(tmp.path().join(dest))
+        };
+        let phases = |t: &Timings| {
+            t.detection + t.locking + t.transfer + t.verification + t.extraction + t.post_processing
+        };
+
+        for (src, extracted) in [
+            ("mem://host/test.txt", false),
+            ("mem://host/bundle.zip", true),
+        ] {
+            let res = get(src, src).get().await.unwrap();
+            let timings = &res.timings;
+            assert!(timings.detection > Duration::ZERO, "{:?}", timings);
+            assert!(timings.locking > Duration::ZERO, "{:?}", timings);
+            assert!(timings.transfer > Duration::ZERO, "{:?}", timings);
+            assert!(timings.verification > Duration::ZERO, "{:?}", timings);
+            assert_eq!(
+                timings.extraction > Duration::ZERO,
+                extracted,
+                "{:?}",
+                timings
+            );
+            // every moment counts toward a phase
+            assert!(phases(timings) <= timings.total);
+            assert!(timings.total - phases(timings) < Duration::from_millis(5));
+            let average = timings.average_rate.unwrap();
+            assert!(average > 0);
+            assert!(timings.peak_rate.unwrap() >= average);
+        }
+
+        // nothing transferred, nothing to rate
+        let res = get("mem://host/test.txt", "dry")
+            .dry_run(true)
+            .get()
+            .await
+            .unwrap();
+        assert!(res.timings.total > Duration::ZERO);
+        assert_eq!(res.timings.transfer, Duration::ZERO);
+        assert_eq!(res.timings.average_rate, None);
+    }
+
+    #[tokio::test]
+    async fn test_get_events() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let log = Arc::new(EventLog::default());
+        let events = || log.0.lock().unwrap().drain(..).collect::<Vec<_>>();
+
+        RequestBuilder::builder()
+            .src("flaky://host/test.txt?checksum=md5:098f6bcd4621d373cade4e832627b4f6")
+            .dest(tmp.path().join("test.txt"))
+            .add_getter("flaky", Box::new(flaky_getter(1)))
+            .retry(RetryPolicy {
+                base_delay: Duration::from_millis(1),
+                ..Default::default()
+            })
+            .events(log.clone())
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(
+            events(),
+            [
+                "detect flaky",
+                "start flaky",
+                "retry 1",
+                "verified md5:098f6bcd4621d373cade4e832627b4f6",
+                "complete 4",
+            ]
+        );
+
+        let source = tmp.path().join("bundle.tar.gz");
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            File::create(&source).unwrap(),
+            Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "bin/tool", "test".as_bytes())
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+        let get = |dest: &str| {
+            RequestBuilder::builder()
+                .src(source.to_str().unwrap())
+                .dest(tmp.path().join(dest))
+                .events(log.clone())
+        };
+
+        let res = get("dest").get().await.unwrap();
+        let mut seen = events();
+        assert_eq!(seen.pop(), Some(format!("complete {}", res.bytes)));
+        assert_eq!(seen, ["detect file", "start file", "extracted tar.gz 1"]);
+
+        // a failed get is told as well, gets that never started included
+        get("dest/bin/tool/nested").get().await.unwrap_err();
+        assert!(events().last().unwrap().starts_with("failed"));
+        RequestBuilder::builder()
+            .src("s4://bucket/key")
+            .dest(tmp.path().join("s4"))
+            .events(log.clone())
+            .get()
+            .await
+            .unwrap_err();
+        assert_eq!(events(), ["detect s4", "failed Config"]);
+    }
+
+    #[tokio::test]
+    async fn test_get_glob_archives() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::time::{Duration, SystemTime};
 
+        let tmp = tempfile::tempdir().unwrap();
+        let builds = tmp.path().join("builds");
+        fs::create_dir(&builds).unwrap();
+        for (i, name) in ["build-b.tar.gz", "build-a.tar.gz"].into_iter().enumerate() {
+            let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+            let mut header = tar::Header::new_gnu();
+            header.set_size(name.len() as u64);
+            header.set_mode(0o644);
+            builder
+                .append_data(&mut header, "name.txt", name.as_bytes())
+                .unwrap();
+            let path = builds.join(name);
+            fs::write(&path, builder.into_inner().unwrap().finish().unwrap()).unwrap();
+            let modified = SystemTime::now() - Duration::from_secs(100 * (i as u64 + 1));
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        }
+        let src = format!("file://{}/build-*.tar.gz", builds.to_str().unwrap());
+
+        // resolving it, or a dry run of it, leaves it unexpanded, even when
+        // nothing matches
+        let missing = format!("file://{}/missing/build-*.tar.gz", builds.to_str().unwrap());
+        for src in [&src, &missing] {
+            let detection = RequestBuilder::builder().src(src).resolve().unwrap();
+            assert_eq!(detection.url.as_str(), src.as_str());
+            let res = RequestBuilder::builder()
+                .src(src)
+                .dest(tmp.path().join("dry"))
+                .dry_run(true)
+                .get()
+                .await
+                .unwrap();
+            assert_eq!(res.bytes, 0);
+        }
+
+        // the newest is fetched, then extracted like any other tarball
+        let dest = tmp.path().join("newest");
+        let res = RequestBuilder::builder()
+            .src(&src)
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.archive.as_deref(), Some("tar.gz"));
+        assert_eq!(
+            fs::read_to_string(dest.join("name.txt")).unwrap(),
+            "build-b.tar.gz"
+        );
+
+        // all of them are placed as they are
+        let dest = tmp.path().join("all");
+        let res = RequestBuilder::builder()
+            .src(format!("{}?mode=copy", src))
+            .dest(&dest)
+            .glob_select(getters::GlobSelect::All)
+            .get_mode(GetMode::Dir)
+            .get()
+            .await
+            .unwrap();
+        assert!(res.archive.is_none());
+        assert!(dest.join("build-a.tar.gz").is_file());
+        assert!(dest.join("build-b.tar.gz").is_file());
+
+        let err = RequestBuilder::builder()
+            .src(format!("{}?glob=one", src))
+            .dest(tmp.path().join("one"))
+            .get()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::GlobMatches { matched: 2, .. }),
+            "{:?}",
+            err
+        );
+        assert_eq!(err.kind(), ErrorKind::Config);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_get_skips_special_files() {
+        use std::os::unix::net::UnixListener;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("source");
+        fs::create_dir(&source).unwrap();
+        fs::write(source.join("a.txt"), "a").unwrap();
+        let _socket = UnixListener::bind(source.join("socket")).unwrap();
+        let log = Arc::new(EventLog::default());
+
+        let dest = tmp.path().join("dest");
+        let res = RequestBuilder::builder()
+            .src(format!("file://{}?mode=copy", source.to_str().unwrap()))
+            .dest(&dest)
+            .events(log.clone())
+            .get()
+            .await
+            .unwrap();
+        assert!(dest.join("a.txt").is_file());
+        assert!(!dest.join("socket").exists());
+        assert_eq!(res.warnings.len(), 1);
+        assert!(res.warnings[0].ends_with("socket, a socket"), "{:?}", res);
+        let warnings: Vec<_> = log
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|e| e.starts_with("warning"))
+            .cloned()
+            .collect();
+        assert_eq!(warnings, [format!("warning {}", res.warnings[0])]);
+
+        let socket = format!(
+            "file://{}?mode=copy",
+            source.join("socket").to_str().unwrap()
+        );
+        let err = RequestBuilder::builder()
+            .src(&socket)
+            .dest(tmp.path().join("refused"))
+            .get()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::UnsupportedSourceType { kind: "socket" }),
+            "{:?}",
+            err
+        );
+        let res = RequestBuilder::builder()
+            .allow_special_files(true)
+            .src(&socket)
+            .dest(tmp.path().join("linked"))
+            .get()
+            .await
+            .unwrap();
+        assert!(tmp.path().join("linked").is_symlink());
+        assert!(res.fallback.is_some());
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        use std::io::ErrorKind as Io;
+
+        let io = |kind| Error::Io(std::io::Error::from(kind));
+        let tests = [
+            (io(Io::NotFound), ErrorKind::NotFound),
+            (io(Io::PermissionDenied), ErrorKind::Auth),
+            (io(Io::ConnectionReset), ErrorKind::Transient),
+            (io(Io::InvalidData), ErrorKind::Other),
+            (Error::SourceNotFound { source: None }, ErrorKind::NotFound),
+            (Error::PathNotFound("a.txt".into()), ErrorKind::NotFound),
+            (
+                Error::AccessDenied {
+                    source: "forbidden".into(),
+                },
+                ErrorKind::Auth,
+            ),
+            (
+                Error::Transfer {
+                    retryable: true,
+                    source: "bad gateway".into(),
+                },
+                ErrorKind::Transient,
+            ),
+            (
+                Error::Throttled {
+                    source: "slow down".into(),
+                },
+                ErrorKind::Transient,
+            ),
+            (
+                Error::Timeout {
+                    phase: Phase::Transfer,
+                },
+                ErrorKind::Timeout,
+            ),
+            (
+                Error::ChecksumMismatch {
+                    algo: "sha256",
+                    expected: "a".into(),
+                    actual: "b".into(),
+                },
+                ErrorKind::Integrity,
+            ),
+            (Error::InvalidArchive("eof".into()), ErrorKind::Archive),
+            (
+                Error::DestinationExists("b.txt".into()),
+                ErrorKind::Destination,
+            ),
+            (
+                Error::GetterNotFound("s4".into(), vec![]),
+                ErrorKind::Config,
+            ),
+            (Error::Cancelled, ErrorKind::Cancelled),
+        ];
+        for (err, kind) in tests {
+            assert_eq!(err.kind(), kind, "{:?}", err);
+            assert_eq!(err.is_not_found(), kind == ErrorKind::NotFound);
+            assert_eq!(err.is_auth(), kind == ErrorKind::Auth);
+            assert_eq!(err.is_retryable(), kind == ErrorKind::Transient);
+            assert_eq!(err.is_throttled(), matches!(err, Error::Throttled { .. }));
+        }
+
+        assert!(Error::SourceNotFound { source: None }.is_permanent());
+        assert!(!io(Io::ConnectionReset).is_permanent());
+        assert!(!Error::Timeout {
+            phase: Phase::Transfer
+        }
+        .is_permanent());
+        // the context a get adds doesn't change what went wrong
+        let context = ErrorContext::new("a.txt", Path::new("b.txt"));
+        let err = Err::<(), _>(Error::SourceNotFound { source: None })
+            .context(&context, Phase::Transfer)
+            .unwrap_err();
+        assert!(err.is_not_found() && err.is_permanent(), "{:?}", err);
+
+        // a source that's gone is the same whatever found out about it
+        let err = Error::source_io(std::io::Error::new(Io::NotFound, "gone"));
+        assert!(matches!(err, Error::SourceNotFound { .. }), "{:?}", err);
+        assert_eq!(std::error::Error::source(&err).unwrap().to_string(), "gone");
+        assert!(matches!(
+            Error::source_io(Io::PermissionDenied.into()),
+            Error::Io(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_leaves_nothing_behind_on_failure() {
+        let tmp = tempfile::tempdir().unwrap();
+        let listing = || {
+            let mut names: Vec<_> = fs::read_dir(tmp.path())
+                .unwrap()
+                .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        for src in ["crash://host/test.txt", "crash://host/test.tar.gz"] {
+            let err = RequestBuilder::builder()
+                .src(src)
+                .dest(tmp.path().join("dest"))
+                .add_getter("crash", Box::new(CrashingGetter))
+                .get()
+                .await
+                .unwrap_err();
+            assert!(matches!(err.inner(), Error::Io(_)), "{:?}", err);
+            assert!(listing().is_empty(), "{}: {:?}", src, listing());
+        }
+
+        // what's at dest stays until what replaces it checks out
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+        let dest = tmp.path().join("dest.txt");
+        fs::write(&dest, "old").unwrap();
+        let err = RequestBuilder::builder()
+            .src(format!(
+                "{}?checksum=sha256:{}",
+                source.to_str().unwrap(),
+                "0".repeat(64)
+            ))
+            .dest(&dest)
+            .on_conflict(OnConflict::Overwrite)
+            .file_mode(getters::FileMode::Copy)
+            .get()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::ChecksumMismatch { .. }),
+            "{:?}",
+            err
+        );
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "old");
+        assert_eq!(listing(), ["dest.txt", "test.txt"]);
+    }
+
+    #[tokio::test]
+    async fn test_get_errors_carry_context() {
+        use std::error::Error as _;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dest = tmp.path().join("dest");
+        let err = RequestBuilder::builder()
+            .src("crash://host/test.txt")
+            .dest(&dest)
+            .add_getter("crash", Box::new(CrashingGetter))
+            .get()
+            .await
+            .unwrap_err();
+        let context = err.context().unwrap();
+        assert_eq!(context.src, "crash://host/test.txt");
+        assert_eq!(
+            context.url.as_ref().unwrap().as_str(),
+            "crash://host/test.txt"
+        );
+        assert_eq!(context.dest, dest);
+        assert_eq!(context.phase, Phase::Transfer);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "getting crash://host/test.txt to {} failed during transfer: connection reset",
+                dest.display()
+            )
+        );
+        assert_eq!(err.source().unwrap().to_string(), "connection reset");
+        assert!(matches!(err.inner(), Error::Io(_)), "{:?}", err);
+
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+        let src = format!("{}?checksum=md5:{}", source.display(), "0".repeat(32));
+        let err = RequestBuilder::builder()
+            .src(&src)
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap_err();
+        assert_eq!(err.context().unwrap().phase, Phase::Verification);
+        assert!(
+            matches!(err.inner(), Error::ChecksumMismatch { .. }),
+            "{:?}",
+            err
+        );
+        assert!(err.to_string().starts_with(&format!(
+            "getting {} (file://{}) to {} failed during verification: md5 checksum mismatch",
+            src,
+            source.display(),
+            dest.display()
+        )));
+
+        fs::write(tmp.path().join("fake.tar.gz"), "not gzip").unwrap();
+        let err = RequestBuilder::builder()
+            .src(tmp.path().join("fake.tar.gz").to_str().unwrap())
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap_err();
+        assert_eq!(err.context().unwrap().phase, Phase::Extraction);
+
+        // detection fails before there's a url
+        let err = RequestBuilder::builder()
+            .src(format!("{}?strip_components=x", source.display()))
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap_err();
+        let context = err.context().unwrap();
+        assert_eq!(
+            (context.phase, context.url.as_ref()),
+            (Phase::Detection, None)
+        );
+        assert!(!dest.exists());
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        progress: std::sync::Mutex<Vec<ExtractProgress>>,
+        stats: std::sync::Mutex<Option<ExtractStats>>,
+    }
+
+    impl ProgressReporter for Recorder {
+        fn extracting(&self, progress: &ExtractProgress) {
+            self.progress.lock().unwrap().push(progress.clone());
+        }
+
+        fn extracted(&self, stats: &ExtractStats) {
+            *self.stats.lock().unwrap() = Some(stats.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_reports_extraction_progress() {
+        let tmp = tempfile::tempdir().unwrap();
+        let tarball = tmp.path().join("bundle.tar");
+        let mut builder = tar::Builder::new(File::create(&tarball).unwrap());
+        for name in ["a.txt", "b.txt"] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(4);
+            header.set_mode(0o644);
+            builder
+                .append_data(&mut header, name, "test".as_bytes())
+                .unwrap();
+        }
+        builder.finish().unwrap();
+
+        let zip = tmp.path().join("bundle.zip");
+        let mut w = zip::ZipWriter::new(File::create(&zip).unwrap());
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            w.start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            w.write_all(b"test").unwrap();
+        }
+        w.finish().unwrap();
+
+        for (source, format, entries, total) in
+            [(tarball, "tar", 2, None), (zip, "zip", 3, Some(3))]
+        {
+            let recorder = Arc::new(Recorder::default());
+            RequestBuilder::builder()
+                .src(source.to_str().unwrap())
+                .dest(tmp.path().join(format))
+                .progress(recorder.clone())
+                .get()
+                .await
+                .unwrap();
+
+            let progress = recorder.progress.lock().unwrap();
+            assert!(progress
+                .windows(2)
+                .all(|p| p[0].entries <= p[1].entries && p[0].bytes <= p[1].bytes));
+            assert!(progress.iter().all(|p| p.total_entries == total));
+
+            let stats = recorder.stats.lock().unwrap().clone().unwrap();
+            assert_eq!(stats.format, format);
+            assert_eq!(stats.entries, entries);
+            assert_eq!(stats.bytes, 4 * entries);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_call() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut f = File::create(tmp.path().join("test-get-call.txt")).unwrap();
         f.write_all("test".as_bytes()).unwrap();
+
+        // relative sources and dests are taken from the base dir, leaving the
+        // working directory, which every test shares, alone
+        for (src, dest) in [
+            ("./test-get-call.txt", "./test-get-call-destination.txt"),
+            ("file://./test-get-call.txt", "sub/destination.txt"),
+        ] {
+            let res = RequestBuilder::builder()
+                .src(src)
+                .dest(dest)
+                .strict_file_detection(true)
+                .base_dir(tmp.path())
+                .dest_jail(".")
+                .get()
+                .await
+                .unwrap();
+            assert_eq!(res.final_path, tmp.path().join(dest), "{}", src);
+            assert_eq!(fs::read_to_string(&res.final_path).unwrap(), "test");
+            assert!(!Path::new(dest).exists(), "{}", dest);
+        }
+
+        let err = RequestBuilder::builder()
+            .src("./test-get-call.txt")
+            .dest("../outside.txt")
+            .base_dir(tmp.path())
+            .dest_jail(".")
+            .get()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::DestOutsideJail { .. }),
+            "{:?}",
+            err
+        );
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn test_get_into_non_utf8_dests() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+        let archive = tmp.path().join("bundle.tar.gz");
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            File::create(&archive).unwrap(),
+            Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "bin/tool", "test".as_bytes())
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let dir = tmp.path().join(OsStr::from_bytes(b"d\xffst"));
+        let dest = dir.join("test.txt");
+        let res = RequestBuilder::builder()
+            .src(source.to_str().unwrap())
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.final_path, dest);
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+
+        // the checksum is recorded next to what was extracted
+        let checksum = Checksum::of_file(&archive).await.unwrap();
+        let dest = dir.join(OsStr::from_bytes(b"bundle-\xfe"));
+        RequestBuilder::builder()
+            .src(format!(
+                "{}?checksum={}",
+                archive.to_str().unwrap(),
+                checksum
+            ))
+            .dest(&dest)
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(dest.join("bin/tool")).unwrap(), "test");
+        assert!(dir
+            .join(OsStr::from_bytes(b".bundle-\xfe.gette-checksum"))
+            .exists());
+    }
+
+    /// A getter for sources that are always directories, counting how
+    /// often it's asked to fetch one.
+    #[derive(Default)]
+    struct DirGetter(Arc<std::sync::atomic::AtomicUsize>);
+
+    #[async_trait]
+    impl Getter for DirGetter {
+        async fn get(&self, dest: &Path, _: &Url, _: &GetOptions) -> Result<(), Error> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            fs::create_dir_all(dest)?;
+            Ok(())
+        }
+
+        async fn mode(&self, _: &Url, _: &GetOptions) -> Result<Option<GetMode>, Error> {
+            Ok(Some(GetMode::Dir))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_mode() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("source.txt");
+        fs::write(&file, "test").unwrap();
+        let dir = tmp.path().join("source");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("test.txt"), "test").unwrap();
+        let archive = tmp.path().join("bundle.tar.gz");
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            File::create(&archive).unwrap(),
+            Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "test.txt", "test".as_bytes())
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let get = |src: &Path, mode: GetMode, dest: &str| {
+            let dest = tmp.path().join(dest);
+            let req = RequestBuilder::builder()
+                .file_mode(getters::FileMode::Copy)
+                .get_mode(mode)
+                .src(src.to_str().unwrap())
+                .dest(&dest);
+            async move { req.get().await.map(|_| dest) }
+        };
+
+        assert!(get(&file, GetMode::File, "file").await.unwrap().is_file());
+        assert!(get(&dir, GetMode::Dir, "dir").await.unwrap().is_dir());
+        assert!(get(&dir, GetMode::Any, "any").await.unwrap().is_dir());
+        // archives are directories once they're extracted
+        assert!(get(&archive, GetMode::Dir, "extracted")
+            .await
+            .unwrap()
+            .is_dir());
+
+        let tests = vec![
+            (&file, GetMode::Dir, GetMode::File),
+            (&dir, GetMode::File, GetMode::Dir),
+            (&archive, GetMode::File, GetMode::Dir),
+        ];
+        for (src, expected, found) in tests {
+            let err = get(src, expected, "mismatched").await.unwrap_err();
+            match err.inner() {
+                Error::ModeMismatch {
+                    expected: e,
+                    found: f,
+                } => assert_eq!((*e, *f), (expected, found), "{}", src.display()),
+                e => panic!("{}: {:?}", src.display(), e),
+            }
+            assert!(!tmp.path().join("mismatched").exists());
+        }
+
+        // nothing's fetched when the getter can tell beforehand
+        let getter = DirGetter::default();
+        let fetched = getter.0.clone();
+        let err = RequestBuilder::builder()
+            .add_getter("dirs", Box::new(getter))
+            .get_mode(GetMode::File)
+            .src("dirs://host/prefix")
+            .dest(tmp.path().join("early"))
+            .get()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::ModeMismatch { .. }),
+            "{:?}",
+            err
+        );
+        assert_eq!(fetched.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_get_fails_without_getter() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dest = tmp.path().join("dest").to_str().unwrap().to_string();
+
+        let err = RequestBuilder::builder()
+            .src("s4://bucket/key")
+            .dest(dest.clone())
+            .get()
+            .await
+            .unwrap_err();
+        let (registered, message): (&[&str], _) = if cfg!(feature = "s3") {
+            (&["file", "s3"], "file, s3")
+        } else {
+            (&["file"], "file")
+        };
+        assert!(
+            matches!(err.inner(), Error::GetterNotFound(ref name, ref r) if name == "s4" && r == registered),
+            "{:?}",
+            err
+        );
+        assert_eq!(
+            err.inner().to_string(),
+            format!(
+                "getter for s4 not found, the registered getters are {}",
+                message
+            )
+        );
+
+        let source = tmp.path().join("test.txt");
+        fs::write(&source, "test").unwrap();
+        let err = RequestBuilder::builder()
+            .src(format!("file://{}", source.to_str().unwrap()))
+            .dest(dest)
+            .clear_getters()
+            .get()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::GetterNotFound(ref name, ref registered) if name == "file" && registered.is_empty()),
+            "{:?}",
+            err
+        );
+        assert_eq!(
+            err.inner().to_string(),
+            "getter for file not found, no getters are registered"
+        );
+        assert!(!tmp.path().join("dest").exists());
+    }
+
+    #[test]
+    fn test_resolve_options() {
+        let src = "https://host/app.zip?archive=tgz&strip_components=2&token=abc";
+        let detection = RequestBuilder::builder()
+            .strip_components(1)
+            .options(GetOptions {
+                archive: Some(ArchiveOption::Disabled),
+                decompress_depth: Some(3),
+                ..Default::default()
+            })
+            .src(src)
+            .dest("dest")
+            .resolve()
+            .unwrap();
+        assert_eq!(detection.archive, None);
+        assert_eq!(detection.options.strip_components, Some(1));
+        assert_eq!(detection.options.decompress_depth, Some(3));
+        assert_eq!(detection.url.as_str(), "https://host/app.zip?token=abc");
+
+        let err = RequestBuilder::builder()
+            .strict_params(true)
+            .src(src)
+            .dest("dest")
+            .resolve()
+            .unwrap_err();
+        assert!(matches!(err.inner(), Error::InvalidUrl { .. }), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_registries() {
+        let builder = RequestBuilder::builder();
+        #[cfg(feature = "s3")]
+        {
+            assert_eq!(builder.getter_names(), ["file", "s3"]);
+            assert_eq!(builder.detector_names(), ["s3", "azure", "git", "file"]);
+        }
+        #[cfg(not(feature = "s3"))]
+        {
+            assert_eq!(builder.getter_names(), ["file"]);
+            assert_eq!(builder.detector_names(), ["azure", "git", "file"]);
+        }
+        assert!(builder.decompressor_names().contains(&"tar.gz"));
+
+        let builder = builder
+            .remove_getter("s3")
+            .remove_getter("missing")
+            .remove_detector("s3")
+            .remove_decompressor("tar.gz");
+        assert_eq!(builder.getter_names(), ["file"]);
+        assert_eq!(builder.detector_names(), ["azure", "git", "file"]);
+        assert!(!builder.decompressor_names().contains(&"tar.gz"));
+
+        // left to the file detector, with the s3 one gone
+        let detection = builder
+            .src("bucket.s3.amazonaws.com/key")
+            .dest("dest")
+            .resolve()
+            .unwrap();
+        assert_eq!(detection.getter, "file");
+
         let builder = RequestBuilder::builder()
-            .src(source.to_string())
-            .dest(dest.to_string());
+            .clear_getters()
+            .clear_detectors()
+            .clear_decompressors();
+        assert!(builder.getter_names().is_empty());
+        assert!(builder.detector_names().is_empty());
+        assert!(builder.decompressor_names().is_empty());
+        let err = builder
+            .src("s3+https://s3.amazonaws.com/bucket/key")
+            .dest("dest")
+            .get()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::GetterNotFound(..)),
+            "{:?}",
+            err
+        );
+    }
 
-        println!("src: {:?}\ndest: {:?}", builder.src, builder.dest);
-        builder.get().await.unwrap();
-        fs::remove_file(source).unwrap();
-        fs::remove_file(dest).unwrap();
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        fn host() -> impl Strategy<Value = String> {
+            prop_oneof![
+                "[a-z0-9-]{1,8}\\.s3\\.[a-z0-9-]{1,8}\\.amazonaws\\.com",
+                "(s3\\.)?[a-z0-9-]{1,8}\\.[a-z0-9-]{1,8}\\.amazonaws\\.com",
+                "[a-z0-9-]{1,8}\\.amazonaws\\.com",
+                "[a-z0-9]{1,8}\\.blob\\.core\\.windows\\.net",
+                "git@[a-z0-9.-]{1,12}:",
+                "[a-z0-9.-]{0,12}",
+            ]
+        }
+
+        fn source() -> impl Strategy<Value = (Option<String>, String, Option<String>)> {
+            (
+                proptest::option::of("[a-z0-9]{1,5}"),
+                (
+                    proptest::option::of(Just("https://")),
+                    host(),
+                    "(/[A-Za-z0-9._-]{0,8}){0,4}",
+                ),
+                proptest::option::of(
+                    "[a-z]{1,5}=([A-Za-z0-9._-]|%2B){0,8}(&[a-z]{1,5}=([A-Za-z0-9._-]|%2F){0,8}){0,3}",
+                ),
+            )
+                .prop_filter("archive is consumed by gette", |(_, _, query)| {
+                    !query.as_deref().unwrap_or_default().contains("archive")
+                })
+                .prop_map(|(forced, (scheme, host, path), query)| {
+                    let mut src = format!("{}{}{}", scheme.unwrap_or_default(), host, path);
+                    if let Some(query) = &query {
+                        src = format!("{}?{}", src, query);
+                    }
+                    if let Some(forced) = &forced {
+                        src = format!("{}+{}", forced, src);
+                    }
+                    (forced, src, query)
+                })
+        }
+
+        /// Detection alone, with no getter to be asked about the source, so
+        /// that arbitrary sources never reach the filesystem.
+        fn resolve(src: &str) -> Result<Detection, Error> {
+            RequestBuilder::builder()
+                .clear_getters()
+                .src(src.to_string())
+                .dest("dest")
+                .resolve()
+        }
+
+        proptest! {
+            #[test]
+            fn resolve_never_panics(src in ".*") {
+                let _ = resolve(&src);
+            }
+
+            #[test]
+            fn forced_proto_round_trips(proto in "[A-Za-z0-9]{1,8}", rest in "(?s).*") {
+                let v = format!("{}+{}", proto, rest);
+                prop_assert_eq!(get_forced_proto(&v), (Some(proto.as_str()), rest.as_str()));
+            }
+
+            #[test]
+            fn detection_invariants((forced, src, query) in source()) {
+                let Ok(detection) = resolve(&src) else {
+                    return Ok(());
+                };
+
+                if let Some(forced) = &forced {
+                    prop_assert_eq!(&detection.getter, forced);
+                }
+
+                if let Some(query) = &query {
+                    prop_assert_eq!(detection.url.query(), Some(query.as_str()));
+                }
+
+                // resolving the resolved source again must be a no-op
+                let again = resolve(&format!("{}+{}", detection.getter, detection.url)).unwrap();
+                prop_assert_eq!(&again.getter, &detection.getter);
+                prop_assert_eq!(&again.url, &detection.url);
+            }
+        }
     }
 }
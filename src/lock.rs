@@ -0,0 +1,186 @@
+//! Advisory locks on dest, so that gets into the same dest from different
+//! processes take turns rather than race each other.
+
+use std::{
+    fs::{self, File, OpenOptions, TryLockError},
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, Instant},
+};
+
+use crate::Error;
+
+/// How often a lock someone else holds is tried again.
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Path the lock on dest is taken on, `<dest>.lock`.
+fn lock_path(dest: &Path) -> PathBuf {
+    match dest.file_name() {
+        Some(name) => {
+            let mut name = name.to_owned();
+            name.push(".lock");
+            dest.with_file_name(name)
+        }
+        None => dest.join(".gette.lock"),
+    }
+}
+
+/// An exclusive lock on dest, held for as long as it's alive. The lock file
+/// holds the pid of the process holding the lock, and is removed when the
+/// lock is dropped, unwinding from a panic included.
+pub(crate) struct DestLock {
+    path: PathBuf,
+    // the lock goes with the handle, once the file is removed
+    _file: File,
+}
+
+impl DestLock {
+    /// Takes the lock on dest, waiting for whoever holds it for up to
+    /// `timeout`, or for as long as it takes without one. The directory dest
+    /// is in is created for the lock file if it doesn't exist yet.
+    pub(crate) async fn acquire(dest: &Path, timeout: Option<Duration>) -> Result<Self, Error> {
+        let path = lock_path(dest);
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(Error::DestinationNotCreated)?;
+        }
+        let start = Instant::now();
+        loop {
+            if let Some(lock) = Self::try_acquire(&path)? {
+                return Ok(lock);
+            }
+            if timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+                return Err(Error::DestinationLocked {
+                    dest: dest.display().to_string(),
+                    pid: holder(&path),
+                });
+            }
+            tokio::time::sleep(RETRY_INTERVAL).await;
+        }
+    }
+
+    fn try_acquire(path: &Path) -> Result<Option<Self>, Error> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path);
+        let mut file = match file {
+            Ok(file) => file,
+            // windows won't open a file that's being removed until it's gone
+            Err(e) if cfg!(windows) && e.kind() == std::io::ErrorKind::PermissionDenied => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Ok(None),
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+        // the last holder may have removed the file after it was opened here,
+        // and whoever opens the path now would get a lock of their own
+        if !is_at(&file, path) {
+            return Ok(None);
+        }
+
+        file.set_len(0)?;
+        write!(file, "{}", process::id())?;
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            _file: file,
+        }))
+    }
+}
+
+impl Drop for DestLock {
+    fn drop(&mut self) {
+        // removed while it's still locked, so nobody takes the lock on it
+        // only to find it gone
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Pid of the process holding the lock at path, if it can be told. Windows
+/// doesn't let anyone else read a locked file.
+fn holder(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Whether the file is still the one at path.
+#[cfg(unix)]
+fn is_at(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_at(_file: &File, path: &Path) -> bool {
+    path.exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_path() {
+        let tests = vec![
+            ("dest", "dest.lock"),
+            ("dir/dest.tar.gz", "dir/dest.tar.gz.lock"),
+            ("dir/dest/", "dir/dest.lock"),
+        ];
+        for (dest, expected) in tests {
+            assert_eq!(lock_path(Path::new(dest)), Path::new(expected), "{}", dest);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dest_lock() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dest = tmp.path().join("dest");
+        let timeout = Some(Duration::from_millis(100));
+
+        let lock = DestLock::acquire(&dest, timeout).await.unwrap();
+        assert!(tmp.path().join("dest.lock").exists());
+        let err = DestLock::acquire(&dest, timeout).await.err().unwrap();
+        match err {
+            #[cfg(unix)]
+            Error::DestinationLocked { pid, .. } => assert_eq!(pid, Some(process::id())),
+            #[cfg(not(unix))]
+            Error::DestinationLocked { .. } => {}
+            e => panic!("{:?}", e),
+        }
+
+        // whoever's waiting gets it once it's let go
+        let waiting = tokio::spawn({
+            let dest = dest.clone();
+            async move { DestLock::acquire(&dest, None).await.map(drop) }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(lock);
+        waiting.await.unwrap().unwrap();
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+
+        // even when the holder panics
+        let held = std::thread::spawn({
+            let dest = dest.clone();
+            move || {
+                let rt = tokio::runtime::Builder::new_current_thread()
+                    .enable_time()
+                    .build()
+                    .unwrap();
+                let _lock = rt.block_on(DestLock::acquire(&dest, None)).unwrap();
+                panic!("holder crashed");
+            }
+        });
+        assert!(held.join().is_err());
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+        DestLock::acquire(&dest, timeout).await.unwrap();
+    }
+}
@@ -0,0 +1,289 @@
+//! Lockfiles pinning what a batch fetched, so that it can be fetched again
+//! exactly as it was, or not at all.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Checksum, Error, GetOptions, GetResult, MultiRequestBuilder, SkipExisting, Url};
+
+/// What every source of a batch resolved to when it was fetched, made with
+/// [`MultiRequestBuilder::lock`] and serialized to any format serde
+/// supports. Sources are ordered by dest, then by source, so that the same
+/// batch always makes the same lockfile.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let batch = gette::RequestBuilder::builder()
+///     .queue("s3+https://s3.amazonaws.com/bucket/app.tar.gz", "app")
+///     .queue("s3+https://s3.amazonaws.com/bucket/data.zip", "data");
+/// let lockfile = batch.lock().await?;
+/// std::fs::write("gette.lock", serde_json::to_string_pretty(&lockfile)?)?;
+///
+/// // later on, fetching only what doesn't match it anymore
+/// let lockfile = serde_json::from_slice(&std::fs::read("gette.lock")?)?;
+/// for res in batch.get_locked(&lockfile).await {
+///     res?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// Version of the format, [`Lockfile::VERSION`] for those gette makes.
+    pub version: u32,
+    pub sources: Vec<LockedSource>,
+}
+
+/// A source as it was fetched, see [`Lockfile`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedSource {
+    /// The source, as it was queued.
+    pub src: String,
+    /// Where the source was fetched to, as it was queued.
+    pub dest: PathBuf,
+    /// The url the source resolved to, along with the ref, the version or
+    /// whatever else the getter was told to fetch in its query.
+    pub url: Url,
+    /// Name of the getter the source was fetched with.
+    pub getter: String,
+    /// Sha256 of the bytes downloaded, for sources that are downloaded as
+    /// a file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<Checksum>,
+    /// Tree checksum of what the source ended up as at dest, see
+    /// [`checksum::tree`](crate::checksum::tree).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tree_checksum: Option<Checksum>,
+    /// Bytes the source took up, see [`GetResult::bytes`](crate::GetResult::bytes).
+    pub size: u64,
+}
+
+impl Lockfile {
+    /// Version of the format this release of gette makes and reads.
+    pub const VERSION: u32 = 1;
+
+    /// The source locked as it was queued, if it is.
+    pub fn source(&self, src: &str, dest: impl AsRef<Path>) -> Option<&LockedSource> {
+        let dest = dest.as_ref();
+        self.sources.iter().find(|s| s.src == src && s.dest == dest)
+    }
+}
+
+impl MultiRequestBuilder {
+    /// Fetches every source queued, as [`MultiRequestBuilder::get_all`]
+    /// does, and pins what each of them resolved to in a lockfile. Every
+    /// source is fetched afresh, whatever the builder says about skipping
+    /// them, and the lockfile is only made when none of them failed.
+    pub async fn lock(&self) -> Result<Lockfile, Error> {
+        let builder = self
+            .builder
+            .clone()
+            .skip_existing(SkipExisting::Never)
+            .update(false)
+            .dry_run(false)
+            .record_checksum(true)
+            .tree_checksum(true);
+        let results = self.get_all_with(&builder, |_| Ok(None)).await;
+
+        let mut sources = Vec::with_capacity(results.len());
+        let mut failed = None;
+        for ((src, dest, _), res) in self.queue.iter().zip(results) {
+            match res {
+                Ok(res) => sources.push(LockedSource {
+                    src: src.clone(),
+                    dest: dest.given(),
+                    url: res.source_url,
+                    getter: res.getter,
+                    checksum: res.checksum,
+                    tree_checksum: res.tree_checksum,
+                    size: res.bytes,
+                }),
+                // the source that failed says more than those cancelled for it
+                Err(e) if failed.is_none() || matches!(failed, Some(Error::Cancelled)) => {
+                    failed = Some(e);
+                }
+                Err(_) => {}
+            }
+        }
+        if let Some(e) = failed {
+            return Err(e);
+        }
+
+        sources.sort_by(|a, b| (&a.dest, &a.src).cmp(&(&b.dest, &b.src)));
+        Ok(Lockfile {
+            version: Lockfile::VERSION,
+            sources,
+        })
+    }
+
+    /// Fetches every source queued strictly as the lockfile pins it, failing
+    /// those that aren't in it, that resolve to anything else, or whose
+    /// download or tree doesn't match its checksums anymore. Sources whose
+    /// dest still matches the tree checksum they're locked with are skipped
+    /// without going over the network. Sources in the lockfile that aren't
+    /// queued are left alone.
+    pub async fn get_locked(&self, lockfile: &Lockfile) -> Vec<Result<GetResult, Error>> {
+        if lockfile.version != Lockfile::VERSION {
+            return self
+                .queue
+                .iter()
+                .map(|_| {
+                    Err(Error::Unsupported {
+                        what: format!("lockfile version {}", lockfile.version),
+                    })
+                })
+                .collect();
+        }
+
+        let builder = self
+            .builder
+            .clone()
+            .skip_existing(SkipExisting::IfMatching)
+            .update(false);
+        let pin = |i: usize| {
+            let (src, dest, options) = &self.queue[i];
+            let mismatch = |reason: String| Error::LockfileMismatch {
+                src: src.clone(),
+                reason,
+            };
+            let locked = lockfile
+                .source(src, dest.given())
+                .ok_or_else(|| mismatch("it isn't in the lockfile".to_string()))?;
+
+            // resolving the source is enough to tell whether it's the one
+            // locked, before anything is fetched
+            let mut resolving = builder.clone();
+            if let Some(options) = options {
+                resolving = resolving.options(options.clone());
+            }
+            let detection = resolving.resolve_src(src)?;
+            if detection.getter != locked.getter || detection.url != locked.url {
+                return Err(mismatch(format!(
+                    "it resolves to {}::{}, the lockfile has {}::{}",
+                    detection.getter, detection.url, locked.getter, locked.url
+                )));
+            }
+
+            Ok(Some(GetOptions {
+                checksum: locked.checksum.clone(),
+                tree_checksum: locked.tree_checksum.clone(),
+                ..Default::default()
+            }))
+        };
+        self.get_all_with(&builder, pin).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        checksum,
+        test_util::{stream_builder, InMemoryGetter},
+        SkipReason,
+    };
+
+    fn batch(getter: &InMemoryGetter, dir: &Path) -> MultiRequestBuilder {
+        stream_builder(getter, dir)
+            .queue("stream://host/b.txt?ref=v1", "b.txt")
+            .queue("stream://host/a.txt", "a.txt")
+    }
+
+    #[tokio::test]
+    async fn test_lock() {
+        let tmp = tempfile::tempdir().unwrap();
+        let getter = InMemoryGetter::new()
+            .file("host/a.txt", "a")
+            .file("host/b.txt", "b");
+
+        let lockfile = batch(&getter, tmp.path()).lock().await.unwrap();
+        assert_eq!(lockfile.version, Lockfile::VERSION);
+        let dests: Vec<_> = lockfile.sources.iter().map(|s| &s.dest).collect();
+        assert_eq!(dests, [Path::new("a.txt"), Path::new("b.txt")]);
+        let b = lockfile
+            .source("stream://host/b.txt?ref=v1", "b.txt")
+            .unwrap();
+        assert_eq!(b.url.as_str(), "stream://host/b.txt?ref=v1");
+        assert_eq!(b.getter, "stream");
+        assert_eq!(b.size, 1);
+        let b_txt = tmp.path().join("b.txt");
+        assert!(b
+            .checksum
+            .as_ref()
+            .unwrap()
+            .matches_file(&b_txt)
+            .await
+            .unwrap());
+        assert_eq!(b.tree_checksum, Some(checksum::tree(&b_txt).await.unwrap()));
+
+        let json = serde_json::to_string(&lockfile).unwrap();
+        assert_eq!(serde_json::from_str::<Lockfile>(&json).unwrap(), lockfile);
+        // the same batch makes the same lockfile
+        let relocked = batch(&getter, tmp.path()).lock().await.unwrap();
+        assert_eq!(serde_json::to_string(&relocked).unwrap(), json);
+
+        let failing = batch(&getter, tmp.path()).queue("stream://host/missing", "c.txt");
+        assert!(failing.lock().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_locked() {
+        let tmp = tempfile::tempdir().unwrap();
+        let getter = InMemoryGetter::new()
+            .file("host/a.txt", "a")
+            .file("host/b.txt", "b");
+        let lockfile = batch(&getter, tmp.path()).lock().await.unwrap();
+        let downloads = getter.downloads();
+
+        // dests matching the lockfile aren't fetched again
+        for res in batch(&getter, tmp.path()).get_locked(&lockfile).await {
+            assert_eq!(res.unwrap().skipped, Some(SkipReason::ChecksumMatched));
+        }
+        assert_eq!(getter.downloads(), downloads);
+
+        // those that don't are, as long as the source hasn't changed either
+        let a_txt = tmp.path().join("a.txt");
+        fs::write(&a_txt, "changed").unwrap();
+        let results = batch(&getter, tmp.path()).get_locked(&lockfile).await;
+        assert_eq!(results[1].as_ref().unwrap().skipped, None);
+        assert_eq!(fs::read_to_string(&a_txt).unwrap(), "a");
+        assert_eq!(getter.downloads(), downloads + 1);
+
+        fs::write(&a_txt, "changed").unwrap();
+        let changed = InMemoryGetter::new()
+            .file("host/a.txt", "changed upstream")
+            .file("host/b.txt", "b");
+        let results = batch(&changed, tmp.path()).get_locked(&lockfile).await;
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1].as_ref().map_err(Error::inner),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+
+        assert_eq!(fs::read_to_string(&a_txt).unwrap(), "changed");
+
+        // sources queued otherwise than they're locked fail without a fetch
+        fs::write(&a_txt, "a").unwrap();
+        let results = batch(&getter, tmp.path())
+            .queue("stream://host/a.txt", "c.txt")
+            .get_locked(&lockfile)
+            .await;
+        assert!(matches!(results[2], Err(Error::LockfileMismatch { .. })));
+        let mut moved = lockfile.clone();
+        moved.sources[1].url = Url::parse("stream://host/b.txt?ref=v2").unwrap();
+        let results = batch(&getter, tmp.path()).get_locked(&moved).await;
+        assert!(matches!(results[0], Err(Error::LockfileMismatch { .. })));
+        assert!(results[1].is_ok());
+        assert_eq!(getter.downloads(), downloads + 1);
+
+        let future = Lockfile {
+            version: Lockfile::VERSION + 1,
+            ..lockfile
+        };
+        let results = batch(&getter, tmp.path()).get_locked(&future).await;
+        assert!(matches!(results[0], Err(Error::Unsupported { .. })));
+    }
+}
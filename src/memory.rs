@@ -0,0 +1,356 @@
+//! Gets without a dest, into memory for sources small enough that a file
+//! of their own would be pure ceremony, like a config file, or as a stream
+//! to be read as it arrives.
+
+use std::{fs, path::Path, pin::Pin};
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{
+    checksum::VerifyingReader,
+    context::Context,
+    deadline::{within, Deadlines},
+    staging::{scratch, TempDest},
+    Detection, Error, ErrorContext, GetMode, Getter, NoDest, Phase, RequestBuilder, Src,
+};
+
+/// Bytes a get into memory may take up unless the builder says otherwise.
+pub(crate) const DEFAULT_MAX_IN_MEMORY: u64 = 64 << 20;
+
+impl RequestBuilder<Src, NoDest> {
+    /// Fetches the source into memory instead of to a dest, checking it
+    /// against its checksum and signature like [`get`] does. Getters that
+    /// can open the source as a stream are read from directly, anything
+    /// else goes through a temporary file, and sources that turn out to be
+    /// directories fail with [`Error::ModeMismatch`]. Archives are returned
+    /// as they are, never extracted.
+    ///
+    /// Nothing larger than [`max_in_memory`] is ever buffered, it fails
+    /// with [`Error::TooLargeForMemory`] instead.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// # let tmp = tempfile::tempdir().unwrap();
+    /// # std::fs::write(tmp.path().join("config.toml"), "debug = false").unwrap();
+    /// let config = gette::RequestBuilder::builder()
+    ///     .base_dir(tmp.path())
+    ///     .src("./config.toml")
+    ///     .get_bytes()
+    ///     .await
+    ///     .unwrap();
+    /// assert_eq!(config, b"debug = false");
+    /// # })
+    /// ```
+    ///
+    /// [`get`]: RequestBuilder::get
+    /// [`max_in_memory`]: RequestBuilder::max_in_memory
+    pub async fn get_bytes(&self) -> Result<Vec<u8>, Error> {
+        self.bytes_of(&self.src.0).await
+    }
+
+    /// Fetches the source into memory like [`get_bytes`], failing with
+    /// [`Error::NotUtf8`] when it isn't text.
+    ///
+    /// [`get_bytes`]: RequestBuilder::get_bytes
+    pub async fn get_string(&self) -> Result<String, Error> {
+        self.string_of(&self.src.0).await
+    }
+
+    /// Opens the source as a stream of its bytes, for piping it somewhere
+    /// without it ever touching the disk. Only getters that can open a
+    /// stream support this, the built-in file and S3 getters among them,
+    /// anything else fails with [`Error::Unsupported`].
+    ///
+    /// A source with a checksum is checked against it as it's read, the
+    /// read reaching the end failing when it doesn't match, see
+    /// [`VerifyingReader`]. What was read before then is unverified.
+    /// Signatures can't be checked this way, unless they're over a sums
+    /// file.
+    ///
+    /// ```
+    /// # tokio_test::block_on(async {
+    /// # let tmp = tempfile::tempdir().unwrap();
+    /// # std::fs::write(tmp.path().join("events.log"), "started\n").unwrap();
+    /// use tokio::io::AsyncBufReadExt;
+    ///
+    /// let reader = gette::RequestBuilder::builder()
+    ///     .base_dir(tmp.path())
+    ///     .src("./events.log")
+    ///     .get_reader()
+    ///     .await
+    ///     .unwrap();
+    /// let mut lines = tokio::io::BufReader::new(reader).lines();
+    /// assert_eq!(lines.next_line().await.unwrap().unwrap(), "started");
+    /// # })
+    /// ```
+    pub async fn get_reader(&self) -> Result<impl AsyncRead + Unpin + Send, Error> {
+        self.reader_of(&self.src.0).await
+    }
+}
+
+impl<S, D> RequestBuilder<S, D> {
+    /// Fetches src into memory, what [`RequestBuilder::get_bytes`] does for
+    /// the source it was built with.
+    pub(crate) async fn bytes_of(&self, src: &str) -> Result<Vec<u8>, Error> {
+        let deadlines = self.timeouts.start();
+        let (detection, getter, context) = self.prepare(src, &deadlines).await?;
+        let scratch = scratch();
+        let signature = self.fetch_signature(&detection, &scratch, &deadlines);
+        let signature = signature.await.context(&context, Phase::Detection)?;
+
+        let read = self.read_source(getter, &detection);
+        let bytes = within(deadlines.transfer(), Phase::Transfer, read)
+            .await
+            .context(&context, Phase::Transfer)?;
+
+        if let Some(signature) = &signature {
+            let verified = self.keys.verify(signature, &bytes[..]);
+            verified.context(&context, Phase::Verification)?;
+        }
+        if let Some(expected) = &detection.options.checksum {
+            let actual = expected.algo().of_reader(&bytes[..]).await;
+            let verified = actual.and_then(|actual| expected.verify(&actual));
+            verified.context(&context, Phase::Verification)?;
+        }
+        Ok(bytes)
+    }
+
+    /// Opens src as a stream, what [`RequestBuilder::get_reader`] does for
+    /// the source it was built with.
+    pub(crate) async fn reader_of(
+        &self,
+        src: &str,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>, Error> {
+        let deadlines = self.timeouts.start();
+        let (detection, getter, context) = self.prepare(src, &deadlines).await?;
+        if detection.options.source_signature().is_some() {
+            let unsupported = Error::Unsupported {
+                what: "checking the signature of a source while streaming it".to_string(),
+            };
+            return Err(unsupported).context(&context, Phase::Verification);
+        }
+
+        let body = self.retrying(|| getter.open(&detection.url, &detection.options));
+        let body = within(deadlines.transfer(), Phase::Transfer, body)
+            .await
+            .context(&context, Phase::Transfer)?;
+        let Some(body) = body else {
+            let unsupported = Error::Unsupported {
+                what: format!("streaming sources of the {} getter", detection.getter),
+            };
+            return Err(unsupported).context(&context, Phase::Transfer);
+        };
+        Ok(match detection.options.checksum {
+            Some(checksum) => Box::pin(VerifyingReader::new(body, checksum)),
+            None => body,
+        })
+    }
+
+    /// Resolves src and looks up the checksum it's to be checked against,
+    /// for a get without a dest.
+    async fn prepare(
+        &self,
+        src: &str,
+        deadlines: &Deadlines,
+    ) -> Result<(Detection, &(dyn Getter + Send + Sync), ErrorContext), Error> {
+        let mut context = ErrorContext::new(src, Path::new(""));
+        let mut detection = self.resolve_src(src).context(&context, Phase::Detection)?;
+        context.url = Some(detection.url.clone());
+        let getter = self
+            .getter(&detection.getter)
+            .context(&context, Phase::Detection)?;
+
+        let scratch = scratch();
+        let looked_up = self.lookup_checksum(getter, &mut detection, &scratch, deadlines);
+        looked_up.await.context(&context, Phase::Detection)?;
+        Ok((detection, getter, context))
+    }
+
+    /// Fetches src into memory as text, what [`RequestBuilder::get_string`]
+    /// does for the source it was built with.
+    pub(crate) async fn string_of(&self, src: &str) -> Result<String, Error> {
+        let bytes = self.bytes_of(src).await?;
+        String::from_utf8(bytes)
+            .map_err(Error::NotUtf8)
+            .context(&ErrorContext::new(src, Path::new("")), Phase::Verification)
+    }
+
+    /// Reads the source, never holding more than the builder lets a get
+    /// into memory take up.
+    async fn read_source(
+        &self,
+        getter: &(dyn Getter + Send + Sync),
+        detection: &Detection,
+    ) -> Result<Vec<u8>, Error> {
+        let max = self.max_in_memory;
+        let body = self.retrying(|| getter.open(&detection.url, &detection.options));
+        if let Some(body) = body.await? {
+            let mut bytes = Vec::new();
+            // a byte over is all it takes to tell the source doesn't fit
+            body.take(max.saturating_add(1))
+                .read_to_end(&mut bytes)
+                .await?;
+            if bytes.len() as u64 > max {
+                return Err(Error::TooLargeForMemory { max });
+            }
+            return Ok(bytes);
+        }
+
+        let download = TempDest::new(&scratch());
+        self.retrying(|| async {
+            download.clear()?;
+            getter
+                .get(download.path(), &detection.url, &detection.options)
+                .await
+        })
+        .await?;
+        let meta = fs::metadata(download.path())?;
+        if !meta.is_file() {
+            return Err(Error::ModeMismatch {
+                expected: GetMode::File,
+                found: GetMode::Dir,
+            });
+        }
+        if meta.len() > max {
+            return Err(Error::TooLargeForMemory { max });
+        }
+        Ok(fs::read(download.path())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{FailingGetter, InMemoryGetter};
+
+    fn builder(getter: &InMemoryGetter) -> RequestBuilder<crate::NoSrc, NoDest> {
+        RequestBuilder::builder().add_getter("mem", Box::new(getter.clone()))
+    }
+
+    #[tokio::test]
+    async fn it_should_get_into_memory() {
+        let getter = InMemoryGetter::new()
+            .file("host/config.toml", "debug = false")
+            .file("host/binary", vec![0xff, 0xfe]);
+        let config = builder(&getter)
+            .src("mem://host/config.toml")
+            .get_string()
+            .await
+            .unwrap();
+        assert_eq!(config, "debug = false");
+        // streamed, never written anywhere
+        assert_eq!((getter.opens(), getter.downloads()), (1, 0));
+
+        let res = builder(&getter).src("mem://host/binary").get_string().await;
+        assert!(
+            matches!(res.as_ref().map_err(Error::inner), Err(Error::NotUtf8(_))),
+            "{:?}",
+            res
+        );
+        let res = builder(&getter).src("mem://host/missing").get_bytes().await;
+        assert!(res.unwrap_err().is_not_found());
+    }
+
+    #[tokio::test]
+    async fn it_should_cap_what_it_buffers() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join("big.txt"), "0123456789").unwrap();
+        fs::create_dir(tmp.path().join("dir")).unwrap();
+        let getter = InMemoryGetter::new().file("host/big.txt", "0123456789");
+
+        // streamed and through a temporary file alike
+        for src in ["mem://host/big.txt", "tmp://host/big.txt"] {
+            let b = builder(&getter)
+                .add_getter("tmp", Box::new(FailingGetter::new(getter.clone())))
+                .base_dir(tmp.path());
+            let bytes = b.clone().max_in_memory(10).src(src).get_bytes().await;
+            assert_eq!(bytes.unwrap(), b"0123456789");
+            let err = b.max_in_memory(9).src(src).get_bytes().await.unwrap_err();
+            assert!(
+                matches!(err.inner(), Error::TooLargeForMemory { max: 9 }),
+                "{}: {:?}",
+                src,
+                err
+            );
+        }
+
+        let err = builder(&getter)
+            .base_dir(tmp.path())
+            .src("./dir")
+            .get_bytes()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::ModeMismatch { .. }),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_verify_what_it_gets() {
+        let getter = InMemoryGetter::new().file("host/a.txt", "test");
+        let sha256 = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        let bytes = builder(&getter)
+            .src(format!("mem://host/a.txt?checksum=sha256:{}", sha256))
+            .get_bytes()
+            .await;
+        assert_eq!(bytes.unwrap(), b"test");
+
+        let err = builder(&getter)
+            .src(format!(
+                "mem://host/a.txt?checksum=sha256:{}",
+                sha256.replace('9', "8")
+            ))
+            .get_bytes()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::ChecksumMismatch { .. }),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn it_should_stream_sources() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join("a.txt"), "test").unwrap();
+        let getter = InMemoryGetter::new().file("host/a.txt", "test");
+        let md5 = "098f6bcd4621d373cade4e832627b4f6";
+        let b = builder(&getter)
+            .add_getter("tmp", Box::new(FailingGetter::new(getter.clone())))
+            .base_dir(tmp.path());
+
+        for src in [
+            "./a.txt".to_string(),
+            format!("mem://host/a.txt?checksum=md5:{}", md5),
+        ] {
+            let mut reader = b.clone().src(&src).get_reader().await.unwrap();
+            let mut read = String::new();
+            reader.read_to_string(&mut read).await.unwrap();
+            assert_eq!(read, "test", "{}", src);
+        }
+        assert_eq!((getter.opens(), getter.downloads()), (1, 0));
+
+        let src = format!("mem://host/a.txt?checksum=md5:{}", md5.replace('0', "1"));
+        let mut reader = b.clone().src(src).get_reader().await.unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).await.unwrap_err();
+        let err = err.into_inner().unwrap().downcast::<Error>().unwrap();
+        assert!(matches!(*err, Error::ChecksumMismatch { .. }), "{:?}", err);
+
+        for src in [
+            "tmp://host/a.txt",
+            "mem://host/a.txt?signature=mem://host/a.txt.sig",
+        ] {
+            let res = b.clone().src(src).get_reader().await;
+            let err = res.err().unwrap();
+            assert!(
+                matches!(err.inner(), Error::Unsupported { .. }),
+                "{}: {:?}",
+                src,
+                err
+            );
+        }
+    }
+}
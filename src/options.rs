@@ -0,0 +1,572 @@
+//! Options of a single get, typed, along with the one parser that takes them
+//! out of the query string of the source.
+
+use std::{collections::HashMap, fmt, path::Path, str::FromStr};
+
+use crate::{
+    detectors::take_param,
+    getters::{CopyLinks, FileMode, GlobSelect, SymlinkTarget},
+    AllowedHosts, Checksum, ChecksumAlgo, Error, InvalidUrlKind, OnExists, Password,
+};
+
+/// Query parameters gette reads, taken out of the source before it's handed
+/// to the getter.
+const PARAMS: &[&str] = &[
+    "archive_password",
+    "archive",
+    "strip_components",
+    "on_exists",
+    "decompress_depth",
+    "checksum",
+    "tree_checksum",
+    "signature",
+    "mode",
+    "symlink",
+    "links",
+    "exclude",
+    "glob",
+];
+
+/// Options of a get, given either as query parameters of the source or on
+/// the builder with [`RequestBuilder::options`] and the setters of each of
+/// them. Where both give one, the builder's is used.
+///
+/// Query parameters that aren't gette's are left in the url untouched, as
+/// the remote may need them, unless [`RequestBuilder::strict_params`] says
+/// otherwise.
+///
+/// [`RequestBuilder::options`]: crate::RequestBuilder::options
+/// [`RequestBuilder::strict_params`]: crate::RequestBuilder::strict_params
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct GetOptions {
+    /// Archive format the source is decompressed with, `?archive=`. Found
+    /// from the extension of the source when not given.
+    pub archive: Option<ArchiveOption>,
+    /// Leading path components dropped from archive entries,
+    /// `?strip_components=`.
+    pub strip_components: Option<usize>,
+    /// What to do when archives are extracted into a non-empty destination,
+    /// `?on_exists=`.
+    pub on_exists: Option<OnExists>,
+    /// Password encrypted archive entries are decrypted with,
+    /// `?archive_password=`.
+    pub archive_password: Option<Password>,
+    /// How many levels of archives nested in each other are extracted,
+    /// `?decompress_depth=`.
+    pub decompress_depth: Option<usize>,
+    /// Digest the downloaded bytes must have, `?checksum=<algo>:<hex>`.
+    pub checksum: Option<Checksum>,
+    /// Sums file the checksum is looked up in instead,
+    /// `?checksum=file:<url>`.
+    pub checksum_file: Option<String>,
+    /// Sha256 tree checksum of what the source ends up as at dest,
+    /// `?tree_checksum=`.
+    pub tree_checksum: Option<Checksum>,
+    /// Detached signature of the sums file or of the source,
+    /// `?signature=<url>`.
+    pub signature: Option<String>,
+    /// How the file getter puts sources at dest, `?mode=`.
+    pub file_mode: Option<FileMode>,
+    /// What the symlinks the file getter makes point to, `?symlink=`.
+    pub symlink_target: Option<SymlinkTarget>,
+    /// What the file getter does with the symlinks in the directories it
+    /// copies, `?links=`.
+    pub copy_links: Option<CopyLinks>,
+    /// Globs of the paths the file getter leaves out of the directories it
+    /// copies, `?exclude=<glob>,<glob>`.
+    pub exclude: Vec<String>,
+    /// Which of the files a glob source matches the file getter fetches,
+    /// `?glob=`.
+    pub glob: Option<GlobSelect>,
+    /// Whether a file or a directory is expected at dest. Only set on the
+    /// builder, with [`RequestBuilder::get_mode`].
+    ///
+    /// [`RequestBuilder::get_mode`]: crate::RequestBuilder::get_mode
+    pub get_mode: Option<GetMode>,
+    /// Settings of the getter the source is dispatched to, for getters of
+    /// your own. Only set on the builder, with
+    /// [`RequestBuilder::getter_config`].
+    ///
+    /// [`RequestBuilder::getter_config`]: crate::RequestBuilder::getter_config
+    pub getter_config: HashMap<String, String>,
+    /// Hosts the get may contact, which getters following redirects check
+    /// each of them against. Only set on the builder, with
+    /// [`RequestBuilder::allowed_hosts`].
+    ///
+    /// [`RequestBuilder::allowed_hosts`]: crate::RequestBuilder::allowed_hosts
+    pub allowed_hosts: Option<AllowedHosts>,
+    /// Bytes streaming gets read and write at a time, see
+    /// [`RequestBuilder::buffer_size`].
+    ///
+    /// [`RequestBuilder::buffer_size`]: crate::RequestBuilder::buffer_size
+    pub buffer_size: Option<usize>,
+    /// Algorithm getters hash the bytes they write in as they write them,
+    /// reporting the digest as [`GetReport::checksum`]. Set by gette on the
+    /// options it hands the getter when the download is checked or its
+    /// checksum recorded, see [`RequestBuilder::record_checksum`].
+    ///
+    /// [`GetReport::checksum`]: crate::GetReport::checksum
+    /// [`RequestBuilder::record_checksum`]: crate::RequestBuilder::record_checksum
+    pub hash: Option<ChecksumAlgo>,
+}
+
+/// How the archive format of a source is settled, see
+/// [`GetOptions::archive`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchiveOption {
+    /// Never decompress the source, `?archive=false`.
+    Disabled,
+    /// Decompress it with the decompressor registered under this name, or
+    /// claiming this extension.
+    Format(String),
+}
+
+impl FromStr for ArchiveOption {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err("archive must be a format or false".to_string()),
+            "false" => Ok(ArchiveOption::Disabled),
+            format => Ok(ArchiveOption::Format(format.to_string())),
+        }
+    }
+}
+
+/// Deserialized from what `?archive=` takes, or from `false` as a boolean.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ArchiveOption {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = ArchiveOption;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an archive format or false")
+            }
+
+            fn visit_bool<E: serde::de::Error>(self, v: bool) -> Result<Self::Value, E> {
+                match v {
+                    false => Ok(ArchiveOption::Disabled),
+                    true => Err(E::custom("archive must be a format or false")),
+                }
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// What a get is expected to put at dest. Gets that would put anything
+/// else there fail with [`Error::ModeMismatch`], before anything is
+/// transferred when the getter can tell what the source is.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum GetMode {
+    /// A single file.
+    File,
+    /// A directory, fetched as one or extracted from an archive.
+    Dir,
+    /// Whatever the source turns out to be.
+    #[default]
+    Any,
+}
+
+impl GetMode {
+    /// Fails unless what's at path is what's expected.
+    pub(crate) fn check(self, path: &Path) -> Result<(), Error> {
+        let found = if path.is_dir() {
+            GetMode::Dir
+        } else {
+            GetMode::File
+        };
+        self.check_found(found)
+    }
+
+    pub(crate) fn check_found(self, found: GetMode) -> Result<(), Error> {
+        match (self, found) {
+            (GetMode::File, GetMode::Dir) | (GetMode::Dir, GetMode::File) => {
+                Err(Error::ModeMismatch {
+                    expected: self,
+                    found,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for GetMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GetMode::File => "file",
+            GetMode::Dir => "directory",
+            GetMode::Any => "file or directory",
+        })
+    }
+}
+
+impl GetOptions {
+    /// The signature over the source itself, a signature along with a sums
+    /// file being over the sums file.
+    pub(crate) fn source_signature(&self) -> Option<&str> {
+        self.signature
+            .as_deref()
+            .filter(|_| self.checksum_file.is_none())
+    }
+
+    /// Takes the options gette reads out of the query string of src, leaving
+    /// every other parameter byte-for-byte intact. Those are an error in
+    /// strict mode instead.
+    pub(crate) fn parse(src: &str, strict: bool) -> Result<(String, Self), Error> {
+        let invalid = |src: &str, param: &'static str, reason: String| Error::InvalidUrl {
+            input: src.to_string(),
+            kind: InvalidUrlKind::InvalidParam { param, reason },
+        };
+
+        // taken out first so that the password can't end up in an error
+        let (src, archive_password) = take_param(src, "archive_password");
+        let archive_password = archive_password.map(|p| Password::new(decode_param(p)));
+
+        let (src, archive) = take_param(&src, "archive");
+        let archive = archive
+            .map(|a| {
+                decode_param(a)
+                    .parse()
+                    .map_err(|e| invalid(&src, "archive", e))
+            })
+            .transpose()?;
+        let (src, strip_components) = take_param(&src, "strip_components");
+        let strip_components = strip_components
+            .map(|n| {
+                n.parse().map_err(|_| {
+                    invalid(
+                        &src,
+                        "strip_components",
+                        "strip_components must be a number".to_string(),
+                    )
+                })
+            })
+            .transpose()?;
+        let (src, on_exists) = take_param(&src, "on_exists");
+        let on_exists = on_exists
+            .map(|p| p.parse().map_err(|e| invalid(&src, "on_exists", e)))
+            .transpose()?;
+        let (src, decompress_depth) = take_param(&src, "decompress_depth");
+        let decompress_depth = decompress_depth
+            .map(|n| match n.parse() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => Err(invalid(
+                    &src,
+                    "decompress_depth",
+                    "decompress_depth must be a number of at least 1".to_string(),
+                )),
+            })
+            .transpose()?;
+        let (src, checksum) = take_param(&src, "checksum");
+        let checksum = checksum.map(decode_param);
+        let (checksum, checksum_file) = match checksum.as_deref().map(|c| c.strip_prefix("file:")) {
+            Some(Some(file)) => (None, Some(file.to_string())),
+            Some(None) => (checksum, None),
+            None => (None, None),
+        };
+        let checksum = checksum
+            .map(|c| c.parse().map_err(|e| invalid(&src, "checksum", e)))
+            .transpose()?;
+        let (src, tree_checksum) = take_param(&src, "tree_checksum");
+        let tree_checksum = tree_checksum
+            .map(|c| match decode_param(c).parse::<Checksum>() {
+                Ok(c) if c.algo() == ChecksumAlgo::Sha256 => Ok(c),
+                _ => Err(invalid(
+                    &src,
+                    "tree_checksum",
+                    "tree_checksum must be a sha256 checksum".to_string(),
+                )),
+            })
+            .transpose()?;
+        let (src, signature) = take_param(&src, "signature");
+        let signature = signature.map(decode_param);
+        let (src, file_mode) = take_param(&src, "mode");
+        let file_mode = file_mode
+            .map(|m| {
+                decode_param(m)
+                    .parse()
+                    .map_err(|e| invalid(&src, "mode", e))
+            })
+            .transpose()?;
+        let (src, symlink_target) = take_param(&src, "symlink");
+        let symlink_target = symlink_target
+            .map(|t| {
+                decode_param(t)
+                    .parse()
+                    .map_err(|e| invalid(&src, "symlink", e))
+            })
+            .transpose()?;
+        let (src, copy_links) = take_param(&src, "links");
+        let copy_links = copy_links
+            .map(|l| {
+                decode_param(l)
+                    .parse()
+                    .map_err(|e| invalid(&src, "links", e))
+            })
+            .transpose()?;
+        let (src, exclude) = take_param(&src, "exclude");
+        let exclude = exclude
+            .map(|e| {
+                decode_param(e)
+                    .split(',')
+                    .filter(|g| !g.is_empty())
+                    .map(|g| g.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let (src, glob) = take_param(&src, "glob");
+        let glob = glob
+            .map(|g| {
+                decode_param(g)
+                    .parse()
+                    .map_err(|e| invalid(&src, "glob", e))
+            })
+            .transpose()?;
+
+        if strict {
+            if let Some(unknown) = unknown_param(&src) {
+                return Err(Error::InvalidUrl {
+                    input: src,
+                    kind: InvalidUrlKind::UnknownParam(unknown),
+                });
+            }
+        }
+
+        Ok((
+            src,
+            Self {
+                archive,
+                strip_components,
+                on_exists,
+                archive_password,
+                decompress_depth,
+                checksum,
+                checksum_file,
+                tree_checksum,
+                signature,
+                file_mode,
+                symlink_target,
+                copy_links,
+                exclude,
+                glob,
+                get_mode: None,
+                getter_config: HashMap::new(),
+                allowed_hosts: None,
+                buffer_size: None,
+                hash: None,
+            },
+        ))
+    }
+
+    /// These options, with those they don't give taken from other.
+    pub(crate) fn or(self, other: Self) -> Self {
+        // a checksum and a sums file to look it up in are one option
+        let (checksum, checksum_file) = match (&self.checksum, &self.checksum_file) {
+            (None, None) => (other.checksum, other.checksum_file),
+            _ => (self.checksum, self.checksum_file),
+        };
+        Self {
+            archive: self.archive.or(other.archive),
+            strip_components: self.strip_components.or(other.strip_components),
+            on_exists: self.on_exists.or(other.on_exists),
+            archive_password: self.archive_password.or(other.archive_password),
+            decompress_depth: self.decompress_depth.or(other.decompress_depth),
+            checksum,
+            checksum_file,
+            tree_checksum: self.tree_checksum.or(other.tree_checksum),
+            signature: self.signature.or(other.signature),
+            file_mode: self.file_mode.or(other.file_mode),
+            symlink_target: self.symlink_target.or(other.symlink_target),
+            copy_links: self.copy_links.or(other.copy_links),
+            exclude: match self.exclude.is_empty() {
+                true => other.exclude,
+                false => self.exclude,
+            },
+            glob: self.glob.or(other.glob),
+            get_mode: self.get_mode.or(other.get_mode),
+            getter_config: match self.getter_config.is_empty() {
+                true => other.getter_config,
+                false => self.getter_config,
+            },
+            allowed_hosts: self.allowed_hosts.or(other.allowed_hosts),
+            buffer_size: self.buffer_size.or(other.buffer_size),
+            hash: self.hash.or(other.hash),
+        }
+    }
+}
+
+/// First parameter of the query of src that gette doesn't read.
+fn unknown_param(src: &str) -> Option<String> {
+    let rest = src.split('#').next().unwrap_or_default();
+    let (_, query) = rest.split_once('?')?;
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| pair.split('=').next().unwrap_or_default())
+        .find(|key| !PARAMS.contains(key))
+        .map(|key| key.to_string())
+}
+
+/// Src with another query parameter added to it.
+pub(crate) fn with_param(src: &str, name: &str, value: impl fmt::Display) -> String {
+    let sep = if src.contains('?') { '&' } else { '?' };
+    format!("{}{}{}={}", src, sep, name, value)
+}
+
+/// Decodes a query parameter that may hold characters the query itself uses.
+pub(crate) fn decode_param(value: String) -> String {
+    url::form_urlencoded::parse(format!("p={}", value).as_bytes())
+        .next()
+        .map(|(_, v)| v.into_owned())
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA256: &str = "sha256:9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    #[test]
+    fn it_should_parse_options() {
+        let src = format!(
+            "https://host/app.bin?id=1&archive=tgz&strip_components=1&on_exists=clean&archive_password=p%26ss&decompress_depth=2&checksum={}&signature=./app.sig&sig=a%2Bb",
+            SHA256
+        );
+        let (src, options) = GetOptions::parse(&src, false).unwrap();
+        assert_eq!(src, "https://host/app.bin?id=1&sig=a%2Bb");
+        assert_eq!(
+            options,
+            GetOptions {
+                archive: Some(ArchiveOption::Format("tgz".to_string())),
+                strip_components: Some(1),
+                on_exists: Some(OnExists::Clean),
+                archive_password: Some(Password::new("p&ss")),
+                decompress_depth: Some(2),
+                checksum: Some(SHA256.parse().unwrap()),
+                checksum_file: None,
+                tree_checksum: None,
+                signature: Some("./app.sig".to_string()),
+                ..Default::default()
+            }
+        );
+
+        let (src, options) = GetOptions::parse(
+            "https://host/app.zip?checksum=file:SUMS&archive=false",
+            false,
+        )
+        .unwrap();
+        assert_eq!(src, "https://host/app.zip");
+        assert_eq!(options.archive, Some(ArchiveOption::Disabled));
+        assert_eq!(options.checksum, None);
+        assert_eq!(options.checksum_file.as_deref(), Some("SUMS"));
+
+        let (src, options) = GetOptions::parse("https://host/app.zip", false).unwrap();
+        assert_eq!(src, "https://host/app.zip");
+        assert_eq!(options, GetOptions::default());
+    }
+
+    #[test]
+    fn it_should_reject_invalid_options() {
+        let tests = vec![
+            "https://host/app.zip?strip_components=one",
+            "https://host/app.zip?decompress_depth=0",
+            "https://host/app.zip?on_exists=sometimes",
+            "https://host/app.zip?checksum=crc99:00",
+            "https://host/app.zip?tree_checksum=md5:098f6bcd4621d373cade4e832627b4f6",
+            "https://host/app.zip?archive=",
+            "file:///tmp/app?mode=move",
+            "file:///tmp/app?symlink=sideways",
+            "https://host/app.zip?archive_password=secret&strip_components=one",
+        ];
+        for src in tests {
+            match GetOptions::parse(src, false) {
+                Err(Error::InvalidUrl { input, .. }) => {
+                    assert!(!input.contains("secret"), "{}", input)
+                }
+                res => panic!("{}: {:?}", src, res),
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_reject_unknown_params_when_strict() {
+        let src = "https://host/app.zip?strip_component=1&archive=zip";
+        let (rest, _) = GetOptions::parse(src, false).unwrap();
+        assert_eq!(rest, "https://host/app.zip?strip_component=1");
+        let err = GetOptions::parse(src, true).unwrap_err();
+        assert!(
+            matches!(err, Error::InvalidUrl { kind: InvalidUrlKind::UnknownParam(ref param), .. } if param == "strip_component"),
+            "{:?}",
+            err
+        );
+
+        let (rest, options) =
+            GetOptions::parse("file:///tmp/app?mode=copy&exclude=.git,*.log,", true).unwrap();
+        assert_eq!(rest, "file:///tmp/app");
+        assert_eq!(options.file_mode, Some(FileMode::Copy));
+        assert_eq!(options.exclude, [".git", "*.log"]);
+        let (rest, options) = GetOptions::parse("file:///tmp/app-*?glob=version", true).unwrap();
+        assert_eq!(rest, "file:///tmp/app-*");
+        assert_eq!(options.glob, Some(GlobSelect::Version));
+    }
+
+    #[test]
+    fn test_get_mode() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("file");
+        std::fs::write(&file, "test").unwrap();
+
+        assert!(GetMode::Any.check(tmp.path()).is_ok());
+        assert!(GetMode::Any.check(&file).is_ok());
+        assert!(GetMode::Dir.check(tmp.path()).is_ok());
+        assert!(GetMode::File.check(&file).is_ok());
+        let err = GetMode::File.check(tmp.path()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected a file at dest, but the source is a directory"
+        );
+        assert!(matches!(
+            GetMode::Dir.check(&file),
+            Err(Error::ModeMismatch {
+                expected: GetMode::Dir,
+                found: GetMode::File
+            })
+        ));
+    }
+
+    #[test]
+    fn it_should_prefer_its_own_options() {
+        let builder = GetOptions {
+            strip_components: Some(0),
+            checksum: Some(SHA256.parse().unwrap()),
+            ..Default::default()
+        };
+        let query = GetOptions {
+            strip_components: Some(2),
+            on_exists: Some(OnExists::Merge),
+            checksum_file: Some("SUMS".to_string()),
+            ..Default::default()
+        };
+        let options = builder.or(query);
+        assert_eq!(options.strip_components, Some(0));
+        assert_eq!(options.on_exists, Some(OnExists::Merge));
+        assert_eq!(options.checksum, Some(SHA256.parse().unwrap()));
+        assert_eq!(options.checksum_file, None);
+    }
+}
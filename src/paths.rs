@@ -0,0 +1,136 @@
+//! Making local paths absolute, the one way the file detector and getter
+//! both do it.
+
+use std::{
+    env,
+    path::{Component, Path, PathBuf},
+};
+
+use path_clean::PathClean;
+
+use crate::Error;
+
+/// What relative paths are taken from.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Base {
+    /// The directory relative paths are in, the working directory without
+    /// one.
+    pub(crate) dir: Option<PathBuf>,
+    /// Take `~` as the home directory, and `~/...` as a path in it.
+    pub(crate) expand_home: bool,
+}
+
+impl Base {
+    /// Path made absolute and cleaned up, `..` and all, without touching the
+    /// filesystem.
+    pub(crate) fn absolute(&self, path: &Path) -> Result<PathBuf, Error> {
+        let mut path = separators(path);
+        if self.expand_home {
+            path = expand_home(path);
+        }
+        let abs = match &self.dir {
+            _ if path.is_absolute() => path,
+            Some(dir) => Base::default().absolute(dir)?.join(path),
+            None => env::current_dir()?.join(path),
+        }
+        .clean();
+
+        Ok(abs)
+    }
+}
+
+/// The home directory of the user, from `HOME`, or `USERPROFILE` where
+/// there's none.
+pub(crate) fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Path with a leading `~` taken from the home directory. Homes of other
+/// users, `~user`, are left alone, as is everything when there's no home.
+fn expand_home(path: PathBuf) -> PathBuf {
+    let mut components = path.components();
+    match (components.next(), home_dir()) {
+        (Some(Component::Normal(tilde)), Some(home)) if tilde == "~" => {
+            home.join(components.as_path())
+        }
+        _ => path,
+    }
+}
+
+/// Path with the forward slashes Windows takes along with backslashes made
+/// backslashes, which the extended-length paths it's later turned into
+/// need. Paths are left as they are anywhere else.
+fn separators(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    if let Some(s) = path.to_str().filter(|s| s.contains('/')) {
+        return PathBuf::from(s.replace('/', "\\"));
+    }
+    path.to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_absolute() {
+        let cwd = env::current_dir().unwrap();
+        let base = Base::default();
+        assert_eq!(base.absolute(Path::new("a/../b")).unwrap(), cwd.join("b"));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let base = Base {
+            dir: Some(tmp.path().to_path_buf()),
+            ..Default::default()
+        };
+        assert_eq!(
+            base.absolute(Path::new("./a/b")).unwrap(),
+            tmp.path().join("a/b")
+        );
+        let abs = cwd.join("c");
+        assert_eq!(base.absolute(&abs).unwrap(), abs);
+        // bases can be relative themselves
+        let base = Base {
+            dir: Some("sub".into()),
+            ..Default::default()
+        };
+        assert_eq!(base.absolute(Path::new("a")).unwrap(), cwd.join("sub/a"));
+    }
+
+    #[test]
+    fn test_expand_home() {
+        let Some(home) = home_dir() else {
+            return;
+        };
+        let base = Base::default();
+        let cwd = env::current_dir().unwrap();
+        assert_eq!(base.absolute(Path::new("~/a")).unwrap(), cwd.join("~/a"));
+
+        let base = Base {
+            expand_home: true,
+            ..Default::default()
+        };
+        assert_eq!(base.absolute(Path::new("~")).unwrap(), home.clean());
+        assert_eq!(
+            base.absolute(Path::new("~/a/../b")).unwrap(),
+            home.join("b").clean()
+        );
+        assert_eq!(
+            base.absolute(Path::new("~user/a")).unwrap(),
+            cwd.join("~user/a")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_separators() {
+        let base = Base::default();
+        assert_eq!(
+            base.absolute(Path::new("C:/a/b\\c")).unwrap(),
+            Path::new(r"C:\a\b\c")
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Modes and owners given to whatever a get creates, overriding those the
+//! getter or the archive would leave it with.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::Error;
+
+/// Modes and owner to give what a get creates, see
+/// [`RequestBuilder::file_permissions`](crate::RequestBuilder::file_permissions).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Perms {
+    pub(crate) file_mode: Option<u32>,
+    pub(crate) dir_mode: Option<u32>,
+    /// Uid and gid, only ever set on unix.
+    pub(crate) owner: Option<(u32, u32)>,
+}
+
+impl Perms {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Directories dest would be created in that don't exist yet, from the
+    /// outermost in, for [`Perms::apply_dirs`] once they're created.
+    pub(crate) fn missing_dirs(&self, dest: &Path) -> Vec<PathBuf> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        let mut missing: Vec<_> = dest
+            .ancestors()
+            .skip(1)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .take_while(|dir| fs::symlink_metadata(dir).is_err())
+            .map(Path::to_path_buf)
+            .collect();
+        missing.reverse();
+        missing
+    }
+
+    /// Gives the directories the dir mode and the owner.
+    pub(crate) fn apply_dirs(&self, dirs: &[PathBuf]) -> Result<(), Error> {
+        for dir in dirs {
+            match fs::symlink_metadata(dir) {
+                Ok(meta) if meta.is_dir() => self.apply(dir, &meta)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Gives path, and everything in it when it's a directory, the modes and
+    /// the owner. Symlinks get the owner but are never followed, and files
+    /// with other hardlinks to them are left alone, since what they're
+    /// linked to isn't the get's to change.
+    pub(crate) fn apply_tree(&self, path: &Path) -> Result<(), Error> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let meta = match fs::symlink_metadata(path) {
+            Ok(meta) => meta,
+            // not every getter has something to put there
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        if meta.is_dir() {
+            for entry in fs::read_dir(path)? {
+                self.apply_tree(&entry?.path())?;
+            }
+        }
+        self.apply(path, &meta)
+    }
+
+    #[cfg(unix)]
+    fn apply(&self, path: &Path, meta: &fs::Metadata) -> Result<(), Error> {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        if meta.is_file() && meta.nlink() > 1 {
+            return Ok(());
+        }
+        // the owner goes first, changing it may clear setuid bits
+        if let Some((uid, gid)) = self.owner {
+            std::os::unix::fs::lchown(path, Some(uid), Some(gid))?;
+        }
+        let mode = match meta.file_type() {
+            t if t.is_dir() => self.dir_mode,
+            t if t.is_file() => self.file_mode,
+            _ => None,
+        };
+        if let Some(mode) = mode {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        }
+        Ok(())
+    }
+
+    /// Other platforms have no mode bits or owners to set.
+    #[cfg(not(unix))]
+    fn apply(&self, _path: &Path, _meta: &fs::Metadata) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    use super::*;
+
+    fn mode(path: &Path) -> u32 {
+        fs::symlink_metadata(path).unwrap().permissions().mode() & 0o7777
+    }
+
+    #[test]
+    fn it_should_apply_modes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("sub/file"), "test").unwrap();
+        fs::write(tmp.path().join("source"), "test").unwrap();
+        fs::hard_link(tmp.path().join("source"), root.join("linked")).unwrap();
+        std::os::unix::fs::symlink(tmp.path().join("source"), root.join("link")).unwrap();
+        fs::set_permissions(tmp.path().join("source"), fs::Permissions::from_mode(0o600)).unwrap();
+
+        let meta = fs::metadata(&root).unwrap();
+        let perms = Perms {
+            file_mode: Some(0o640),
+            dir_mode: Some(0o750),
+            owner: Some((meta.uid(), meta.gid())),
+        };
+        perms.apply_tree(&root).unwrap();
+        assert_eq!(mode(&root), 0o750);
+        assert_eq!(mode(&root.join("sub")), 0o750);
+        assert_eq!(mode(&root.join("sub/file")), 0o640);
+        // what's linked to isn't touched
+        assert_eq!(mode(&tmp.path().join("source")), 0o600);
+        perms.apply_tree(&tmp.path().join("missing")).unwrap();
+
+        let dest = tmp.path().join("a/b/dest");
+        let missing = perms.missing_dirs(&dest);
+        assert_eq!(missing, [tmp.path().join("a"), tmp.path().join("a/b")]);
+        fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        perms.apply_dirs(&missing).unwrap();
+        assert_eq!(mode(&tmp.path().join("a")), 0o750);
+        assert_eq!(mode(&tmp.path().join("a/b")), 0o750);
+        assert!(Perms::default().missing_dirs(&dest).is_empty());
+    }
+}
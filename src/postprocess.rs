@@ -0,0 +1,45 @@
+//! Transforms run on what a get fetched before it's put at dest.
+
+use std::path::Path;
+
+use async_trait::async_trait;
+
+use crate::{Error, GetResult};
+
+/// Transforms what a get fetched in place, like stripping a byte order mark
+/// or making a binary executable, set with
+/// [`RequestBuilder::post_process`](crate::RequestBuilder::post_process).
+///
+/// Post-processors run once the source is fetched, extracted and verified,
+/// on what's staged to replace dest: the file or directory the source was
+/// fetched as, or the directory an archive was extracted into. Dest is only
+/// touched once every one of them is done, and a failing one fails the get
+/// with what was staged removed, so dest is never left half transformed.
+/// The result they're given is the one the get returns. Sources a get skips
+/// aren't post-processed.
+///
+/// ```
+/// use std::path::Path;
+///
+/// use async_trait::async_trait;
+/// use gette::{Error, GetResult, PostProcessor};
+///
+/// struct StripBom;
+///
+/// #[async_trait]
+/// impl PostProcessor for StripBom {
+///     async fn process(&self, staged: &Path, _result: &mut GetResult) -> Result<(), Error> {
+///         let contents = std::fs::read(staged)?;
+///         if let Some(rest) = contents.strip_prefix(b"\xef\xbb\xbf") {
+///             std::fs::write(staged, rest)?;
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let builder = gette::RequestBuilder::builder().post_process(std::sync::Arc::new(StripBom));
+/// ```
+#[async_trait]
+pub trait PostProcessor: Send + Sync {
+    async fn process(&self, staged: &Path, result: &mut GetResult) -> Result<(), Error>;
+}
@@ -0,0 +1,109 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::Timings;
+
+/// Receives updates while a source is fetched, so that long running gets can
+/// be shown to users instead of looking like a hang. Every method has a no-op
+/// default, implement the ones you care about.
+pub trait ProgressReporter: Send + Sync {
+    /// Called as an archive is being extracted.
+    fn extracting(&self, _progress: &ExtractProgress) {}
+    /// Called once an archive has been extracted.
+    fn extracted(&self, _stats: &ExtractStats) {}
+    /// Called as each source of a batch is done with, see
+    /// [`MultiRequestBuilder`](crate::MultiRequestBuilder).
+    fn batch(&self, _progress: &BatchProgress) {}
+}
+
+impl std::fmt::Debug for dyn ProgressReporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressReporter")
+    }
+}
+
+/// How far along an extraction is.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExtractProgress {
+    /// Entries processed so far, including the ones that were skipped.
+    pub entries: u64,
+    /// Entries in the archive, for formats that know it before extracting.
+    pub total_entries: Option<u64>,
+    /// Bytes written so far.
+    pub bytes: u64,
+}
+
+/// What an extraction produced.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExtractStats {
+    /// Name of the format the archive was extracted as.
+    pub format: String,
+    /// Entries in the archive.
+    pub entries: u64,
+    /// Bytes written.
+    pub bytes: u64,
+}
+
+/// How far along a batch of sources is.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BatchProgress {
+    /// Sources done with so far, failed ones included.
+    pub done: usize,
+    /// Sources that failed so far.
+    pub failed: usize,
+    /// Sources in the batch.
+    pub total: usize,
+    /// Bytes downloaded across the sources that were fetched so far.
+    pub bytes: u64,
+    /// Wall time of the batch so far.
+    pub elapsed: Duration,
+    /// Timings of the sources that were fetched so far added up, phase by
+    /// phase, with the highest of their peak rates. Gets running at the
+    /// same time make for more than the elapsed time, which the average
+    /// rate is over, for the throughput of the batch as a whole.
+    pub timings: Timings,
+}
+
+/// Forwards the progress of an extraction while keeping the latest of it, so
+/// that its totals can be reported once it's done.
+pub(crate) struct Tally {
+    inner: Option<Arc<dyn ProgressReporter>>,
+    last: Mutex<ExtractProgress>,
+}
+
+impl Tally {
+    pub(crate) fn new(inner: Option<Arc<dyn ProgressReporter>>) -> Self {
+        Self {
+            inner,
+            last: Mutex::new(ExtractProgress::default()),
+        }
+    }
+
+    /// Reports the totals of the extraction, returning them.
+    pub(crate) fn finish(&self, format: &str) -> ExtractStats {
+        let last = self.last.lock().unwrap();
+        let stats = ExtractStats {
+            format: format.to_string(),
+            entries: last.entries,
+            bytes: last.bytes,
+        };
+        if let Some(inner) = &self.inner {
+            inner.extracted(&stats);
+        }
+        stats
+    }
+}
+
+impl ProgressReporter for Tally {
+    fn extracting(&self, progress: &ExtractProgress) {
+        *self.last.lock().unwrap() = progress.clone();
+        if let Some(inner) = &self.inner {
+            inner.extracting(progress);
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! Receipts of what gets put at their dests, so that whatever gette fetched
+//! can be told apart and traced back to its source later on, see
+//! [`Receipt`].
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{checksum, checksum::Record, paths, staging::TempDest, Error, GetResult};
+
+/// Directory receipts are kept in by default, relative to the parent of
+/// their dest.
+pub const DEFAULT_DIR: &str = ".gette/receipts";
+
+/// What a get put at its dest, written as json once it succeeds under
+/// [`RequestBuilder::write_receipt`](crate::RequestBuilder::write_receipt)
+/// and read back with [`lookup`].
+///
+/// ```no_run
+/// # fn run() -> Result<(), gette::Error> {
+/// if let Some(receipt) = gette::receipts::lookup("./app")? {
+///     println!("{} from {}", receipt.src, receipt.result.source_url);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Receipt {
+    /// Version of the format, [`Receipt::VERSION`] for those gette writes.
+    pub version: u32,
+    /// Version of gette that made the get.
+    pub gette_version: String,
+    /// The source, as it was given.
+    pub src: String,
+    pub started: SystemTime,
+    pub finished: SystemTime,
+    /// Fingerprint the getter gave the source, for gets made under
+    /// [`RequestBuilder::update`](crate::RequestBuilder::update).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// What the get did, with the url the source resolved to and the
+    /// checksum of what was downloaded.
+    pub result: GetResult,
+}
+
+impl Receipt {
+    /// Version of the format this release of gette writes and reads.
+    pub const VERSION: u32 = 1;
+
+    /// Receipt of a get of src that finished now with result.
+    pub(crate) fn new(src: &str, fingerprint: Option<String>, result: GetResult) -> Self {
+        let finished = SystemTime::now();
+        Self {
+            version: Self::VERSION,
+            gette_version: env!("CARGO_PKG_VERSION").to_string(),
+            src: src.to_string(),
+            started: finished - result.timings.total,
+            finished,
+            fingerprint,
+            result,
+        }
+    }
+
+    /// What skip_existing and update go by, as they would from a sidecar.
+    pub(crate) fn record(&self) -> Record {
+        Record::of(&self.result, true, self.fingerprint.clone())
+    }
+
+    /// Writes the receipt to path, replacing whatever receipt was there.
+    pub(crate) fn write(&self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::from)?;
+        let temp = TempDest::new(path);
+        fs::write(temp.path(), json)?;
+        temp.promote(path)?;
+
+        Ok(())
+    }
+}
+
+/// The receipt of the get that last put something at dest, if there's one
+/// in the [`DEFAULT_DIR`] next to it.
+pub fn lookup(dest: impl AsRef<Path>) -> Result<Option<Receipt>, Error> {
+    path(dest.as_ref(), None).map_or(Ok(None), |path| read(&path))
+}
+
+/// The receipt of the get that last put something at dest, if there's one
+/// in dir, for gets made with
+/// [`RequestBuilder::receipts_dir`](crate::RequestBuilder::receipts_dir).
+pub fn lookup_in(dir: impl AsRef<Path>, dest: impl AsRef<Path>) -> Result<Option<Receipt>, Error> {
+    path(dest.as_ref(), Some(dir.as_ref())).map_or(Ok(None), |path| read(&path))
+}
+
+/// Reads the receipt at path, none when there isn't one. Receipts that
+/// don't parse fail with an [`io::ErrorKind::InvalidData`] error.
+pub(crate) fn read(path: &Path) -> Result<Option<Receipt>, Error> {
+    let json = match fs::read(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let receipt =
+        serde_json::from_slice(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(Some(receipt))
+}
+
+/// What the receipt of dest, in dir or next to it, records about the
+/// source last fetched there. A receipt that doesn't read is taken for
+/// none, for dest to be fetched again.
+pub(crate) fn recorded(dest: &Path, dir: Option<&Path>) -> Option<Record> {
+    let path = path(dest, dir)?;
+    match read(&path) {
+        Ok(receipt) => receipt.map(|r| r.record()),
+        Err(e) => {
+            tracing::warn!("ignoring the receipt {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Path the receipt of dest is kept at, in dir or in the [`DEFAULT_DIR`]
+/// next to dest. Receipts next to dest go by its name, so that they still
+/// apply once the directory they're in is moved, and receipts in dir by
+/// the absolute path of dest, for dests all over to share it.
+pub(crate) fn path(dest: &Path, dir: Option<&Path>) -> Option<PathBuf> {
+    let dest = paths::Base::default().absolute(dest).ok()?;
+    let (dir, key) = match dir {
+        Some(dir) => (dir.to_path_buf(), dest.as_os_str()),
+        None => (dest.parent()?.join(DEFAULT_DIR), dest.file_name()?),
+    };
+    let digest = Sha256::digest(key.as_encoded_bytes());
+    Some(dir.join(format!("{}.json", checksum::hex(&digest))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{stream_builder, InMemoryGetter},
+        RequestBuilder, SkipReason,
+    };
+
+    #[test]
+    fn test_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let a = path(&tmp.path().join("a"), None).unwrap();
+        assert_eq!(a.parent().unwrap(), tmp.path().join(DEFAULT_DIR));
+        assert_ne!(path(&tmp.path().join("b"), None), Some(a.clone()));
+        // the same name elsewhere has the same key
+        let moved = path(&tmp.path().join("sub/a"), None).unwrap();
+        assert_eq!(moved.file_name(), a.file_name());
+
+        // in a dir of their own, receipts go by the whole path
+        let dir = tmp.path().join("receipts");
+        let a = path(&tmp.path().join("a"), Some(&dir)).unwrap();
+        let moved = path(&tmp.path().join("sub/a"), Some(&dir)).unwrap();
+        assert_eq!(a.parent().unwrap(), dir);
+        assert_ne!(a, moved);
+    }
+
+    #[tokio::test]
+    async fn test_write_receipt() {
+        let tmp = tempfile::tempdir().unwrap();
+        let getter = InMemoryGetter::new().file("host/a.txt", "a");
+        let dest = tmp.path().join("a.txt");
+
+        // nothing is written unless asked for
+        let res = stream_builder(&getter, tmp.path())
+            .src("stream://host/a.txt")
+            .dest("a.txt")
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(lookup(&dest).unwrap(), None);
+        assert!(!tmp.path().join(DEFAULT_DIR).exists());
+
+        let written = stream_builder(&getter, tmp.path())
+            .record_checksum(true)
+            .write_receipt(true)
+            .src("stream://host/a.txt")
+            .dest("a.txt")
+            .get()
+            .await
+            .unwrap();
+        let receipt = lookup(&dest).unwrap().unwrap();
+        assert_eq!(receipt.version, Receipt::VERSION);
+        assert_eq!(receipt.gette_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(receipt.src, "stream://host/a.txt");
+        assert_eq!(receipt.result.source_url, res.source_url);
+        assert_eq!(receipt.result.final_path, written.final_path);
+        assert!(receipt.result.checksum.is_some());
+        assert_eq!(receipt.result.checksum, written.checksum);
+        assert!(receipt.started <= receipt.finished);
+        // in place of a sidecar
+        assert!(!checksum::sidecar_path(&dest).unwrap().exists());
+
+        // a receipt that doesn't parse is an error
+        fs::write(path(&dest, None).unwrap(), "{").unwrap();
+        let err = lookup(&dest).unwrap_err();
+        assert!(
+            matches!(&err, Error::Io(e) if e.kind() == io::ErrorKind::InvalidData),
+            "{:?}",
+            err
+        );
+
+        // or in a dir of their own
+        let dir = tmp.path().join("receipts");
+        stream_builder(&getter, tmp.path())
+            .receipts_dir(&dir)
+            .src("stream://host/a.txt")
+            .dest("b.txt")
+            .get()
+            .await
+            .unwrap();
+        let b = tmp.path().join("b.txt");
+        assert_eq!(lookup(&b).unwrap(), None);
+        let receipt = lookup_in(&dir, &b).unwrap().unwrap();
+        assert_eq!(receipt.result.final_path, b);
+    }
+
+    #[tokio::test]
+    async fn test_skip_with_receipt() {
+        let tmp = tempfile::tempdir().unwrap();
+        let getter = InMemoryGetter::new().file("host/a.txt", "a");
+        let get = |builder: RequestBuilder<crate::NoSrc, crate::NoDest>, src: &str| {
+            let builder = builder.src(src).dest("a.txt");
+            async move { builder.get().await }
+        };
+
+        get(
+            stream_builder(&getter, tmp.path()).write_receipt(true),
+            "stream://host/a.txt",
+        )
+        .await
+        .unwrap();
+        // the receipt vouches for dest holding the source
+        let skipping = || stream_builder(&getter, tmp.path()).skip_existing(true);
+        let res = get(skipping(), "stream://host/a.txt").await.unwrap();
+        assert_eq!(res.skipped, Some(SkipReason::Recorded));
+        assert_eq!(getter.downloads(), 1);
+
+        // and for the fingerprint the source had
+        fs::write(tmp.path().join("src.txt"), "src").unwrap();
+        let update = || {
+            stream_builder(&getter, tmp.path())
+                .file_mode(crate::getters::FileMode::Copy)
+                .update(true)
+        };
+        get(update().write_receipt(true), "./src.txt")
+            .await
+            .unwrap();
+        let receipt = lookup(tmp.path().join("a.txt")).unwrap().unwrap();
+        assert!(receipt.fingerprint.is_some());
+        let res = get(update(), "./src.txt").await.unwrap();
+        assert_eq!(res.skipped, Some(SkipReason::Unchanged));
+
+        // a receipt goes away with what it was of, even when what's fetched
+        // in its place is recorded in a sidecar
+        let res = get(update(), "stream://host/a.txt").await.unwrap();
+        assert_eq!(res.skipped, None);
+        assert_eq!(lookup(tmp.path().join("a.txt")).unwrap(), None);
+        let res = get(skipping(), "stream://host/a.txt").await.unwrap();
+        assert_eq!(res.skipped, Some(SkipReason::Recorded));
+        assert_eq!(getter.downloads(), 2);
+    }
+}
@@ -0,0 +1,194 @@
+//! A process-wide registry of getters and detectors every builder starts
+//! out with, for applications that would otherwise register the same ones
+//! at every call site.
+//!
+//! ```
+//! # use gette::{registry, Error};
+//! # fn run() -> Result<(), Error> {
+//! registry::register_getter("oci", Box::new(gette::getters::File::default()))?;
+//! // from here on, every builder fetches oci:: sources with it
+//! let builder = gette::RequestBuilder::builder();
+//! assert!(builder.getter_names().contains(&"oci"));
+//! # Ok(())
+//! # }
+//! # run().unwrap();
+//! ```
+//!
+//! The registry is frozen by the first builder made, and changing it from
+//! then on is an error, so that no builder ever misses what was meant to be
+//! in it. Getters are shared by every builder, so they are made once.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock, PoisonError},
+};
+
+use crate::{Detector, Error, Getter};
+
+/// Getters and detectors builders start out with, on top of the built-in
+/// ones, see [`install`].
+#[derive(Default, Clone)]
+pub struct Registry {
+    pub(crate) getters: HashMap<String, Arc<dyn Getter + Send + Sync>>,
+    pub(crate) detectors: Vec<Arc<dyn Detector + Send + Sync>>,
+}
+
+impl Registry {
+    /// An empty registry, which leaves builders with the built-in getters
+    /// and detectors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a getter under a name, in place of any built-in one going
+    /// by the same name.
+    pub fn getter(mut self, name: &str, getter: Box<dyn Getter + Send + Sync>) -> Self {
+        self.getters.insert(name.to_string(), getter.into());
+        self
+    }
+
+    /// Register a detector, tried before the built-in ones in the order
+    /// they're registered.
+    pub fn detector(mut self, detector: Box<dyn Detector + Send + Sync>) -> Self {
+        self.detectors.push(detector.into());
+        self
+    }
+}
+
+/// The registry as it's being set up, then frozen. It's only ever frozen
+/// with `pending` locked, so whoever holds the lock sees whether it is.
+struct Global {
+    pending: Mutex<Option<Registry>>,
+    frozen: OnceLock<Registry>,
+}
+
+static GLOBAL: Global = Global::new();
+
+impl Global {
+    const fn new() -> Self {
+        Self {
+            pending: Mutex::new(None),
+            frozen: OnceLock::new(),
+        }
+    }
+
+    fn install(&self, registry: Registry) -> Result<(), Error> {
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        if pending.is_some() || self.frozen.get().is_some() {
+            return Err(Error::RegistryInUse);
+        }
+        *pending = Some(registry);
+        Ok(())
+    }
+
+    fn update(&self, f: impl FnOnce(Registry) -> Registry) -> Result<(), Error> {
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        if self.frozen.get().is_some() {
+            return Err(Error::RegistryInUse);
+        }
+        *pending = Some(f(pending.take().unwrap_or_default()));
+        Ok(())
+    }
+
+    fn current(&self) -> &Registry {
+        if let Some(registry) = self.frozen.get() {
+            return registry;
+        }
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        self.frozen
+            .get_or_init(|| pending.take().unwrap_or_default())
+    }
+}
+
+/// Install the registry builders start out with. Fails once a registry is
+/// installed, by this or by [`register_getter`] and [`register_detector`],
+/// or once a builder has been made.
+pub fn install(registry: Registry) -> Result<(), Error> {
+    GLOBAL.install(registry)
+}
+
+/// Add a getter to the registry, see [`Registry::getter`]. Fails once a
+/// builder has been made.
+pub fn register_getter(name: &str, getter: Box<dyn Getter + Send + Sync>) -> Result<(), Error> {
+    GLOBAL.update(|registry| registry.getter(name, getter))
+}
+
+/// Add a detector to the registry, see [`Registry::detector`]. Fails once
+/// a builder has been made.
+pub fn register_detector(detector: Box<dyn Detector + Send + Sync>) -> Result<(), Error> {
+    GLOBAL.update(|registry| registry.detector(detector))
+}
+
+/// The registry builders start out with, frozen from now on.
+pub(crate) fn current() -> &'static Registry {
+    GLOBAL.current()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{detectors, getters, RequestBuilder};
+
+    // the process-wide registry would leak into every other test, so these
+    // use registries of their own
+
+    #[test]
+    fn it_should_freeze_on_first_use() {
+        let global = Global::new();
+        global
+            .update(|r| r.getter("oci", Box::new(getters::File::default())))
+            .unwrap();
+        global
+            .update(|r| r.detector(Box::new(detectors::Git)))
+            .unwrap();
+        assert!(matches!(
+            global.install(Registry::new()),
+            Err(Error::RegistryInUse)
+        ));
+
+        let registry = global.current();
+        assert!(registry.getters.contains_key("oci"));
+        assert_eq!(registry.detectors.len(), 1);
+        assert!(matches!(
+            global.update(|r| r.getter("late", Box::new(getters::File::default()))),
+            Err(Error::RegistryInUse)
+        ));
+        assert!(!global.current().getters.contains_key("late"));
+
+        let global = Global::new();
+        global.current();
+        assert!(matches!(
+            global.install(Registry::new()),
+            Err(Error::RegistryInUse)
+        ));
+    }
+
+    #[test]
+    fn it_should_be_consulted_before_built_ins() {
+        let registry = Registry::new()
+            .getter("oci", Box::new(getters::File::default()))
+            .getter("file", Box::new(getters::File::default()))
+            .detector(Box::new(detectors::Github));
+        let builtin = RequestBuilder::from_registry(&Registry::new());
+        let builder = RequestBuilder::from_registry(&registry);
+
+        let mut names = builtin.getter_names();
+        names.push("oci");
+        names.sort_unstable();
+        assert_eq!(builder.getter_names(), names);
+        assert!(!Arc::ptr_eq(
+            &builder.getters["file"],
+            &builtin.getters["file"]
+        ));
+        assert!(Arc::ptr_eq(
+            &builder.getters["file"],
+            &registry.getters["file"]
+        ));
+        assert_eq!(builder.detector_names()[0], "github");
+        assert_eq!(&builder.detector_names()[1..], builtin.detector_names());
+
+        // what a builder registers itself still wins
+        let builder = builder.remove_getter("oci");
+        assert!(!builder.getter_names().contains(&"oci"));
+    }
+}
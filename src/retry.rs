@@ -0,0 +1,158 @@
+use std::{collections::hash_map::RandomState, future::Future, hash::BuildHasher, time::Duration};
+
+use crate::Error;
+
+/// How getters are retried on errors that might go away when tried again,
+/// see [`Error::is_retryable`] and [`RequestBuilder::retry`].
+///
+/// [`RequestBuilder::retry`]: crate::RequestBuilder::retry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts in all, the first one included.
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for every retry after it.
+    pub base_delay: Duration,
+    /// Longest wait between two attempts.
+    pub max_delay: Duration,
+    /// Wait a random time between half the delay and the delay, so that
+    /// clients failing together don't retry together as well.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait after the given attempt failed, counting from 1.
+    fn delay(&self, attempt: u32) -> Duration {
+        let doubled = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(doubled).min(self.max_delay);
+        if !self.jitter {
+            return delay;
+        }
+
+        // no need for a proper rng, just for different waits every time
+        let random = RandomState::new().hash_one(attempt) as f64 / u64::MAX as f64;
+        delay.mul_f64(0.5 + random / 2.0)
+    }
+
+    /// Runs the attempt until it succeeds, fails with an error that isn't
+    /// retryable, or the attempts run out. Every failed attempt that's
+    /// retried is passed to on_retry first.
+    pub(crate) async fn run<T, F, Fut>(
+        &self,
+        mut attempt: F,
+        on_retry: impl Fn(u32, &Error),
+    ) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let mut n = 1;
+        loop {
+            match attempt().await {
+                Err(e) if e.is_retryable() && n < self.max_attempts => {
+                    tracing::warn!(attempt = n, error = %e, "getter failed, retrying");
+                    on_retry(n, &e);
+                    tokio::time::sleep(self.delay(n)).await;
+                    n += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+            jitter: false,
+        };
+        let delays: Vec<_> = (1..=5).map(|n| policy.delay(n).as_millis()).collect();
+        assert_eq!(delays, vec![100, 200, 400, 500, 500]);
+        assert_eq!(policy.delay(u32::MAX), Duration::from_millis(500));
+
+        let policy = RetryPolicy {
+            jitter: true,
+            ..policy
+        };
+        for n in 1..=5 {
+            let delay = policy.delay(n).as_millis();
+            let max = delays[n as usize - 1];
+            assert!(delay >= max / 2 && delay <= max, "{}: {}", n, delay);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let reset = || Error::Io(std::io::ErrorKind::ConnectionReset.into());
+
+        // retried until it works
+        let attempts = AtomicU32::new(0);
+        let res = policy
+            .run(
+                || async {
+                    match attempts.fetch_add(1, Ordering::SeqCst) {
+                        0 => Err(reset()),
+                        _ => Ok(()),
+                    }
+                },
+                |_, _| {},
+            )
+            .await;
+        assert!(res.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        // or until the attempts run out
+        let attempts = AtomicU32::new(0);
+        let retried = std::sync::Mutex::new(Vec::new());
+        let res: Result<(), _> = policy
+            .run(
+                || async {
+                    attempts.fetch_add(1, Ordering::SeqCst);
+                    Err(reset())
+                },
+                |n, e| retried.lock().unwrap().push((n, e.is_retryable())),
+            )
+            .await;
+        assert!(matches!(res, Err(Error::Io(_))));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(*retried.lock().unwrap(), [(1, true), (2, true)]);
+
+        // and never on errors that won't go away
+        let attempts = AtomicU32::new(0);
+        let res: Result<(), _> = policy
+            .run(
+                || async {
+                    attempts.fetch_add(1, Ordering::SeqCst);
+                    Err(Error::SourceNotFound { source: None })
+                },
+                |_, _| unreachable!(),
+            )
+            .await;
+        assert!(matches!(res, Err(Error::SourceNotFound { .. })));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+}
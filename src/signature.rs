@@ -0,0 +1,394 @@
+use std::io::Read;
+
+use crate::Error;
+
+/// Keys whose signatures on sources and their sums files are trusted.
+#[derive(Default, Clone)]
+pub(crate) struct TrustedKeys {
+    /// Armored OpenPGP public keys.
+    #[cfg(feature = "gpg")]
+    pub(crate) gpg: Vec<String>,
+    /// Minisign public keys, either a whole `.pub` file or its base64 line.
+    #[cfg(feature = "minisign")]
+    pub(crate) minisign: Vec<String>,
+}
+
+impl TrustedKeys {
+    /// Checks a detached signature over data, which any of the keys may have
+    /// made. Minisign signatures are told apart from OpenPGP ones by the
+    /// untrusted comment they start with.
+    #[cfg_attr(
+        not(any(feature = "gpg", feature = "minisign")),
+        allow(unused_variables, unused_mut)
+    )]
+    pub(crate) fn verify(&self, signature: &[u8], mut data: impl Read) -> Result<(), Error> {
+        if signature.starts_with(b"untrusted comment:") {
+            #[cfg(feature = "minisign")]
+            return match self.minisign.is_empty() {
+                true => Err(Error::SignatureKeyMissing),
+                false => minisign::verify(&self.minisign, signature, &mut data),
+            };
+            #[cfg(not(feature = "minisign"))]
+            return Err(Error::MalformedSignature(
+                "minisign signatures need the minisign feature".to_string(),
+            ));
+        }
+
+        #[cfg(feature = "gpg")]
+        if !self.gpg.is_empty() {
+            let mut buf = Vec::new();
+            data.read_to_end(&mut buf)?;
+            return gpg::verify(&self.gpg, signature, &buf);
+        }
+
+        Err(Error::SignatureKeyMissing)
+    }
+}
+
+#[cfg(feature = "minisign")]
+mod minisign {
+    use std::io::{self, Read};
+
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use blake2::{Blake2b512, Digest};
+    use ed25519_dalek::{Signature, VerifyingKey};
+
+    use crate::Error;
+
+    /// A parsed `.minisig` file.
+    struct Minisig {
+        /// Made over the BLAKE2b-512 hash of the data rather than the data.
+        prehashed: bool,
+        key_id: [u8; 8],
+        signature: Signature,
+        trusted_comment: String,
+        /// Made over the signature followed by the trusted comment.
+        global: Signature,
+    }
+
+    /// Checks a minisign signature against the key with its key id.
+    pub(super) fn verify(
+        keys: &[String],
+        signature: &[u8],
+        data: &mut dyn Read,
+    ) -> Result<(), Error> {
+        let sig = parse_signature(signature)?;
+        let invalid = || Error::SignatureInvalid {
+            key: format!("{:016X}", u64::from_le_bytes(sig.key_id)),
+        };
+
+        let mut key = None;
+        for k in keys {
+            let (id, k) = parse_key(k)?;
+            if id == sig.key_id {
+                key = Some(k);
+            }
+        }
+        let key = key.ok_or_else(invalid)?;
+
+        let message = if sig.prehashed {
+            let mut hasher = Blake2b512::new();
+            io::copy(data, &mut hasher)?;
+            hasher.finalize().to_vec()
+        } else {
+            let mut buf = Vec::new();
+            data.read_to_end(&mut buf)?;
+            buf
+        };
+        key.verify_strict(&message, &sig.signature)
+            .map_err(|_| invalid())?;
+
+        let mut global = sig.signature.to_bytes().to_vec();
+        global.extend_from_slice(sig.trusted_comment.as_bytes());
+        key.verify_strict(&global, &sig.global)
+            .map_err(|_| invalid())
+    }
+
+    fn parse_signature(signature: &[u8]) -> Result<Minisig, Error> {
+        let malformed = |reason: &str| Error::MalformedSignature(format!("minisign: {}", reason));
+        let text = std::str::from_utf8(signature).map_err(|_| malformed("not text"))?;
+        let mut lines = text.lines().skip(1);
+        let mut next = |what: &str| {
+            lines
+                .next()
+                .ok_or_else(|| malformed(&format!("no {}", what)))
+        };
+
+        let sig = decode(next("signature")?).filter(|s| s.len() == 74);
+        let sig = sig.ok_or_else(|| malformed("signature isn't 74 bytes of base64"))?;
+        let prehashed = match &sig[..2] {
+            b"Ed" => false,
+            b"ED" => true,
+            _ => return Err(malformed("unsupported signature algorithm")),
+        };
+        let trusted_comment = next("trusted comment")?
+            .strip_prefix("trusted comment: ")
+            .ok_or_else(|| malformed("no trusted comment"))?
+            .to_string();
+        let global = decode(next("global signature")?).filter(|s| s.len() == 64);
+        let global =
+            global.ok_or_else(|| malformed("global signature isn't 64 bytes of base64"))?;
+
+        Ok(Minisig {
+            prehashed,
+            key_id: sig[2..10].try_into().unwrap(),
+            signature: Signature::from_slice(&sig[10..]).map_err(|e| malformed(&e.to_string()))?,
+            trusted_comment,
+            global: Signature::from_slice(&global).map_err(|e| malformed(&e.to_string()))?,
+        })
+    }
+
+    /// Parses a public key, returning its key id along with it.
+    fn parse_key(key: &str) -> Result<([u8; 8], VerifyingKey), Error> {
+        let malformed =
+            |reason: &str| Error::MalformedSignature(format!("minisign key: {}", reason));
+        let line = key
+            .lines()
+            .map(str::trim)
+            .rfind(|l| !l.is_empty() && !l.starts_with("untrusted comment:"))
+            .unwrap_or_default();
+
+        let key = decode(line).filter(|k| k.len() == 42 && k.starts_with(b"Ed"));
+        let key = key.ok_or_else(|| malformed("not 42 bytes of base64 starting with Ed"))?;
+        let verifying = VerifyingKey::from_bytes(key[10..].try_into().unwrap())
+            .map_err(|e| malformed(&e.to_string()))?;
+        Ok((key[2..10].try_into().unwrap(), verifying))
+    }
+
+    fn decode(line: &str) -> Option<Vec<u8>> {
+        STANDARD.decode(line.trim()).ok()
+    }
+
+    #[cfg(test)]
+    pub(crate) mod tests {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        use super::*;
+
+        /// Generates a key, the same one for the same seed, with the seed as
+        /// its key id.
+        pub(crate) fn key(seed: u8) -> ([u8; 8], SigningKey) {
+            ([seed; 8], SigningKey::from_bytes(&[seed; 32]))
+        }
+
+        /// Makes the `.pub` file of a key.
+        pub(crate) fn public((id, key): &([u8; 8], SigningKey)) -> String {
+            let mut bytes = b"Ed".to_vec();
+            bytes.extend_from_slice(id);
+            bytes.extend_from_slice(key.verifying_key().as_bytes());
+            format!(
+                "untrusted comment: minisign public key\n{}\n",
+                STANDARD.encode(bytes)
+            )
+        }
+
+        /// Makes a `.minisig` file for data, prehashed like minisign does by
+        /// default.
+        pub(crate) fn sign(
+            (id, key): &([u8; 8], SigningKey),
+            data: &[u8],
+            prehashed: bool,
+        ) -> String {
+            let (algo, signature) = match prehashed {
+                true => (b"ED", key.sign(&Blake2b512::digest(data))),
+                false => (b"Ed", key.sign(data)),
+            };
+            let trusted_comment = "timestamp:0\tfile:tool.txt";
+            let mut global = signature.to_bytes().to_vec();
+            global.extend_from_slice(trusted_comment.as_bytes());
+
+            let mut bytes = algo.to_vec();
+            bytes.extend_from_slice(id);
+            bytes.extend_from_slice(&signature.to_bytes());
+            format!(
+                "untrusted comment: signature from minisign secret key\n{}\ntrusted comment: {}\n{}\n",
+                STANDARD.encode(bytes),
+                trusted_comment,
+                STANDARD.encode(key.sign(&global).to_bytes()),
+            )
+        }
+
+        #[test]
+        fn it_should_verify_minisign_signatures() {
+            let signer = key(1);
+            let other = key(2);
+            let data = b"test";
+
+            for prehashed in [true, false] {
+                let signature = sign(&signer, data, prehashed);
+                verify(&[public(&signer)], signature.as_bytes(), &mut &data[..]).unwrap();
+                verify(
+                    &[public(&other), public(&signer)],
+                    signature.as_bytes(),
+                    &mut &data[..],
+                )
+                .unwrap();
+
+                let err = verify(&[public(&signer)], signature.as_bytes(), &mut &b"evil"[..])
+                    .unwrap_err();
+                assert!(
+                    matches!(err, Error::SignatureInvalid { ref key } if key == "0101010101010101"),
+                    "{:?}",
+                    err
+                );
+            }
+
+            // the key is only known by its base64 line here
+            let signature = sign(&signer, data, true);
+            let line = public(&signer).lines().nth(1).unwrap().to_string();
+            verify(&[line], signature.as_bytes(), &mut &data[..]).unwrap();
+
+            let err = verify(&[public(&other)], signature.as_bytes(), &mut &data[..]).unwrap_err();
+            assert!(matches!(err, Error::SignatureInvalid { .. }), "{:?}", err);
+
+            // the trusted comment is covered by the global signature
+            let forged = signature.replace("file:tool.txt", "file:other.txt");
+            let err = verify(&[public(&signer)], forged.as_bytes(), &mut &data[..]).unwrap_err();
+            assert!(matches!(err, Error::SignatureInvalid { .. }), "{:?}", err);
+
+            let truncated: String = signature.lines().take(2).collect::<Vec<_>>().join("\n");
+            let err = verify(&[public(&signer)], truncated.as_bytes(), &mut &data[..]).unwrap_err();
+            assert!(matches!(err, Error::MalformedSignature(_)), "{:?}", err);
+
+            let err = verify(
+                &["not a key".to_string()],
+                signature.as_bytes(),
+                &mut &data[..],
+            )
+            .unwrap_err();
+            assert!(matches!(err, Error::MalformedSignature(_)), "{:?}", err);
+        }
+    }
+}
+
+#[cfg(feature = "gpg")]
+mod gpg {
+    use pgp::{Deserializable, SignedPublicKey, StandaloneSignature};
+
+    use crate::Error;
+
+    /// Checks an armored or binary detached signature against the primary
+    /// keys and subkeys of armored public keys.
+    pub(super) fn verify(keys: &[String], signature: &[u8], data: &[u8]) -> Result<(), Error> {
+        let signature = match std::str::from_utf8(signature) {
+            Ok(armored) if armored.trim_start().starts_with("-----BEGIN") => {
+                StandaloneSignature::from_string(armored).map(|(s, _)| s)
+            }
+            _ => StandaloneSignature::from_bytes(signature),
+        }
+        .map_err(|e| Error::MalformedSignature(e.to_string()))?;
+
+        for armored in keys {
+            let (key, _) = SignedPublicKey::from_string(armored)
+                .map_err(|e| Error::MalformedSignature(format!("gpg key: {}", e)))?;
+            if signature.verify(&key, data).is_ok()
+                || key
+                    .public_subkeys
+                    .iter()
+                    .any(|k| signature.verify(k, data).is_ok())
+            {
+                return Ok(());
+            }
+        }
+
+        let key = signature
+            .signature
+            .issuer()
+            .first()
+            .map(|id| format!("{:X}", id))
+            .unwrap_or_else(|| "unknown".to_string());
+        Err(Error::SignatureInvalid { key })
+    }
+
+    #[cfg(test)]
+    pub(crate) mod tests {
+        use pgp::{
+            crypto::hash::HashAlgorithm,
+            packet::{SignatureConfig, SignatureType, Subpacket, SubpacketData},
+            types::{PublicKeyTrait, SecretKeyTrait},
+            KeyType, SecretKeyParamsBuilder, SignedSecretKey,
+        };
+        use rand::{rngs::StdRng, SeedableRng};
+
+        use super::*;
+
+        /// Generates a signing key, the same one for the same seed.
+        pub(crate) fn key(seed: u64) -> SignedSecretKey {
+            let mut rng = StdRng::seed_from_u64(seed);
+            SecretKeyParamsBuilder::default()
+                .key_type(KeyType::EdDSALegacy)
+                .can_sign(true)
+                .primary_user_id("gette <test@example.com>".to_string())
+                .build()
+                .unwrap()
+                .generate(&mut rng)
+                .unwrap()
+                .sign(&mut rng, String::new)
+                .unwrap()
+        }
+
+        /// Makes an armored detached signature of data.
+        pub(crate) fn sign(key: &SignedSecretKey, data: &[u8]) -> String {
+            let mut config = SignatureConfig::v4(
+                SignatureType::Binary,
+                key.algorithm(),
+                HashAlgorithm::SHA2_256,
+            );
+            config.hashed_subpackets = vec![Subpacket::regular(SubpacketData::IssuerFingerprint(
+                key.fingerprint(),
+            ))];
+            config.unhashed_subpackets =
+                vec![Subpacket::regular(SubpacketData::Issuer(key.key_id()))];
+
+            let signature = config.sign(key, String::new, data).unwrap();
+            StandaloneSignature::new(signature)
+                .to_armored_string(None.into())
+                .unwrap()
+        }
+
+        pub(crate) fn public(key: &SignedSecretKey) -> String {
+            key.public_key()
+                .sign(&mut StdRng::seed_from_u64(0), key, String::new)
+                .unwrap()
+                .to_armored_string(None.into())
+                .unwrap()
+        }
+
+        #[test]
+        fn it_should_verify_detached_signatures() {
+            let signer = key(1);
+            let other = key(2);
+            let data =
+                b"9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08  tool.txt\n";
+            let signature = sign(&signer, data);
+
+            verify(&[public(&signer)], signature.as_bytes(), data).unwrap();
+            verify(
+                &[public(&other), public(&signer)],
+                signature.as_bytes(),
+                data,
+            )
+            .unwrap();
+
+            let mut tampered = data.to_vec();
+            tampered[0] = b'8';
+            let err = verify(&[public(&signer)], signature.as_bytes(), &tampered).unwrap_err();
+            assert!(
+                matches!(err, Error::SignatureInvalid { ref key } if *key == format!("{:X}", signer.key_id())),
+                "{:?}",
+                err
+            );
+
+            let err = verify(&[public(&other)], signature.as_bytes(), data).unwrap_err();
+            assert!(matches!(err, Error::SignatureInvalid { .. }), "{:?}", err);
+
+            let err = verify(&[public(&signer)], b"not a signature", data).unwrap_err();
+            assert!(matches!(err, Error::MalformedSignature(_)), "{:?}", err);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "gpg"))]
+pub(crate) use gpg::tests as gpg_fixtures;
+#[cfg(all(test, feature = "minisign"))]
+pub(crate) use minisign::tests as minisign_fixtures;
@@ -0,0 +1,226 @@
+//! Checks that what's fetched fits on the filesystem of dest before it's
+//! written there, see [`SpaceCheck`].
+
+use std::{fs, io, path::Path};
+
+use crate::{paths, Error};
+
+/// Bytes archives that are compressed as a whole are guessed to extract to,
+/// for every byte of the archive.
+const COMPRESSION_RATIO: u64 = 4;
+
+/// How gets check that what they fetch fits on the filesystem of dest. The
+/// check is made once the size is known and before anything is written:
+/// sources fetched over the network whose getter tells their size fail with
+/// [`Error::InsufficientSpace`] before they're transferred, and archives are
+/// checked again before they're extracted, against an estimate of what
+/// they extract to.
+///
+/// Estimates add up the sizes zip archives list for their entries, and
+/// take archives compressed as a whole, like a `.tar.gz`, to extract to
+/// 4 times their size. [`SpaceCheck::on_estimate`] says what's done when
+/// one doesn't fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SpaceCheck {
+    /// Bytes to leave free on top of what's fetched.
+    pub margin: u64,
+    pub on_estimate: OnEstimate,
+}
+
+/// What's done when an archive is estimated not to fit once extracted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnEstimate {
+    /// Extract it all the same.
+    Ignore,
+    /// Log a warning and extract it all the same.
+    #[default]
+    Warn,
+    /// Fail with [`Error::InsufficientSpace`].
+    Fail,
+}
+
+impl SpaceCheck {
+    /// Fails unless bytes, and the margin, fit on the filesystem path is on.
+    pub(crate) fn ensure(&self, path: &Path, bytes: u64) -> Result<(), Error> {
+        let needed = bytes.saturating_add(self.margin);
+        match available(path) {
+            Some(available) if available < needed => {
+                Err(Error::InsufficientSpace { needed, available })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the archive, estimated to extract to bytes, fits on the
+    /// filesystem path is on, doing what [`SpaceCheck::on_estimate`] says
+    /// when it doesn't.
+    pub(crate) fn ensure_estimate(&self, path: &Path, bytes: u64) -> Result<(), Error> {
+        match (self.ensure(path, bytes), self.on_estimate) {
+            (Err(e), OnEstimate::Fail) => Err(e),
+            (Err(e), OnEstimate::Warn) => {
+                tracing::warn!("extracting to {}: {}", path.display(), e);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Estimate of what the archive at path, in format, extracts to.
+pub(crate) fn extracted_size(format: &str, path: &Path) -> Option<u64> {
+    if format == "zip" {
+        let mut archive = zip::ZipArchive::new(fs::File::open(path).ok()?).ok()?;
+        let mut size = 0u64;
+        for i in 0..archive.len() {
+            size = size.saturating_add(archive.by_index_raw(i).ok()?.size());
+        }
+        return Some(size);
+    }
+    Some(estimated_size(format, fs::metadata(path).ok()?.len()))
+}
+
+/// Estimate of what an archive in format, of size bytes, extracts to, when
+/// nothing but its size is known.
+pub(crate) fn estimated_size(format: &str, size: u64) -> u64 {
+    match format {
+        "tar" | "zip" => size,
+        _ => size.saturating_mul(COMPRESSION_RATIO),
+    }
+}
+
+/// Bytes that can be written to the filesystem path is on, or would be
+/// once it's made. None when it can't be told.
+pub(crate) fn available(path: &Path) -> Option<u64> {
+    // the ancestors of a relative path end in one that's empty, not `.`
+    let path = paths::Base::default().absolute(path).ok()?;
+    let existing = path.ancestors().find(|p| p.exists())?;
+    match free_space(existing) {
+        Ok(available) => Some(available),
+        Err(e) => {
+            tracing::debug!("free space of {} is unknown: {}", existing.display(), e);
+            None
+        }
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn free_space(path: &Path) -> io::Result<u64> {
+    use std::{ffi::CString, mem::MaybeUninit, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: path is nul terminated and stat is only read once statvfs
+    // has filled it in
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        stat.assume_init()
+    };
+    #[allow(clippy::useless_conversion)]
+    let (blocks, size) = (u64::from(stat.f_bavail), u64::from(stat.f_frsize));
+    Ok(blocks.saturating_mul(size))
+}
+
+#[cfg(windows)]
+fn free_space(path: &Path) -> io::Result<u64> {
+    use std::{os::windows::ffi::OsStrExt, ptr};
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetDiskFreeSpaceExW(
+            directory: *const u16,
+            available: *mut u64,
+            total: *mut u64,
+            free: *mut u64,
+        ) -> i32;
+    }
+
+    let path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available = 0;
+    // SAFETY: path is nul terminated and the totals it isn't asked for may
+    // be null
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            path.as_ptr(),
+            &mut available,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(available)
+}
+
+#[cfg(not(any(target_family = "unix", windows)))]
+fn free_space(_path: &Path) -> io::Result<u64> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_available() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(available(tmp.path()).unwrap() > 0);
+        // dests that aren't there yet are on the filesystem of their parents
+        let missing = tmp.path().join("a/b/c");
+        assert!(available(&missing).is_some());
+        // as are relative ones, the working directory being theirs
+        assert!(available(Path::new("missing/rel.bin")).is_some());
+    }
+
+    #[test]
+    fn test_ensure() {
+        let tmp = tempfile::tempdir().unwrap();
+        let check = SpaceCheck::default();
+        check.ensure(tmp.path(), 1).unwrap();
+        let err = check.ensure(tmp.path(), u64::MAX).unwrap_err();
+        assert!(
+            matches!(err, Error::InsufficientSpace { needed: u64::MAX, available } if available > 0),
+            "{:?}",
+            err
+        );
+        let margin = SpaceCheck {
+            margin: u64::MAX - 1,
+            ..Default::default()
+        };
+        assert!(margin.ensure(tmp.path(), 1).is_err());
+        assert!(margin.ensure(Path::new("rel.bin"), 1).is_err());
+
+        // estimates only fail when asked to
+        check.ensure_estimate(tmp.path(), u64::MAX).unwrap();
+        let fail = SpaceCheck {
+            on_estimate: OnEstimate::Fail,
+            ..Default::default()
+        };
+        assert!(fail.ensure_estimate(tmp.path(), u64::MAX).is_err());
+        fail.ensure_estimate(tmp.path(), 1).unwrap();
+    }
+
+    #[test]
+    fn test_extracted_size() {
+        let tmp = tempfile::tempdir().unwrap();
+        let zip = tmp.path().join("a.zip");
+        let mut w = zip::ZipWriter::new(fs::File::create(&zip).unwrap());
+        for name in ["a", "b"] {
+            w.start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            w.write_all(&[0; 1000]).unwrap();
+        }
+        w.finish().unwrap();
+        assert_eq!(extracted_size("zip", &zip), Some(2000));
+
+        let gz = tmp.path().join("a.tar.gz");
+        fs::write(&gz, [0; 10]).unwrap();
+        assert_eq!(extracted_size("tar.gz", &gz), Some(40));
+        assert_eq!(extracted_size("tar", &gz), Some(10));
+        assert_eq!(extracted_size("zip", &gz), None);
+    }
+}
@@ -0,0 +1,245 @@
+//! Requests declared in manifests, like a list of artifacts kept in a
+//! config file, rather than built up in code.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::{de::IgnoredAny, Deserialize};
+
+use crate::{
+    options, ArchiveOption, Checksum, Dest, Error, GetMode, GetOptions, MultiRequestBuilder,
+    NoDest, NoSrc, RequestBuilder, Src,
+};
+
+/// A request as declared in a manifest, deserialized from any format serde
+/// supports. Fields it doesn't know fail the request, so that a misspelled
+/// one isn't silently ignored, unless [`RequestSpec::ignore_unknown_fields`]
+/// says otherwise.
+///
+/// ```
+/// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let specs: Vec<gette::RequestSpec> = serde_json::from_str(r#"[
+///     { "src": "./release/tool.tar.gz", "dest": "./tool", "mode": "dir" },
+///     { "src": "git+https://github.com/org/repo.git", "dest": "./repo", "ref": "v1.0.0" }
+/// ]"#)?;
+/// let batch = gette::MultiRequestBuilder::from_specs(specs)?;
+/// # Ok(())
+/// # }
+/// # run().unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RequestSpec {
+    /// The source, as given to [`RequestBuilder::src`].
+    pub src: String,
+    /// Where the source ends up, as given to [`RequestBuilder::dest`].
+    pub dest: PathBuf,
+    /// Digest the downloaded bytes must have, see [`GetOptions::checksum`].
+    #[serde(default)]
+    pub checksum: Option<Checksum>,
+    /// Whether a file or a directory is expected at dest, see
+    /// [`RequestBuilder::get_mode`].
+    #[serde(default)]
+    pub mode: Option<GetMode>,
+    /// Archive format the source is decompressed with, or `false`, see
+    /// [`GetOptions::archive`].
+    #[serde(default)]
+    pub archive: Option<ArchiveOption>,
+    /// Leading path components dropped from archive entries, see
+    /// [`GetOptions::strip_components`].
+    #[serde(default)]
+    pub strip_components: Option<usize>,
+    /// Revision of a repository to check out, passed on to the getter as
+    /// `?ref=`.
+    #[serde(default, rename = "ref")]
+    pub git_ref: Option<String>,
+    #[serde(flatten)]
+    unknown: BTreeMap<String, IgnoredAny>,
+}
+
+impl RequestSpec {
+    /// Names of the fields of the manifest this request doesn't know.
+    pub fn unknown_fields(&self) -> impl Iterator<Item = &str> {
+        self.unknown.keys().map(String::as_str)
+    }
+
+    /// Let the request through despite fields it doesn't know, for manifests
+    /// that hold more than gette reads.
+    pub fn ignore_unknown_fields(mut self) -> Self {
+        self.unknown.clear();
+        self
+    }
+
+    /// The source, with whatever the getter reads added to its query, dest
+    /// and the options gette reads.
+    fn into_parts(self) -> Result<(String, PathBuf, GetOptions), Error> {
+        if !self.unknown.is_empty() {
+            return Err(Error::UnknownSpecFields {
+                src: self.src,
+                fields: self.unknown.into_keys().collect(),
+            });
+        }
+
+        let src = match &self.git_ref {
+            Some(git_ref) => options::with_param(&self.src, "ref", git_ref),
+            None => self.src,
+        };
+        let options = GetOptions {
+            checksum: self.checksum,
+            archive: self.archive,
+            strip_components: self.strip_components,
+            get_mode: self.mode,
+            ..Default::default()
+        };
+        Ok((src, self.dest, options))
+    }
+}
+
+impl RequestBuilder<Src, Dest> {
+    /// A builder for the request a manifest declares, with the default
+    /// settings otherwise.
+    pub fn from_spec(spec: RequestSpec) -> Result<Self, Error> {
+        RequestBuilder::builder().spec(spec)
+    }
+}
+
+impl RequestBuilder<NoSrc, NoDest> {
+    /// Set the source, dest and options of the request a manifest declares,
+    /// which win over the options set on the builder.
+    pub fn spec(self, spec: RequestSpec) -> Result<RequestBuilder<Src, Dest>, Error> {
+        let (src, dest, options) = spec.into_parts()?;
+        Ok(self.options(options).src(src).dest(dest))
+    }
+}
+
+impl MultiRequestBuilder {
+    /// A batch of the requests a manifest declares, with the default
+    /// settings otherwise. Fails on the first request that's invalid,
+    /// before anything is fetched.
+    pub fn from_specs(specs: impl IntoIterator<Item = RequestSpec>) -> Result<Self, Error> {
+        specs
+            .into_iter()
+            .try_fold(Self::new(RequestBuilder::builder()), Self::queue_spec)
+    }
+
+    /// Queue up the request a manifest declares, with options of its own.
+    pub fn queue_spec(self, spec: RequestSpec) -> Result<Self, Error> {
+        let (src, dest, options) = spec.into_parts()?;
+        Ok(self.queue_with(src, dest, options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::GetResult;
+
+    fn spec(json: &str) -> RequestSpec {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn it_should_deserialize_specs() {
+        let spec = spec(
+            r#"{
+                "src": "git+https://github.com/org/repo.git//module",
+                "dest": "./module",
+                "checksum": "md5:098f6bcd4621d373cade4e832627b4f6",
+                "mode": "dir",
+                "archive": false,
+                "strip_components": 1,
+                "ref": "v1.0.0"
+            }"#,
+        );
+        assert_eq!(spec.mode, Some(GetMode::Dir));
+        assert_eq!(spec.archive, Some(ArchiveOption::Disabled));
+        assert_eq!(spec.unknown_fields().count(), 0);
+
+        let (src, dest, options) = spec.into_parts().unwrap();
+        assert_eq!(
+            src,
+            "git+https://github.com/org/repo.git//module?ref=v1.0.0"
+        );
+        assert_eq!(dest, PathBuf::from("./module"));
+        assert_eq!(options.strip_components, Some(1));
+        assert_eq!(options.get_mode, Some(GetMode::Dir));
+        assert_eq!(
+            options.checksum.unwrap().to_string(),
+            "md5:098f6bcd4621d373cade4e832627b4f6"
+        );
+
+        let archive = |json| serde_json::from_str::<ArchiveOption>(json);
+        assert_eq!(
+            archive(r#""zip""#).unwrap(),
+            ArchiveOption::Format("zip".to_string())
+        );
+        assert!(archive("true").is_err());
+        assert!(serde_json::from_str::<RequestSpec>(
+            r#"{"src": "a", "dest": "b", "checksum": "md5:12"}"#
+        )
+        .is_err());
+        assert!(serde_json::from_str::<RequestSpec>(
+            r#"{"src": "a", "dest": "b", "mode": "link"}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn it_should_reject_unknown_fields() {
+        let spec = spec(r#"{"src": "a.txt", "dest": "b.txt", "chekcsum": "md5:0", "mod": "dir"}"#);
+        assert_eq!(
+            spec.unknown_fields().collect::<Vec<_>>(),
+            ["chekcsum", "mod"]
+        );
+
+        let err = RequestBuilder::from_spec(spec.clone()).err().unwrap();
+        assert!(
+            matches!(err, Error::UnknownSpecFields { ref src, ref fields } if src == "a.txt" && fields == &["chekcsum", "mod"]),
+            "{:?}",
+            err
+        );
+        assert!(MultiRequestBuilder::from_specs([spec.clone()]).is_err());
+
+        let builder = RequestBuilder::from_spec(spec.ignore_unknown_fields()).unwrap();
+        assert_eq!(builder.src.0, "a.txt");
+    }
+
+    #[tokio::test]
+    async fn it_should_get_specs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("source.txt");
+        fs::write(&source, "test").unwrap();
+        let specs: Vec<RequestSpec> = serde_json::from_value(serde_json::json!([
+            {
+                "src": source,
+                "dest": tmp.path().join("file.txt"),
+                "checksum": "md5:098f6bcd4621d373cade4e832627b4f6",
+            },
+            { "src": source, "dest": tmp.path().join("dir"), "mode": "dir" },
+        ]))
+        .unwrap();
+
+        let res = RequestBuilder::from_spec(specs[0].clone())
+            .unwrap()
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(res.checksum, specs[0].checksum);
+
+        let results = MultiRequestBuilder::from_specs(specs)
+            .unwrap()
+            .get_all()
+            .await;
+        assert!(results[0].is_ok(), "{:?}", results[0]);
+        let err = results[1].as_ref().unwrap_err();
+        assert!(
+            matches!(err.inner(), Error::ModeMismatch { .. }),
+            "{:?}",
+            err
+        );
+
+        // what a get did can be kept and read back
+        let json = serde_json::to_string(&res).unwrap();
+        assert_eq!(serde_json::from_str::<GetResult>(&json).unwrap(), res);
+    }
+}
@@ -0,0 +1,293 @@
+//! Paths next to dest, or in the staging directory, that what's fetched is
+//! written to before it's moved into place, so that dest only ever holds
+//! something complete.
+
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Path the archive is downloaded to before being extracted into `dest`. It
+/// lives next to dest so that it ends up on the same filesystem.
+pub(crate) fn archive_path(dest: &Path) -> PathBuf {
+    temp_path(dest, None, "archive")
+}
+
+/// Where sums, signatures and sources that can't be streamed are fetched
+/// to, for gets without a dest to fetch them next to, or whose dest isn't
+/// known until they're fetched.
+pub(crate) fn scratch() -> PathBuf {
+    std::env::temp_dir().join("gette")
+}
+
+/// A hidden path in dir, or next to dest without one, that nothing else
+/// will pick.
+fn temp_path(dest: &Path, dir: Option<&Path>, kind: &str) -> PathBuf {
+    // told apart by a counter as well, for gets racing in the same process
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let name = format!(".gette-{}-{}-{}-{}", kind, process::id(), nanos, count);
+
+    match dir.or(dest.parent()) {
+        Some(dir) => dir.join(name),
+        None => PathBuf::from(name),
+    }
+}
+
+/// A temporary path for a getter to write to, next to dest or in the staging
+/// directory, promoted to dest once what was written there is complete and
+/// checked. Whatever is left at the path is removed when it's dropped
+/// without being promoted, so that a failed get leaves nothing behind.
+pub(crate) struct TempDest {
+    path: PathBuf,
+    promoted: bool,
+}
+
+impl TempDest {
+    /// A path nothing is at yet, in the same directory as dest so that
+    /// promoting it is an atomic rename on the same filesystem.
+    pub(crate) fn new(dest: &Path) -> Self {
+        Self::new_in(dest, None)
+    }
+
+    /// A path nothing is at yet in the staging directory dir, or next to
+    /// dest without one. Promoting it from another filesystem than dest's
+    /// is a copy, see [`rename`].
+    pub(crate) fn new_in(dest: &Path, dir: Option<&Path>) -> Self {
+        Self {
+            path: temp_path(dest, dir, "tmp"),
+            promoted: false,
+        }
+    }
+
+    /// A directory in the staging directory dir, or next to dest without
+    /// one, for an archive to be extracted into before what was extracted
+    /// is moved over to dest. It's never promoted as a whole.
+    pub(crate) fn staging(dest: &Path, dir: Option<&Path>) -> Self {
+        Self {
+            path: temp_path(dest, dir, "staging"),
+            promoted: false,
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Moves what was written over to dest, which has to be clear. A getter
+    /// that wrote nothing leaves dest as it is.
+    pub(crate) fn promote(mut self, dest: &Path) -> io::Result<()> {
+        if fs::symlink_metadata(&self.path).is_ok() {
+            rename(&self.path, dest)?;
+        }
+        self.promoted = true;
+
+        Ok(())
+    }
+
+    /// Removes whatever was written so far, for the path to be written to
+    /// from scratch.
+    pub(crate) fn clear(&self) -> io::Result<()> {
+        match fs::symlink_metadata(&self.path) {
+            Ok(meta) if meta.is_dir() => fs::remove_dir_all(&self.path),
+            Ok(_) => fs::remove_file(&self.path),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+impl Drop for TempDest {
+    fn drop(&mut self) {
+        if self.promoted {
+            return;
+        }
+
+        // errors can't be reported from here, and at worst leave a hidden file
+        let _ = self.clear();
+    }
+}
+
+/// Moves from over to to, which has to be clear, like [`fs::rename`]. When
+/// they're on different filesystems, as they are with a staging directory
+/// on another volume than dest, what's at from is copied next to to, synced
+/// to disk and renamed into place instead, so that to still only ever holds
+/// something complete, and from is removed once it's there.
+pub(crate) fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => move_across(from, to),
+        res => res,
+    }
+}
+
+fn move_across(from: &Path, to: &Path) -> io::Result<()> {
+    let copy = TempDest::new(to);
+    copy_all(from, copy.path())?;
+    copy.promote(to)?;
+    if let Some(parent) = to.parent().filter(|p| !p.as_os_str().is_empty()) {
+        sync_dir(parent)?;
+    }
+
+    match fs::symlink_metadata(from)?.is_dir() {
+        true => fs::remove_dir_all(from),
+        false => fs::remove_file(from),
+    }
+}
+
+/// Copies the file, directory or symlink at from to to, syncing every file
+/// it copies to disk. Anything else, like a FIFO, can't be copied.
+fn copy_all(from: &Path, to: &Path) -> io::Result<()> {
+    let kind = fs::symlink_metadata(from)?.file_type();
+    if kind.is_symlink() {
+        return copy_link(from, to);
+    }
+    if kind.is_file() {
+        copy_file(from, to)?;
+        return fs::File::open(to)?.sync_all();
+    }
+    if !kind.is_dir() {
+        return Err(io::Error::new(
+            ErrorKind::Unsupported,
+            format!("{} can't be copied to another filesystem", from.display()),
+        ));
+    }
+
+    fs::create_dir(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_all(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    fs::set_permissions(to, fs::metadata(from)?.permissions())?;
+    sync_dir(to)
+}
+
+/// Syncs the entries of dir to disk, for what was created in it or renamed
+/// into it to stay there. Directories can't be synced outside unix.
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(target_family = "unix")]
+    fs::File::open(dir)?.sync_all()?;
+    #[cfg(not(target_family = "unix"))]
+    let _ = dir;
+
+    Ok(())
+}
+
+/// Copies a file along with its permissions and mtime. `fs::copy` hands the
+/// copy to the kernel where it can, with `copy_file_range` on Linux, which
+/// reflinks on filesystems that support it, and `fclonefile` on macOS.
+pub(crate) fn copy_file(from: &Path, to: &Path) -> io::Result<()> {
+    fs::copy(from, to)?;
+    let modified = fs::metadata(from)?.modified()?;
+
+    // opening it for reading is enough to set the mtime of a file one owns,
+    // which keeps read-only files working
+    #[cfg(target_family = "unix")]
+    let file = fs::File::open(to)?;
+    #[cfg(not(target_family = "unix"))]
+    let file = fs::File::options().write(true).open(to)?;
+    file.set_modified(modified)
+}
+
+/// Makes to a symlink pointing where from does.
+pub(crate) fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    let target = fs::read_link(from)?;
+
+    #[cfg(target_family = "unix")]
+    std::os::unix::fs::symlink(target, to)?;
+    #[cfg(target_family = "windows")]
+    match fs::metadata(from).map(|m| m.is_dir()) {
+        Ok(true) => std::os::windows::fs::symlink_dir(target, to)?,
+        _ => std::os::windows::fs::symlink_file(target, to)?,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_dest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dest = tmp.path().join("dest");
+
+        let file = TempDest::new(&dest);
+        let dir = TempDest::new(&dest);
+        assert_ne!(file.path(), dir.path());
+        assert_eq!(file.path().parent(), dest.parent());
+        fs::write(file.path(), "test").unwrap();
+        fs::create_dir_all(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/file"), "test").unwrap();
+        drop((file, dir));
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 0);
+
+        let file = TempDest::new(&dest);
+        fs::write(file.path(), "test").unwrap();
+        file.promote(&dest).unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+
+        // nothing written, nothing moved
+        TempDest::new(&dest).promote(&dest).unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+    }
+
+    #[test]
+    fn test_temp_dest_in() {
+        let tmp = tempfile::tempdir().unwrap();
+        let staging = tempfile::tempdir().unwrap();
+        let dest = tmp.path().join("dest");
+
+        let file = TempDest::new_in(&dest, Some(staging.path()));
+        assert_eq!(file.path().parent(), Some(staging.path()));
+        let dir = TempDest::staging(&dest, None);
+        assert_eq!(dir.path().parent(), dest.parent());
+        fs::write(file.path(), "test").unwrap();
+        file.promote(&dest).unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "test");
+        assert_eq!(fs::read_dir(staging.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_move_across() {
+        let tmp = tempfile::tempdir().unwrap();
+        let from = tmp.path().join("from");
+        fs::create_dir_all(from.join("sub")).unwrap();
+        fs::write(from.join("sub/file"), "test").unwrap();
+        let mtime = UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        fs::File::open(from.join("sub/file"))
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        #[cfg(target_family = "unix")]
+        std::os::unix::fs::symlink("sub/file", from.join("link")).unwrap();
+
+        // what a rename across filesystems falls back to
+        let to = tmp.path().join("to");
+        move_across(&from, &to).unwrap();
+        assert!(!from.exists());
+        assert_eq!(fs::read_to_string(to.join("sub/file")).unwrap(), "test");
+        let meta = fs::metadata(to.join("sub/file")).unwrap();
+        assert_eq!(meta.modified().unwrap(), mtime);
+        #[cfg(target_family = "unix")]
+        assert_eq!(
+            fs::read_link(to.join("link")).unwrap(),
+            Path::new("sub/file")
+        );
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+
+        let file = tmp.path().join("file");
+        move_across(&to.join("sub/file"), &file).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "test");
+        assert!(!to.join("sub/file").exists());
+    }
+}
@@ -0,0 +1,260 @@
+//! Dests derived from the source that's fetched to them, see
+//! [`RequestBuilder::dest_template`](crate::RequestBuilder::dest_template).
+
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use percent_encoding::percent_decode_str;
+
+use crate::{checksum, ChecksumAlgo, Detection, Error};
+
+/// A dest with variables filled in from the source, like
+/// `./cache/{host}/{basename}`. The variables are
+///
+/// - `{basename}`, the last segment of the path of the url
+/// - `{path}`, the path of the url, without the leading slash
+/// - `{host}`, the host of the url
+/// - `{ref}`, the revision the source asks for with `?ref=`
+/// - `{sha256}`, or `{sha256:8}` for its first 8 hex digits, the checksum
+///   of the source in any of the algorithms of [`ChecksumAlgo`]
+///
+/// and `{{` and `}}` are braces. A checksum variable takes the checksum the
+/// download is verified against before anything is put at dest, which has
+/// to be given with `?checksum=`, on the builder, in a sums file or be
+/// looked up under
+/// [`RequestBuilder::verify_remote_checksums`](crate::RequestBuilder::verify_remote_checksums).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DestTemplate {
+    template: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Basename,
+    Path,
+    Host,
+    Ref,
+    Checksum(ChecksumAlgo, Option<usize>),
+}
+
+impl DestTemplate {
+    /// Parses a template, failing on variables it doesn't know.
+    pub fn parse(template: &str) -> Result<Self, Error> {
+        let invalid = |reason: String| Error::InvalidDestTemplate {
+            template: template.to_string(),
+            reason,
+        };
+
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => {
+                    return Err(invalid(
+                        "} isn't closing anything, write }} for a brace".into(),
+                    ))
+                }
+                '{' => {
+                    let Some((name, rest)) = chars.as_str().split_once('}') else {
+                        return Err(invalid("{ is never closed, write {{ for a brace".into()));
+                    };
+                    let part = variable(name)
+                        .ok_or_else(|| invalid(format!("unknown variable {{{}}}", name)))?;
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(part);
+                    chars = rest.chars();
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Self {
+            template: template.to_string(),
+            parts,
+        })
+    }
+
+    /// The template as it was given.
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Whether the template names dest after the checksum of the source.
+    pub(crate) fn uses_checksum(&self) -> bool {
+        self.parts.iter().any(|p| matches!(p, Part::Checksum(..)))
+    }
+
+    /// The dest of the source detection resolved.
+    pub(crate) fn render(&self, detection: &Detection) -> Result<PathBuf, Error> {
+        let invalid = |reason: String| Error::InvalidDestTemplate {
+            template: self.template.clone(),
+            reason,
+        };
+        let url = &detection.url;
+        let decoded = |s: &str| percent_decode_str(s).decode_utf8_lossy().into_owned();
+
+        let mut dest = String::new();
+        for part in &self.parts {
+            let (name, value) = match part {
+                Part::Literal(literal) => {
+                    dest.push_str(literal);
+                    continue;
+                }
+                Part::Basename => {
+                    let basename = url.path_segments().and_then(|mut s| s.next_back());
+                    ("basename", basename.map(decoded))
+                }
+                Part::Path => ("path", Some(decoded(url.path().trim_start_matches('/')))),
+                Part::Host => ("host", url.host_str().map(str::to_string)),
+                Part::Ref => ("ref", detection.params.get("ref").cloned()),
+                Part::Checksum(algo, len) => {
+                    let checksum = detection
+                        .options
+                        .checksum
+                        .as_ref()
+                        .filter(|c| c.algo() == *algo)
+                        .ok_or_else(|| {
+                            invalid(format!("the source has no {} checksum", algo.name()))
+                        })?;
+                    let hex = checksum::hex(checksum.digest());
+                    let hex = match len {
+                        Some(len) => hex.chars().take(*len).collect(),
+                        None => hex,
+                    };
+                    (algo.name(), Some(hex))
+                }
+            };
+            let value = value
+                .filter(|v| !v.is_empty())
+                .ok_or_else(|| invalid(format!("the source has no {}", name)))?;
+            // whatever the source says, dest stays where the template puts it
+            if value.split(['/', '\\']).any(|c| c == "..") {
+                return Err(invalid(format!("the {} of the source leaves dest", name)));
+            }
+            dest.push_str(&value);
+        }
+
+        Ok(PathBuf::from(dest))
+    }
+}
+
+/// The variable a name between braces stands for.
+fn variable(name: &str) -> Option<Part> {
+    Some(match name {
+        "basename" => Part::Basename,
+        "path" => Part::Path,
+        "host" => Part::Host,
+        "ref" => Part::Ref,
+        name => {
+            let (algo, len) = match name.split_once(':') {
+                Some((algo, len)) => (algo, Some(len.parse().ok().filter(|len| *len > 0)?)),
+                None => (name, None),
+            };
+            Part::Checksum(ChecksumAlgo::from_name(algo)?, len)
+        }
+    })
+}
+
+impl FromStr for DestTemplate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for DestTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RequestBuilder;
+
+    fn render(template: &str, src: &str) -> Result<PathBuf, Error> {
+        let detection = RequestBuilder::builder().build().resolve(src)?;
+        DestTemplate::parse(template)?.render(&detection)
+    }
+
+    #[test]
+    fn it_should_render_templates() {
+        let src = "https://host/releases/v1/tool%20x.tar.gz?ref=v1.0.0&checksum=sha256:9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        let tests = [
+            ("./cache/{host}/{basename}", "./cache/host/tool x.tar.gz"),
+            ("{path}", "releases/v1/tool x.tar.gz"),
+            ("tools/{ref}/{sha256:8}", "tools/v1.0.0/9f86d081"),
+            (
+                "{{{sha256}}}",
+                "{9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08}",
+            ),
+            ("plain", "plain"),
+        ];
+        for (template, expected) in tests {
+            assert_eq!(
+                render(template, src).unwrap(),
+                PathBuf::from(expected),
+                "{}",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn it_should_reject_invalid_templates() {
+        for template in [
+            "{version}",
+            "{sha256:0}",
+            "{sha256:x}",
+            "{md4}",
+            "{host",
+            "host}",
+            "{}",
+        ] {
+            let err = DestTemplate::parse(template).unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidDestTemplate { .. }),
+                "{}: {:?}",
+                template,
+                err
+            );
+        }
+
+        // and on what the source doesn't have
+        for (template, src) in [
+            ("{ref}", "https://host/a.txt"),
+            ("{sha256}", "https://host/a.txt"),
+            (
+                "{sha1}",
+                "https://host/a.txt?checksum=md5:098f6bcd4621d373cade4e832627b4f6",
+            ),
+            ("{basename}", "https://host/"),
+            ("{ref}", "https://host/a.txt?ref=../.."),
+        ] {
+            let err = render(template, src).unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidDestTemplate { .. }),
+                "{}: {:?}",
+                template,
+                err
+            );
+        }
+    }
+}
@@ -0,0 +1,397 @@
+//! Getters, detectors and a fixture server for testing code that fetches
+//! with gette, without the network or the sources it'd fetch in production.
+//! Built with the `test-util` feature.
+//!
+//! ```
+//! # tokio_test::block_on(async {
+//! use gette::test_util::InMemoryGetter;
+//!
+//! let tmp = tempfile::tempdir().unwrap();
+//! let getter = InMemoryGetter::new().file("host/tool.txt", "test");
+//! gette::RequestBuilder::builder()
+//!     .add_getter("mem", Box::new(getter.clone()))
+//!     .src("mem://host/tool.txt")
+//!     .dest(tmp.path().join("tool.txt"))
+//!     .get()
+//!     .await
+//!     .unwrap();
+//! assert_eq!(getter.downloads(), 1);
+//! # })
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+use async_trait::async_trait;
+use percent_encoding::percent_decode_str;
+use tokio::io::AsyncRead;
+use url::Url;
+
+use crate::{Detector, Error, GetMode, GetOptions, Getter};
+#[cfg(test)]
+use crate::{NoDest, NoSrc, OnConflict, RequestBuilder};
+
+/// Serves sources out of memory, keyed by their url without the scheme, so
+/// that `mem://host/a.txt` is `host/a.txt`. Sources it doesn't have are
+/// [`Error::SourceNotFound`]. Clones share what they serve and what they
+/// count, keep one to look at after the getter is handed to a builder.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryGetter {
+    files: Arc<HashMap<String, Vec<u8>>>,
+    downloads: Arc<AtomicUsize>,
+    opens: Arc<AtomicUsize>,
+}
+
+impl InMemoryGetter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve contents under key.
+    pub fn file(mut self, key: &str, contents: impl Into<Vec<u8>>) -> Self {
+        Arc::make_mut(&mut self.files).insert(key.to_string(), contents.into());
+        self
+    }
+
+    /// How many times a source was fetched to a file.
+    pub fn downloads(&self) -> usize {
+        self.downloads.load(Ordering::SeqCst)
+    }
+
+    /// How many times a source was opened as a stream.
+    pub fn opens(&self) -> usize {
+        self.opens.load(Ordering::SeqCst)
+    }
+
+    fn contents(&self, url: &Url) -> Result<&[u8], Error> {
+        let key = format!("{}{}", url.host_str().unwrap_or_default(), url.path());
+        self.files
+            .get(&key)
+            .map(Vec::as_slice)
+            .ok_or_else(|| Error::source_not_found(format!("nothing in memory under {}", key)))
+    }
+}
+
+impl From<HashMap<String, Vec<u8>>> for InMemoryGetter {
+    fn from(files: HashMap<String, Vec<u8>>) -> Self {
+        Self {
+            files: Arc::new(files),
+            ..Default::default()
+        }
+    }
+}
+
+#[async_trait]
+impl Getter for InMemoryGetter {
+    async fn get(&self, dest: &Path, url: &Url, _: &GetOptions) -> Result<(), Error> {
+        let contents = self.contents(url)?;
+        self.downloads.fetch_add(1, Ordering::SeqCst);
+        fs::write(dest, contents)?;
+        Ok(())
+    }
+
+    async fn open(
+        &self,
+        url: &Url,
+        _: &GetOptions,
+    ) -> Result<Option<Pin<Box<dyn AsyncRead + Send>>>, Error> {
+        let contents = self.contents(url)?.to_vec();
+        self.opens.fetch_add(1, Ordering::SeqCst);
+        Ok(Some(Box::pin(io::Cursor::new(contents))))
+    }
+
+    async fn mode(&self, url: &Url, _: &GetOptions) -> Result<Option<GetMode>, Error> {
+        self.contents(url).map(|_| Some(GetMode::File))
+    }
+
+    async fn size(&self, url: &Url, _: &GetOptions) -> Result<Option<u64>, Error> {
+        self.contents(url).map(|c| Some(c.len() as u64))
+    }
+}
+
+/// Fails with the errors it's given, one per attempt, before handing the
+/// attempts after them to another getter, for testing retries.
+///
+/// ```
+/// use gette::{test_util::{FailingGetter, InMemoryGetter}, Error};
+///
+/// let reset = || Error::from(std::io::Error::from(std::io::ErrorKind::ConnectionReset));
+/// let getter = FailingGetter::new(InMemoryGetter::new().file("host/a.txt", "test"))
+///     .fail_with([reset(), reset()]);
+/// ```
+pub struct FailingGetter {
+    getter: Box<dyn Getter + Send + Sync>,
+    errors: Mutex<VecDeque<Error>>,
+    partial: Option<Vec<u8>>,
+    attempts: Arc<AtomicUsize>,
+}
+
+impl FailingGetter {
+    /// Hands every attempt to getter until errors are added.
+    pub fn new(getter: impl Getter + Send + 'static) -> Self {
+        Self {
+            getter: Box::new(getter),
+            errors: Mutex::new(VecDeque::new()),
+            partial: None,
+            attempts: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Fail the next attempts with errors, in order.
+    pub fn fail_with(self, errors: impl IntoIterator<Item = Error>) -> Self {
+        self.errors.lock().unwrap().extend(errors);
+        self
+    }
+
+    /// Write contents to dest before failing, like a transfer cut short.
+    pub fn partial(mut self, contents: impl Into<Vec<u8>>) -> Self {
+        self.partial = Some(contents.into());
+        self
+    }
+
+    /// A count of the attempts made, failed or not, that lives on once the
+    /// getter is handed to a builder.
+    pub fn attempts(&self) -> Arc<AtomicUsize> {
+        self.attempts.clone()
+    }
+}
+
+#[async_trait]
+impl Getter for FailingGetter {
+    async fn get(&self, dest: &Path, url: &Url, options: &GetOptions) -> Result<(), Error> {
+        self.attempts.fetch_add(1, Ordering::SeqCst);
+        let err = self.errors.lock().unwrap().pop_front();
+        match err {
+            Some(err) => {
+                if let Some(partial) = &self.partial {
+                    fs::write(dest, partial)?;
+                }
+                Err(err)
+            }
+            None => self.getter.get(dest, url, options).await,
+        }
+    }
+}
+
+/// Records every source it's asked to detect, claiming those starting with
+/// a prefix, if it's given one, and declining the rest. Clones share what
+/// they recorded.
+#[derive(Debug, Clone)]
+pub struct RecordingDetector {
+    name: String,
+    claim: Option<(String, String)>,
+    seen: Arc<Mutex<Vec<String>>>,
+}
+
+impl RecordingDetector {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            claim: None,
+            seen: Default::default(),
+        }
+    }
+
+    /// Claim sources starting with prefix, resolving them to url with the
+    /// rest of the source appended.
+    pub fn claim(mut self, prefix: &str, url: &str) -> Self {
+        self.claim = Some((prefix.to_string(), url.to_string()));
+        self
+    }
+
+    /// The sources asked about so far, in order.
+    pub fn seen(&self) -> Vec<String> {
+        self.seen.lock().unwrap().clone()
+    }
+}
+
+impl Detector for RecordingDetector {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn detect(&self, path: &str) -> Result<Option<String>, Error> {
+        self.seen.lock().unwrap().push(path.to_string());
+        Ok(self.claim.as_ref().and_then(|(prefix, url)| {
+            path.strip_prefix(prefix.as_str())
+                .map(|rest| format!("{}{}", url, rest))
+        }))
+    }
+}
+
+/// An HTTP server on localhost serving the files of a directory to GET and
+/// HEAD requests, for getters that fetch over HTTP. Stops when dropped.
+pub struct FixtureServer {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<String>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FixtureServer {
+    /// Serves dir on a free port.
+    pub fn serve(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let requests: Arc<Mutex<Vec<String>>> = Default::default();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = std::thread::spawn({
+            let requests = requests.clone();
+            let stop = stop.clone();
+            move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    // a client that hangs up is its own business
+                    if let Ok(stream) = stream {
+                        let _ = respond(stream, &dir, &requests);
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            addr,
+            requests,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// The url of path on the server.
+    pub fn url(&self, path: &str) -> Url {
+        let url = format!("http://{}/{}", self.addr, path.trim_start_matches('/'));
+        Url::parse(&url).expect("fixture urls are valid")
+    }
+
+    /// The requests served so far, as their method and path.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for FixtureServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // wakes the server up to see it's stopped
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A builder fetching `stream://` sources from getter, taking relative dests
+/// from dir and letting what it fetches replace what's there, which the
+/// tests of the crate share.
+#[cfg(test)]
+pub(crate) fn stream_builder(getter: &InMemoryGetter, dir: &Path) -> RequestBuilder<NoSrc, NoDest> {
+    RequestBuilder::builder()
+        .base_dir(dir)
+        .on_conflict(OnConflict::Overwrite)
+        .add_getter("stream", Box::new(getter.clone()))
+}
+
+/// Answers a single request, closing the connection after it.
+fn respond(stream: TcpStream, dir: &Path, requests: &Mutex<Vec<String>>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    // the headers are of no interest
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = line.split_whitespace();
+    let (method, target) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or("/"),
+    );
+    requests
+        .lock()
+        .unwrap()
+        .push(format!("{} {}", method, target));
+
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    let path = percent_decode_str(path).decode_utf8_lossy();
+    let path = Path::new(path.trim_start_matches('/'));
+    // files are streamed from disk, so that serving a large one doesn't
+    // show up in the memory of the process
+    let file = match path.components().all(|c| matches!(c, Component::Normal(_))) {
+        true => fs::File::open(dir.join(path))
+            .ok()
+            .filter(|f| f.metadata().is_ok_and(|meta| meta.is_file())),
+        false => None,
+    };
+
+    let mut stream = reader.into_inner();
+    let (status, file) = match (method, file) {
+        ("GET" | "HEAD", Some(file)) => ("200 OK", Some(file)),
+        ("GET" | "HEAD", None) => ("404 Not Found", None),
+        _ => ("405 Method Not Allowed", None),
+    };
+    let len = match &file {
+        Some(file) => file.metadata()?.len(),
+        None => 0,
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status, len
+    )?;
+    if let (Some(mut file), false) = (file, method == "HEAD") {
+        io::copy(&mut file, &mut stream)?;
+    }
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    fn fetch(server: &FixtureServer, request: &str) -> String {
+        let mut stream = TcpStream::connect(server.addr).unwrap();
+        write!(stream, "{} HTTP/1.1\r\nHost: localhost\r\n\r\n", request).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn it_should_serve_a_directory() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir(tmp.path().join("sub")).unwrap();
+        fs::write(tmp.path().join("sub/a b.txt"), "test").unwrap();
+        let server = FixtureServer::serve(tmp.path()).unwrap();
+        assert_eq!(server.url("sub/a b.txt").path(), "/sub/a%20b.txt");
+
+        let response = fetch(&server, "GET /sub/a%20b.txt");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.ends_with("\r\n\r\ntest"), "{}", response);
+        let response = fetch(&server, "HEAD /sub/a%20b.txt");
+        assert!(response.ends_with("Content-Length: 4\r\nConnection: close\r\n\r\n"));
+
+        for request in ["GET /missing.txt", "GET /../sub/a%20b.txt", "GET /sub"] {
+            let response = fetch(&server, request);
+            assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+        }
+        assert_eq!(server.requests().len(), 5);
+    }
+}
@@ -0,0 +1,215 @@
+//! How many gets of a batch contact the same host at once, adapted to the
+//! host telling them to slow down, see
+//! [`MultiRequestBuilder::host_concurrency`](crate::MultiRequestBuilder::host_concurrency).
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::Url;
+
+/// The limits on the gets in flight to each host, shared by the gets of a
+/// batch. A host starts out at the initial limit, which is halved whenever
+/// it throttles a get and raised by one after as many gets in a row as the
+/// limit went through without being throttled, up to the max.
+pub(crate) struct HostLimits {
+    initial: usize,
+    max: usize,
+    hosts: Mutex<HashMap<String, Arc<Host>>>,
+}
+
+struct Host {
+    /// Holds as many permits as the limit, less those in flight.
+    permits: Arc<Semaphore>,
+    window: Mutex<Window>,
+}
+
+struct Window {
+    limit: usize,
+    /// Permits still in flight that go away rather than back once they're
+    /// done, for the limit to have been lowered.
+    debt: usize,
+    /// Gets done without being throttled since the limit last changed.
+    successes: usize,
+    /// Times the limit was lowered, so that gets already in flight when it
+    /// was don't lower it again for the same burst.
+    epoch: u64,
+}
+
+impl HostLimits {
+    pub(crate) fn new(initial: usize, max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            initial: initial.clamp(1, max),
+            max,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits for the host to be under its limit, and takes up a place in it
+    /// until the permit is dropped.
+    pub(crate) async fn acquire(&self, host: &str) -> HostPermit {
+        let host = self
+            .hosts
+            .lock()
+            .unwrap()
+            .entry(host.to_string())
+            .or_insert_with(|| {
+                Arc::new(Host {
+                    permits: Arc::new(Semaphore::new(self.initial)),
+                    window: Mutex::new(Window {
+                        limit: self.initial,
+                        debt: 0,
+                        successes: 0,
+                        epoch: 0,
+                    }),
+                })
+            })
+            .clone();
+        let permit = host.permits.clone().acquire_owned().await;
+        let epoch = host.window.lock().unwrap().epoch;
+        HostPermit {
+            host,
+            max: self.max,
+            // the semaphore is never closed
+            permit: permit.ok(),
+            epoch,
+        }
+    }
+
+    /// Gets in flight the host is limited to, for tests to follow.
+    #[cfg(test)]
+    pub(crate) fn limit(&self, host: &str) -> Option<usize> {
+        let host = self.hosts.lock().unwrap().get(host)?.clone();
+        let limit = host.window.lock().unwrap().limit;
+        Some(limit)
+    }
+}
+
+/// A get's place among those in flight to its host, see
+/// [`HostLimits::acquire`].
+pub(crate) struct HostPermit {
+    host: Arc<Host>,
+    max: usize,
+    permit: Option<OwnedSemaphorePermit>,
+    epoch: u64,
+}
+
+impl HostPermit {
+    /// Halves the limit of the host for it throttling the get, unless it was
+    /// already lowered since the get started.
+    pub(crate) fn throttled(&self) {
+        let mut window = self.host.window.lock().unwrap();
+        if window.epoch != self.epoch {
+            return;
+        }
+        let limit = (window.limit / 2).max(1);
+        let excess = window.limit - limit;
+        let forgotten = self.host.permits.forget_permits(excess);
+        window.debt += excess - forgotten;
+        window.limit = limit;
+        window.successes = 0;
+        window.epoch += 1;
+        tracing::debug!(limit, "host is throttling, lowering its concurrency");
+    }
+
+    /// Raises the limit of the host by one once as many gets as the limit
+    /// went through in a row without being throttled.
+    pub(crate) fn succeeded(&self) {
+        let mut window = self.host.window.lock().unwrap();
+        window.successes += 1;
+        if window.successes < window.limit || window.limit >= self.max {
+            return;
+        }
+        window.limit += 1;
+        window.successes = 0;
+        match window.debt {
+            0 => self.host.permits.add_permits(1),
+            _ => window.debt -= 1,
+        }
+    }
+}
+
+impl Drop for HostPermit {
+    fn drop(&mut self) {
+        let mut window = self.host.window.lock().unwrap();
+        if let Some(permit) = self.permit.take() {
+            if window.debt > 0 {
+                window.debt -= 1;
+                permit.forget();
+            }
+        }
+    }
+}
+
+/// The host the limits of url go by, its scheme and host along with the
+/// port, none for urls without a host.
+pub(crate) fn host_key(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}://{}:{}", url.scheme(), host, port),
+        None => format!("{}://{}", url.scheme(), host),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_host_limits() {
+        let limits = HostLimits::new(8, 10);
+        let permits: Vec<_> = futures::future::join_all((0..8).map(|_| limits.acquire("a"))).await;
+        // full, other hosts aside
+        let ninth = limits.acquire("a");
+        assert!(tokio::time::timeout(Duration::from_millis(20), ninth)
+            .await
+            .is_err());
+        drop(limits.acquire("b").await);
+
+        // halved once for the burst, however many of it were throttled
+        for permit in &permits {
+            permit.throttled();
+        }
+        assert_eq!(limits.limit("a"), Some(4));
+        // the permits in flight over the limit go away as they're done
+        drop(permits);
+        let permits: Vec<_> = futures::future::join_all((0..4).map(|_| limits.acquire("a"))).await;
+        let fifth = limits.acquire("a");
+        assert!(tokio::time::timeout(Duration::from_millis(20), fifth)
+            .await
+            .is_err());
+
+        // raised by one after a window of successes, up to the max
+        for permit in &permits {
+            permit.succeeded();
+        }
+        assert_eq!(limits.limit("a"), Some(5));
+        drop(permits);
+        for _ in 0..100 {
+            limits.acquire("a").await.succeeded();
+        }
+        assert_eq!(limits.limit("a"), Some(10));
+        let permits: Vec<_> = futures::future::join_all((0..10).map(|_| limits.acquire("a"))).await;
+        permits[0].throttled();
+        assert_eq!(limits.limit("a"), Some(5));
+        assert_eq!(limits.limit("c"), None);
+    }
+
+    #[test]
+    fn test_host_key() {
+        let key = |url: &str| host_key(&Url::parse(url).unwrap());
+        assert_eq!(key("https://host/a").as_deref(), Some("https://host"));
+        assert_eq!(
+            key("http://host:8080/a").as_deref(),
+            Some("http://host:8080")
+        );
+        assert_eq!(key("s3://bucket/key").as_deref(), Some("s3://bucket"));
+        assert_eq!(key("file:///a/b"), None);
+    }
+}
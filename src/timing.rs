@@ -0,0 +1,240 @@
+//! How long gets take and how fast they transfer, see [`Timings`].
+
+use std::{
+    convert::Infallible,
+    fs,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::Phase;
+
+/// Shortest stretch of a transfer the peak rate is taken over, so that a
+/// few bytes arriving at once don't make for a peak of their own.
+const RATE_WINDOW: Duration = Duration::from_millis(250);
+
+/// How long a get spent in each of its phases and how fast it transferred,
+/// see [`GetResult::timings`](crate::GetResult::timings). Every moment of
+/// the get counts toward the phase it was in, so that the phases add up to
+/// the total. Archives extracted as they stream in are extracted during the
+/// transfer, which their time counts toward.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Timings {
+    /// Wall time of the get as a whole.
+    pub total: Duration,
+    pub detection: Duration,
+    pub locking: Duration,
+    pub transfer: Duration,
+    pub verification: Duration,
+    pub extraction: Duration,
+    pub post_processing: Duration,
+    /// Bytes per second over the whole transfer, for gets that transferred
+    /// anything.
+    pub average_rate: Option<u64>,
+    /// Bytes per second over the fastest stretch of the transfer, never
+    /// less than the average. Taken from the bytes as they arrive, or from
+    /// the download growing for getters that write it themselves.
+    pub peak_rate: Option<u64>,
+}
+
+impl Timings {
+    /// Time spent in phase.
+    pub fn phase(&self, phase: Phase) -> Duration {
+        match phase {
+            Phase::Detection => self.detection,
+            Phase::Locking => self.locking,
+            Phase::Transfer => self.transfer,
+            Phase::Verification => self.verification,
+            Phase::Extraction => self.extraction,
+            Phase::PostProcessing => self.post_processing,
+        }
+    }
+
+    fn phase_mut(&mut self, phase: Phase) -> &mut Duration {
+        match phase {
+            Phase::Detection => &mut self.detection,
+            Phase::Locking => &mut self.locking,
+            Phase::Transfer => &mut self.transfer,
+            Phase::Verification => &mut self.verification,
+            Phase::Extraction => &mut self.extraction,
+            Phase::PostProcessing => &mut self.post_processing,
+        }
+    }
+
+    /// Adds the time other spent in each phase to these, keeping the
+    /// highest of the peak rates. The average rate is left for the caller,
+    /// who knows what it's over.
+    pub(crate) fn add(&mut self, other: &Timings) {
+        self.total += other.total;
+        for phase in PHASES {
+            *self.phase_mut(phase) += other.phase(phase);
+        }
+        self.peak_rate = self.peak_rate.max(other.peak_rate);
+    }
+
+    /// Records these on span, in milliseconds and bytes per second.
+    pub(crate) fn record(&self, span: &tracing::Span) {
+        span.record("total_ms", self.total.as_millis() as u64);
+        span.record("detection_ms", self.detection.as_millis() as u64);
+        span.record("locking_ms", self.locking.as_millis() as u64);
+        span.record("transfer_ms", self.transfer.as_millis() as u64);
+        span.record("verification_ms", self.verification.as_millis() as u64);
+        span.record("extraction_ms", self.extraction.as_millis() as u64);
+        span.record(
+            "post_processing_ms",
+            self.post_processing.as_millis() as u64,
+        );
+        if let Some(rate) = self.average_rate {
+            span.record("average_rate", rate);
+        }
+        if let Some(rate) = self.peak_rate {
+            span.record("peak_rate", rate);
+        }
+    }
+}
+
+const PHASES: [Phase; 6] = [
+    Phase::Detection,
+    Phase::Locking,
+    Phase::Transfer,
+    Phase::Verification,
+    Phase::Extraction,
+    Phase::PostProcessing,
+];
+
+/// Bytes per second of bytes over elapsed, if there's anything to go by.
+pub(crate) fn rate(bytes: u64, elapsed: Duration) -> Option<u64> {
+    let nanos = elapsed.as_nanos();
+    (bytes > 0 && nanos > 0).then(|| (bytes as u128 * 1_000_000_000 / nanos) as u64)
+}
+
+/// Times the phases of a single get as it goes through them, and the rate
+/// of its transfer.
+pub(crate) struct Stopwatch {
+    start: Instant,
+    state: Mutex<State>,
+}
+
+struct State {
+    timings: Timings,
+    phase: Phase,
+    since: Instant,
+    /// Start of the stretch of the transfer being measured, along with the
+    /// bytes transferred by then.
+    window: Option<(Instant, u64)>,
+}
+
+impl Stopwatch {
+    /// A stopwatch of a get starting now, with detection.
+    pub(crate) fn start() -> Self {
+        let start = Instant::now();
+        Self {
+            start,
+            state: Mutex::new(State {
+                timings: Timings::default(),
+                phase: Phase::Detection,
+                since: start,
+                window: None,
+            }),
+        }
+    }
+
+    /// Counts the time since the last phase was entered toward it, and goes
+    /// on with phase.
+    pub(crate) fn enter(&self, phase: Phase) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let since = std::mem::replace(&mut state.since, now);
+        let current = state.phase;
+        *state.timings.phase_mut(current) += now - since;
+        state.phase = phase;
+    }
+
+    /// Takes note of the bytes transferred so far, for the peak rate. Fewer
+    /// than before means the transfer started over.
+    pub(crate) fn transferred(&self, bytes: u64) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        match state.window {
+            Some((start, from)) if bytes >= from => {
+                let elapsed = now - start;
+                if elapsed >= RATE_WINDOW {
+                    let peak = rate(bytes - from, elapsed);
+                    state.timings.peak_rate = state.timings.peak_rate.max(peak);
+                    state.window = Some((now, bytes));
+                }
+            }
+            _ => state.window = Some((now, bytes)),
+        }
+    }
+
+    /// Follows the size of the file a getter is downloading to at path, for
+    /// the peak rate, until it's dropped.
+    pub(crate) async fn watch(&self, path: &Path) -> Infallible {
+        let mut ticker = tokio::time::interval(RATE_WINDOW / 2);
+        loop {
+            ticker.tick().await;
+            if let Some(meta) = fs::symlink_metadata(path).ok().filter(|m| m.is_file()) {
+                self.transferred(meta.len());
+            }
+        }
+    }
+
+    /// The timings of the get, done now, which transferred bytes.
+    pub(crate) fn finish(&self, bytes: u64) -> Timings {
+        self.enter(Phase::Detection);
+        let mut timings = self.state.lock().unwrap().timings.clone();
+        timings.total = self.start.elapsed();
+        timings.average_rate = rate(bytes, timings.transfer);
+        let peak = timings.peak_rate.unwrap_or_default();
+        timings.peak_rate = timings.average_rate.map(|average| peak.max(average));
+        timings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stopwatch() {
+        let stopwatch = Stopwatch::start();
+        std::thread::sleep(Duration::from_millis(5));
+        stopwatch.enter(Phase::Transfer);
+        stopwatch.transferred(0);
+        std::thread::sleep(RATE_WINDOW);
+        stopwatch.transferred(1000);
+        stopwatch.enter(Phase::Extraction);
+        std::thread::sleep(Duration::from_millis(5));
+
+        let timings = stopwatch.finish(1000);
+        assert!(timings.detection >= Duration::from_millis(5));
+        assert!(timings.transfer >= RATE_WINDOW);
+        assert!(timings.extraction >= Duration::from_millis(5));
+        assert_eq!(timings.locking, Duration::ZERO);
+        let phases: Duration = PHASES.iter().map(|p| timings.phase(*p)).sum();
+        assert!(
+            timings.total - phases < Duration::from_millis(1),
+            "{:?}",
+            timings
+        );
+        let average = timings.average_rate.unwrap();
+        assert!(average > 0 && average <= 4000, "{:?}", timings);
+        assert!(timings.peak_rate.unwrap() >= average);
+
+        // nothing transferred, no rates
+        let timings = Stopwatch::start().finish(0);
+        assert_eq!(timings.average_rate, None);
+        assert_eq!(timings.peak_rate, None);
+    }
+
+    #[test]
+    fn test_rate() {
+        assert_eq!(rate(1000, Duration::from_millis(500)), Some(2000));
+        assert_eq!(rate(0, Duration::from_secs(1)), None);
+        assert_eq!(rate(1000, Duration::ZERO), None);
+    }
+}